@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::db::{list_channels, Database};
+
+/// Parse a `--download-archive` file, which has a line like `youtube dQw4w9WgXcQ` for each
+/// downloaded video. Returns the video IDs for each extractor
+fn parse_archive(text: &str) -> HashMap<String, HashSet<String>> {
+    let mut ret: HashMap<String, HashSet<String>> = HashMap::new();
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        if let (Some(extractor), Some(id)) = (words.next(), words.next()) {
+            ret.entry(extractor.to_lowercase())
+                .or_default()
+                .insert(id.to_string());
+        }
+    }
+    ret
+}
+
+/// Mark videos listed in the archive file as `Grabbed`, defaulting to the configured
+/// `VIDL_DOWNLOAD_ARCHIVE`. Returns number of videos changed
+pub fn import(path: Option<&str>) -> Result<usize> {
+    let cfg = crate::config::Config::load();
+    let path = match path {
+        Some(p) => std::path::PathBuf::from(p),
+        None => cfg.download_archive.clone().ok_or_else(|| {
+            anyhow::anyhow!("No archive file given, and VIDL_DOWNLOAD_ARCHIVE is not set")
+        })?,
+    };
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read archive file {:?}", &path))?;
+    let archive = parse_archive(&text);
+
+    let db = Database::open(&cfg)?;
+    import_entries(&db, &archive)
+}
+
+fn import_entries(db: &Database, archive: &HashMap<String, HashSet<String>>) -> Result<usize> {
+    let mut changed = 0;
+    for chan in list_channels(db)? {
+        if let Some(ids) = archive.get(chan.service.archive_extractor()) {
+            let n = chan.mark_grabbed(db, ids)?;
+            if n > 0 {
+                info!("Marked {} videos in {:?} as grabbed", n, &chan.title);
+            }
+            changed += n;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{ChannelID, LiveStatus, VideoStatus, YoutubeID};
+    use crate::db::DBVideoInfo;
+    use crate::source::base::VideoInfo;
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let archive = parse_archive("youtube aaa\nyoutube  bbb\n\nvimeo ccc\nbroken\n");
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive["youtube"].len(), 2);
+
+        let mdb = Database::create_in_memory(true)?;
+        let c = crate::db::Channel::create(
+            &mdb,
+            &ChannelID::Youtube(YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let mut videos = vec![];
+        for id in &["aaa", "ccc", "ddd"] {
+            videos.push(c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: id.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?);
+        }
+
+        assert_eq!(import_entries(&mdb, &archive)?, 1);
+        let status = |v: &DBVideoInfo| -> Result<VideoStatus> {
+            Ok(DBVideoInfo::get_by_sqlid(&mdb, v.id)?.status)
+        };
+        assert_eq!(status(&videos[0])?, VideoStatus::Grabbed);
+        // Only Vimeo channels match `vimeo` entries
+        assert_eq!(status(&videos[1])?, VideoStatus::New);
+        assert_eq!(status(&videos[2])?, VideoStatus::New);
+
+        // Already grabbed so nothing changes
+        assert_eq!(import_entries(&mdb, &archive)?, 0);
+        Ok(())
+    }
+}
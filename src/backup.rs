@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 
-use crate::common::{Service, VideoStatus};
+use crate::common::{Backend, ChannelID, LiveStatus, Service, VideoStatus};
 use crate::config::Config;
 use crate::db::{Channel, DBVideoInfo, Database};
 use crate::source::base::VideoInfo;
@@ -14,6 +14,26 @@ struct BackupChannel {
     service: String,
     icon: String,
     id: i64,
+    #[serde(default)]
+    backend: Option<String>,
+    #[serde(default)]
+    handle: Option<String>,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    subtitles: Option<bool>,
+    #[serde(default)]
+    download_dir: Option<String>,
+    #[serde(default)]
+    filename_format: Option<String>,
+    #[serde(default)]
+    ignore_min_duration: Option<i32>,
+    #[serde(default)]
+    ignore_max_duration: Option<i32>,
+    #[serde(default)]
+    ignore_title: Option<String>,
+    #[serde(default)]
+    archived: bool,
 }
 
 impl From<&Channel> for BackupChannel {
@@ -23,6 +43,16 @@ impl From<&Channel> for BackupChannel {
             service: src.service.as_str().into(),
             icon: src.thumbnail.clone(),
             id: src.id,
+            backend: src.backend.map(|b| b.as_str().into()),
+            handle: src.handle.clone(),
+            format: src.format.clone(),
+            subtitles: src.subtitles,
+            download_dir: src.download_dir.clone(),
+            filename_format: src.filename_format.clone(),
+            ignore_min_duration: src.ignore_rules.min_duration,
+            ignore_max_duration: src.ignore_rules.max_duration,
+            ignore_title: src.ignore_rules.title.clone(),
+            archived: src.archived,
         }
     }
 }
@@ -40,6 +70,16 @@ struct BackupVideoInfo {
     thumbnail_url: String,
     channel_id: i64,
     duration: i32,
+    #[serde(default)]
+    live_status: Option<String>,
+    #[serde(default)]
+    view_count: Option<i64>,
+    #[serde(default)]
+    captions: Vec<String>,
+    #[serde(default)]
+    scheduled_at: Option<String>,
+    #[serde(default)]
+    members_only: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -62,8 +102,21 @@ impl From<BackupVideoInfo> for VideoInfo {
             description: src.description,
             description_alt: src.description_alt,
             thumbnail_url: src.thumbnail_url,
+            thumbnails: vec![],
             published_at: when,
             duration: src.duration,
+            live_status: src
+                .live_status
+                .and_then(|s| LiveStatus::from_str(&s).ok())
+                .unwrap_or(LiveStatus::NotLive),
+            view_count: src.view_count,
+            captions: src.captions,
+            scheduled_at: src.scheduled_at.and_then(|x| {
+                DateTime::parse_from_rfc3339(&x)
+                    .ok()
+                    .map(|d| d.with_timezone(&Utc))
+            }),
+            members_only: src.members_only,
         }
     }
 }
@@ -82,10 +135,28 @@ impl From<&DBVideoInfo> for BackupVideoInfo {
             description_alt: src.info.description_alt.clone(),
             thumbnail_url: src.info.thumbnail_url.clone(),
             duration: src.info.duration,
+            live_status: Some(src.info.live_status.as_str().into()),
+            view_count: src.info.view_count,
+            captions: src.info.captions.clone(),
+            scheduled_at: src.info.scheduled_at.map(|x| x.to_rfc3339()),
+            members_only: src.info.members_only,
         }
     }
 }
 
+impl Channel {
+    /// The channel and all its videos as JSON, in the same format as a full backup, so
+    /// `import` can add it to another database
+    pub fn export(&self, db: &Database) -> Result<serde_json::Value> {
+        let vids = self.all_videos(db, i64::MAX, 0, None)?;
+        let back = Backup {
+            channels: vec![self.into()],
+            videos: vids.iter().map(|v| v.into()).collect(),
+        };
+        Ok(serde_json::to_value(back)?)
+    }
+}
+
 /// Load backup file
 pub fn import() -> Result<()> {
     let cfg = Config::load();
@@ -100,13 +171,43 @@ pub fn import() -> Result<()> {
         // Get service
         let service = Service::from_str(&back_chan.service)?;
         // Get channel ID
-        let cid = service.get_channel_id(&back_chan.chanid);
+        let mut cid = service.get_channel_id(&back_chan.chanid);
+        if let ChannelID::Youtube(x) = &mut cid {
+            x.handle = back_chan.handle.clone();
+        }
 
         // Get or create channel
         let db_chan = crate::db::Channel::get(&db, &cid).or_else(|_| {
             crate::db::Channel::create(&db, &cid, &back_chan.chanid, &back_chan.icon)
         })?;
 
+        if let Some(backend) = &back_chan.backend {
+            db_chan.set_backend(&db, Some(Backend::from_str(backend)?))?;
+        }
+        if let Some(format) = &back_chan.format {
+            db_chan.set_format(&db, Some(format))?;
+        }
+        if let Some(dir) = &back_chan.download_dir {
+            db_chan.set_download_dir(&db, Some(dir))?;
+        }
+        if let Some(filename_format) = &back_chan.filename_format {
+            db_chan.set_filename_format(&db, Some(filename_format))?;
+        }
+        let rules = crate::db::IgnoreRules {
+            min_duration: back_chan.ignore_min_duration,
+            max_duration: back_chan.ignore_max_duration,
+            title: back_chan.ignore_title.clone(),
+        };
+        if rules != Default::default() {
+            db_chan.set_ignore_rules(&db, &rules)?;
+        }
+        if back_chan.subtitles.is_some() {
+            db_chan.set_subtitles(&db, back_chan.subtitles)?;
+        }
+        if back_chan.archived {
+            db_chan.set_archived(&db, true)?;
+        }
+
         // Create a mapping from backup-channel-id to database
         backup_id_to_channel_mapper.insert(back_chan.id, db_chan);
     }
@@ -130,20 +231,26 @@ pub fn import() -> Result<()> {
     Ok(())
 }
 
-/// Export channels, videos, and their status etc to a JSON file
-pub fn export(output: Option<&str>) -> Result<()> {
+/// Export channels, videos, and their status etc to a JSON file, or only those of the channel
+/// with SQL ID `channel`
+pub fn export(output: Option<&str>, channel: Option<i64>) -> Result<()> {
     let cfg = Config::load();
     let db = Database::open(&cfg)?;
 
-    let chans = crate::db::list_channels(&db)?;
-    let chans_ser: Vec<BackupChannel> = chans.iter().map(|v| v.into()).collect();
+    let back = if let Some(id) = channel {
+        Channel::get_by_sqlid(&db, id)?.export(&db)?
+    } else {
+        // Archived channels are included, as their videos are
+        let chans = crate::db::list_all_channels(&db)?;
+        let chans_ser: Vec<BackupChannel> = chans.iter().map(|v| v.into()).collect();
 
-    let vids = crate::db::all_videos(&db, std::i64::MAX, 0, None)?;
-    let vids_ser: Vec<BackupVideoInfo> = vids.iter().map(|v| v.into()).collect();
+        let vids = crate::db::all_videos(&db, i64::MAX, 0, None)?;
+        let vids_ser: Vec<BackupVideoInfo> = vids.iter().map(|v| v.into()).collect();
 
-    let back = Backup {
-        channels: chans_ser,
-        videos: vids_ser,
+        serde_json::to_value(Backup {
+            channels: chans_ser,
+            videos: vids_ser,
+        })?
     };
 
     let stdout = std::io::stdout();
@@ -156,3 +263,59 @@ pub fn export(output: Option<&str>) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::YoutubeID;
+
+    #[test]
+    fn test_channel_export() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+        let mut chans = vec![];
+        for name in ["one", "two"] {
+            let c = Channel::create(
+                &mdb,
+                &ChannelID::Youtube(YoutubeID {
+                    id: name.into(),
+                    handle: None,
+                }),
+                name,
+                "http://example.com/thumbnail.jpg",
+            )?;
+            let v = c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: format!("{}_video", name),
+                    url: format!("http://example.com/watch?v={}_video", name),
+                    title: format!("Video from {}", name),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+            v.set_status(&mdb, VideoStatus::Grabbed)?;
+            chans.push(c);
+        }
+
+        // Only includes the exported channel's videos, readable as a backup
+        let back: Backup = serde_json::from_value(chans[1].export(&mdb)?)?;
+        assert_eq!(back.channels.len(), 1);
+        assert_eq!(back.channels[0].chanid, "two");
+        assert_eq!(back.channels[0].id, chans[1].id);
+        assert_eq!(back.videos.len(), 1);
+        assert_eq!(back.videos[0].videoid, "two_video");
+        assert_eq!(back.videos[0].status, VideoStatus::Grabbed.as_str());
+        assert_eq!(back.videos[0].channel_id, chans[1].id);
+        Ok(())
+    }
+}
@@ -2,9 +2,8 @@ use anyhow::Result;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use log::{debug, info, warn};
 
-use crate::common::{ChannelID, Service};
+use crate::common::{Backend, Service};
 use crate::db;
-use crate::source::base::ChannelData;
 use crate::worker::{WorkItem, WorkerPool};
 
 #[derive(Debug, Parser)]
@@ -21,6 +20,29 @@ pub(crate) struct App {
 pub(crate) enum CliService {
     Youtube,
     Vimeo,
+    YoutubePlaylist,
+    Soundcloud,
+    Rumble,
+    Bitchute,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub(crate) enum CliBackend {
+    Invidious,
+    Ytdlp,
+    Api,
+    Piped,
+}
+
+impl From<CliBackend> for Backend {
+    fn from(src: CliBackend) -> Backend {
+        match src {
+            CliBackend::Invidious => Backend::Invidious,
+            CliBackend::Ytdlp => Backend::Ytdlp,
+            CliBackend::Api => Backend::Api,
+            CliBackend::Piped => Backend::Piped,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
@@ -32,20 +54,160 @@ pub(crate) struct GlobalOpts {
 
 #[derive(Debug, Args)]
 pub(crate) struct CmdAdd {
+    /// Channel URL, @handle or ID, or playlist URL/ID for youtube-playlist
     pub(crate) chanid: String,
-    /// youtube or vimeo
+    /// Which service the channel is on
     #[clap(value_enum, default_value_t=CliService::Youtube)]
     pub(crate) service: CliService,
+    /// Where to retrieve channel data from (defaults to VIDL_BACKEND)
+    #[clap(long, value_enum)]
+    pub(crate) backend: Option<CliBackend>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdBackend {
+    pub(crate) id: i64,
+    /// Backend to use, or omit to use the configured default
+    #[clap(value_enum)]
+    pub(crate) backend: Option<CliBackend>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdFormat {
+    pub(crate) id: i64,
+    /// Format passed to the downloader's -f option, or omit to use the configured default
+    pub(crate) format: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdPlaylists {
+    pub(crate) id: i64,
+    /// Add the playlist with the given ID as a channel, instead of listing
+    #[clap(long)]
+    pub(crate) add: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdSubtitles {
+    pub(crate) id: i64,
+    /// Whether to download subtitles, or omit to use the configured default
+    #[clap(action = clap::ArgAction::Set)]
+    pub(crate) enabled: Option<bool>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdSettings {
+    pub(crate) id: i64,
+    /// Queue new videos for download as soon as they are found
+    #[clap(long, action = clap::ArgAction::Set)]
+    pub(crate) auto_download: Option<bool>,
+    /// Minutes between updates, or 0 for the default (see `vidl defaults`)
+    #[clap(long)]
+    pub(crate) update_interval: Option<i64>,
+    /// Whether the channel is updated at all
+    #[clap(long, action = clap::ArgAction::Set)]
+    pub(crate) enabled: Option<bool>,
+    /// Ignore videos still new this many days after being published, or 0 to keep them
+    #[clap(long)]
+    pub(crate) prune_after: Option<i64>,
+    /// Delete videos pruned by --prune-after instead of ignoring them
+    #[clap(long, action = clap::ArgAction::Set)]
+    pub(crate) prune_delete: Option<bool>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdDefaults {
+    /// Setting to show or change (`update_interval` or `format`), or all are shown
+    #[clap(value_parser = db::Setting::from_str)]
+    pub(crate) key: Option<db::Setting>,
+    /// New value of the setting
+    pub(crate) value: Option<String>,
+    /// Remove the setting, going back to the built-in default
+    #[clap(long, requires = "key", conflicts_with = "value")]
+    pub(crate) unset: bool,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdImportArchive {
+    /// Archive file, defaults to VIDL_DOWNLOAD_ARCHIVE
+    pub(crate) file: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdDownloadDir {
+    pub(crate) id: i64,
+    /// Directory to download to, or omit to use the configured default
+    pub(crate) dir: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdFilenameFormat {
+    pub(crate) id: i64,
+    /// Output template like "%(title)s.%(ext)s", or omit to use the configured default
+    pub(crate) filename_format: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdIgnoreRules {
+    pub(crate) id: i64,
+    /// Ignore new videos shorter than this many seconds
+    #[clap(long)]
+    pub(crate) min_duration: Option<i32>,
+    /// Ignore new videos longer than this many seconds
+    #[clap(long)]
+    pub(crate) max_duration: Option<i32>,
+    /// Ignore new videos with titles matching this regex, e.g "(?i)#shorts|trailer"
+    #[clap(long)]
+    pub(crate) title: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdPriority {
+    /// Video number, as shown by "list <channel>"
+    pub(crate) video: i64,
+    /// Higher priorities are downloaded first, or omit to download the video next
+    pub(crate) priority: Option<i64>,
 }
 
 #[derive(Debug, Args)]
 pub(crate) struct CmdRemove {
     pub(crate) id: i64,
+    /// Delete the channel and all its videos, instead of archiving it
+    #[clap(long)]
+    pub(crate) purge: bool,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdRestore {
+    pub(crate) id: i64,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CmdSearch {
+    /// Words to find in titles and descriptions, or part of a channel's name
+    pub(crate) text: String,
+    /// Maximum number of videos to show
+    #[clap(long, short, default_value_t = 20)]
+    pub(crate) limit: i64,
 }
 
 #[derive(Debug, Args)]
 pub(crate) struct CmdList {
     pub(crate) id: Option<i64>,
+    /// List archived channels instead
+    #[clap(long)]
+    pub(crate) archived: bool,
+    /// Only list videos with these statuses (like `GE` or `NE,QU`), from all channels unless
+    /// a channel is given
+    #[clap(long, value_delimiter = ',', value_parser = crate::common::VideoStatus::from_str)]
+    pub(crate) status: Vec<crate::common::VideoStatus>,
+    /// Order videos by `published` date (newest first), date `added`, `duration` (longest
+    /// first) or `title`. Lists videos from all channels unless a channel is given
+    #[clap(long, value_parser = db::VideoOrder::from_str)]
+    pub(crate) order_by: Option<db::VideoOrder>,
+    /// Only list videos with this tag, from all channels unless a channel is given
+    #[clap(long)]
+    pub(crate) tag: Option<String>,
 }
 
 #[derive(Debug, Args)]
@@ -53,7 +215,7 @@ pub(crate) struct CmdUpdate {
     /// Checks for new data even if already updated recently
     #[clap(long, short)]
     pub(crate) force: bool,
-    /// Checks all pages, instead of stopping on an previously-seen video
+    /// Checks all pages, instead of stopping on an previously-seen video. Videos no longer listed are marked as removed
     #[clap(long)]
     pub(crate) full_update: bool,
     /// Filter by channel name
@@ -61,11 +223,25 @@ pub(crate) struct CmdUpdate {
     pub(crate) filter: Option<String>,
 }
 
+#[derive(Debug, Args)]
+pub(crate) struct CmdDownload {
+    /// Show what would be downloaded and where to, without downloading or changing anything
+    #[clap(long)]
+    pub(crate) dry_run: bool,
+    /// Number of videos downloaded at once, fewer than VIDL_NUM_DOWNLOAD_WORKERS to use less
+    /// bandwidth
+    #[clap(long)]
+    pub(crate) workers: Option<usize>,
+}
+
 #[derive(Debug, Args, Clone)]
 pub(crate) struct CmdBackupExport {
     /// Output file
     #[clap(short, long)]
     output: Option<String>,
+    /// Only export this channel (by its ID) and its videos, to move it to another vidl
+    #[clap(long)]
+    channel: Option<i64>,
 }
 
 #[derive(Debug, Args, Clone)]
@@ -77,6 +253,44 @@ pub(crate) enum CmdBackupOpts {
     Import(CmdBackupImport),
 }
 
+#[derive(Debug, Args, Clone)]
+pub(crate) struct CmdDbMaintain {
+    /// Only report problems, without removing anything or compacting the database
+    #[clap(long)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Debug, Args, Clone)]
+pub(crate) struct CmdDoctor {
+    /// Remove broken rows, reset unknown statuses to new and fill in missing values
+    #[clap(long)]
+    pub(crate) fix: bool,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub(crate) enum CmdDbOpts {
+    /// check integrity, remove orphaned rows, then analyze and vacuum the database
+    Maintain(CmdDbMaintain),
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub(crate) enum CmdCollectionOpts {
+    /// list collections
+    List,
+    /// list videos in a collection
+    Show { id: i64 },
+    /// create empty collection
+    Create { name: String },
+    /// rename collection
+    Rename { id: i64, name: String },
+    /// delete collection, leaving the videos in it
+    Delete { id: i64 },
+    /// add video to collection, by the number shown by "list <channel>"
+    Add { id: i64, video: i64 },
+    /// remove video from collection
+    Remove { id: i64, video: i64 },
+}
+
 #[derive(Subcommand, Debug)]
 pub(crate) enum Commands {
     /// Add channel
@@ -84,22 +298,56 @@ pub(crate) enum Commands {
     /// Backup database as simple .json file
     #[clap(subcommand)]
     Backup(CmdBackupOpts),
+    /// set where a channel's data is retrieved from
+    Backend(CmdBackend),
+    /// group videos from any channels into collections
+    #[clap(subcommand)]
+    Collection(CmdCollectionOpts),
+    /// database maintenance
+    #[clap(subcommand)]
+    Db(CmdDbOpts),
+    /// show or change defaults for channels without their own update interval or format
+    Defaults(CmdDefaults),
+    /// check for broken references, unknown statuses or services and missing values
+    Doctor(CmdDoctor),
     /// enqueues videos for download
-    Download,
+    Download(CmdDownload),
+    /// set directory a channel's videos are downloaded to
+    DownloadDir(CmdDownloadDir),
+    /// set filename template for a channel's downloads
+    FilenameFormat(CmdFilenameFormat),
+    /// set download format/quality for a channel
+    Format(CmdFormat),
+    /// set rules for automatically ignoring a channel's new videos (omit all to remove)
+    IgnoreRules(CmdIgnoreRules),
+    /// mark videos in a yt-dlp --download-archive file as grabbed
+    ImportArchive(CmdImportArchive),
     /// Initialise the database
     Init,
     /// list channels/videos
     List(CmdList),
     /// update database schema to be current
     Migrate,
-    /// remove given channel and all videos in it
+    /// list a channel's playlists, and add them as channels
+    Playlists(CmdPlaylists),
+    /// set download priority of a queued video
+    Priority(CmdPriority),
+    /// archive given channel, keeping its videos, or with --purge delete it and all videos in it
     Remove(CmdRemove),
+    /// restore an archived channel
+    Restore(CmdRestore),
+    /// find videos by title, description or channel name, best matches first
+    Search(CmdSearch),
+    /// show or change a channel's update and auto-download settings
+    Settings(CmdSettings),
+    /// set whether subtitles are downloaded for a channel
+    Subtitles(CmdSubtitles),
     /// Updates all added channel info
     Update(CmdUpdate),
     /// serve web interface
     Web,
     /// downloads queued videos
-    Worker,
+    Worker(CmdDownload),
 }
 
 fn update(force: bool, full_update: bool, filter: Option<String>) -> Result<()> {
@@ -108,7 +356,8 @@ fn update(force: bool, full_update: bool, filter: Option<String>) -> Result<()>
     let cfg = crate::config::Config::load();
     let db = db::Database::open(&cfg)?;
 
-    let work = WorkerPool::start();
+    // Only updates are queued, so one download worker is enough
+    let work = WorkerPool::start(1, cfg.num_workers);
 
     // Get list of channels
     let channels = db::list_channels(&db)?;
@@ -142,71 +391,293 @@ fn update(force: bool, full_update: bool, filter: Option<String>) -> Result<()>
 }
 
 /// Add channel
-fn add(name: &str, service_str: &str) -> Result<()> {
+fn add(name: &str, service_str: &str, backend: Option<Backend>) -> Result<()> {
     let service = Service::from_str(service_str)?;
-    let cid = crate::source::invidious::find_channel_id(name, &service)?;
+    let cid = crate::source::find_channel_id(name, &service)?;
+
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+    db::Channel::create_from_source(&db, &cid, backend)?;
+    Ok(())
+}
+
+/// List playlists of channel, or add one of them as a channel
+fn playlists(chan_num: i64, add: Option<&str>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    if let Some(playlist) = add {
+        let cid = crate::source::find_channel_id(playlist, &Service::YoutubePlaylist)?;
+        db::Channel::create_from_source(&db, &cid, None)?;
+        return Ok(());
+    }
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+    let cid = chan.service.get_channel_id(&chan.chanid);
+    let api = crate::source::channel_data(&cid, chan.backend.unwrap_or(cfg.backend));
+    for p in api.playlists()? {
+        let count = match p.video_count {
+            Some(n) => format!("{} videos", n),
+            None => "unknown videos".into(),
+        };
+        println!("{} - {} ({})", p.id, p.title, count);
+    }
+    Ok(())
+}
+
+/// Set backend used for channel
+fn set_backend(chan_num: i64, backend: Option<Backend>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+
+    info!("Setting backend of {:?} to {:?}", &chan, &backend);
+    chan.set_backend(&db, backend)?;
+
+    Ok(())
+}
+
+/// Set download format used for channel
+fn set_format(chan_num: i64, format: Option<&str>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+
+    info!("Setting format of {:?} to {:?}", &chan, &format);
+    chan.set_format(&db, format)?;
+
+    Ok(())
+}
+
+/// Set directory channel's videos are downloaded to
+fn set_download_dir(chan_num: i64, dir: Option<&str>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+
+    info!("Setting download directory of {:?} to {:?}", &chan, &dir);
+    chan.set_download_dir(&db, dir)?;
+
+    Ok(())
+}
+
+/// Set filename template used for channel's downloads
+fn set_filename_format(chan_num: i64, filename_format: Option<&str>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+
+    info!(
+        "Setting filename format of {:?} to {:?}",
+        &chan, &filename_format
+    );
+    chan.set_filename_format(&db, filename_format)?;
+
+    Ok(())
+}
+
+/// Set rules for automatically ignoring channel's new videos
+fn set_ignore_rules(chan_num: i64, rules: db::IgnoreRules) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
 
-    match &cid {
-        ChannelID::Youtube(ytid) => {
-            let yt = crate::source::invidious::YoutubeQuery::new(&ytid);
+    info!("Setting ignore rules of {:?} to {:?}", &chan, &rules);
+    chan.set_ignore_rules(&db, &rules)?;
 
-            let meta = yt.get_metadata()?;
-            let cfg = crate::config::Config::load();
-            let db = db::Database::open(&cfg)?;
-            info!("Adding Youtube channel {:?}", &ytid.id,);
-            db::Channel::create(&db, &cid, &meta.title, &meta.thumbnail)?;
-            Ok(())
+    Ok(())
+}
+
+/// Set whether subtitles are downloaded for channel
+fn set_subtitles(chan_num: i64, enabled: Option<bool>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+
+    info!("Setting subtitles of {:?} to {:?}", &chan, &enabled);
+    chan.set_subtitles(&db, enabled)?;
+
+    Ok(())
+}
+
+/// Change the given settings of channel, then print them
+fn settings(opts: CmdSettings) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, opts.id)?;
+
+    let mut settings = chan.settings(&db)?;
+    if let Some(auto_download) = opts.auto_download {
+        settings.auto_download = auto_download;
+    }
+    if let Some(minutes) = opts.update_interval {
+        settings.update_interval_minutes = Some(minutes).filter(|m| *m != 0);
+    }
+    if let Some(enabled) = opts.enabled {
+        settings.enabled = enabled;
+    }
+    if let Some(days) = opts.prune_after {
+        settings.prune_after_days = Some(days).filter(|d| *d != 0);
+    }
+    if let Some(prune_delete) = opts.prune_delete {
+        settings.prune_delete = prune_delete;
+    }
+    if opts.auto_download.is_some()
+        || opts.update_interval.is_some()
+        || opts.enabled.is_some()
+        || opts.prune_after.is_some()
+        || opts.prune_delete.is_some()
+    {
+        info!("Setting settings of {:?} to {:?}", &chan, &settings);
+        chan.set_settings(&db, &settings)?;
+    }
+
+    let prune = match settings.prune_after_days {
+        Some(days) if settings.prune_delete => format!("delete new videos after {} days", days),
+        Some(days) => format!("ignore new videos after {} days", days),
+        None => "never".into(),
+    };
+    println!(
+        "Auto-download: {}\nUpdate interval: {} minutes\nEnabled: {}\nPrune: {}",
+        settings.auto_download,
+        settings
+            .update_interval(db.default_update_interval()?)
+            .num_minutes(),
+        settings.enabled,
+        prune
+    );
+    Ok(())
+}
+
+/// Change a setting in the database, then print the settings
+fn defaults(opts: CmdDefaults) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    if let Some(key) = opts.key {
+        if opts.unset {
+            info!("Removing setting {}", key.as_str());
+            db.set_setting::<String>(key, None)?;
+        } else if let Some(value) = &opts.value {
+            info!("Setting {} to {:?}", key.as_str(), value);
+            db.set_setting(key, Some(value))?;
         }
-        ChannelID::Vimeo(_) => Err(anyhow::anyhow!("Not yet implemented")),
     }
+
+    for (key, value) in db.settings()? {
+        if opts.key.is_none() || opts.key == Some(key) {
+            println!(
+                "{}: {}",
+                key.as_str(),
+                value.as_deref().unwrap_or("(default)")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Set download priority of video, or move it to the front of the queue
+fn set_priority(video_num: i64, priority: Option<i64>) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let v = db::DBVideoInfo::get_by_sqlid(&db, video_num)?;
+    match priority {
+        Some(p) => v.set_priority(&db, p)?,
+        None => v.bump_priority(&db)?,
+    }
+    info!(
+        "Priority of {:?} is now {}",
+        &v.info.title,
+        v.priority(&db)?
+    );
+    Ok(())
 }
 
 /// Remove channel and videos
-fn remove(chan_num: i64) -> Result<()> {
+fn remove(chan_num: i64, purge: bool) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
+
+    if purge {
+        info!("Removing channel {:?}", &chan);
+        chan.delete(&db)?;
+    } else {
+        info!("Archiving channel {:?}", &chan);
+        chan.set_archived(&db, true)?;
+    }
+
+    Ok(())
+}
+
+/// Show archived channel in the channel list again, and resume updating it
+fn restore(chan_num: i64) -> Result<()> {
     let cfg = crate::config::Config::load();
     let db = db::Database::open(&cfg)?;
 
     let chan = db::Channel::get_by_sqlid(&db, chan_num)?;
 
-    info!("Removing channel {:?}", &chan);
-    chan.delete(&db)?;
+    info!("Restoring channel {:?}", &chan);
+    chan.set_archived(&db, false)?;
 
     Ok(())
 }
 
 /// List videos
-fn list(chan_num: Option<i64>) -> Result<()> {
+fn list(
+    chan_num: Option<i64>,
+    archived: bool,
+    status: Vec<crate::common::VideoStatus>,
+    order_by: Option<db::VideoOrder>,
+    tag: Option<String>,
+) -> Result<()> {
     let cfg = crate::config::Config::load();
     let db = db::Database::open(&cfg)?;
 
+    let filter =
+        (!status.is_empty() || order_by.is_some() || tag.is_some()).then(|| db::FilterParams {
+            status: (!status.is_empty()).then(|| status.into_iter().collect()),
+            order_by: order_by.unwrap_or_default(),
+            tag,
+            ..Default::default()
+        });
+
     if let Some(chan_num) = chan_num {
-        // List specific channel
-        let channels = db::list_channels(&db)?;
+        // List specific channel, which may be archived
+        let channels = db::list_all_channels(&db)?;
         for c in channels {
             if c.id == chan_num {
-                for v in c.all_videos(&db, 50, 0, None)? {
-                    let v = v.info;
-                    let title_alt = if let Some(a) = v.title_alt {
-                        format!(" {}", a)
-                    } else {
-                        "".to_string()
-                    };
-                    println!(
-                        "ID: {}\nTitle: {}{}\nURL: {}\nPublished: {}\nThumbnail: {}\nDescription: {}\n----",
-                        v.id, v.title, title_alt, v.url, v.published_at, v.thumbnail_url, v.description
-                    );
+                for v in c.all_videos(&db, 50, 0, filter.clone())? {
+                    print_video(&db, c.id, v)?;
                 }
             }
         }
+    } else if filter.is_some() {
+        for v in db::all_videos(&db, 50, 0, filter)? {
+            print_video(&db, v.chanid, v)?;
+        }
     } else {
         // List all channels
-        let channels = db::list_channels(&db)?;
+        let channels = if archived {
+            db::list_archived_channels(&db)?
+        } else {
+            db::list_channels(&db)?
+        };
         for c in channels {
             println!(
                 "{} - {} ({} on service {})\nThumbnail: {}",
                 c.id,
                 c.title,
-                c.chanid,
+                c.handle.as_deref().unwrap_or(&c.chanid),
                 c.service.as_str(),
                 c.thumbnail,
             );
@@ -215,6 +686,205 @@ fn list(chan_num: Option<i64>) -> Result<()> {
     Ok(())
 }
 
+/// Print details of a video, as listed in channel `chan_id`
+fn print_video(db: &db::Database, chan_id: i64, v: db::DBVideoInfo) -> Result<()> {
+    let subtitles = v
+        .subtitle_files(db)?
+        .into_iter()
+        .map(|s| s.path)
+        .collect::<Vec<String>>();
+    let error = match (v.error_kind(db)?, &v.error_message) {
+        _ if v.status != crate::common::VideoStatus::GrabError => "".to_string(),
+        (Some(kind), Some(message)) => format!("\nError: {}: {}", kind.as_str(), message),
+        (Some(kind), None) => format!("\nError: {}", kind.as_str()),
+        (None, _) => "".to_string(),
+    };
+    let duplicate = if v.chanid != chan_id {
+        format!("\nDuplicate of a video in channel {}", v.chanid)
+    } else {
+        let others = v
+            .duplicate_channels(db)?
+            .into_iter()
+            .map(|c| c.id.to_string())
+            .collect::<Vec<String>>();
+        if others.is_empty() {
+            "".to_string()
+        } else {
+            format!("\nAlso in channels: {}", others.join(", "))
+        }
+    };
+    let watched = match (v.watched, v.watched_at) {
+        (true, Some(at)) => format!("\nWatched: {}", at),
+        (true, None) => "\nWatched: yes".to_string(),
+        (false, _) => "".to_string(),
+    };
+    let last_seen = match v.last_seen {
+        Some(at) => format!("\nLast seen: {}", at),
+        None => "".to_string(),
+    };
+    let removed = match v.removed_at {
+        Some(at) => format!("\nRemoved from source: {}", at),
+        None => "".to_string(),
+    };
+    let num = v.id;
+    let v = v.info;
+    let title_alt = if let Some(a) = v.title_alt {
+        format!(" {}", a)
+    } else {
+        "".to_string()
+    };
+    let views = if let Some(n) = v.view_count {
+        n.to_string()
+    } else {
+        "unknown".to_string()
+    };
+    let subtitles = if subtitles.is_empty() {
+        "".to_string()
+    } else {
+        format!("\nSubtitles: {}", subtitles.join(", "))
+    };
+    println!(
+        "Number: {}\nID: {}\nTitle: {}{}\nURL: {}\nPublished: {}\nViews: {}\nThumbnail: {}{}{}{}{}{}{}\nDescription: {}\n----",
+        num, v.id, v.title, title_alt, v.url, v.published_at, views, v.thumbnail_url, subtitles, error, watched, last_seen, removed, duplicate, v.description
+    );
+    Ok(())
+}
+
+/// List, change or show collections
+fn collection(opts: CmdCollectionOpts) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    match opts {
+        CmdCollectionOpts::List => {
+            for c in db::list_collections(&db)? {
+                println!("{} - {} ({} videos)", c.id, c.name, c.count_videos(&db)?);
+            }
+        }
+        CmdCollectionOpts::Show { id } => {
+            let c = db::Collection::get_by_sqlid(&db, id)?;
+            for v in c.videos(&db, i64::MAX, 0)? {
+                println!(
+                    "{} - {} [{}]\nURL: {}",
+                    v.id,
+                    v.info.title,
+                    v.status.as_str(),
+                    v.info.url
+                );
+            }
+        }
+        CmdCollectionOpts::Create { name } => {
+            let c = db::Collection::create(&db, &name)?;
+            println!("Created collection {}", c.id);
+        }
+        CmdCollectionOpts::Rename { id, name } => {
+            db::Collection::get_by_sqlid(&db, id)?.rename(&db, &name)?;
+        }
+        CmdCollectionOpts::Delete { id } => {
+            let c = db::Collection::get_by_sqlid(&db, id)?;
+            info!("Deleting collection {:?}", &c);
+            c.delete(&db)?;
+        }
+        CmdCollectionOpts::Add { id, video } => {
+            let c = db::Collection::get_by_sqlid(&db, id)?;
+            let v = db::DBVideoInfo::get_by_sqlid(&db, video)?;
+            if !c.add_video(&db, &v)? {
+                warn!("{:?} is already in {:?}", &v.info.title, &c.name);
+            }
+        }
+        CmdCollectionOpts::Remove { id, video } => {
+            let c = db::Collection::get_by_sqlid(&db, id)?;
+            let v = db::DBVideoInfo::get_by_sqlid(&db, video)?;
+            if !c.remove_video(&db, &v)? {
+                warn!("{:?} isn't in {:?}", &v.info.title, &c.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print videos matching search text
+fn search(text: &str, limit: i64) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let found = db::search(&db, text, limit, 0, None)?;
+    if found.is_empty() {
+        println!("No videos found");
+    }
+    for v in found {
+        println!(
+            "{} - {} [{}]\nURL: {}\nPublished: {}",
+            v.id,
+            v.info.title,
+            v.status.as_str(),
+            v.info.url,
+            v.info.published_at
+        );
+    }
+    Ok(())
+}
+
+/// Check and tidy up the database, printing what was found
+fn maintain(dry_run: bool) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let report = db.maintain(dry_run)?;
+    if !report.integrity_errors.is_empty() {
+        println!("Integrity check failed, database not changed:");
+        for e in &report.integrity_errors {
+            println!("  {}", e);
+        }
+        return Err(anyhow::anyhow!("Database is corrupt"));
+    }
+    println!("Integrity check passed");
+    if report.orphans.is_empty() {
+        println!("No orphaned rows found");
+    }
+    for (table, count) in &report.orphans {
+        if dry_run {
+            println!("Found {} orphaned rows in {}", count, table);
+        } else {
+            println!("Removed {} orphaned rows from {}", count, table);
+        }
+    }
+    if !dry_run {
+        println!("Analyzed and vacuumed database");
+    }
+    Ok(())
+}
+
+/// Check the database's contents, printing any problems found
+fn doctor(fix: bool) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = db::Database::open(&cfg)?;
+
+    let report = db.doctor(fix)?;
+    if report.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+    for (table, rowid) in &report.foreign_key_errors {
+        println!("Row {} of {} refers to a missing row", rowid, table);
+    }
+    for (id, status) in &report.unknown_statuses {
+        println!("Video {} has unknown status {:?}", id, status);
+    }
+    for (id, service) in &report.unknown_services {
+        println!("Channel {} has unknown service {:?}", id, service);
+    }
+    for (table, column, count) in &report.missing_values {
+        println!("{} rows of {} have no {}", count, table, column);
+    }
+    if fix {
+        println!("Fixed problems, except for unknown services");
+    } else {
+        println!("Run with --fix to fix them");
+    }
+    Ok(())
+}
+
 fn migrate() -> Result<()> {
     let cfg = crate::config::Config::load();
     db::Database::migrate(&cfg)?;
@@ -274,31 +944,94 @@ pub fn main() -> Result<()> {
                 match o.service {
                     CliService::Youtube => "youtube",
                     CliService::Vimeo => "vimeo",
+                    CliService::YoutubePlaylist => "youtube_playlist",
+                    CliService::Soundcloud => "soundcloud",
+                    CliService::Rumble => "rumble",
+                    CliService::Bitchute => "bitchute",
                 },
+                o.backend.map(|b| b.into()),
             )?;
         }
+        Commands::Backend(o) => {
+            set_backend(o.id, o.backend.map(|b| b.into()))?;
+        }
         Commands::Backup(o) => match o {
             CmdBackupOpts::Export(o) => {
-                crate::backup::export(o.output.as_deref())?;
+                crate::backup::export(o.output.as_deref(), o.channel)?;
             }
             CmdBackupOpts::Import(_) => {
                 crate::backup::import()?;
             }
         },
-        Commands::Download => {
-            todo!()
+        Commands::Collection(o) => {
+            collection(o)?;
+        }
+        Commands::Db(o) => match o {
+            CmdDbOpts::Maintain(o) => {
+                maintain(o.dry_run)?;
+            }
+        },
+        Commands::Defaults(o) => {
+            defaults(o)?;
+        }
+        Commands::Doctor(o) => {
+            doctor(o.fix)?;
+        }
+        Commands::Download(o) => {
+            crate::worker::main(o.dry_run, o.workers)?;
+        }
+        Commands::DownloadDir(o) => {
+            set_download_dir(o.id, o.dir.as_deref())?;
+        }
+        Commands::FilenameFormat(o) => {
+            set_filename_format(o.id, o.filename_format.as_deref())?;
+        }
+        Commands::Format(o) => {
+            set_format(o.id, o.format.as_deref())?;
+        }
+        Commands::IgnoreRules(o) => {
+            set_ignore_rules(
+                o.id,
+                db::IgnoreRules {
+                    min_duration: o.min_duration,
+                    max_duration: o.max_duration,
+                    title: o.title,
+                },
+            )?;
+        }
+        Commands::ImportArchive(o) => {
+            let changed = crate::archive::import(o.file.as_deref())?;
+            println!("Marked {} videos as grabbed", changed);
         }
         Commands::Init => {
             init()?;
         }
         Commands::List(o) => {
-            list(o.id)?;
+            list(o.id, o.archived, o.status, o.order_by, o.tag)?;
         }
         Commands::Migrate => {
             migrate()?;
         }
+        Commands::Playlists(o) => {
+            playlists(o.id, o.add.as_deref())?;
+        }
+        Commands::Priority(o) => {
+            set_priority(o.video, o.priority)?;
+        }
         Commands::Remove(o) => {
-            remove(o.id)?;
+            remove(o.id, o.purge)?;
+        }
+        Commands::Restore(o) => {
+            restore(o.id)?;
+        }
+        Commands::Search(o) => {
+            search(&o.text, o.limit)?;
+        }
+        Commands::Settings(o) => {
+            settings(o)?;
+        }
+        Commands::Subtitles(o) => {
+            set_subtitles(o.id, o.enabled)?;
         }
         Commands::Update(o) => {
             update(o.force, o.full_update, o.filter)?;
@@ -306,8 +1039,8 @@ pub fn main() -> Result<()> {
         Commands::Web => {
             crate::web::main()?;
         }
-        Commands::Worker => {
-            crate::worker::main()?;
+        Commands::Worker(o) => {
+            crate::worker::main(o.dry_run, o.workers)?;
         }
     }
 
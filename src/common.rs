@@ -5,6 +5,10 @@ use anyhow::Result;
 pub enum Service {
     Youtube,
     Vimeo,
+    YoutubePlaylist,
+    Soundcloud,
+    Rumble,
+    Bitchute,
 }
 
 impl Service {
@@ -12,12 +16,20 @@ impl Service {
         match self {
             Service::Youtube => "youtube",
             Service::Vimeo => "vimeo",
+            Service::YoutubePlaylist => "youtube_playlist",
+            Service::Soundcloud => "soundcloud",
+            Service::Rumble => "rumble",
+            Service::Bitchute => "bitchute",
         }
     }
     pub fn from_str(name: &str) -> Result<Self> {
         match name {
             "youtube" => Ok(Service::Youtube),
             "vimeo" => Ok(Service::Vimeo),
+            "youtube_playlist" => Ok(Service::YoutubePlaylist),
+            "soundcloud" => Ok(Service::Soundcloud),
+            "rumble" => Ok(Service::Rumble),
+            "bitchute" => Ok(Service::Bitchute),
             _ => Err(anyhow::anyhow!("Unknown service string {:?}", name)),
         }
     }
@@ -26,18 +38,228 @@ impl Service {
         match self {
             Service::Youtube => ChannelID::Youtube(YoutubeID {
                 id: chanid_str.into(),
+                handle: None,
             }),
             Service::Vimeo => ChannelID::Vimeo(VimeoID {
                 id: chanid_str.into(),
             }),
+            Service::YoutubePlaylist => ChannelID::YoutubePlaylist(YoutubePlaylistID {
+                id: chanid_str.into(),
+            }),
+            Service::Soundcloud => ChannelID::Soundcloud(SoundcloudID {
+                id: chanid_str.into(),
+            }),
+            Service::Rumble => ChannelID::Rumble(RumbleID {
+                id: chanid_str.into(),
+            }),
+            Service::Bitchute => ChannelID::Bitchute(BitchuteID {
+                id: chanid_str.into(),
+            }),
+        }
+    }
+
+    /// Extractor name used for the service in `--download-archive` files
+    pub fn archive_extractor(&self) -> &str {
+        match self {
+            Service::Youtube | Service::YoutubePlaylist => "youtube",
+            Service::Vimeo => "vimeo",
+            Service::Soundcloud => "soundcloud",
+            Service::Rumble => "rumble",
+            Service::Bitchute => "bitchute",
+        }
+    }
+
+    /// If the service only hosts audio, so downloads should use the audio-only arguments
+    pub fn is_audio_only(&self) -> bool {
+        matches!(self, Service::Soundcloud)
+    }
+}
+
+/// Where channel and video metadata is retrieved from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// The service's own API - Invidious for Youtube
+    Invidious,
+    /// Running `yt-dlp --dump-json`
+    Ytdlp,
+    /// The official YouTube Data API, which requires an API key
+    Api,
+    /// A Piped instance, an alternative to Invidious for Youtube
+    Piped,
+}
+
+impl Backend {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Backend::Invidious => "invidious",
+            Backend::Ytdlp => "ytdlp",
+            Backend::Api => "api",
+            Backend::Piped => "piped",
+        }
+    }
+    pub fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "invidious" => Ok(Backend::Invidious),
+            "ytdlp" => Ok(Backend::Ytdlp),
+            "api" => Ok(Backend::Api),
+            "piped" => Ok(Backend::Piped),
+            _ => Err(anyhow::anyhow!("Unknown backend string {:?}", name)),
+        }
+    }
+}
+
+/// How video titles etc are made safe to use in filenames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilenameStyle {
+    /// Only ASCII letters, digits, `-` and `.`, everything else replaced with `_`
+    Restrict,
+    /// Keep spaces and non-ASCII characters, only replacing ones not allowed in filenames
+    Unicode,
+}
+
+impl FilenameStyle {
+    pub fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "restrict" => Ok(FilenameStyle::Restrict),
+            "unicode" => Ok(FilenameStyle::Unicode),
+            _ => Err(anyhow::anyhow!("Unknown filename style {:?}", name)),
         }
     }
+
+    /// Make `value` safe to use as (part of) a filename
+    pub fn sanitize(&self, value: &str) -> String {
+        let mut ret = String::new();
+        for c in value.chars() {
+            let c = match self {
+                FilenameStyle::Restrict if c.is_ascii_alphanumeric() || c == '-' || c == '.' => c,
+                FilenameStyle::Restrict => '_',
+                FilenameStyle::Unicode
+                    if c.is_control()
+                        || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') =>
+                {
+                    '_'
+                }
+                FilenameStyle::Unicode => c,
+            };
+            if !(c == '_' && ret.ends_with('_')) {
+                ret.push(c);
+            }
+        }
+        // Leading dots would make hidden files
+        ret.trim_matches(|c: char| c == '_' || c.is_whitespace())
+            .trim_start_matches('.')
+            .to_string()
+    }
 }
 
 /// Identifier for channel on Youtube
 #[derive(Debug, Clone, PartialEq)]
 pub struct YoutubeID {
+    /// The `UC..` channel ID
     pub id: String,
+    /// The `@handle` (or legacy username) the channel was added by, kept for display
+    pub handle: Option<String>,
+}
+
+/// The various ways a Youtube channel can be referred to, before being resolved to a `UC..` ID
+#[derive(Debug, Clone, PartialEq)]
+pub enum YoutubeName {
+    /// The `UC..` channel ID itself
+    ChannelID(String),
+    /// A handle such as `@someone` (stored without the `@`)
+    Handle(String),
+    /// Legacy username, from `youtube.com/user/..`
+    Username(String),
+    /// Legacy custom URL, from `youtube.com/c/..`
+    CustomUrl(String),
+}
+
+impl YoutubeName {
+    /// Parse a channel URL, `@handle` or ID. Plain names are assumed to be handles
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let path = match input.find("youtube.com/") {
+            Some(idx) => &input[idx + "youtube.com/".len()..],
+            None => input,
+        };
+        let parts: Vec<&str> = path.split(['/', '?']).filter(|x| !x.is_empty()).collect();
+
+        let name = match parts.as_slice() {
+            ["channel", id, ..] => YoutubeName::ChannelID(id.to_string()),
+            ["user", name, ..] => YoutubeName::Username(name.to_string()),
+            ["c", name, ..] => YoutubeName::CustomUrl(name.to_string()),
+            [name, ..] if name.starts_with('@') && name.len() > 1 => {
+                YoutubeName::Handle(name[1..].to_string())
+            }
+            [id] if id.starts_with("UC") && id.len() == 24 => {
+                YoutubeName::ChannelID(id.to_string())
+            }
+            [name] => YoutubeName::Handle(name.to_string()),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Could not find Youtube channel in {:?}",
+                    input
+                ))
+            }
+        };
+        Ok(name)
+    }
+
+    /// URL of the channel page, as used to look up the channel ID
+    pub fn url(&self) -> String {
+        match self {
+            YoutubeName::ChannelID(id) => format!("https://www.youtube.com/channel/{}", id),
+            YoutubeName::Handle(h) => format!("https://www.youtube.com/@{}", h),
+            YoutubeName::Username(u) => format!("https://www.youtube.com/user/{}", u),
+            YoutubeName::CustomUrl(c) => format!("https://www.youtube.com/c/{}", c),
+        }
+    }
+
+    /// Name shown to the user, or `None` for a plain channel ID
+    pub fn display(&self) -> Option<String> {
+        match self {
+            YoutubeName::ChannelID(_) => None,
+            YoutubeName::Handle(h) => Some(format!("@{}", h)),
+            YoutubeName::Username(u) => Some(u.clone()),
+            YoutubeName::CustomUrl(c) => Some(c.clone()),
+        }
+    }
+}
+
+/// Find the video ID in a Youtube video URL (`watch?v=`, `youtu.be/`, `shorts/` etc), or the ID itself
+pub fn parse_youtube_video_id(input: &str) -> Result<String> {
+    let input = input.trim();
+    let is_id = |x: &str| {
+        x.len() == 11
+            && x.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+
+    let id = if let Some(idx) = input.find("youtu.be/") {
+        input[idx + "youtu.be/".len()..]
+            .split(['/', '?', '&', '#'])
+            .next()
+    } else if let Some(idx) = input.find("youtube.com/") {
+        let path = &input[idx + "youtube.com/".len()..];
+        match path.split_once('?') {
+            Some((_, query)) if path.starts_with("watch") => {
+                query.split('&').find_map(|kv| kv.strip_prefix("v="))
+            }
+            _ => match path.split(['/', '?', '#']).collect::<Vec<_>>().as_slice() {
+                ["shorts" | "live" | "embed" | "v", id, ..] => Some(*id),
+                _ => None,
+            },
+        }
+    } else {
+        Some(input)
+    };
+    match id {
+        Some(id) if is_id(id) => Ok(id.to_string()),
+        _ => Err(anyhow::anyhow!(
+            "Could not find Youtube video in {:?}",
+            input
+        )),
+    }
 }
 
 /// Identifier for channel on Vimeo
@@ -46,11 +268,39 @@ pub struct VimeoID {
     pub id: String,
 }
 
+/// Identifier for a playlist on Youtube (the `PL..` string)
+#[derive(Debug, Clone, PartialEq)]
+pub struct YoutubePlaylistID {
+    pub id: String,
+}
+
+/// Identifier for a user on SoundCloud (numeric user ID)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundcloudID {
+    pub id: String,
+}
+
+/// Identifier for a channel on Rumble (path like `c/SomeChannel` or `user/someone`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RumbleID {
+    pub id: String,
+}
+
+/// Identifier for a channel on BitChute (the random string in the channel URL)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitchuteID {
+    pub id: String,
+}
+
 /// Identifier for a channel on a given service
 #[derive(Debug, Clone, PartialEq)]
 pub enum ChannelID {
     Youtube(YoutubeID),
     Vimeo(VimeoID),
+    YoutubePlaylist(YoutubePlaylistID),
+    Soundcloud(SoundcloudID),
+    Rumble(RumbleID),
+    Bitchute(BitchuteID),
 }
 
 impl ChannelID {
@@ -58,17 +308,32 @@ impl ChannelID {
         match self {
             ChannelID::Vimeo(x) => &x.id,
             ChannelID::Youtube(x) => &x.id,
+            ChannelID::YoutubePlaylist(x) => &x.id,
+            ChannelID::Soundcloud(x) => &x.id,
+            ChannelID::Rumble(x) => &x.id,
+            ChannelID::Bitchute(x) => &x.id,
+        }
+    }
+    /// Youtube `@handle` or username the channel was found by, if any
+    pub fn handle(&self) -> Option<&str> {
+        match self {
+            ChannelID::Youtube(x) => x.handle.as_deref(),
+            _ => None,
         }
     }
     pub fn service(&self) -> Service {
         match self {
             ChannelID::Vimeo(_) => Service::Vimeo,
             ChannelID::Youtube(_) => Service::Youtube,
+            ChannelID::YoutubePlaylist(_) => Service::YoutubePlaylist,
+            ChannelID::Soundcloud(_) => Service::Soundcloud,
+            ChannelID::Rumble(_) => Service::Rumble,
+            ChannelID::Bitchute(_) => Service::Bitchute,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VideoStatus {
     /// New video
     New,
@@ -87,6 +352,18 @@ pub enum VideoStatus {
 
     /// Marked by user as uninteresting
     Ignore,
+
+    /// Scheduled premiere or stream which hasn't happened yet
+    Upcoming,
+
+    /// Members-only or paid video which can't be downloaded
+    Unavailable,
+
+    /// No longer listed by the source (deleted or made private)
+    Removed,
+
+    /// Downloaded file failed verification (empty, or shorter than the video)
+    Corrupt,
 }
 
 impl VideoStatus {
@@ -98,6 +375,10 @@ impl VideoStatus {
             VideoStatus::Grabbed => "GR",
             VideoStatus::GrabError => "GE",
             VideoStatus::Ignore => "IG",
+            VideoStatus::Upcoming => "UP",
+            VideoStatus::Unavailable => "UA",
+            VideoStatus::Removed => "RM",
+            VideoStatus::Corrupt => "CO",
         }
     }
 
@@ -109,7 +390,224 @@ impl VideoStatus {
             "GR" => Ok(VideoStatus::Grabbed),
             "GE" => Ok(VideoStatus::GrabError),
             "IG" => Ok(VideoStatus::Ignore),
+            "UP" => Ok(VideoStatus::Upcoming),
+            "UA" => Ok(VideoStatus::Unavailable),
+            "RM" => Ok(VideoStatus::Removed),
+            "CO" => Ok(VideoStatus::Corrupt),
             _ => Err(anyhow::anyhow!("Unknown status string {:?}", status)),
         }
     }
 }
+
+/// Whether a video is a live stream, as opposed to a normal upload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LiveStatus {
+    /// Normal upload, or a stream which has finished
+    NotLive,
+
+    /// Currently streaming
+    Live,
+
+    /// Scheduled stream which hasn't started yet
+    Upcoming,
+}
+
+impl LiveStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            LiveStatus::NotLive => "none",
+            LiveStatus::Live => "live",
+            LiveStatus::Upcoming => "upcoming",
+        }
+    }
+
+    pub fn from_str(status: &str) -> Result<Self> {
+        match status {
+            "none" => Ok(LiveStatus::NotLive),
+            "live" => Ok(LiveStatus::Live),
+            "upcoming" => Ok(LiveStatus::Upcoming),
+            _ => Err(anyhow::anyhow!("Unknown live status string {:?}", status)),
+        }
+    }
+
+    /// True if the stream hasn't finished yet, so can't be downloaded completely
+    pub fn is_ongoing(&self) -> bool {
+        !matches!(self, LiveStatus::NotLive)
+    }
+}
+
+/// Why a download failed, from the downloader's error messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadErrorKind {
+    /// Deleted, private, or removed by the uploader
+    Unavailable,
+    /// Not available in this country
+    GeoBlocked,
+    /// Connection problems, timeouts, server errors or rate limiting
+    Network,
+    /// Requested format doesn't exist (possibly not yet, for new uploads)
+    FormatUnavailable,
+    Other,
+}
+
+impl DownloadErrorKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DownloadErrorKind::Unavailable => "unavailable",
+            DownloadErrorKind::GeoBlocked => "geo_blocked",
+            DownloadErrorKind::Network => "network",
+            DownloadErrorKind::FormatUnavailable => "format_unavailable",
+            DownloadErrorKind::Other => "other",
+        }
+    }
+
+    pub fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "unavailable" => Ok(DownloadErrorKind::Unavailable),
+            "geo_blocked" => Ok(DownloadErrorKind::GeoBlocked),
+            "network" => Ok(DownloadErrorKind::Network),
+            "format_unavailable" => Ok(DownloadErrorKind::FormatUnavailable),
+            "other" => Ok(DownloadErrorKind::Other),
+            _ => Err(anyhow::anyhow!("Unknown download error kind {:?}", kind)),
+        }
+    }
+
+    /// Classify an error message from youtube-dl/yt-dlp
+    pub fn classify(message: &str) -> Self {
+        let msg = message.to_lowercase();
+        let any = |patterns: &[&str]| patterns.iter().any(|p| msg.contains(p));
+        if any(&[
+            "available in your country",
+            "geo restriction",
+            "geo-restricted",
+            "blocked it in your country",
+        ]) {
+            DownloadErrorKind::GeoBlocked
+        } else if any(&[
+            "video unavailable",
+            "removed by the uploader",
+            "has been removed",
+            "private video",
+            "account associated with this video has been terminated",
+            "no longer available",
+            "does not exist",
+        ]) {
+            DownloadErrorKind::Unavailable
+        } else if any(&[
+            "requested format is not available",
+            "no video formats found",
+        ]) {
+            DownloadErrorKind::FormatUnavailable
+        } else if any(&[
+            "unable to download",
+            "timed out",
+            "connection reset",
+            "connection refused",
+            "name resolution",
+            "network is unreachable",
+            "http error 429",
+            "http error 5",
+            "incompleteread",
+        ]) {
+            DownloadErrorKind::Network
+        } else {
+            DownloadErrorKind::Other
+        }
+    }
+
+    /// If trying again later could succeed
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            DownloadErrorKind::Unavailable | DownloadErrorKind::GeoBlocked
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filename_style() {
+        let title = "A vidéo: part 1/2 ";
+        assert_eq!(FilenameStyle::Restrict.sanitize(title), "A_vid_o_part_1_2");
+        assert_eq!(FilenameStyle::Unicode.sanitize(title), "A vidéo_ part 1_2");
+        assert_eq!(FilenameStyle::Unicode.sanitize("..hidden"), "hidden");
+    }
+
+    #[test]
+    fn test_download_error_kind() {
+        use DownloadErrorKind::*;
+        for (msg, kind) in &[
+            ("[youtube] abc: Video unavailable. This video has been removed by the uploader", Unavailable),
+            ("[youtube] abc: Private video. Sign in if you've been granted access", Unavailable),
+            ("[youtube] abc: The uploader has not made this video available in your country", GeoBlocked),
+            ("[youtube] abc: Requested format is not available. Use --list-formats", FormatUnavailable),
+            ("[youtube] abc: Unable to download webpage: <urlopen error [Errno -3] Temporary failure in name resolution>", Network),
+            ("unable to download video data: HTTP Error 503: Service Unavailable", Network),
+            ("Something odd happened", Other),
+        ] {
+            assert_eq!(DownloadErrorKind::classify(msg), *kind, "{}", msg);
+            assert_eq!(DownloadErrorKind::from_str(kind.as_str()).unwrap(), *kind);
+        }
+        assert!(Network.is_retryable());
+        assert!(!Unavailable.is_retryable());
+    }
+
+    #[test]
+    fn test_youtube_name_parse() -> Result<()> {
+        assert_eq!(
+            YoutubeName::parse("https://www.youtube.com/@someone/videos")?,
+            YoutubeName::Handle("someone".into())
+        );
+        assert_eq!(
+            YoutubeName::parse("@someone")?,
+            YoutubeName::Handle("someone".into())
+        );
+        assert_eq!(
+            YoutubeName::parse("someone")?,
+            YoutubeName::Handle("someone".into())
+        );
+        assert_eq!(
+            YoutubeName::parse("https://youtube.com/user/thegreatsd")?,
+            YoutubeName::Username("thegreatsd".into())
+        );
+        assert_eq!(
+            YoutubeName::parse("https://www.youtube.com/c/SomeName?view=0")?,
+            YoutubeName::CustomUrl("SomeName".into())
+        );
+        assert_eq!(
+            YoutubeName::parse("https://www.youtube.com/channel/UCUBfKCp83QT19JCUekEdxOQ")?,
+            YoutubeName::ChannelID("UCUBfKCp83QT19JCUekEdxOQ".into())
+        );
+        assert_eq!(
+            YoutubeName::parse("UCUBfKCp83QT19JCUekEdxOQ")?,
+            YoutubeName::ChannelID("UCUBfKCp83QT19JCUekEdxOQ".into())
+        );
+        assert!(YoutubeName::parse("https://www.youtube.com/").is_err());
+        assert_eq!(
+            YoutubeName::Handle("someone".into()).display(),
+            Some("@someone".into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_youtube_video_id() -> Result<()> {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=10",
+            "https://youtu.be/dQw4w9WgXcQ?t=10",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ?si=abc",
+            " dQw4w9WgXcQ ",
+        ] {
+            assert_eq!(parse_youtube_video_id(url)?, "dQw4w9WgXcQ");
+        }
+        assert!(parse_youtube_video_id("https://www.youtube.com/@someone").is_err());
+        assert!(parse_youtube_video_id("https://www.youtube.com/watch?list=PL123").is_err());
+        assert!(parse_youtube_video_id("not a video").is_err());
+        Ok(())
+    }
+}
@@ -1,14 +1,93 @@
 use directories::ProjectDirs;
+
+use crate::common::{Backend, FilenameStyle};
 use std::path::PathBuf;
 
 pub struct Config {
     db_filepath: PathBuf,
+    /// Use SQLite's write-ahead log, so the web interface, workers and CLI can read while another writes
+    pub db_wal: bool,
+    /// Milliseconds to wait for another connection to finish writing before failing with "database is locked"
+    pub db_busy_timeout: u64,
+    /// SQLite `synchronous` setting, one of `OFF`, `NORMAL`, `FULL` or `EXTRA`
+    pub db_synchronous: String,
+    /// Copy the database file before upgrading its schema, as migrations can't be undone
+    pub db_migrate_backup: bool,
+    /// Key the database is encrypted with, needing the `sqlcipher` feature
+    pub db_key: Option<String>,
+    /// Command printing the database key, like `secret-tool lookup service vidl` to get it from
+    /// the keyring, used when `db_key` isn't set
+    pub db_key_command: Option<String>,
     pub web_host: String,
     pub web_port: String,
+    /// Path the web interface is served under, like `/vidl`, or empty if at the root
+    pub base_path: String,
+    /// Language the web interface is shown in
+    pub ui_language: crate::i18n::Locale,
+    /// POST requests allowed per minute from each address to the web interface, or 0 for no limit
+    pub post_rate_limit: u32,
+    /// Downloader executable (name or path), or `None` to use whichever of yt-dlp or youtube-dl is installed
+    pub downloader: Option<String>,
     pub extra_youtubedl_args: Vec<String>,
+    /// Arguments used instead of `extra_youtubedl_args` for audio-only services
+    pub audio_youtubedl_args: Vec<String>,
     pub download_dir: PathBuf,
+    /// Where downloads are written until they finish, before being moved into the download dir
+    pub staging_dir: Option<PathBuf>,
     pub filename_format: String,
+    /// How values filled into `filename_format` are made safe for filenames
+    pub filename_style: FilenameStyle,
+    /// Number of worker threads for channel updates and caching thumbnails, which is how many
+    /// channels are updated concurrently
     pub num_workers: usize,
+    /// Number of worker threads for downloads, which is how many videos are downloaded at once
+    pub num_download_workers: usize,
+    pub vimeo_access_token: Option<String>,
+    pub soundcloud_client_id: Option<String>,
+    /// Where Youtube channel data is retrieved from
+    pub backend: Backend,
+    /// Key for the YouTube Data API, used by `Backend::Api`
+    pub youtube_api_key: Option<String>,
+    /// Caption languages to download as subtitles, when the video has them
+    pub subtitle_langs: Vec<String>,
+    /// Language (e.g `en-GB`) requested from sources, for titles, dates etc
+    pub language: Option<String>,
+    /// Region/country code (e.g `GB`) requested from sources
+    pub region: Option<String>,
+    /// Where API responses are cached
+    pub http_cache_dir: PathBuf,
+    /// Seconds a cached API response is used without checking if it has changed. 0 disables caching
+    pub http_cache_ttl: u64,
+    /// Where thumbnails are cached, or `None` to only cache them in memory
+    pub thumbnail_cache_dir: Option<PathBuf>,
+    /// Total bytes of cached thumbnails, after which the least recently used are removed
+    pub thumbnail_cache_size: u64,
+    /// Re-encode cached thumbnails as WebP, with ffmpeg
+    pub thumbnail_webp: bool,
+    /// Make an extra request for each new video to get tags, captions, chapters and the full description
+    pub fetch_video_details: bool,
+    /// Number of fragments of a DASH/HLS video downloaded at once (yt-dlp only)
+    pub concurrent_fragments: Option<u32>,
+    /// Size of chunks requested when downloading, like `10M`, to avoid throttling
+    pub http_chunk_size: Option<String>,
+    /// Check the downloaded file's duration with ffprobe matches the video's
+    pub verify_duration: bool,
+    /// Seconds to wait for running downloads when stopping, before killing them so they are
+    /// resumed on restart. Waits for them to finish if unset
+    pub shutdown_timeout: Option<u64>,
+    /// How many times a failed download is retried
+    pub max_retries: i64,
+    /// Seconds until a failed download is first retried, doubling after each failure
+    pub retry_delay: i64,
+    /// Number of full updates in a row a video must be missing from its source for to be
+    /// marked as removed
+    pub removed_after: i64,
+    /// Download Youtube videos directly from Invidious when possible, instead of with `downloader`
+    pub native_download: bool,
+    /// yt-dlp `--download-archive` file which grabbed videos are appended to
+    pub download_archive: Option<PathBuf>,
+    /// Write a Kodi/Jellyfin `.nfo` file next to each downloaded video
+    pub write_nfo: bool,
 }
 
 impl Config {
@@ -22,10 +101,68 @@ impl Config {
             .unwrap_or(cfg);
         let db_filepath = config_dir.join("vidl.sqlite3");
 
+        let youtube_api_key = std::env::var("VIDL_YOUTUBE_API_KEY").ok();
+        let db_wal = std::env::var("VIDL_DB_WAL")
+            .map(|v| !matches!(v.as_str(), "0" | "false" | "no"))
+            .unwrap_or(true);
+        let num_workers = std::env::var("VIDL_NUM_WORKERS")
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(4);
+        let thumbnail_cache_mb: u64 = std::env::var("VIDL_THUMBNAIL_CACHE_MB")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(100);
+
         Config {
             db_filepath,
+            db_wal,
+            db_busy_timeout: std::env::var("VIDL_DB_BUSY_TIMEOUT")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(5000),
+            // NORMAL is safe from corruption with the write-ahead log, only risking the last
+            // transactions on power loss
+            db_synchronous: std::env::var("VIDL_DB_SYNCHRONOUS")
+                .ok()
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| matches!(s.as_str(), "OFF" | "NORMAL" | "FULL" | "EXTRA"))
+                .unwrap_or_else(|| if db_wal { "NORMAL" } else { "FULL" }.into()),
+            db_migrate_backup: std::env::var("VIDL_DB_MIGRATE_BACKUP")
+                .map(|v| !matches!(v.as_str(), "0" | "false" | "no"))
+                .unwrap_or(true),
+            db_key: std::env::var("VIDL_DB_KEY").ok().filter(|x| !x.is_empty()),
+            db_key_command: std::env::var("VIDL_DB_KEY_COMMAND")
+                .ok()
+                .filter(|x| !x.is_empty()),
+            http_cache_dir: config_dir.join("http_cache"),
+            http_cache_ttl: std::env::var("VIDL_HTTP_CACHE_TTL")
+                .ok()
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(300),
+            thumbnail_cache_dir: Some(config_dir.join("thumbnails"))
+                .filter(|_| thumbnail_cache_mb > 0),
+            thumbnail_cache_size: thumbnail_cache_mb * 1024 * 1024,
+            thumbnail_webp: std::env::var("VIDL_THUMBNAIL_WEBP")
+                .map(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
             web_host: "0.0.0.0".into(),
             web_port: "8448".into(),
+            base_path: std::env::var("VIDL_BASE_PATH")
+                .map(|p| normalize_base_path(&p))
+                .unwrap_or_default(),
+            ui_language: std::env::var("VIDL_UI_LANGUAGE")
+                .ok()
+                .and_then(|l| crate::i18n::Locale::from_str(&l).ok())
+                .unwrap_or(crate::i18n::Locale::English),
+            post_rate_limit: std::env::var("VIDL_POST_RATE_LIMIT")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(60),
+            downloader: std::env::var("VIDL_DOWNLOADER")
+                .ok()
+                .filter(|x| !x.is_empty()),
             extra_youtubedl_args: vec![
                 "--restrict-filenames".into(),
                 "--continue".into(),
@@ -33,11 +170,93 @@ impl Config {
                 "bestvideo[height<=1080]+bestaudio/best".into(),
                 "--sponsorblock-mark=sponsor,intro,outro,selfpromo,interaction".into(),
             ],
+            audio_youtubedl_args: vec![
+                "--restrict-filenames".into(),
+                "--continue".into(),
+                "-f".into(),
+                "bestaudio/best".into(),
+                "--extract-audio".into(),
+            ],
             download_dir: PathBuf::from(
                 std::env::var("VIDL_DOWNLOAD_DIR").unwrap_or("./download".into()),
             ),
+            staging_dir: std::env::var("VIDL_STAGING_DIR")
+                .ok()
+                .filter(|x| !x.is_empty())
+                .map(PathBuf::from),
             filename_format: "%(uploader)s__%(upload_date)s_%(title)s__%(id)s.%(ext)s".into(),
-            num_workers: 4,
+            filename_style: std::env::var("VIDL_FILENAME_STYLE")
+                .ok()
+                .and_then(|s| FilenameStyle::from_str(&s).ok())
+                .unwrap_or(FilenameStyle::Restrict),
+            num_workers,
+            num_download_workers: std::env::var("VIDL_NUM_DOWNLOAD_WORKERS")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(num_workers),
+            vimeo_access_token: std::env::var("VIDL_VIMEO_TOKEN").ok(),
+            soundcloud_client_id: std::env::var("VIDL_SOUNDCLOUD_CLIENT_ID").ok(),
+            backend: std::env::var("VIDL_BACKEND")
+                .ok()
+                .and_then(|b| Backend::from_str(&b).ok())
+                .unwrap_or(if youtube_api_key.is_some() {
+                    Backend::Api
+                } else {
+                    Backend::Invidious
+                }),
+            youtube_api_key,
+            subtitle_langs: std::env::var("VIDL_SUBTITLE_LANGS")
+                .map(|l| {
+                    l.split(',')
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            language: std::env::var("VIDL_LANGUAGE")
+                .ok()
+                .filter(|x| !x.is_empty()),
+            region: std::env::var("VIDL_REGION").ok().filter(|x| !x.is_empty()),
+            fetch_video_details: std::env::var("VIDL_FETCH_VIDEO_DETAILS")
+                .map(|v| !matches!(v.as_str(), "0" | "false" | "no"))
+                .unwrap_or(true),
+            concurrent_fragments: std::env::var("VIDL_CONCURRENT_FRAGMENTS")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .filter(|n| *n > 1),
+            http_chunk_size: std::env::var("VIDL_HTTP_CHUNK_SIZE")
+                .ok()
+                .filter(|x| !x.is_empty()),
+            verify_duration: std::env::var("VIDL_VERIFY_DURATION")
+                .map(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            shutdown_timeout: std::env::var("VIDL_SHUTDOWN_TIMEOUT")
+                .ok()
+                .and_then(|n| n.parse().ok()),
+            max_retries: std::env::var("VIDL_MAX_RETRIES")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(3),
+            retry_delay: std::env::var("VIDL_RETRY_DELAY")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(600),
+            removed_after: std::env::var("VIDL_REMOVED_AFTER")
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(1),
+            native_download: std::env::var("VIDL_NATIVE_DOWNLOAD")
+                .map(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+            download_archive: std::env::var("VIDL_DOWNLOAD_ARCHIVE")
+                .ok()
+                .filter(|x| !x.is_empty())
+                .map(PathBuf::from),
+            write_nfo: std::env::var("VIDL_WRITE_NFO")
+                .map(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
         }
     }
 
@@ -45,3 +264,28 @@ impl Config {
         &self.db_filepath
     }
 }
+
+/// Base path with a leading slash and no trailing slash, so `vidl/`, `/vidl/` and `/vidl` are
+/// all `/vidl`, while `/` is empty
+fn normalize_base_path(path: &str) -> String {
+    let path = path.trim().trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path(""), "");
+        assert_eq!(normalize_base_path("/"), "");
+        assert_eq!(normalize_base_path("vidl"), "/vidl");
+        assert_eq!(normalize_base_path("/vidl/"), "/vidl");
+        assert_eq!(normalize_base_path("/apps/vidl"), "/apps/vidl");
+    }
+}
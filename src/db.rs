@@ -1,16 +1,35 @@
 use std::collections::HashSet;
+use std::sync::Mutex;
 
 use anyhow::{Context, Result};
-use log::{debug, error, trace};
-use rusqlite::types::FromSql;
-use rusqlite::{params, Connection};
+use lazy_static::lazy_static;
+use log::{debug, error, info, trace, warn};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::{FromSql, ToSql, ToSqlOutput};
+use rusqlite::{params, Connection, OptionalExtension};
 use thiserror::Error;
 
-use crate::common::{ChannelID, Service, VideoStatus};
+use crate::common::{Backend, ChannelID, DownloadErrorKind, LiveStatus, Service, VideoStatus};
 use crate::config::Config;
-use crate::source::base::ChannelData;
-use crate::source::base::{ChannelMetadata, VideoInfo};
-use crate::source::invidious::YoutubeQuery;
+use crate::download::SubtitleFile;
+use crate::events::Event;
+use crate::source::base::{ChannelMetadata, Chapter, Thumbnail, VideoInfo};
+
+/// What `Database::maintain` found, and fixed unless it was a dry run
+#[derive(Debug, Default)]
+pub struct MaintenanceReport {
+    /// Problems found by SQLite's integrity check, empty if there were none
+    pub integrity_errors: Vec<String>,
+    /// Tables and how many of their rows refer to a row which doesn't exist, directly or
+    /// through another such row
+    pub orphans: Vec<(String, usize)>,
+}
+
+/// Number of video details `Channel::fetch_video_details` requests at once
+const DETAIL_REQUESTS: usize = 4;
+
+/// Channel ID of `Channel::adhoc`, which can't clash with a real Youtube channel
+pub const ADHOC_CHANNEL_ID: &str = "_adhoc";
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
@@ -19,6 +38,14 @@ pub enum DatabaseError {
 
     #[error("Invalid status string in database {0}")]
     InvalidStatusInDB(String),
+
+    #[error("Invalid backend string in database {0}")]
+    InvalidBackendInDB(String),
+    #[error("Invalid live status string in database {0}")]
+    InvalidLiveStatusInDB(String),
+
+    #[error("Video already in channel {0}")]
+    VideoExists(String),
 }
 
 #[derive(Debug)]
@@ -38,15 +65,58 @@ pub struct DBVideoInfo {
 
     /// When it was added to the VIDL database (not to be confused with the `published_at` date on `VideoInfo`)
     pub date_added: chrono::DateTime<chrono::Utc>,
+
+    /// Marked as watched, independent of the status
+    pub watched: bool,
+    /// When it was marked as watched, if it is. `None` for videos marked before this was recorded
+    pub watched_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Free-form notes written in vidl
+    pub notes: Option<String>,
+
+    /// Why the last download failed, as reported by the downloader. Kept once the video is
+    /// downloaded, so only meaningful with the `GrabError` status
+    pub error_message: Option<String>,
+
+    /// When the source last listed the video, or `None` if not since this was recorded
+    pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// When the video was found to be no longer listed by the source, independent of the status
+    pub removed_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl DBVideoInfo {
+    /// Find video by its ID on `service` (e.g Youtube's `dQw4w9WgXcQ`), in any channel. Youtube
+    /// playlists share Youtube's video IDs, so either service finds videos from both
+    pub fn get_by_video_id(
+        db: &Database,
+        service: Service,
+        video_id: &str,
+    ) -> Result<Option<DBVideoInfo>> {
+        let mut q = db.conn.prepare(
+            "SELECT video.id, channel.service FROM video JOIN channel ON channel.id = video.channel
+            WHERE video.video_id=?1
+            ORDER BY video.id",
+        )?;
+        let found = q
+            .query_map(params![video_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Service>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(i64, Service)>>>()
+            .context("Failed to find video by ID")?
+            .into_iter()
+            .find(|(_, s)| s.archive_extractor() == service.archive_extractor());
+        found
+            .map(|(id, _)| DBVideoInfo::get_by_sqlid(db, id))
+            .transpose()
+    }
+
     /// Retrieve video's info by SQL ID
     pub fn get_by_sqlid(db: &Database, id: i64) -> Result<DBVideoInfo> {
         let chan = db
             .conn
             .query_row(
-                "SELECT id, status, video_id, url, title, description, description_alt, thumbnail, published_at, channel, duration, date_added, title_alt FROM video
+                "SELECT id, status, video_id, url, title, description, description_alt, thumbnail, published_at, channel, duration, date_added, title_alt, live_status, view_count, captions, scheduled_at, members_only, watched, watched_at, notes, error_message, last_seen, removed_at FROM video
                 WHERE id=?1",
                 params![id],
                 |row| {
@@ -62,10 +132,22 @@ impl DBVideoInfo {
                             description: row.get("description")?,
                             description_alt: row.get("description_alt")?,
                             thumbnail_url: row.get("thumbnail")?,
+                            thumbnails: vec![],
                             published_at: row.get("published_at")?,
                             duration: row.get("duration")?,
+                            live_status: row.get("live_status")?,
+                            view_count: row.get("view_count")?,
+                            captions: split_captions(row.get("captions")?),
+                            scheduled_at: row.get("scheduled_at")?,
+                            members_only: row.get("members_only")?,
                         },
                         chanid: row.get("channel")?,
+                        watched: row.get("watched")?,
+                        watched_at: row.get("watched_at")?,
+                        notes: row.get("notes")?,
+                        error_message: row.get("error_message")?,
+                        last_seen: row.get("last_seen")?,
+                        removed_at: row.get("removed_at")?,
                     })
                 },
             )
@@ -86,997 +168,5913 @@ impl DBVideoInfo {
         db.conn
             .execute(
                 "UPDATE video SET status=?1 WHERE id=?2",
-                params![status.as_str(), self.id],
+                params![status, self.id],
             )
             .context("Failed to update video status")?;
+        crate::events::publish(Event::VideoStatus {
+            video: self.id,
+            status,
+        });
 
         // FIXME: Should this update self.status?
 
         Ok(())
     }
 
-    /// Set alternative title
-    pub fn set_title_alt(&self, db: &Database, title: String) -> Result<()> {
-        // Update DB
+    /// Mark as `GrabError`, scheduling a retry with exponential backoff (`delay`, then twice
+    /// that, and so on) unless it has already failed `max_retries` times. Returns when it will be retried
+    pub fn record_grab_error(
+        &self,
+        db: &Database,
+        kind: DownloadErrorKind,
+        message: &str,
+        max_retries: i64,
+        delay: chrono::Duration,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let (retry_count, _) = self.retry_state(db)?;
+        let retry_count = retry_count + 1;
+        let retry_at = if kind.is_retryable() && retry_count <= max_retries {
+            Some(chrono::Utc::now() + delay * 2i32.pow((retry_count - 1).min(16) as u32))
+        } else {
+            None
+        };
         db.conn
             .execute(
-                "UPDATE video SET title_alt=?1 WHERE id=?2",
-                params![&title, self.id],
+                "UPDATE video SET status=?1, retry_count=?2, retry_at=?3, error_kind=?4, error_message=?5
+                WHERE id=?6",
+                params![
+                    VideoStatus::GrabError,
+                    retry_count,
+                    retry_at,
+                    kind.as_str(),
+                    message,
+                    self.id
+                ],
             )
-            .context("Failed to update alt video title")?;
+            .context("Failed to record video grab error")?;
+        crate::events::publish(Event::VideoStatus {
+            video: self.id,
+            status: VideoStatus::GrabError,
+        });
+        Ok(retry_at)
+    }
 
-        Ok(())
+    /// Queue a `GrabError` video again, starting its automatic retries over. Returns false if
+    /// the video had not failed
+    pub fn retry(&self, db: &Database) -> Result<bool> {
+        let changed = db
+            .conn
+            .execute(
+                "UPDATE video SET status=?1, retry_count=0, retry_at=NULL WHERE id=?2 AND status=?3",
+                params![
+                    VideoStatus::Queued,
+                    self.id,
+                    VideoStatus::GrabError
+                ],
+            )
+            .context("Failed to retry video")?;
+        if changed > 0 {
+            crate::events::publish(Event::VideoStatus {
+                video: self.id,
+                status: VideoStatus::Queued,
+            });
+        }
+        Ok(changed > 0)
     }
 
-    /// Set alternative title
-    pub fn set_description_alt(&self, db: &Database, title: String) -> Result<()> {
-        // Update DB
+    /// Mark as watched now, keeping the original time if it already was, or as unwatched
+    pub fn set_watched(&self, db: &Database, watched: bool) -> Result<()> {
+        let now = watched.then(chrono::Utc::now);
         db.conn
             .execute(
-                "UPDATE video SET description_alt=?1 WHERE id=?2",
-                params![&title, self.id],
+                "UPDATE video SET watched=?1,
+                    watched_at=CASE WHEN ?1 AND watched THEN watched_at ELSE ?2 END
+                WHERE id=?3",
+                params![watched, now, self.id],
             )
-            .context("Failed to update alt video description")?;
-
+            .context("Failed to update video watched flag")?;
         Ok(())
     }
-}
 
-/// Wraps connection to a database
-pub struct Database {
-    pub conn: Connection,
-}
+    /// Why the last download failed
+    pub fn error_kind(&self, db: &Database) -> Result<Option<DownloadErrorKind>> {
+        let kind: Option<String> = db
+            .conn
+            .query_row(
+                "SELECT error_kind FROM video WHERE id=?1",
+                params![self.id],
+                |row| row.get("error_kind"),
+            )
+            .context("Failed to get video error kind")?;
+        kind.map(|k| DownloadErrorKind::from_str(&k)).transpose()
+    }
 
-impl Database {
-    fn connect(cfg: &Config, create: bool) -> Result<Connection> {
-        let path = cfg.db_filepath();
-        if let Some(p) = path.parent() {
-            if !path.exists() {
-                debug!("Creating {:?}", p);
-                std::fs::create_dir_all(p)?
-            }
-        };
-        debug!("Loading DB from {:?}", path);
+    /// Download priority, higher is downloaded sooner
+    pub fn priority(&self, db: &Database) -> Result<i64> {
+        db.conn
+            .query_row(
+                "SELECT priority FROM video WHERE id=?1",
+                params![self.id],
+                |row| row.get("priority"),
+            )
+            .context("Failed to get video priority")
+    }
 
-        use rusqlite::OpenFlags;
-        let flags = if create {
-            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
-        } else {
-            OpenFlags::SQLITE_OPEN_READ_WRITE
-        };
-        let conn = Connection::open_with_flags(path, flags)?;
+    pub fn set_priority(&self, db: &Database, priority: i64) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET priority=?1 WHERE id=?2",
+                params![priority, self.id],
+            )
+            .context("Failed to update video priority")?;
+        Ok(())
+    }
 
-        Ok(conn)
+    /// Give higher priority than any other video, so it is downloaded next
+    pub fn bump_priority(&self, db: &Database) -> Result<()> {
+        let max: i64 = db
+            .conn
+            .query_row("SELECT COALESCE(MAX(priority), 0) FROM video", [], |row| {
+                row.get(0)
+            })
+            .context("Failed to get highest video priority")?;
+        self.set_priority(db, max + 1)
     }
 
-    /// Create a new database
-    pub fn create(cfg: &Config) -> Result<Database> {
-        // Create new database
-        let conn = Database::connect(&cfg, true)?;
+    /// Take out of the download queue, changing from `Queued` back to `New`. Returns false if it
+    /// was no longer queued
+    pub fn unqueue(&self, db: &Database) -> Result<bool> {
+        let changed = db
+            .conn
+            .execute(
+                "UPDATE video SET status=?1 WHERE id=?2 AND status=?3",
+                params![VideoStatus::New, self.id, VideoStatus::Queued],
+            )
+            .context("Failed to remove video from queue")?;
+        if changed == 1 {
+            crate::events::publish(Event::VideoStatus {
+                video: self.id,
+                status: VideoStatus::New,
+            });
+        }
+        Ok(changed == 1)
+    }
 
-        // Create tables
-        let mig = crate::db_migration::get_migrator(&conn);
-        mig.setup()?;
-        mig.upgrade()?;
+    /// Change from `Queued` to `Downloading`. Returns false if it was no longer queued,
+    /// e.g as another worker took it first
+    pub fn claim_download(&self, db: &Database) -> Result<bool> {
+        let changed = db
+            .conn
+            .execute(
+                "UPDATE video SET status=?1, download_pid=?2, download_heartbeat=datetime('now')
+                    WHERE id=?3 AND status=?4",
+                params![
+                    VideoStatus::Downloading,
+                    std::process::id(),
+                    self.id,
+                    VideoStatus::Queued
+                ],
+            )
+            .context("Failed to claim video for download")?;
+        if changed == 1 {
+            crate::events::publish(Event::VideoStatus {
+                video: self.id,
+                status: VideoStatus::Downloading,
+            });
+        }
+        Ok(changed == 1)
+    }
 
-        // Return connection
-        Ok(Database { conn })
+    /// Show this process is still downloading the video, so `requeue_interrupted` leaves it alone
+    pub fn download_heartbeat(&self, db: &Database) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET download_heartbeat=datetime('now')
+                    WHERE id=?1 AND status=?2 AND download_pid=?3",
+                params![self.id, VideoStatus::Downloading, std::process::id()],
+            )
+            .context("Failed to update download heartbeat")?;
+        Ok(())
     }
 
-    /// Opens connection to database. Will throw error if schema is updated (can be updated with `Database::migrate`)
-    pub fn open(cfg: &Config) -> Result<Database> {
-        let conn = Database::connect(&cfg, false)?;
+    /// Record file being downloaded to, so an interrupted download can be found. `None` once complete
+    pub fn set_partial_path(&self, db: &Database, path: Option<&str>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET partial_path=?1 WHERE id=?2",
+                params![path, self.id],
+            )
+            .context("Failed to update video partial path")?;
+        Ok(())
+    }
 
-        let mig = crate::db_migration::get_migrator(&conn);
-        mig.setup()?;
+    /// Record where the finished download was saved and its size in bytes, as downloaded now
+    pub fn set_downloaded_file(&self, db: &Database, path: &str, size: Option<u64>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET file_path=?1, file_size=?2, downloaded_at=?3 WHERE id=?4",
+                params![path, size, chrono::Utc::now(), self.id],
+            )
+            .context("Failed to update video file path")?;
+        Ok(())
+    }
 
-        if !mig.is_db_current()? {
-            return Err(anyhow::anyhow!(
-                "Database schema is incorrect version, currently {:?} should be {:?}",
-                mig.get_db_version()?,
-                mig.get_latest_version(),
-            ));
-        }
+    /// Every change of the video's status, oldest first, starting with when it was added
+    pub fn history(&self, db: &Database) -> Result<Vec<StatusChange>> {
+        let mut q = db.conn.prepare(
+            "SELECT old_status, new_status, origin, changed_at FROM video_history
+            WHERE video=?1
+            ORDER BY id",
+        )?;
+        let changes = q
+            .query_map(params![self.id], |row| {
+                Ok(StatusChange {
+                    old: row.get("old_status")?,
+                    new: row.get("new_status")?,
+                    origin: row.get("origin")?,
+                    changed_at: row.get("changed_at")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<StatusChange>>>()
+            .context("Failed to get video history")?;
+        Ok(changes)
+    }
 
-        Ok(Database { conn })
+    /// Earlier values of the alternative title and description, newest first
+    pub fn alt_edits(&self, db: &Database) -> Result<Vec<AltEdit>> {
+        let mut q = db.conn.prepare(
+            "SELECT id, field, old_value, changed_at FROM video_alt_edit
+            WHERE video=?1
+            ORDER BY id DESC",
+        )?;
+        let edits = q
+            .query_map(params![self.id], |row| {
+                let field: String = row.get("field")?;
+                Ok(AltEdit {
+                    id: row.get("id")?,
+                    field: AltField::from_str(&field).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            1,
+                            rusqlite::types::Type::Text,
+                            e.into(),
+                        )
+                    })?,
+                    old_value: row.get("old_value")?,
+                    changed_at: row.get("changed_at")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<AltEdit>>>()
+            .context("Failed to get video edits")?;
+        Ok(edits)
     }
 
-    /// Upgrade database to latest schema version
-    pub fn migrate(cfg: &Config) -> Result<()> {
-        let conn = Database::connect(&cfg, false)?;
+    /// Put back the value an edit replaced. This is recorded as an edit too, so can be undone
+    pub fn revert_alt_edit(&self, db: &Database, edit_id: i64) -> Result<()> {
+        let edit = self
+            .alt_edits(db)?
+            .into_iter()
+            .find(|e| e.id == edit_id)
+            .with_context(|| format!("Video {} has no edit {}", self.id, edit_id))?;
+        db.conn
+            .execute(
+                &format!("UPDATE video SET {}=?1 WHERE id=?2", edit.field.as_str()),
+                params![edit.old_value, self.id],
+            )
+            .context("Failed to revert video edit")?;
+        Ok(())
+    }
 
-        let mig = crate::db_migration::get_migrator(&conn);
-        mig.setup()?;
+    /// Other channels which also list this video, besides the one it belongs to
+    pub fn duplicate_channels(&self, db: &Database) -> Result<Vec<Channel>> {
+        let mut q = db.conn.prepare(&format!(
+            "SELECT {} FROM channel
+            WHERE id IN (SELECT channel FROM video_duplicate WHERE video=?1)
+            ORDER BY title",
+            CHANNEL_COLUMNS
+        ))?;
+        let chans = q
+            .query_map(params![self.id], Channel::from_row)?
+            .collect::<rusqlite::Result<Vec<Channel>>>()
+            .context("Failed to get channels listing duplicate video")?;
+        Ok(chans)
+    }
 
-        mig.upgrade()?;
+    /// Downloaded video file with its size and when it was downloaded, if known
+    pub fn downloaded_file(&self, db: &Database) -> Result<Option<DownloadedFile>> {
+        let file = db
+            .conn
+            .query_row(
+                "SELECT file_path, file_size, downloaded_at FROM video WHERE id=?1",
+                params![self.id],
+                |row| {
+                    let path: Option<String> = row.get("file_path")?;
+                    let size = row.get("file_size")?;
+                    let downloaded_at = row.get("downloaded_at")?;
+                    Ok(path.map(|path| DownloadedFile {
+                        path,
+                        size,
+                        downloaded_at,
+                    }))
+                },
+            )
+            .context("Failed to get video file")?;
+        Ok(file)
+    }
 
-        Ok(())
+    /// Downloaded video file, if known
+    pub fn file_path(&self, db: &Database) -> Result<Option<String>> {
+        db.conn
+            .query_row(
+                "SELECT file_path FROM video WHERE id=?1",
+                params![self.id],
+                |row| row.get("file_path"),
+            )
+            .context("Failed to get video file path")
     }
 
-    /// Opens a non-persistant database in memory. Likely only useful for test cases.
-    #[cfg(test)]
-    pub fn create_in_memory(with_tables: bool) -> Result<Database> {
-        // Create database in memory
-        let conn = Connection::open_in_memory()?;
+    /// Number of failed download attempts, and when the next is due
+    pub fn retry_state(
+        &self,
+        db: &Database,
+    ) -> Result<(i64, Option<chrono::DateTime<chrono::Utc>>)> {
+        db.conn
+            .query_row(
+                "SELECT retry_count, retry_at FROM video WHERE id=?1",
+                params![self.id],
+                |row| Ok((row.get("retry_count")?, row.get("retry_at")?)),
+            )
+            .context("Failed to get video retry state")
+    }
 
-        if with_tables {
-            // Setup migrator table
-            let mig = crate::db_migration::get_migrator(&conn);
-            mig.setup()?;
+    /// Tags (keywords, category etc) of video, in alphabetical order
+    pub fn tags(&self, db: &Database) -> Result<Vec<String>> {
+        let mut q = db.conn.prepare(
+            "SELECT tag.name FROM video_tag JOIN tag ON tag.id = video_tag.tag
+            WHERE video_tag.video=?1 ORDER BY tag.name",
+        )?;
+        let mapped = q.query_map(params![self.id], |row| row.get("name"))?;
 
-            // Setup latest schema
-            mig.upgrade()?;
+        let mut ret = vec![];
+        for m in mapped {
+            ret.push(m?);
         }
+        Ok(ret)
+    }
 
-        Ok(Database { conn })
+    /// Add tags to video, ignoring any which are already present
+    pub fn add_tags(&self, db: &Database, tags: &[String]) -> Result<()> {
+        for t in tags {
+            self.add_tag(db, t)?;
+        }
+        Ok(())
     }
-}
 
-/// Converison from SQL text to `Service` instance
-impl FromSql for Service {
-    fn column_result(
-        value: rusqlite::types::ValueRef,
-    ) -> Result<Self, rusqlite::types::FromSqlError> {
-        let raw: &str = value.as_str()?;
-        match Service::from_str(raw) {
-            Ok(s) => Ok(s),
-            Err(_e) => Err(rusqlite::types::FromSqlError::Other(Box::new(
-                DatabaseError::InvalidServiceInDB(raw.into()),
-            ))),
+    /// Add tag to video, with surrounding whitespace removed. False if it already had it
+    pub fn add_tag(&self, db: &Database, tag: &str) -> Result<bool> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(anyhow::anyhow!("Tag can't be empty"));
         }
+        db.conn
+            .execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", params![tag])
+            .context("Failed to create tag")?;
+        let added = db
+            .conn
+            .execute(
+                "INSERT OR IGNORE INTO video_tag (video, tag) SELECT ?1, id FROM tag WHERE name=?2",
+                params![self.id, tag],
+            )
+            .context("Failed to add video tag")?;
+        Ok(added > 0)
     }
-}
 
-/// Converison from SQL text to `Service` instance
-impl FromSql for VideoStatus {
-    fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<Self> {
-        let raw: &str = value.as_str()?;
-        match VideoStatus::from_str(raw) {
-            Ok(s) => Ok(s),
-            Err(_e) => Err(rusqlite::types::FromSqlError::Other(Box::new(
-                DatabaseError::InvalidStatusInDB(raw.into()),
-            ))),
+    /// Collections the video has been added to, by name
+    pub fn collections(&self, db: &Database) -> Result<Vec<Collection>> {
+        let mut q = db.conn.prepare(
+            "SELECT collection.id, collection.name FROM collection_video
+            JOIN collection ON collection.id = collection_video.collection
+            WHERE collection_video.video=?1 ORDER BY collection.name",
+        )?;
+        let mapped = q.query_map(params![self.id], Collection::from_row)?;
+        let mut ret = vec![];
+        for m in mapped {
+            ret.push(m?);
         }
+        Ok(ret)
     }
-}
 
-#[derive(Debug)]
-/// Statistics for a channel over the last week
-pub struct ChannelStats {
-    pub grabbed: usize,
-    pub new: usize,
-    pub other: usize,
-}
+    /// Remove tag from video. False if it didn't have it
+    pub fn remove_tag(&self, db: &Database, tag: &str) -> Result<bool> {
+        let removed = db
+            .conn
+            .execute(
+                "DELETE FROM video_tag WHERE video=?1 AND tag IN (SELECT id FROM tag WHERE name=?2)",
+                params![self.id, tag.trim()],
+            )
+            .context("Failed to remove video tag")?;
+        Ok(removed > 0)
+    }
 
-/// Channel which contains a bunch of videos
-#[derive(Debug)]
-pub struct Channel {
-    /// SQL ID number
-    pub id: i64,
-    /// ID of the video with the given service
-    pub chanid: String,
-    /// Which service the channel is on
-    pub service: Service,
+    /// Chapters of video, in order
+    pub fn chapters(&self, db: &Database) -> Result<Vec<Chapter>> {
+        let mut q = db
+            .conn
+            .prepare("SELECT start, title FROM video_chapter WHERE video=?1 ORDER BY start")?;
+        let mapped = q.query_map(params![self.id], |row| {
+            Ok(Chapter {
+                start: row.get("start")?,
+                title: row.get("title")?,
+            })
+        })?;
 
-    /// Human-readable title
-    pub title: String,
-    /// URL to icon for channel
-    pub thumbnail: String,
-}
+        let mut ret = vec![];
+        for m in mapped {
+            ret.push(m?);
+        }
+        Ok(ret)
+    }
 
-impl Channel {
-    pub fn stats_all(&self, db: &Database) -> Result<ChannelStats> {
-        let mut stmt = db.conn.prepare(
-            "SELECT COUNT(*) AS count, status FROM video
-            WHERE channel=?1
-            GROUP BY status",
+    /// Replace chapters of video
+    pub fn set_chapters(&self, db: &Database, chapters: &[Chapter]) -> Result<()> {
+        db.conn
+            .execute("DELETE FROM video_chapter WHERE video=?1", params![self.id])
+            .context("Failed to remove video chapters")?;
+        for c in chapters {
+            db.conn
+                .execute(
+                    "INSERT INTO video_chapter (video, start, title) VALUES (?1, ?2, ?3)",
+                    params![self.id, c.start, c.title],
+                )
+                .context("Failed to add video chapter")?;
+        }
+        Ok(())
+    }
+
+    /// Every size of the thumbnail, smallest first
+    pub fn thumbnails(&self, db: &Database) -> Result<Vec<Thumbnail>> {
+        let mut q = db.conn.prepare(
+            "SELECT url, width, height FROM video_thumbnail WHERE video=?1 ORDER BY width, id",
         )?;
-        let chaniter = stmt.query_map(params![self.id], |row| {
-            Ok((row.get("count")?, row.get("status")?))
-        })?;
-        let mut grabbed = 0;
-        let mut new = 0;
-        let mut other = 0;
-        for r in chaniter {
-            let (count, status): (i64, VideoStatus) = r?;
-            match status {
-                VideoStatus::Grabbed => grabbed = count as usize,
-                VideoStatus::New => new = count as usize,
-                _ => other += count as usize,
-            }
-        }
-
-        Ok(ChannelStats {
-            grabbed,
-            new,
-            other,
-        })
+        let thumbnails = q
+            .query_map(params![self.id], |row| {
+                Ok(Thumbnail {
+                    url: row.get("url")?,
+                    width: row.get("width")?,
+                    height: row.get("height")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Thumbnail>>>()
+            .context("Failed to get video thumbnails")?;
+        Ok(thumbnails)
     }
-    pub fn stats_1w(&self, db: &Database) -> Result<ChannelStats> {
-        let mut stmt = db.conn.prepare(
-            "SELECT COUNT(*) AS count, status FROM video
-            WHERE channel=?1 AND published_at > datetime('now', '-7 days')
-            GROUP BY status",
-        )?;
-        let chaniter = stmt.query_map(params![self.id], |row| {
-            Ok((row.get("count")?, row.get("status")?))
-        })?;
-        let mut grabbed = 0;
-        let mut new = 0;
-        let mut other = 0;
-        for r in chaniter {
-            let (count, status): (i64, VideoStatus) = r?;
-            match status {
-                VideoStatus::Grabbed => grabbed = count as usize,
-                VideoStatus::New => new = count as usize,
-                _ => other += count as usize,
-            }
+
+    /// Replace the sizes of the video's thumbnail
+    pub fn set_thumbnails(&self, db: &Database, thumbnails: &[Thumbnail]) -> Result<()> {
+        db.conn
+            .execute(
+                "DELETE FROM video_thumbnail WHERE video=?1",
+                params![self.id],
+            )
+            .context("Failed to remove video thumbnails")?;
+        for t in thumbnails {
+            db.conn
+                .execute(
+                    "INSERT OR IGNORE INTO video_thumbnail (video, url, width, height) VALUES (?1, ?2, ?3, ?4)",
+                    params![self.id, t.url, t.width, t.height],
+                )
+                .context("Failed to add video thumbnail")?;
         }
+        Ok(())
+    }
 
-        Ok(ChannelStats {
-            grabbed,
-            new,
-            other,
-        })
+    /// URL of the smallest thumbnail at least `width` pixels wide (or the largest, if none are
+    /// or no width is given), falling back to `thumbnail_url` if no sizes are known
+    pub fn thumbnail_url_for(&self, db: &Database, width: Option<u32>) -> Result<String> {
+        let thumbnails = self.thumbnails(db)?;
+        let fitting = width.and_then(|w| thumbnails.iter().find(|t| t.width >= w as i64));
+        Ok(fitting
+            .or_else(|| thumbnails.last())
+            .map(|t| t.url.clone())
+            .unwrap_or_else(|| self.info.thumbnail_url.clone()))
     }
 
-    pub fn get_by_sqlid(db: &Database, id: i64) -> Result<Channel> {
-        let chan = db
+    /// Subtitle files written when the video was downloaded
+    pub fn subtitle_files(&self, db: &Database) -> Result<Vec<SubtitleFile>> {
+        let mut q = db
             .conn
-            .query_row(
-                "SELECT id, chanid, service, title, thumbnail FROM channel WHERE id=?1",
-                params![id],
-                |row| {
-                    Ok(Channel {
-                        id: row.get("id")?,
-                        chanid: row.get("chanid")?,
-                        service: row.get("service")?,
-                        title: row.get("title")?,
-                        thumbnail: row.get("thumbnail")?,
-                    })
-                },
-            )
-            .context("Failed to find channel by ID")?;
+            .prepare("SELECT lang, path FROM video_subtitle WHERE video=?1 ORDER BY lang")?;
+        let mapped = q.query_map(params![self.id], |row| {
+            Ok(SubtitleFile {
+                lang: row.get("lang")?,
+                path: row.get("path")?,
+            })
+        })?;
 
-        Ok(chan)
+        let mut ret = vec![];
+        for m in mapped {
+            ret.push(m?);
+        }
+        Ok(ret)
     }
 
-    /// Get Channel object for given channel, returning error it it does not exist
-    pub fn get(db: &Database, cid: &ChannelID) -> Result<Channel> {
-        let chan = db.conn
-            .query_row(
-                "SELECT id, chanid, service, title, thumbnail FROM channel WHERE chanid=?1 AND service = ?2",
-                params![cid.id_str(), cid.service().as_str()],
-                |row| {
-                    Ok(Channel {
-                        id: row.get("id")?,
-                        chanid: row.get("chanid")?,
-                        service: row.get("service")?,
-                        title: row.get("title")?,
-                        thumbnail: row.get("thumbnail")?,
-                    })
-                },
+    /// Replace recorded subtitle files of video
+    pub fn set_subtitle_files(&self, db: &Database, files: &[SubtitleFile]) -> Result<()> {
+        db.conn
+            .execute(
+                "DELETE FROM video_subtitle WHERE video=?1",
+                params![self.id],
             )
-            .context("Failed to find channel from ID and service")?;
-
-        Ok(chan)
+            .context("Failed to remove video subtitles")?;
+        for f in files {
+            db.conn
+                .execute(
+                    "INSERT INTO video_subtitle (video, lang, path) VALUES (?1, ?2, ?3)",
+                    params![self.id, f.lang, f.path],
+                )
+                .context("Failed to add video subtitle")?;
+        }
+        Ok(())
     }
 
-    /// Create channel in database
-    pub fn create(
-        db: &Database,
-        cid: &ChannelID,
-        channel_title: &str,
-        thumbnail_url: &str,
-    ) -> Result<Channel> {
-        let check_existing = db.conn.query_row(
-            "SELECT id FROM channel WHERE chanid=?1 AND service=?2",
-            params![cid.id_str(), cid.service().as_str()],
-            |_| Ok(()),
-        );
-        match check_existing {
-            // Throw error if channel already exists
-            Ok(_) => Err(anyhow::anyhow!("Channel already exists in database")),
-
-            // No results is good
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(()),
-
-            // Propogate any other errors
-            Err(e) => Err(anyhow::anyhow!(e)),
-        }?;
-
+    /// Replace (probably truncated) description with the full one
+    pub fn set_description(&self, db: &Database, description: &str) -> Result<()> {
         db.conn
             .execute(
-                "INSERT INTO channel (chanid, service, title, thumbnail) VALUES (?1, ?2, ?3, ?4)",
-                params![
-                    cid.id_str(),
-                    cid.service().as_str(),
-                    channel_title,
-                    thumbnail_url,
-                ],
+                "UPDATE video SET description=?1 WHERE id=?2",
+                params![description, self.id],
             )
-            .context("Insert channel query")?;
-
-        // Return newly created channel
-        Channel::get(&db, cid)
-    }
-
-    pub fn last_update(&self, db: &Database) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
-        let result: Option<chrono::DateTime<chrono::Utc>> = db.conn.query_row(
-            "SELECT last_update FROM channel WHERE id=?1",
-            params![self.id],
-            |row| Ok(row.get("last_update")?),
-        )?;
-        Ok(result)
+            .context("Failed to update video description")?;
+        Ok(())
     }
 
-    /// Set the `last_update` time to now
-    pub fn set_last_update(&self, db: &Database) -> Result<()> {
-        let now = chrono::Utc::now();
+    /// Set language codes of available captions
+    pub fn set_captions(&self, db: &Database, captions: &[String]) -> Result<()> {
         db.conn
             .execute(
-                "UPDATE channel SET last_update=?1 WHERE id=?2",
-                params![now, self.id],
+                "UPDATE video SET captions=?1 WHERE id=?2",
+                params![captions.join(","), self.id],
             )
-            .context("Failed to update last_update time")?;
+            .context("Failed to update video captions")?;
         Ok(())
     }
 
-    /// Determines if an update for this channel is due based on `last_update` time
-    pub fn update_required(&self, db: &Database) -> Result<bool> {
-        let last_update = self.last_update(&db)?;
-        match last_update {
-            Some(last_update) => {
-                let now = chrono::Utc::now();
-                let delta = now - last_update;
-                let due_for_update = delta > chrono::Duration::minutes(60);
-                let shedule_due = if due_for_update {
-                    // FIXME: Something like chan.id % 60 == current_minute
-                    true
-                } else {
-                    false
-                };
-                Ok(shedule_due)
-            }
-            None => Ok(true),
-        }
-    }
-
-    pub fn update_metadata(&self, db: &Database, meta: &ChannelMetadata) -> Result<()> {
+    /// Set alternative title
+    pub fn set_title_alt(&self, db: &Database, title: String) -> Result<()> {
+        // Update DB
         db.conn
             .execute(
-                "UPDATE channel SET title=?1, thumbnail=?2 WHERE id=?3",
-                params![meta.title, meta.thumbnail, self.id],
+                "UPDATE video SET title_alt=?1 WHERE id=?2",
+                params![&title, self.id],
             )
-            .context("Failed to update channel metadata")?;
+            .context("Failed to update alt video title")?;
+
         Ok(())
     }
 
-    /// Add supplied video to database
-    pub fn add_video(&self, db: &Database, video: &VideoInfo) -> Result<DBVideoInfo> {
+    /// Set alternative title
+    pub fn set_description_alt(&self, db: &Database, title: String) -> Result<()> {
+        // Update DB
         db.conn
             .execute(
-                "INSERT INTO video (channel, video_id, url, title, description, thumbnail, published_at, status, duration, date_added)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                params![
-                    self.id,
-                    video.id,
-                    video.url,
-                    video.title,
-                    video.description,
-                    video.thumbnail_url,
-                    video.published_at.to_rfc3339(),
-                    VideoStatus::New.as_str(), // Default status
-                    video.duration,
-                    chrono::Utc::now(),
-                ],
+                "UPDATE video SET description_alt=?1 WHERE id=?2",
+                params![&title, self.id],
             )
-            .context("Add video query")?;
-        let last_id = db.conn.last_insert_rowid();
+            .context("Failed to update alt video description")?;
 
-        Ok(DBVideoInfo::get_by_sqlid(&db, last_id)?)
+        Ok(())
     }
 
-    /// Get the URL's of the most recently published videos - returning up to and including `num` results.
-    pub fn last_n_video_urls(&self, db: &Database, num: i64) -> Result<HashSet<String>> {
-        let mut q = db.conn.prepare(
-            "SELECT url FROM video
-                WHERE channel=?1
-                ORDER BY published_at DESC
-                LIMIT ?2",
-        )?;
-        let mapped = q.query_map(params![self.id, num], |row| row.get("url"))?;
+    /// Set notes, removing them if `notes` is blank
+    pub fn set_notes(&self, db: &Database, notes: &str) -> Result<()> {
+        let notes = Some(notes.trim()).filter(|n| !n.is_empty());
+        db.conn
+            .execute(
+                "UPDATE video SET notes=?1 WHERE id=?2",
+                params![notes, self.id],
+            )
+            .context("Failed to update video notes")?;
 
-        let mut set = HashSet::new();
-        for m in mapped {
-            let url: String = m?;
-            set.insert(url);
-        }
+        Ok(())
+    }
+}
 
-        Ok(set)
+/// Write a consistent copy of the database to `path`, which must not exist
+fn backup_to(conn: &Connection, path: &std::path::Path) -> Result<()> {
+    if path.exists() {
+        anyhow::bail!("Backup file {} already exists", path.display());
     }
+    conn.execute(
+        "VACUUM INTO ?1",
+        params![path.to_str().context("Backup path isn't valid UTF-8")?],
+    )
+    .with_context(|| format!("Failed to back up database to {}", path.display()))?;
+    Ok(())
+}
 
-    pub fn all_videos(
-        &self,
-        db: &Database,
-        limit: i64,
-        page: i64,
-        filter: Option<FilterParams>,
-    ) -> Result<Vec<DBVideoInfo>> {
-        let filter = match filter {
-            Some(f) => Some(FilterParams {
-                name_contains: f.name_contains,
-                status: f.status,
-                chanid: Some(self.id),
-            }),
-            None => Some(FilterParams {
-                name_contains: None,
-                status: None,
-                chanid: Some(self.id),
-            }),
-        };
+/// Problems found by `Database::doctor`
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    /// Rows referring to others which don't exist, as their table and rowid
+    pub foreign_key_errors: Vec<(String, i64)>,
+    /// Videos with a status vidl doesn't know, as their SQL ID and status
+    pub unknown_statuses: Vec<(i64, String)>,
+    /// Channels with a service vidl doesn't know, as their SQL ID and service
+    pub unknown_services: Vec<(i64, String)>,
+    /// Table and column which should be populated, with how many rows are NULL
+    pub missing_values: Vec<(&'static str, &'static str, usize)>,
+}
 
-        all_videos(&db, limit, page, filter)
+impl DoctorReport {
+    pub fn is_empty(&self) -> bool {
+        self.foreign_key_errors.is_empty()
+            && self.unknown_statuses.is_empty()
+            && self.unknown_services.is_empty()
+            && self.missing_values.is_empty()
     }
+}
 
-    pub fn update(&self, db: &Database, full_update: bool) -> Result<()> {
-        // Set updated time now (even in case of failure)
-        self.set_last_update(&db)?;
-
-        let mut chanid = crate::common::YoutubeID {
-            id: self.chanid.clone(),
-        };
+/// Part of vidl which changed a video's status, as recorded in its history
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    Cli,
+    Web,
+    Worker,
+}
 
-        match self.service {
-            Service::Youtube => {
-                if !self.chanid.starts_with("UC") {
-                    if let Ok(fixed_id) =
-                        crate::source::invidious::find_channel_id_workaround(&self.chanid)
-                    {
-                        log::info!(
-                            "Updating chanid {} username/channel-name to Youtube ID {}",
-                            self.chanid,
-                            fixed_id
-                        );
-                        db.conn.execute(
-                            "UPDATE channel SET chanid = ?1 WHERE id = ?2",
-                            params![fixed_id, self.id],
-                        )?;
-                        chanid.id = fixed_id;
-                    } else {
-                        log::error!("Failed to update channel id {}", self.chanid);
-                    }
-                }
-            }
-            Service::Vimeo => {}
+impl Origin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Origin::Cli => "cli",
+            Origin::Web => "web",
+            Origin::Worker => "worker",
         }
+    }
+}
 
-        let api: Box<dyn ChannelData> = match self.service {
-            Service::Youtube => Box::new(YoutubeQuery::new(&chanid)),
-            Service::Vimeo => {
-                // FIXME
-                error!("Ignoring Vimeo channel {:?}", &self);
-                return Ok(());
-            }
-        };
-
-        let meta = api.get_metadata();
-
-        match meta {
-            Ok(meta) => self.update_metadata(&db, &meta)?,
-            Err(e) => {
-                error!(
-                    "Error fetching metadata for {:?} - {} - skipping channel",
-                    chanid, e
-                );
-                // Skip to next channel
-                return Ok(());
-            }
-        }
+thread_local! {
+    /// What changes made on this thread come from, returned by the `vidl_origin()` SQL function
+    static ORIGIN: std::cell::Cell<Origin> = const { std::cell::Cell::new(Origin::Cli) };
+}
 
-        let seen_videos = self
-            .last_n_video_urls(&db, 200)
-            .context("Failed to find latest video URLs")?;
+/// Record changes made on the current thread as coming from `origin`
+pub fn set_origin(origin: Origin) {
+    ORIGIN.with(|o| o.set(origin));
+}
 
-        trace!("Last seen video URL's: {:?}", &seen_videos);
+/// Add functions used by triggers to the connection
+fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "vidl_origin",
+        0,
+        rusqlite::functions::FunctionFlags::SQLITE_UTF8,
+        |_| Ok(ORIGIN.with(|o| o.get()).as_str()),
+    )
+}
 
-        let mut new_videos: Vec<crate::source::base::VideoInfo> = vec![];
+lazy_static! {
+    /// Connections shared by the web interface and workers, created on first use
+    static ref POOL: Mutex<Option<r2d2::Pool<SqliteConnectionManager>>> = Mutex::new(None);
+}
 
-        for v in api.videos() {
-            let v = v?;
+/// Key the database is encrypted with, from `VIDL_DB_KEY` or else the output of
+/// `VIDL_DB_KEY_COMMAND`
+fn database_key(cfg: &Config) -> Result<Option<String>> {
+    if let Some(key) = &cfg.db_key {
+        return Ok(Some(key.clone()));
+    }
+    let cmd = match &cfg.db_key_command {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+    let output = std::process::Command::new("sh")
+        .args(["-c", cmd])
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run database key command {:?}", cmd))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Database key command {:?} failed with {}",
+            cmd,
+            output.status
+        );
+    }
+    let key = String::from_utf8(output.stdout).context("Database key isn't valid UTF-8")?;
+    let key = key.trim_end_matches(['\r', '\n']);
+    if key.is_empty() {
+        anyhow::bail!("Database key command {:?} printed nothing", cmd);
+    }
+    Ok(Some(key.to_string()))
+}
 
-            if seen_videos.contains(&v.url) && !full_update {
-                debug!("Already seen video by URL {:?}", v.url);
-                break;
-            }
+/// Settings applied to every connection, from the `Config`
+#[derive(Clone)]
+struct ConnectionSettings {
+    busy_timeout: u64,
+    wal: bool,
+    synchronous: String,
+    key: Option<String>,
+}
 
-            trace!("New video {:?}", &v);
-            new_videos.push(v);
+impl ConnectionSettings {
+    fn new(cfg: &Config) -> Result<ConnectionSettings> {
+        let key = database_key(cfg)?;
+        // Without SQLCipher the key would be ignored, leaving the database unencrypted
+        if key.is_some() && !cfg!(feature = "sqlcipher") {
+            anyhow::bail!(
+                "A database key is set, but vidl was built without the sqlcipher feature"
+            );
         }
+        Ok(ConnectionSettings {
+            busy_timeout: cfg.db_busy_timeout,
+            wal: cfg.db_wal,
+            synchronous: cfg.db_synchronous.clone(),
+            key,
+        })
+    }
 
-        for v in new_videos {
-            debug!("Adding {0}", v.title);
-            trace!("{:?}", &v);
-            // TODO: Stop on "already seen video" error
-            match self.add_video(&db, &v) {
-                Ok(_) => (),
-                Err(e) => error!("Error adding video {:?} - {:?}", &v, e),
-            };
+    fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        // Must be first, as nothing can be read until the database is decrypted
+        if let Some(key) = &self.key {
+            conn.pragma_update(None, "key", key)?;
+        }
+        register_functions(conn)?;
+        conn.busy_timeout(std::time::Duration::from_millis(self.busy_timeout))?;
+        if self.wal {
+            // Stays in the previous mode where WAL isn't supported, e.g on network filesystems
+            let mode: String =
+                conn.query_row("PRAGMA journal_mode = WAL", params![], |row| row.get(0))?;
+            if !mode.eq_ignore_ascii_case("wal") {
+                warn!("Unable to use write-ahead log, journal mode is {:?}", mode);
+            }
         }
+        conn.pragma_update(None, "synchronous", &self.synchronous)?;
         Ok(())
     }
+}
 
-    /// Deletes channel and all videos it contains
-    pub fn delete(self, db: &Database) -> Result<()> {
-        db.conn
-            .execute("DELETE FROM video WHERE channel=?1", params![self.id])
-            .context("Failed to delete videos in channel")?;
+/// Connection owned by a `Database`, or taken from the shared pool and returned to it when dropped
+pub enum DbConnection {
+    Owned(Connection),
+    Pooled(r2d2::PooledConnection<SqliteConnectionManager>),
+}
 
-        db.conn
-            .execute("DELETE FROM channel WHERE id=?1", params![self.id])
-            .context("Failed to delete channel")?;
+impl std::ops::Deref for DbConnection {
+    type Target = Connection;
 
-        Ok(())
+    fn deref(&self) -> &Connection {
+        match self {
+            DbConnection::Owned(c) => c,
+            DbConnection::Pooled(c) => c,
+        }
     }
 }
 
-/// All channels present in database
-pub fn list_channels(db: &Database) -> Result<Vec<Channel>> {
-    let mut stmt = db
-        .conn
-        .prepare("SELECT id, chanid, service, title, thumbnail FROM channel ORDER BY title")?;
-    let chaniter = stmt.query_map(params![], |row| {
-        Ok(Channel {
-            id: row.get("id")?,
-            chanid: row.get("chanid")?,
-            service: row.get("service")?,
-            title: row.get("title")?,
-            thumbnail: row.get("thumbnail")?,
+/// Wraps connection to a database
+pub struct Database {
+    pub conn: DbConnection,
+}
+
+impl Database {
+    fn connect(cfg: &Config, create: bool) -> Result<Connection> {
+        let path = cfg.db_filepath();
+        if let Some(p) = path.parent() {
+            if !path.exists() {
+                debug!("Creating {:?}", p);
+                std::fs::create_dir_all(p)?
+            }
+        };
+        debug!("Loading DB from {:?}", path);
+
+        use rusqlite::OpenFlags;
+        let flags = if create {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+        let conn = Connection::open_with_flags(path, flags)?;
+        ConnectionSettings::new(cfg)?
+            .apply(&conn)
+            .context("Failed to open database, is the key correct?")?;
+
+        Ok(conn)
+    }
+
+    /// Like `open`, but using a connection from the pool shared by the whole process, which is
+    /// returned to it when dropped. The schema version is only checked when the pool is created
+    pub fn pooled(cfg: &Config) -> Result<Database> {
+        let pool = {
+            let mut pool = POOL.lock().unwrap();
+            match &*pool {
+                Some(p) => p.clone(),
+                None => {
+                    let p = Database::create_pool(cfg)?;
+                    *pool = Some(p.clone());
+                    p
+                }
+            }
+        };
+        let conn = pool
+            .get()
+            .context("Failed to get database connection from pool")?;
+        Ok(Database {
+            conn: DbConnection::Pooled(conn),
         })
-    })?;
-    let mut ret = vec![];
-    for r in chaniter {
-        ret.push(r?);
     }
-    Ok(ret)
-}
 
-pub struct FilterParams {
-    pub name_contains: Option<String>,
-    pub status: Option<HashSet<VideoStatus>>,
-    pub chanid: Option<i64>,
-}
+    fn create_pool(cfg: &Config) -> Result<r2d2::Pool<SqliteConnectionManager>> {
+        // Check the schema is current, as with `open`
+        Database::open(cfg)?;
+
+        let settings = ConnectionSettings::new(cfg)?;
+        let manager = SqliteConnectionManager::file(cfg.db_filepath())
+            .with_flags(rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .with_init(move |c| settings.apply(c));
+        // Each worker holds one while busy (a download's progress is recorded with its worker's),
+        // leaving the rest for web requests
+        let pool = r2d2::Pool::builder()
+            .max_size((cfg.num_workers + cfg.num_download_workers) as u32 + 8)
+            .min_idle(Some(1))
+            .build(manager)
+            .context("Failed to create database connection pool")?;
+        Ok(pool)
+    }
 
-pub fn all_videos(
-    db: &Database,
-    limit: i64,
-    page: i64,
-    filter: Option<FilterParams>,
-) -> Result<Vec<DBVideoInfo>> {
-    let mapper = |row: &rusqlite::Row| {
-        Ok(DBVideoInfo {
-            id: row.get("id")?,
-            status: row.get("status")?,
-            date_added: row.get("date_added")?,
-            info: VideoInfo {
-                id: row.get("video_id")?,
-                url: row.get("url")?,
-                title: row.get("title")?,
-                title_alt: row.get("title_alt")?,
-                description: row.get("description")?,
-                description_alt: row.get("description_alt")?,
-                thumbnail_url: row.get("thumbnail")?,
-                published_at: row.get("published_at")?,
-                duration: row.get("duration")?,
-            },
-            chanid: row.get("channel")?,
+    /// Create a new database
+    pub fn create(cfg: &Config) -> Result<Database> {
+        // Create new database
+        let conn = Database::connect(&cfg, true)?;
+
+        // Create tables
+        let mig = crate::db_migration::get_migrator(&conn);
+        mig.setup()?;
+        mig.upgrade()?;
+
+        // Return connection
+        Ok(Database {
+            conn: DbConnection::Owned(conn),
         })
-    };
+    }
 
-    let mut ret: Vec<DBVideoInfo> = vec![];
+    /// Opens connection to database. Will throw error if schema is updated (can be updated with `Database::migrate`)
+    pub fn open(cfg: &Config) -> Result<Database> {
+        let conn = Database::connect(&cfg, false)?;
 
-    // Create query snippet like:
-    // (status = "NE" OR status = "GE")
-    // Or `1` as placeholder if no statuses are set.
-    let status_pred: String = if let Some(ref filter) = filter {
-        if let Some(status) = &filter.status {
-            let s = status
-                .iter()
-                .map(|s| format!(r#"status = "{}""#, s.as_str()))
-                .collect::<Vec<String>>()
-                .join(" OR ");
+        let mig = crate::db_migration::get_migrator(&conn);
+        mig.setup()?;
 
-            if status.len() > 1 {
-                format!("({})", s)
-            } else {
-                s
-            }
-        } else {
-            "1".into() // 1 i.e true
+        if !mig.is_db_current()? {
+            return Err(anyhow::anyhow!(
+                "Database schema is incorrect version, currently {:?} should be {:?}",
+                mig.get_db_version()?,
+                mig.get_latest_version(),
+            ));
         }
-    } else {
-        "1".into() // 1 i.e true
-    };
 
-    let chanid_pred: String = if let Some(ref filter) = filter {
-        if let Some(cid) = filter.chanid {
-            format!("channel = {}", cid)
-        } else {
-            "1".into()
-        }
-    } else {
-        "1".into()
-    };
+        Ok(Database {
+            conn: DbConnection::Owned(conn),
+        })
+    }
 
-    let sql = format!(
-        r#"SELECT id, status, video_id, url, title, title_alt, description, description_alt, thumbnail, published_at, channel, duration, date_added
-        FROM video
-        WHERE title LIKE ("%" || ?3 || "%")
-            AND {}
-            AND {}
-        ORDER BY published_at DESC
-        LIMIT ?1
-        OFFSET ?2
-        "#,
-        status_pred, chanid_pred,
-    );
+    /// Schema version of the database and the latest version, without requiring them to match
+    pub fn schema_version(cfg: &Config) -> Result<(Option<i64>, i64)> {
+        let conn = Database::connect(cfg, false)?;
 
-    trace!("all_videos query SQL {}", &sql);
+        let mig = crate::db_migration::get_migrator(&conn);
+        mig.setup()?;
 
-    let mut q = db.conn.prepare(&sql)?;
-    let mapped = q.query_map(
-        params![
-            limit,
-            page * limit,
-            filter.and_then(|x| x.name_contains).unwrap_or("".into()),
-        ],
-        mapper,
-    )?;
-    for r in mapped {
-        ret.push(r?);
+        Ok((mig.get_db_version()?, mig.get_latest_version()))
     }
-    Ok(ret)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_list_channels() -> Result<()> {
-        // Create database in memory
-        let mdb = Database::create_in_memory(true)?;
+    /// Upgrade database to latest schema version, first backing up the file unless disabled
+    /// with `VIDL_DB_MIGRATE_BACKUP`
+    pub fn migrate(cfg: &Config) -> Result<()> {
+        let conn = Database::connect(&cfg, false)?;
 
-        // Check no channels exist
-        {
-            let chans = list_channels(&mdb)?;
+        let mig = crate::db_migration::get_migrator(&conn);
+        mig.setup()?;
 
-            // Setup latest schema
-            assert_eq!(chans.len(), 0);
+        let version = mig.get_db_version()?;
+        if cfg.db_migrate_backup && version.is_some_and(|v| v != mig.get_latest_version()) {
+            // Like `vidl.sqlite3.v36-20230131-120000.bak`, next to the database
+            let mut name = cfg.db_filepath().file_name().unwrap_or_default().to_owned();
+            name.push(format!(
+                ".v{}-{}.bak",
+                version.unwrap_or_default(),
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            ));
+            let path = cfg.db_filepath().with_file_name(name);
+            backup_to(&conn, &path)?;
+            println!("Backed up database to {}", path.display());
         }
 
-        // Create channel
-        {
-            let cid = ChannelID::Youtube(crate::common::YoutubeID {
-                id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
-            });
+        mig.upgrade()?;
 
-            Channel::create(
-                &mdb,
-                &cid,
-                "test channel",
-                "http://example.com/thumbnail.jpg",
-            )?;
+        Ok(())
+    }
+
+    /// Value of `setting` parsed as a `T`, or `None` if it isn't set
+    pub fn setting<T>(&self, setting: Setting) -> Result<Option<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let value: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM settings WHERE key=?1",
+                params![setting.as_str()],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to get setting")?;
+        value
+            .map(|v| {
+                v.parse().map_err(|e| {
+                    anyhow::anyhow!("Invalid {} setting {:?} - {}", setting.as_str(), v, e)
+                })
+            })
+            .transpose()
+    }
+
+    /// Change `setting`, or remove it to use the default if `value` is `None`
+    pub fn set_setting<T: ToString>(&self, setting: Setting, value: Option<T>) -> Result<()> {
+        match value.map(|v| v.to_string()) {
+            Some(v) => {
+                setting.validate(&v)?;
+                self.conn
+                    .execute(
+                        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                        ON CONFLICT(key) DO UPDATE SET value=excluded.value",
+                        params![setting.as_str(), v],
+                    )
+                    .context("Failed to update setting")?;
+            }
+            None => {
+                self.conn
+                    .execute(
+                        "DELETE FROM settings WHERE key=?1",
+                        params![setting.as_str()],
+                    )
+                    .context("Failed to remove setting")?;
+            }
         }
+        Ok(())
+    }
 
-        let chans = list_channels(&mdb)?;
-        assert_eq!(chans.len(), 1);
-        assert_eq!(chans[0].id, 1);
-        assert_eq!(chans[0].chanid, "UCUBfKCp83QT19JCUekEdxOQ");
-        assert_eq!(chans[0].title, "test channel");
-        assert_eq!(chans[0].thumbnail, "http://example.com/thumbnail.jpg");
+    /// All known settings, with their values if set. Settings from other versions of vidl are
+    /// left out
+    pub fn settings(&self) -> Result<Vec<(Setting, Option<String>)>> {
+        Setting::ALL
+            .iter()
+            .map(|s| Ok((*s, self.setting(*s)?)))
+            .collect()
+    }
 
-        let c = &chans[0];
+    /// Time between updates of channels without their own interval
+    pub fn default_update_interval(&self) -> Result<chrono::Duration> {
+        let minutes = self.setting(Setting::UpdateInterval)?.unwrap_or(60);
+        Ok(chrono::Duration::minutes(minutes))
+    }
 
-        // Check no videos exist
-        {
-            let vids = c.all_videos(&mdb, 50, 0, None)?;
-            assert_eq!(vids.len(), 0);
+    /// Check the database's integrity, remove rows referring to others which no longer exist
+    /// (only counting them if `dry_run`), then update the query planner's statistics and compact
+    /// the file. Nothing is changed if the integrity check fails, or by a dry run
+    pub fn maintain(&self, dry_run: bool) -> Result<MaintenanceReport> {
+        let mut report = MaintenanceReport::default();
+
+        let mut q = self.conn.prepare("PRAGMA integrity_check")?;
+        let results = q
+            .query_map(params![], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .context("Failed to check database integrity")?;
+        report.integrity_errors = results.into_iter().filter(|r| r != "ok").collect();
+        if !report.integrity_errors.is_empty() {
+            return Ok(report);
         }
 
-        // ..and no latest video
-        {
-            let latest = c.last_n_video_urls(&mdb, 50)?;
-            assert_eq!(latest.len(), 0);
+        report.orphans = self.remove_broken_rows(dry_run)?;
+
+        if !dry_run {
+            self.conn
+                .execute_batch("ANALYZE")
+                .context("Failed to analyze database")?;
+            self.conn
+                .execute_batch("VACUUM")
+                .context("Failed to vacuum database")?;
         }
+        Ok(report)
+    }
 
-        // Create new video
-        {
-            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
-                .with_timezone(&chrono::Utc);
+    /// Check for rows which break foreign keys, videos and channels with a status or service
+    /// vidl doesn't know, and columns which should be populated but are NULL. If `fix`, broken
+    /// rows are removed, unknown statuses reset to new and missing values filled in, while
+    /// channels with unknown services are only reported. The report is of what was found
+    pub fn doctor(&self, fix: bool) -> Result<DoctorReport> {
+        let mut report = DoctorReport {
+            foreign_key_errors: self.foreign_key_errors()?,
+            ..Default::default()
+        };
 
-            let new_video = VideoInfo {
-                id: "an id".into(),
-                url: "http://example.com/watch?v=abc123".into(),
-                title: "A title!".into(),
-                title_alt: None,
-                description: "A ficticious video.\nIt is quite good".into(),
-                description_alt: None,
-                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
-                published_at: when,
-                duration: 12341,
-            };
-            c.add_video(&mdb, &new_video)?;
+        let mut q = self.conn.prepare("SELECT id, status FROM video")?;
+        report.unknown_statuses = q
+            .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(i64, String)>>>()?
+            .into_iter()
+            .filter(|(_, status)| VideoStatus::from_str(status).is_err())
+            .collect();
+        let mut q = self.conn.prepare("SELECT id, service FROM channel")?;
+        report.unknown_services = q
+            .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(i64, String)>>>()?
+            .into_iter()
+            .filter(|(_, service)| Service::from_str(service).is_err())
+            .collect();
+
+        // Nullable columns which are read as always set, with what to fill them with
+        let required = [
+            ("video", "date_added", "datetime(published_at)"),
+            ("video_history", "changed_at", "CURRENT_TIMESTAMP"),
+        ];
+        for (table, column, _) in required {
+            let count: i64 = self.conn.query_row(
+                &format!("SELECT COUNT(*) FROM {} WHERE {} IS NULL", table, column),
+                params![],
+                |row| row.get(0),
+            )?;
+            if count > 0 {
+                report.missing_values.push((table, column, count as usize));
+            }
         }
 
-        // Check video now exists
-        {
-            let vids = c.all_videos(&mdb, 50, 0, None)?;
-            assert_eq!(vids.len(), 1);
-            let first = &vids[0].info;
-            assert_eq!(first.id, "an id");
-            assert_eq!(first.url, "http://example.com/watch?v=abc123");
-            assert_eq!(first.title, "A title!");
-            assert_eq!(first.description, "A ficticious video.\nIt is quite good");
-            assert_eq!(first.thumbnail_url, "http://example.com/vidthumb.jpg");
-            assert_eq!(
-                first.published_at,
-                chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
-                    .with_timezone(&chrono::Utc)
-            );
-            assert_eq!(first.duration, 12341);
+        if !fix {
+            return Ok(report);
         }
 
-        // Create "older" video for latest_video test
-        {
-            let when = chrono::DateTime::parse_from_rfc3339("1999-04-01T12:30:01Z")?
-                .with_timezone(&chrono::Utc);
+        let tx = self.conn.unchecked_transaction()?;
+        for (id, _) in &report.unknown_statuses {
+            tx.execute(
+                "UPDATE video SET status=?1 WHERE id=?2",
+                params![VideoStatus::New, id],
+            )?;
+        }
+        for (table, column, value) in required {
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET {} = {} WHERE {} IS NULL",
+                    table, column, value, column
+                ),
+                params![],
+            )
+            .with_context(|| format!("Failed to fill in {}.{}", table, column))?;
+        }
+        tx.commit()?;
 
-            let new_video = VideoInfo {
-                id: "old id".into(),
-                url: "http://example.com/watch?v=old".into(),
-                title: "Old video".into(),
+        self.remove_broken_rows(false)?;
+
+        Ok(report)
+    }
+
+    /// Remove rows which break foreign keys, and then those referring to them, returning how many
+    /// were removed from each table (sorted by table). If `dry_run` they are only counted
+    fn remove_broken_rows(&self, dry_run: bool) -> Result<Vec<(String, usize)>> {
+        // Can't be changed within a transaction, and otherwise stops rows being removed in
+        // whatever order they were found
+        let enforced: bool = self
+            .conn
+            .query_row("PRAGMA foreign_keys", params![], |row| row.get(0))?;
+        self.conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+        let removed = (|| -> Result<Vec<(String, usize)>> {
+            // A dry run removes them as usual, but isn't committed
+            let tx = self.conn.unchecked_transaction()?;
+            let mut removed: Vec<(String, usize)> = vec![];
+            // Removing a row can leave rows referring to it broken, so repeat until none are left
+            let mut errors = self.foreign_key_errors()?;
+            while !errors.is_empty() {
+                for (table, rowid) in &errors {
+                    tx.execute(
+                        &format!("DELETE FROM {} WHERE rowid=?1", table),
+                        params![rowid],
+                    )
+                    .with_context(|| format!("Failed to remove broken row from {}", table))?;
+                    match removed.iter_mut().find(|(t, _)| t == table) {
+                        Some((_, n)) => *n += 1,
+                        None => removed.push((table.clone(), 1)),
+                    }
+                }
+                errors = self.foreign_key_errors()?;
+            }
+            if !dry_run {
+                tx.commit()?;
+            }
+            removed.sort();
+            Ok(removed)
+        })();
+        self.conn.execute_batch(if enforced {
+            "PRAGMA foreign_keys = ON"
+        } else {
+            "PRAGMA foreign_keys = OFF"
+        })?;
+        removed
+    }
+
+    /// Rows referring to others which don't exist, as their table and rowid
+    fn foreign_key_errors(&self) -> Result<Vec<(String, i64)>> {
+        let mut q = self.conn.prepare("PRAGMA foreign_key_check")?;
+        let errors = q
+            .query_map(params![], |row| Ok((row.get("table")?, row.get("rowid")?)))?
+            .collect::<rusqlite::Result<Vec<(String, i64)>>>()
+            .context("Failed to check foreign keys")?;
+        Ok(errors)
+    }
+
+    /// Opens a non-persistant database in memory. Likely only useful for test cases.
+    #[cfg(test)]
+    pub fn create_in_memory(with_tables: bool) -> Result<Database> {
+        // Create database in memory
+        let conn = Connection::open_in_memory()?;
+        register_functions(&conn)?;
+
+        if with_tables {
+            // Setup migrator table
+            let mig = crate::db_migration::get_migrator(&conn);
+            mig.setup()?;
+
+            // Setup latest schema
+            mig.upgrade()?;
+        }
+
+        Ok(Database {
+            conn: DbConnection::Owned(conn),
+        })
+    }
+}
+
+/// Converison from SQL text to `Service` instance
+impl FromSql for Service {
+    fn column_result(
+        value: rusqlite::types::ValueRef,
+    ) -> Result<Self, rusqlite::types::FromSqlError> {
+        let raw: &str = value.as_str()?;
+        match Service::from_str(raw) {
+            Ok(s) => Ok(s),
+            Err(_e) => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                DatabaseError::InvalidServiceInDB(raw.into()),
+            ))),
+        }
+    }
+}
+
+impl ToSql for Service {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+/// Converison from SQL text to `Backend` instance
+impl FromSql for Backend {
+    fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<Self> {
+        let raw: &str = value.as_str()?;
+        match Backend::from_str(raw) {
+            Ok(s) => Ok(s),
+            Err(_e) => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                DatabaseError::InvalidBackendInDB(raw.into()),
+            ))),
+        }
+    }
+}
+
+/// Converison from SQL text to `LiveStatus` instance
+impl FromSql for LiveStatus {
+    fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<Self> {
+        let raw: &str = value.as_str()?;
+        match LiveStatus::from_str(raw) {
+            Ok(s) => Ok(s),
+            Err(_e) => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                DatabaseError::InvalidLiveStatusInDB(raw.into()),
+            ))),
+        }
+    }
+}
+
+/// Converison from SQL text to `Service` instance
+impl FromSql for VideoStatus {
+    fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlResult<Self> {
+        let raw: &str = value.as_str()?;
+        match VideoStatus::from_str(raw) {
+            Ok(s) => Ok(s),
+            Err(_e) => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                DatabaseError::InvalidStatusInDB(raw.into()),
+            ))),
+        }
+    }
+}
+
+impl ToSql for VideoStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+#[derive(Debug)]
+/// Statistics for a channel over the last week
+pub struct ChannelStats {
+    pub grabbed: usize,
+    pub new: usize,
+    pub other: usize,
+    /// `GrabError` videos, which are also counted in `other`
+    pub failed: usize,
+}
+
+/// Channel which contains a bunch of videos
+#[derive(Debug)]
+pub struct Channel {
+    /// SQL ID number
+    pub id: i64,
+    /// ID of the video with the given service
+    pub chanid: String,
+    /// Which service the channel is on
+    pub service: Service,
+
+    /// Human-readable title
+    pub title: String,
+    /// URL to icon for channel
+    pub thumbnail: String,
+    /// Where to retrieve channel data from, or `None` to use the configured default
+    pub backend: Option<Backend>,
+    /// Description from the service, updated with the metadata
+    pub description: String,
+    /// URL to banner image
+    pub banner: Option<String>,
+    pub subscriber_count: Option<i64>,
+    /// Youtube `@handle` or username the channel was added by, shown instead of the `UC..` ID
+    pub handle: Option<String>,
+    /// Downloader format string (`-f` argument) used instead of the configured one
+    pub format: Option<String>,
+    /// Whether to download subtitles, or `None` to download them if `VIDL_SUBTITLE_LANGS` is set
+    pub subtitles: Option<bool>,
+    /// Directory videos are downloaded to, instead of the configured one
+    pub download_dir: Option<String>,
+    /// Downloader output template used instead of the configured one
+    pub filename_format: Option<String>,
+    /// Which new videos are automatically ignored
+    pub ignore_rules: IgnoreRules,
+    /// Hidden from the channel list and never updated, but its videos are kept
+    pub archived: bool,
+}
+
+/// Rules for automatically setting new videos to `Ignore`, e.g to skip shorts or trailers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IgnoreRules {
+    /// Ignore videos shorter than this many seconds
+    pub min_duration: Option<i32>,
+    /// Ignore videos longer than this many seconds
+    pub max_duration: Option<i32>,
+    /// Ignore videos with a title matching this regex
+    pub title: Option<String>,
+}
+
+impl IgnoreRules {
+    /// Check title regex is valid
+    pub fn validate(&self) -> Result<()> {
+        if let Some(t) = &self.title {
+            regex::Regex::new(t).with_context(|| format!("Invalid title pattern {:?}", t))?;
+        }
+        Ok(())
+    }
+
+    /// If the video should be ignored. Duration rules are skipped when the duration is
+    /// unknown (zero), as for streams
+    pub fn matches(&self, video: &VideoInfo) -> bool {
+        if video.duration > 0 {
+            if self.min_duration.is_some_and(|min| video.duration < min) {
+                return true;
+            }
+            if self.max_duration.is_some_and(|max| video.duration > max) {
+                return true;
+            }
+        }
+        match self.title.as_deref().map(regex::Regex::new) {
+            Some(Ok(re)) => re.is_match(&video.title),
+            Some(Err(e)) => {
+                warn!("Invalid title pattern - {:?}", e);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Per-channel behaviour stored in `channel_settings`. Quality and download directory are
+/// the channel's `format` and `download_dir`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelSettings {
+    /// Queue new videos for download as soon as they are found
+    pub auto_download: bool,
+    /// Minutes between updates, or `None` for the `update_interval` setting
+    pub update_interval_minutes: Option<i64>,
+    /// Whether the channel is updated at all
+    pub enabled: bool,
+    /// Videos still `New` this many days after being published are ignored, or `None` to keep
+    /// them
+    pub prune_after_days: Option<i64>,
+    /// Delete videos pruned by `prune_after_days` instead of ignoring them
+    pub prune_delete: bool,
+}
+
+impl Default for ChannelSettings {
+    fn default() -> Self {
+        ChannelSettings {
+            auto_download: false,
+            update_interval_minutes: None,
+            enabled: true,
+            prune_after_days: None,
+            prune_delete: false,
+        }
+    }
+}
+
+impl ChannelSettings {
+    /// Time between updates, `default` if the channel doesn't have its own interval
+    pub fn update_interval(&self, default: chrono::Duration) -> chrono::Duration {
+        self.update_interval_minutes
+            .map(chrono::Duration::minutes)
+            .unwrap_or(default)
+    }
+}
+
+/// Options stored in the `settings` table, which can be changed while vidl is running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    /// Minutes between updates of channels without their own interval, defaults to an hour
+    UpdateInterval,
+    /// Downloader format (`-f` argument) for channels without their own, instead of the one in
+    /// the downloader arguments
+    Format,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 2] = [Setting::UpdateInterval, Setting::Format];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Setting::UpdateInterval => "update_interval",
+            Setting::Format => "format",
+        }
+    }
+
+    pub fn from_str(key: &str) -> Result<Self> {
+        match key {
+            "update_interval" => Ok(Setting::UpdateInterval),
+            "format" => Ok(Setting::Format),
+            _ => Err(anyhow::anyhow!("Unknown setting {:?}", key)),
+        }
+    }
+
+    /// Check `value` is valid for the setting
+    fn validate(&self, value: &str) -> Result<()> {
+        match self {
+            Setting::UpdateInterval => {
+                let minutes: i64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid update interval {:?}", value))?;
+                if minutes < 1 {
+                    anyhow::bail!("Update interval must be at least a minute");
+                }
+            }
+            Setting::Format => {
+                if value.is_empty() {
+                    anyhow::bail!("Format can't be empty");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Columns selected to construct a `Channel` with `Channel::from_row`
+const CHANNEL_COLUMNS: &str =
+    "id, chanid, service, title, thumbnail, backend, description, banner, subscriber_count, handle, format, subtitles, download_dir, filename_format, ignore_min_duration, ignore_max_duration, ignore_title, archived";
+
+impl Channel {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Channel> {
+        Ok(Channel {
+            id: row.get("id")?,
+            chanid: row.get("chanid")?,
+            service: row.get("service")?,
+            title: row.get("title")?,
+            thumbnail: row.get("thumbnail")?,
+            backend: row.get("backend")?,
+            description: row.get("description")?,
+            banner: row.get("banner")?,
+            subscriber_count: row.get("subscriber_count")?,
+            handle: row.get("handle")?,
+            format: row.get("format")?,
+            subtitles: row.get("subtitles")?,
+            download_dir: row.get("download_dir")?,
+            filename_format: row.get("filename_format")?,
+            ignore_rules: IgnoreRules {
+                min_duration: row.get("ignore_min_duration")?,
+                max_duration: row.get("ignore_max_duration")?,
+                title: row.get("ignore_title")?,
+            },
+            archived: row.get("archived")?,
+        })
+    }
+
+    pub fn stats_all(&self, db: &Database) -> Result<ChannelStats> {
+        let mut stmt = db.conn.prepare(
+            "SELECT COUNT(*) AS count, status FROM video
+            WHERE channel=?1
+            GROUP BY status",
+        )?;
+        let chaniter = stmt.query_map(params![self.id], |row| {
+            Ok((row.get("count")?, row.get("status")?))
+        })?;
+        let mut grabbed = 0;
+        let mut new = 0;
+        let mut other = 0;
+        let mut failed = 0;
+        for r in chaniter {
+            let (count, status): (i64, VideoStatus) = r?;
+            match status {
+                VideoStatus::Grabbed => grabbed = count as usize,
+                VideoStatus::New => new = count as usize,
+                VideoStatus::GrabError => {
+                    failed = count as usize;
+                    other += count as usize;
+                }
+                _ => other += count as usize,
+            }
+        }
+
+        Ok(ChannelStats {
+            grabbed,
+            new,
+            other,
+            failed,
+        })
+    }
+
+    /// Total size in bytes of the channel's downloaded files, where known
+    pub fn disk_usage(&self, db: &Database) -> Result<u64> {
+        let size: i64 = db
+            .conn
+            .query_row(
+                "SELECT IFNULL(SUM(file_size), 0) FROM video WHERE channel=?1",
+                params![self.id],
+                |row| row.get(0),
+            )
+            .context("Failed to sum channel's file sizes")?;
+        Ok(size as u64)
+    }
+
+    pub fn stats_1w(&self, db: &Database) -> Result<ChannelStats> {
+        let mut stmt = db.conn.prepare(
+            "SELECT COUNT(*) AS count, status FROM video
+            WHERE channel=?1 AND published_at > datetime('now', '-7 days')
+            GROUP BY status",
+        )?;
+        let chaniter = stmt.query_map(params![self.id], |row| {
+            Ok((row.get("count")?, row.get("status")?))
+        })?;
+        let mut grabbed = 0;
+        let mut new = 0;
+        let mut other = 0;
+        let mut failed = 0;
+        for r in chaniter {
+            let (count, status): (i64, VideoStatus) = r?;
+            match status {
+                VideoStatus::Grabbed => grabbed = count as usize,
+                VideoStatus::New => new = count as usize,
+                VideoStatus::GrabError => {
+                    failed = count as usize;
+                    other += count as usize;
+                }
+                _ => other += count as usize,
+            }
+        }
+
+        Ok(ChannelStats {
+            grabbed,
+            new,
+            other,
+            failed,
+        })
+    }
+
+    pub fn get_by_sqlid(db: &Database, id: i64) -> Result<Channel> {
+        let chan = db
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM channel WHERE id=?1", CHANNEL_COLUMNS),
+                params![id],
+                Channel::from_row,
+            )
+            .context("Failed to find channel by ID")?;
+
+        Ok(chan)
+    }
+
+    /// Get Channel object for given channel, returning error it it does not exist
+    pub fn get(db: &Database, cid: &ChannelID) -> Result<Channel> {
+        let chan = db
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM channel WHERE chanid=?1 AND service = ?2",
+                    CHANNEL_COLUMNS
+                ),
+                params![cid.id_str(), cid.service()],
+                Channel::from_row,
+            )
+            .context("Failed to find channel from ID and service")?;
+
+        Ok(chan)
+    }
+
+    /// Create channel in database
+    pub fn create(
+        db: &Database,
+        cid: &ChannelID,
+        channel_title: &str,
+        thumbnail_url: &str,
+    ) -> Result<Channel> {
+        let check_existing = db.conn.query_row(
+            "SELECT id FROM channel WHERE chanid=?1 AND service=?2",
+            params![cid.id_str(), cid.service()],
+            |_| Ok(()),
+        );
+        match check_existing {
+            // Throw error if channel already exists
+            Ok(_) => Err(anyhow::anyhow!("Channel already exists in database")),
+
+            // No results is good
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(()),
+
+            // Propogate any other errors
+            Err(e) => Err(anyhow::anyhow!(e)),
+        }?;
+
+        db.conn
+            .execute(
+                "INSERT INTO channel (chanid, service, title, thumbnail, handle) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    cid.id_str(),
+                    cid.service(),
+                    channel_title,
+                    thumbnail_url,
+                    cid.handle(),
+                ],
+            )
+            .context("Insert channel query")?;
+
+        // Return newly created channel
+        Channel::get(&db, cid)
+    }
+
+    /// Channel holding videos added individually from their URL, created the first time it's
+    /// needed. It is never updated, as it doesn't exist on Youtube
+    pub fn adhoc(db: &Database) -> Result<Channel> {
+        let cid = Service::Youtube.get_channel_id(ADHOC_CHANNEL_ID);
+        match Channel::get(db, &cid) {
+            Ok(chan) => Ok(chan),
+            Err(_) => Channel::create(db, &cid, "Ad-hoc videos", ""),
+        }
+    }
+
+    pub fn is_adhoc(&self) -> bool {
+        self.service == Service::Youtube && self.chanid == ADHOC_CHANNEL_ID
+    }
+
+    /// Retrieve channel metadata from the source and add the channel to the database
+    pub fn create_from_source(
+        db: &Database,
+        cid: &ChannelID,
+        backend: Option<Backend>,
+    ) -> Result<Channel> {
+        let meta = crate::source::channel_data(cid, backend.unwrap_or(Config::load().backend))
+            .get_metadata()?;
+
+        info!(
+            "Adding {} channel {:?}",
+            cid.service().as_str(),
+            cid.id_str()
+        );
+        let chan = Channel::create(db, cid, &meta.title, &meta.thumbnail)?;
+        chan.set_backend(db, backend)?;
+        Ok(chan)
+    }
+
+    pub fn last_update(&self, db: &Database) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let result: Option<chrono::DateTime<chrono::Utc>> = db.conn.query_row(
+            "SELECT last_update FROM channel WHERE id=?1",
+            params![self.id],
+            |row| Ok(row.get("last_update")?),
+        )?;
+        Ok(result)
+    }
+
+    /// Set the `last_update` time to now
+    pub fn set_last_update(&self, db: &Database) -> Result<()> {
+        let now = chrono::Utc::now();
+        db.conn
+            .execute(
+                "UPDATE channel SET last_update=?1 WHERE id=?2",
+                params![now, self.id],
+            )
+            .context("Failed to update last_update time")?;
+        Ok(())
+    }
+
+    /// Settings of the channel, the defaults if none have been set
+    pub fn settings(&self, db: &Database) -> Result<ChannelSettings> {
+        let settings = db
+            .conn
+            .query_row(
+                "SELECT auto_download, update_interval_minutes, enabled, prune_after_days, prune_delete
+                FROM channel_settings WHERE channel=?1",
+                params![self.id],
+                |row| {
+                    Ok(ChannelSettings {
+                        auto_download: row.get("auto_download")?,
+                        update_interval_minutes: row.get("update_interval_minutes")?,
+                        enabled: row.get("enabled")?,
+                        prune_after_days: row.get("prune_after_days")?,
+                        prune_delete: row.get("prune_delete")?,
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to get channel settings")?;
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// Replace the channel's settings
+    pub fn set_settings(&self, db: &Database, settings: &ChannelSettings) -> Result<()> {
+        if settings.update_interval_minutes.is_some_and(|m| m < 1) {
+            anyhow::bail!("Update interval must be at least a minute");
+        }
+        if settings.prune_after_days.is_some_and(|d| d < 1) {
+            anyhow::bail!("Videos must be kept for at least a day");
+        }
+        db.conn
+            .execute(
+                "INSERT INTO channel_settings (channel, auto_download, update_interval_minutes, enabled, prune_after_days, prune_delete)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT(channel) DO UPDATE SET
+                    auto_download=excluded.auto_download,
+                    update_interval_minutes=excluded.update_interval_minutes,
+                    enabled=excluded.enabled,
+                    prune_after_days=excluded.prune_after_days,
+                    prune_delete=excluded.prune_delete",
+                params![
+                    self.id,
+                    settings.auto_download,
+                    settings.update_interval_minutes,
+                    settings.enabled,
+                    settings.prune_after_days,
+                    settings.prune_delete
+                ],
+            )
+            .context("Failed to update channel settings")?;
+        Ok(())
+    }
+
+    /// Determines if an update for this channel is due based on `last_update` time and
+    /// the channel's update interval
+    pub fn update_required(&self, db: &Database) -> Result<bool> {
+        if self.is_adhoc() || self.archived {
+            return Ok(false);
+        }
+        let settings = self.settings(db)?;
+        if !settings.enabled {
+            return Ok(false);
+        }
+        let last_update = self.last_update(&db)?;
+        match last_update {
+            Some(last_update) => {
+                let now = chrono::Utc::now();
+                let delta = now - last_update;
+                let due_for_update =
+                    delta > settings.update_interval(db.default_update_interval()?);
+                let shedule_due = if due_for_update {
+                    // FIXME: Something like chan.id % 60 == current_minute
+                    true
+                } else {
+                    false
+                };
+                Ok(shedule_due)
+            }
+            None => Ok(true),
+        }
+    }
+
+    pub fn update_metadata(&self, db: &Database, meta: &ChannelMetadata) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET title=?1, thumbnail=?2, description=?3, banner=?4, subscriber_count=?5 WHERE id=?6",
+                params![
+                    meta.title,
+                    meta.thumbnail,
+                    meta.description,
+                    meta.banner,
+                    meta.subscriber_count,
+                    self.id
+                ],
+            )
+            .context("Failed to update channel metadata")?;
+        Ok(())
+    }
+
+    /// Set which backend is used to retrieve channel data, `None` to use the configured default
+    pub fn set_backend(&self, db: &Database, backend: Option<Backend>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET backend=?1 WHERE id=?2",
+                params![backend.map(|b| b.as_str().to_string()), self.id],
+            )
+            .context("Failed to update channel backend")?;
+        Ok(())
+    }
+
+    /// Set format (quality) used when downloading videos, or `None` to use the configured default
+    pub fn set_format(&self, db: &Database, format: Option<&str>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET format=?1 WHERE id=?2",
+                params![format, self.id],
+            )
+            .context("Failed to update channel format")?;
+        Ok(())
+    }
+
+    /// Set directory videos are downloaded to, or `None` to use the configured default
+    pub fn set_download_dir(&self, db: &Database, download_dir: Option<&str>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET download_dir=?1 WHERE id=?2",
+                params![download_dir, self.id],
+            )
+            .context("Failed to update channel download directory")?;
+        Ok(())
+    }
+
+    /// Set downloader output template, or `None` to use the configured default
+    pub fn set_filename_format(&self, db: &Database, filename_format: Option<&str>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET filename_format=?1 WHERE id=?2",
+                params![filename_format, self.id],
+            )
+            .context("Failed to update channel filename format")?;
+        Ok(())
+    }
+
+    /// Replace rules for automatically ignoring new videos
+    pub fn set_ignore_rules(&self, db: &Database, rules: &IgnoreRules) -> Result<()> {
+        rules.validate()?;
+        db.conn
+            .execute(
+                "UPDATE channel SET ignore_min_duration=?1, ignore_max_duration=?2, ignore_title=?3 WHERE id=?4",
+                params![rules.min_duration, rules.max_duration, rules.title, self.id],
+            )
+            .context("Failed to update channel ignore rules")?;
+        Ok(())
+    }
+
+    /// Set whether subtitles are downloaded, or `None` to use the configured default
+    pub fn set_subtitles(&self, db: &Database, subtitles: Option<bool>) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET subtitles=?1 WHERE id=?2",
+                params![subtitles, self.id],
+            )
+            .context("Failed to update channel subtitles")?;
+        Ok(())
+    }
+
+    /// Archive the channel, hiding it from the channel list and stopping updates while
+    /// keeping its videos, or restore it
+    pub fn set_archived(&self, db: &Database, archived: bool) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE channel SET archived=?1 WHERE id=?2",
+                params![archived, self.id],
+            )
+            .context("Failed to update channel archived flag")?;
+        Ok(())
+    }
+
+    /// Add supplied video to database
+    pub fn add_video(&self, db: &Database, video: &VideoInfo) -> Result<DBVideoInfo> {
+        // Videos which can't be watched yet are held back until they go live, and
+        // ones which can never be downloaded are kept out of the queue
+        let status = if video.members_only {
+            VideoStatus::Unavailable
+        } else if video.live_status == LiveStatus::Upcoming {
+            VideoStatus::Upcoming
+        } else if self.ignore_rules.matches(video) {
+            debug!("Ignoring {:?} due to channel rules", &video.title);
+            VideoStatus::Ignore
+        } else {
+            VideoStatus::New
+        };
+
+        // The same video listed by another channel is linked to it instead of being added again,
+        // so it is only downloaded once
+        let existing: Option<(i64, i64)> = db
+            .conn
+            .query_row(
+                "SELECT video.id, video.channel FROM video JOIN channel ON channel.id = video.channel
+                WHERE video.url=?1 OR (video.video_id=?2 AND channel.service=?3 AND channel.id != ?4)
+                ORDER BY video.url=?1 DESC",
+                params![video.url, video.id, self.service, self.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .context("Failed to check for duplicate video")?;
+        // The URL is unique, so it can't be added to this channel again either
+        if existing.is_some_and(|(_, channel)| channel == self.id) {
+            return Err(DatabaseError::VideoExists(video.url.clone()).into());
+        }
+        if let Some((id, channel)) = existing {
+            debug!(
+                "{:?} is already in channel {}, linking as a duplicate",
+                &video.title, channel
+            );
+            db.conn
+                .execute(
+                    "INSERT OR IGNORE INTO video_duplicate (video, channel) VALUES (?1, ?2)",
+                    params![id, self.id],
+                )
+                .context("Failed to link duplicate video")?;
+            return DBVideoInfo::get_by_sqlid(db, id);
+        }
+
+        db.conn
+            .execute(
+                "INSERT INTO video (channel, video_id, url, title, description, thumbnail, published_at, status, duration, date_added, live_status, view_count, captions, scheduled_at, members_only)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                params![
+                    self.id,
+                    video.id,
+                    video.url,
+                    video.title,
+                    video.description,
+                    video.thumbnail_url,
+                    video.published_at.to_rfc3339(),
+                    status,
+                    video.duration,
+                    // Formatted like `CURRENT_TIMESTAMP`, the column's default
+                    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                    video.live_status.as_str(),
+                    video.view_count,
+                    video.captions.join(","),
+                    video.scheduled_at,
+                    video.members_only,
+                ],
+            )
+            .context("Add video query")?;
+        let last_id = db.conn.last_insert_rowid();
+
+        let dbv = DBVideoInfo::get_by_sqlid(&db, last_id)?;
+        dbv.set_thumbnails(db, &video.thumbnails)?;
+        Ok(dbv)
+    }
+
+    /// Like `add_video`, but a video the channel already has with the same URL (e.g as it was
+    /// re-published) has its title, description and duration updated instead. Returns the
+    /// video, and whether it was newly added to this channel
+    pub fn upsert_video(&self, db: &Database, video: &VideoInfo) -> Result<(DBVideoInfo, bool)> {
+        let err = match self.add_video(db, video) {
+            Ok(dbv) => {
+                let added = dbv.chanid == self.id;
+                return Ok((dbv, added));
+            }
+            Err(e) => e,
+        };
+        if !matches!(
+            err.downcast_ref::<DatabaseError>(),
+            Some(DatabaseError::VideoExists(_))
+        ) {
+            return Err(err);
+        }
+
+        debug!("Updating already added video {:?}", &video.url);
+        // Listings can have a shortened description, or no duration for streams, so they don't
+        // replace the full ones fetched with the video's details
+        let id: i64 = db
+            .conn
+            .query_row(
+                "UPDATE video SET title=?1,
+                    description=CASE WHEN ?2 = '' OR instr(description, ?2) = 1 THEN description ELSE ?2 END,
+                    duration=CASE WHEN ?3 > 0 THEN ?3 ELSE duration END
+                WHERE channel=?4 AND url=?5
+                RETURNING id",
+                params![
+                    video.title,
+                    video.description,
+                    video.duration,
+                    self.id,
+                    video.url
+                ],
+                |row| row.get(0),
+            )
+            .context("Failed to update existing video")?;
+        Ok((DBVideoInfo::get_by_sqlid(db, id)?, false))
+    }
+
+    /// Update the live status of an already-added video, e.g once a stream has finished
+    pub fn set_video_live_status(
+        &self,
+        db: &Database,
+        url: &str,
+        live_status: LiveStatus,
+    ) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET live_status=?1 WHERE channel=?2 AND url=?3",
+                params![live_status.as_str(), self.id, url],
+            )
+            .context("Failed to update video live status")?;
+        Ok(())
+    }
+
+    /// Update the scheduled start of an already-added video, as premieres can be rescheduled
+    pub fn set_video_scheduled_at(
+        &self,
+        db: &Database,
+        url: &str,
+        scheduled_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET scheduled_at=?1 WHERE channel=?2 AND url=?3",
+                params![scheduled_at, self.id, url],
+            )
+            .context("Failed to update video scheduled time")?;
+        Ok(())
+    }
+
+    /// Mark an already-added video as members-only, so it is no longer downloadable. Videos
+    /// which were grabbed or ignored are left as they are
+    pub fn mark_unavailable(&self, db: &Database, url: &str) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET members_only=1, status=?1
+                WHERE channel=?2 AND url=?3 AND status IN (?4, ?5, ?6, ?7)",
+                params![
+                    VideoStatus::Unavailable,
+                    self.id,
+                    url,
+                    VideoStatus::New,
+                    VideoStatus::Queued,
+                    VideoStatus::GrabError,
+                    VideoStatus::Upcoming,
+                ],
+            )
+            .context("Failed to mark video unavailable")?;
+        Ok(())
+    }
+
+    /// Record videos with URLs in `listed_urls` as seen in the source now
+    pub fn mark_seen(&self, db: &Database, listed_urls: &HashSet<String>) -> Result<()> {
+        let now = chrono::Utc::now();
+        let mut q = db
+            .conn
+            .prepare("UPDATE video SET last_seen=?1, missed_updates=0 WHERE url=?2")?;
+        for url in listed_urls {
+            q.execute(params![now, url])
+                .context("Failed to mark video seen")?;
+        }
+        Ok(())
+    }
+
+    /// Count a missed update for videos whose URL is not in `listed_urls` (the complete list
+    /// from the source), recording them as removed once they have been missing from
+    /// `removed_after` lists in a row. Their status becomes `Removed`, unless they are
+    /// downloaded or being downloaded, which is kept. Returns the number of videos marked
+    pub fn mark_removed(
+        &self,
+        db: &Database,
+        listed_urls: &HashSet<String>,
+        removed_after: i64,
+    ) -> Result<usize> {
+        let mut q = db.conn.prepare(
+            "SELECT id, url, status, missed_updates FROM video
+                WHERE channel=?1 AND removed_at IS NULL",
+        )?;
+        let mapped = q.query_map(params![self.id], |row| {
+            Ok((
+                row.get("id")?,
+                row.get("url")?,
+                row.get("status")?,
+                row.get("missed_updates")?,
+            ))
+        })?;
+
+        let now = chrono::Utc::now();
+        let mut removed = 0;
+        for m in mapped {
+            let (id, url, status, missed): (i64, String, VideoStatus, i64) = m?;
+            if listed_urls.contains(&url) {
+                continue;
+            }
+            if missed + 1 >= removed_after {
+                let status = match status {
+                    VideoStatus::Grabbed | VideoStatus::Queued | VideoStatus::Downloading => status,
+                    _ => VideoStatus::Removed,
+                };
+                db.conn
+                    .execute(
+                        "UPDATE video SET status=?1, removed_at=?2, missed_updates=?3 WHERE id=?4",
+                        params![status, now, missed + 1, id],
+                    )
+                    .context("Failed to mark video removed")?;
+                removed += 1;
+            } else {
+                db.conn
+                    .execute(
+                        "UPDATE video SET missed_updates=?1 WHERE id=?2",
+                        params![missed + 1, id],
+                    )
+                    .context("Failed to count missed update of video")?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Mark videos with the given service IDs as `Grabbed`, e.g when they were downloaded
+    /// outside of vidl. Returns number of videos changed
+    pub fn mark_grabbed(&self, db: &Database, video_ids: &HashSet<String>) -> Result<usize> {
+        let mut q = db.conn.prepare(
+            "SELECT id, video_id FROM video WHERE channel=?1 AND status != ?2 AND status != ?3",
+        )?;
+        let mapped = q.query_map(
+            params![self.id, VideoStatus::Grabbed, VideoStatus::Downloading],
+            |row| Ok((row.get("id")?, row.get("video_id")?)),
+        )?;
+
+        let mut found: Vec<i64> = vec![];
+        for m in mapped {
+            let (id, video_id): (i64, String) = m?;
+            if video_ids.contains(&video_id) {
+                found.push(id);
+            }
+        }
+
+        for id in &found {
+            db.conn
+                .execute(
+                    "UPDATE video SET status=?1 WHERE id=?2",
+                    params![VideoStatus::Grabbed, id],
+                )
+                .context("Failed to mark video grabbed")?;
+            crate::events::publish(Event::VideoStatus {
+                video: *id,
+                status: VideoStatus::Grabbed,
+            });
+        }
+        Ok(found.len())
+    }
+
+    /// Change all of channel's `New` videos (optionally only those published before `before`)
+    /// to `status` in one update, returning the SQL IDs of videos changed. Times are compared
+    /// with `datetime()`, as `published_at` is stored formatted differently to `before`
+    fn set_new_videos_status(
+        &self,
+        db: &Database,
+        status: VideoStatus,
+        before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<i64>> {
+        let mut q = db.conn.prepare(
+            "UPDATE video SET status=?1
+            WHERE channel=?2 AND status=?3
+                AND (?4 IS NULL OR datetime(published_at) < datetime(?4))
+            RETURNING id",
+        )?;
+        let ids = q
+            .query_map(params![status, self.id, VideoStatus::New, before], |row| {
+                row.get("id")
+            })?
+            .collect::<rusqlite::Result<Vec<i64>>>()
+            .context("Failed to update videos status")?;
+        for id in &ids {
+            crate::events::publish(Event::VideoStatus { video: *id, status });
+        }
+        Ok(ids)
+    }
+
+    /// Queue all of channel's `New` videos for download, returning them
+    pub fn queue_new(&self, db: &Database) -> Result<Vec<DBVideoInfo>> {
+        self.set_new_videos_status(db, VideoStatus::Queued, None)?
+            .into_iter()
+            .map(|id| DBVideoInfo::get_by_sqlid(db, id))
+            .collect()
+    }
+
+    /// Queue all of channel's `GrabError` videos again (as with `DBVideoInfo::retry`), returning them
+    pub fn retry_failed(&self, db: &Database) -> Result<Vec<DBVideoInfo>> {
+        let mut q = db.conn.prepare(
+            "UPDATE video SET status=?1, retry_count=0, retry_at=NULL
+            WHERE channel=?2 AND status=?3
+            RETURNING id",
+        )?;
+        let ids = q
+            .query_map(
+                params![VideoStatus::Queued, self.id, VideoStatus::GrabError],
+                |row| row.get("id"),
+            )?
+            .collect::<rusqlite::Result<Vec<i64>>>()
+            .context("Failed to retry failed videos")?;
+        ids.into_iter()
+            .map(|id| {
+                crate::events::publish(Event::VideoStatus {
+                    video: id,
+                    status: VideoStatus::Queued,
+                });
+                DBVideoInfo::get_by_sqlid(db, id)
+            })
+            .collect()
+    }
+
+    /// Ignore channel's `New` videos published before `before`. Returns number of videos changed
+    pub fn ignore_older_than(
+        &self,
+        db: &Database,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize> {
+        Ok(self
+            .set_new_videos_status(db, VideoStatus::Ignore, Some(before))?
+            .len())
+    }
+
+    /// Apply the channel's retention policy, ignoring (or deleting) videos still `New` more than
+    /// `prune_after_days` after being published. Returns number of videos pruned
+    pub fn prune_stale(&self, db: &Database) -> Result<usize> {
+        let settings = self.settings(db)?;
+        let days = match settings.prune_after_days {
+            Some(d) => d,
+            None => return Ok(0),
+        };
+        let before = chrono::Utc::now() - chrono::Duration::days(days);
+        if settings.prune_delete {
+            delete_videos(
+                db,
+                "channel=?1 AND status=?2 AND datetime(published_at) < datetime(?3)",
+                &[&self.id, &VideoStatus::New, &before],
+            )
+        } else {
+            self.ignore_older_than(db, before)
+        }
+    }
+
+    /// Change `Upcoming` videos to `New` once they are no longer upcoming. Returns number of videos changed
+    pub fn release_upcoming(&self, db: &Database) -> Result<usize> {
+        let changed = db
+            .conn
+            .execute(
+                "UPDATE video SET status=?1
+                WHERE channel=?2 AND status=?3 AND live_status != ?4",
+                params![
+                    VideoStatus::New,
+                    self.id,
+                    VideoStatus::Upcoming,
+                    LiveStatus::Upcoming.as_str(),
+                ],
+            )
+            .context("Failed to release upcoming videos")?;
+        Ok(changed)
+    }
+
+    /// Update the view count of an already-added video
+    pub fn set_video_view_count(&self, db: &Database, url: &str, view_count: i64) -> Result<()> {
+        db.conn
+            .execute(
+                "UPDATE video SET view_count=?1 WHERE channel=?2 AND url=?3",
+                params![view_count, self.id, url],
+            )
+            .context("Failed to update video view count")?;
+        Ok(())
+    }
+
+    /// Get the URL's of the most recently published videos - returning up to and including `num` results.
+    pub fn last_n_video_urls(&self, db: &Database, num: i64) -> Result<HashSet<String>> {
+        let mut q = db.conn.prepare(
+            "SELECT url FROM video
+                WHERE channel=?1 OR id IN (SELECT video FROM video_duplicate WHERE channel=?1)
+                ORDER BY published_at DESC
+                LIMIT ?2",
+        )?;
+        let mapped = q.query_map(params![self.id, num], |row| row.get("url"))?;
+
+        let mut set = HashSet::new();
+        for m in mapped {
+            let url: String = m?;
+            set.insert(url);
+        }
+
+        Ok(set)
+    }
+
+    pub fn all_videos(
+        &self,
+        db: &Database,
+        limit: i64,
+        page: i64,
+        filter: Option<FilterParams>,
+    ) -> Result<Vec<DBVideoInfo>> {
+        all_videos(&db, limit, page, Some(self.own_filter(filter)))
+    }
+
+    /// Like `all_videos`, but continuing after the video `cursor` points to
+    pub fn all_videos_after(
+        &self,
+        db: &Database,
+        limit: i64,
+        cursor: Option<&VideoCursor>,
+        filter: Option<FilterParams>,
+    ) -> Result<Vec<DBVideoInfo>> {
+        all_videos_after(db, limit, cursor, Some(self.own_filter(filter)))
+    }
+
+    /// `filter` restricted to this channel's videos
+    fn own_filter(&self, filter: Option<FilterParams>) -> FilterParams {
+        match filter {
+            Some(f) => FilterParams {
+                name_contains: f.name_contains,
+                status: f.status,
+                status_not: f.status_not,
+                chanid: Some(self.id),
+                collection: f.collection,
+                tag: f.tag,
+                search: f.search,
+                published_after: f.published_after,
+                published_before: f.published_before,
+                watched: f.watched,
+                order_by: f.order_by,
+            },
+            None => FilterParams {
+                name_contains: None,
+                status: None,
+                status_not: None,
+                chanid: Some(self.id),
+                collection: None,
+                tag: None,
+                search: None,
+                published_after: None,
+                published_before: None,
+                watched: None,
+                order_by: VideoOrder::default(),
+            },
+        }
+    }
+
+    /// Number of the channel's videos matching `filter`
+    pub fn count_videos(&self, db: &Database, filter: Option<FilterParams>) -> Result<i64> {
+        count_videos(db, Some(self.own_filter(filter)))
+    }
+
+    /// Retrieve metadata and new videos, returning the videos which were added
+    pub fn update(&self, db: &Database, full_update: bool) -> Result<Vec<DBVideoInfo>> {
+        // Set updated time now (even in case of failure)
+        self.set_last_update(&db)?;
+
+        if self.is_adhoc() {
+            debug!("Not updating {:?} as it has no source", &self.title);
+            return Ok(vec![]);
+        }
+
+        let cid = self.service.get_channel_id(&self.chanid);
+        let backend = self.backend.unwrap_or_else(|| Config::load().backend);
+        let api = crate::source::channel_data(&cid, backend);
+        let api = &*api;
+
+        // Videos are listed on another thread, so the first page is requested while the
+        // metadata is, and each following page while the videos before it are checked
+        let listing = std::thread::scope(|s| -> Result<Option<_>> {
+            // Holds one video, so at most a page more than needed is requested. Dropped on
+            // returning, which stops the listing thread
+            let (sender, listed) = std::sync::mpsc::sync_channel(0);
+            s.spawn(move || {
+                for v in api.videos() {
+                    if sender.send(v).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let meta = api.get_metadata();
+
+            match meta {
+                Ok(meta) => self.update_metadata(&db, &meta)?,
+                Err(e) => {
+                    error!(
+                        "Error fetching metadata for {:?} - {} - skipping channel",
+                        self.chanid, e
+                    );
+                    // Skip to next channel
+                    return Ok(None);
+                }
+            }
+
+            let seen_videos = self
+                .last_n_video_urls(&db, 200)
+                .context("Failed to find latest video URLs")?;
+
+            trace!("Last seen video URL's: {:?}", &seen_videos);
+
+            let mut new_videos: Vec<crate::source::base::VideoInfo> = vec![];
+
+            // Every URL listed by the source, all of the channel's videos on a full update
+            let mut listed_urls: HashSet<String> = HashSet::new();
+
+            for v in listed {
+                let v = v?;
+                listed_urls.insert(v.url.clone());
+
+                if seen_videos.contains(&v.url) {
+                    // Streams are seen before they finish, so keep their status current
+                    self.set_video_live_status(db, &v.url, v.live_status)?;
+                    self.set_video_scheduled_at(db, &v.url, v.scheduled_at)?;
+                    if v.members_only {
+                        self.mark_unavailable(db, &v.url)?;
+                    }
+                    if let Some(views) = v.view_count {
+                        self.set_video_view_count(db, &v.url, views)?;
+                    }
+
+                    if !full_update {
+                        if v.live_status.is_ongoing() {
+                            // Continue past ongoing streams as they may be newer than the last seen upload
+                            continue;
+                        }
+                        debug!("Already seen video by URL {:?}", v.url);
+                        break;
+                    }
+                }
+
+                trace!("New video {:?}", &v);
+                new_videos.push(v);
+            }
+            Ok(Some((new_videos, listed_urls)))
+        })?;
+        let (new_videos, listed_urls) = match listing {
+            Some(l) => l,
+            None => return Ok(vec![]),
+        };
+
+        // Changes are made together once everything has been listed, so a failure part way
+        // through leaves the channel as it was. It's rolled back if dropped before committing
+        let tx = db
+            .conn
+            .unchecked_transaction()
+            .context("Failed to start channel update transaction")?;
+
+        let mut added: Vec<DBVideoInfo> = vec![];
+        for v in new_videos {
+            debug!("Adding {0}", v.title);
+            trace!("{:?}", &v);
+            // Videos listed twice, or beyond the last seen videos on a full update, are updated
+            // instead, as are duplicates of another channel's video
+            match self.upsert_video(&db, &v) {
+                Ok((dbv, true)) => added.push(dbv),
+                Ok((_, false)) => (),
+                Err(e) => error!("Error adding video {:?} - {:?}", &v, e),
+            };
+        }
+
+        self.mark_seen(db, &listed_urls)?;
+
+        // Videos no longer listed have been deleted or made private. An empty listing more likely
+        // means something went wrong with the source than every video being removed
+        if full_update && !listed_urls.is_empty() {
+            let removed = self.mark_removed(db, &listed_urls, Config::load().removed_after)?;
+            if removed > 0 {
+                info!("{} videos removed from {:?}", removed, self.title);
+            }
+        }
+
+        // Live status of seen videos was refreshed above, so premieres which have started can be released
+        let released = self.release_upcoming(db)?;
+        if released > 0 {
+            info!(
+                "{} upcoming videos now available in {:?}",
+                released, self.title
+            );
+        }
+
+        // Before auto-downloading, so stale videos found by a full update aren't queued
+        let pruned = self.prune_stale(db)?;
+        if pruned > 0 {
+            info!("Pruned {} stale new videos from {:?}", pruned, self.title);
+        }
+
+        if self.settings(db)?.auto_download {
+            let queued = self.queue_new(db)?;
+            if !queued.is_empty() {
+                info!("Queued {} new videos from {:?}", queued.len(), self.title);
+            }
+        }
+
+        tx.commit()
+            .context("Failed to commit channel update transaction")?;
+        Ok(added)
+    }
+
+    /// Retrieve tags, captions, full description and chapters for the given
+    /// videos, which requires a request per video. Up to `DETAIL_REQUESTS` are
+    /// made at once, subject to the source's rate limit
+    pub fn fetch_video_details(&self, db: &Database, videos: &[DBVideoInfo]) -> Result<()> {
+        let cid = self.service.get_channel_id(&self.chanid);
+        let backend = self.backend.unwrap_or_else(|| Config::load().backend);
+        let api = crate::source::channel_data(&cid, backend);
+        let api = &*api;
+
+        // Requested on other threads, and saved on this one with the connection as they arrive
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let next = &next;
+        std::thread::scope(|s| -> Result<()> {
+            let (sender, fetched) = std::sync::mpsc::channel();
+            for _ in 0..DETAIL_REQUESTS.min(videos.len()) {
+                let sender = sender.clone();
+                s.spawn(move || loop {
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let dbv = match videos.get(i) {
+                        Some(v) => v,
+                        None => break,
+                    };
+                    if sender.send((dbv, api.video_details(&dbv.info.id))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            for (dbv, details) in fetched {
+                match details {
+                    Ok(details) => {
+                        dbv.add_tags(db, &details.tags)?;
+                        if !details.captions.is_empty() {
+                            dbv.set_captions(db, &details.captions)?;
+                        }
+                        if let Some(desc) = &details.description {
+                            dbv.set_description(db, desc)?;
+                        }
+                        dbv.set_chapters(db, &details.chapters)?;
+                    }
+                    Err(e) => error!("Error fetching details for {:?} - {:?}", &dbv.info, e),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Delete the channel and all its videos, which unlike `set_archived` can't be undone
+    pub fn delete(self, db: &Database) -> Result<()> {
+        db.conn
+            .execute(
+                "DELETE FROM video_duplicate WHERE channel=?1",
+                params![self.id],
+            )
+            .context("Failed to delete duplicates listed by channel")?;
+
+        delete_videos(db, "channel=?1", &[&self.id])?;
+
+        db.conn
+            .execute(
+                "DELETE FROM channel_settings WHERE channel=?1",
+                params![self.id],
+            )
+            .context("Failed to delete channel settings")?;
+
+        db.conn
+            .execute("DELETE FROM channel WHERE id=?1", params![self.id])
+            .context("Failed to delete channel")?;
+
+        Ok(())
+    }
+}
+
+/// Delete videos matching `pred`, an SQL condition on the `video` table using `params`, along
+/// with the rows of other tables referring to them. Returns number of videos deleted
+fn delete_videos(db: &Database, pred: &str, params: &[&dyn ToSql]) -> Result<usize> {
+    let videos = format!("SELECT id FROM video WHERE {}", pred);
+    for table in [
+        "video_tag",
+        "video_chapter",
+        "video_subtitle",
+        "collection_video",
+        "video_thumbnail",
+        "video_history",
+        "video_alt_edit",
+        "video_duplicate",
+    ] {
+        db.conn
+            .execute(
+                &format!("DELETE FROM {} WHERE video IN ({})", table, videos),
+                params,
+            )
+            .with_context(|| format!("Failed to delete {} rows of videos", table))?;
+    }
+    let deleted = db
+        .conn
+        .execute(&format!("DELETE FROM video WHERE {}", pred), params)
+        .context("Failed to delete videos")?;
+    Ok(deleted)
+}
+
+/// All channels present in database, except archived ones
+pub fn list_channels(db: &Database) -> Result<Vec<Channel>> {
+    query_channels(db, Some(false))
+}
+
+/// Number of channels `list_channels` returns, i.e those which aren't archived
+pub fn count_channels(db: &Database) -> Result<i64> {
+    db.conn
+        .query_row(
+            "SELECT COUNT(*) FROM channel WHERE NOT archived",
+            params![],
+            |row| row.get(0),
+        )
+        .context("Failed to count channels")
+}
+
+/// Only the archived channels
+pub fn list_archived_channels(db: &Database) -> Result<Vec<Channel>> {
+    query_channels(db, Some(true))
+}
+
+/// All channels present in database, including archived ones
+pub fn list_all_channels(db: &Database) -> Result<Vec<Channel>> {
+    query_channels(db, None)
+}
+
+/// Channels ordered by title, either those which are or aren't archived or all of them
+fn query_channels(db: &Database, archived: Option<bool>) -> Result<Vec<Channel>> {
+    let mut stmt = db.conn.prepare(&format!(
+        "SELECT {} FROM channel WHERE ?1 IS NULL OR archived=?1 ORDER BY title",
+        CHANNEL_COLUMNS
+    ))?;
+    let chaniter = stmt.query_map(params![archived], Channel::from_row)?;
+    let mut ret = vec![];
+    for r in chaniter {
+        ret.push(r?);
+    }
+    Ok(ret)
+}
+
+/// Change of a video's status, from its history
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    /// `None` when the video was added
+    pub old: Option<VideoStatus>,
+    pub new: VideoStatus,
+    /// `cli`, `web` or `worker`, as from `Origin::as_str`
+    pub origin: String,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Text of a video which can be replaced by hand, as the name of its column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AltField {
+    Title,
+    Description,
+}
+
+impl AltField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AltField::Title => "title_alt",
+            AltField::Description => "description_alt",
+        }
+    }
+
+    pub fn from_str(field: &str) -> Result<Self> {
+        match field {
+            "title_alt" => Ok(AltField::Title),
+            "description_alt" => Ok(AltField::Description),
+            _ => Err(anyhow::anyhow!("Unknown video field {:?}", field)),
+        }
+    }
+}
+
+/// Change of a video's alternative title or description
+#[derive(Debug, Clone, PartialEq)]
+pub struct AltEdit {
+    /// SQL ID, to revert it with
+    pub id: i64,
+    pub field: AltField,
+    /// Value before the edit, `None` if it wasn't set (showing the source's)
+    pub old_value: Option<String>,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Where a video was downloaded to
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadedFile {
+    pub path: String,
+    /// In bytes, unknown for videos downloaded before it was recorded
+    pub size: Option<u64>,
+    /// Unknown for videos downloaded before it was recorded
+    pub downloaded_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Group of videos chosen by hand from any channels, like "watch later"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collection {
+    /// SQL ID number
+    pub id: i64,
+    pub name: String,
+}
+
+impl Collection {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Collection> {
+        Ok(Collection {
+            id: row.get("id")?,
+            name: row.get("name")?,
+        })
+    }
+
+    /// Create empty collection, named `name` without surrounding whitespace
+    pub fn create(db: &Database, name: &str) -> Result<Collection> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Collection name can't be empty"));
+        }
+        db.conn
+            .query_row(
+                "INSERT INTO collection (name) VALUES (?1) RETURNING id, name",
+                params![name],
+                Collection::from_row,
+            )
+            .with_context(|| format!("Failed to create collection {:?}", name))
+    }
+
+    pub fn get_by_sqlid(db: &Database, id: i64) -> Result<Collection> {
+        db.conn
+            .query_row(
+                "SELECT id, name FROM collection WHERE id=?1",
+                params![id],
+                Collection::from_row,
+            )
+            .context("Failed to find collection by ID")
+    }
+
+    pub fn rename(&mut self, db: &Database, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Collection name can't be empty"));
+        }
+        db.conn
+            .execute(
+                "UPDATE collection SET name=?2 WHERE id=?1",
+                params![self.id, name],
+            )
+            .with_context(|| format!("Failed to rename collection to {:?}", name))?;
+        self.name = name.into();
+        Ok(())
+    }
+
+    /// Delete collection, leaving the videos in it
+    pub fn delete(self, db: &Database) -> Result<()> {
+        db.conn
+            .execute(
+                "DELETE FROM collection_video WHERE collection=?1",
+                params![self.id],
+            )
+            .context("Failed to remove videos from collection")?;
+        db.conn
+            .execute("DELETE FROM collection WHERE id=?1", params![self.id])
+            .context("Failed to delete collection")?;
+        Ok(())
+    }
+
+    /// Add video to collection. False if it was already in it
+    pub fn add_video(&self, db: &Database, video: &DBVideoInfo) -> Result<bool> {
+        let added = db
+            .conn
+            .execute(
+                "INSERT OR IGNORE INTO collection_video (collection, video) VALUES (?1, ?2)",
+                params![self.id, video.id],
+            )
+            .context("Failed to add video to collection")?;
+        Ok(added > 0)
+    }
+
+    /// Remove video from collection. False if it wasn't in it
+    pub fn remove_video(&self, db: &Database, video: &DBVideoInfo) -> Result<bool> {
+        let removed = db
+            .conn
+            .execute(
+                "DELETE FROM collection_video WHERE collection=?1 AND video=?2",
+                params![self.id, video.id],
+            )
+            .context("Failed to remove video from collection")?;
+        Ok(removed > 0)
+    }
+
+    /// Videos in the collection, newest first
+    pub fn videos(&self, db: &Database, limit: i64, page: i64) -> Result<Vec<DBVideoInfo>> {
+        let filter = FilterParams {
+            collection: Some(self.id),
+            ..FilterParams::default()
+        };
+        all_videos(db, limit, page, Some(filter))
+    }
+
+    pub fn count_videos(&self, db: &Database) -> Result<i64> {
+        db.conn
+            .query_row(
+                "SELECT COUNT(*) FROM collection_video WHERE collection=?1",
+                params![self.id],
+                |row| row.get(0),
+            )
+            .context("Failed to count videos in collection")
+    }
+}
+
+/// All collections, by name
+pub fn list_collections(db: &Database) -> Result<Vec<Collection>> {
+    let mut q = db
+        .conn
+        .prepare("SELECT id, name FROM collection ORDER BY name")?;
+    let mapped = q.query_map(params![], Collection::from_row)?;
+    let mut ret = vec![];
+    for m in mapped {
+        ret.push(m?);
+    }
+    Ok(ret)
+}
+
+/// Convert comma separated caption languages from the database into a list
+fn split_captions(raw: String) -> Vec<String> {
+    raw.split(',')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+#[derive(Clone, Default)]
+pub struct FilterParams {
+    pub name_contains: Option<String>,
+    pub status: Option<HashSet<VideoStatus>>,
+    /// Exclude videos with any of these statuses
+    pub status_not: Option<HashSet<VideoStatus>>,
+    pub chanid: Option<i64>,
+    /// Only videos in this collection
+    pub collection: Option<i64>,
+    /// Only videos with this exact tag
+    pub tag: Option<String>,
+    /// Words which must all be in the title, alternative title or description (or prefixes
+    /// of words in them), unless the text is in the channel's name. Words like `tag:music`
+    /// instead only match videos with that tag
+    pub search: Option<String>,
+    /// Only videos published at or after this time
+    pub published_after: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only videos published before this time
+    pub published_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only watched (or with `false`, unwatched) videos
+    pub watched: Option<bool>,
+    /// Order the videos are listed in
+    pub order_by: VideoOrder,
+}
+
+/// Order videos are listed in
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum VideoOrder {
+    /// Newest first
+    #[default]
+    Published,
+    /// Most recently added to vidl first
+    Added,
+    /// Longest first
+    Duration,
+    /// Alphabetically by title
+    Title,
+}
+
+impl VideoOrder {
+    pub fn as_str(&self) -> &str {
+        match self {
+            VideoOrder::Published => "published",
+            VideoOrder::Added => "added",
+            VideoOrder::Duration => "duration",
+            VideoOrder::Title => "title",
+        }
+    }
+
+    pub fn from_str(order: &str) -> Result<Self> {
+        match order {
+            "published" => Ok(VideoOrder::Published),
+            "added" => Ok(VideoOrder::Added),
+            "duration" => Ok(VideoOrder::Duration),
+            "title" => Ok(VideoOrder::Title),
+            _ => Err(anyhow::anyhow!("Unknown video order {:?}", order)),
+        }
+    }
+
+    /// `ORDER BY` terms, with the SQL ID keeping the order stable between pages
+    fn sql(&self) -> &str {
+        match self {
+            VideoOrder::Published => "datetime(published_at) DESC, id DESC",
+            // Stored in one format, so it is ordered by its index
+            VideoOrder::Added => "date_added DESC, id DESC",
+            VideoOrder::Duration => "duration DESC, id DESC",
+            VideoOrder::Title => "title COLLATE NOCASE ASC, id ASC",
+        }
+    }
+}
+
+/// Search text without any `tag:name` words, and the tag names from them
+fn split_search_tags(text: &str) -> (String, Vec<String>) {
+    let (tags, words): (Vec<&str>, Vec<&str>) = text
+        .split_whitespace()
+        .partition(|w| w.starts_with("tag:") && w.len() > 4);
+    (
+        words.join(" "),
+        tags.iter().map(|t| t[4..].to_string()).collect(),
+    )
+}
+
+/// Full-text search query matching all words in `text` as prefixes, or `None` if there are no words
+fn fts_query(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        // Quoted so punctuation isn't treated as query syntax
+        .map(|w| format!("\"{}\"*", w.replace('"', "")))
+        .filter(|w| w != "\"\"*")
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// `Queued` videos in the order they should be downloaded - highest priority first, then oldest
+pub fn queued_videos(db: &Database) -> Result<Vec<DBVideoInfo>> {
+    let mut q = db.conn.prepare(
+        "SELECT id FROM video WHERE status=?1 ORDER BY priority DESC, published_at ASC, id ASC",
+    )?;
+    let mapped = q.query_map(params![VideoStatus::Queued], |row| row.get("id"))?;
+
+    let mut ret = vec![];
+    for m in mapped {
+        let id: i64 = m?;
+        ret.push(DBVideoInfo::get_by_sqlid(db, id)?);
+    }
+    Ok(ret)
+}
+
+/// Move queued video `offset` places in the download queue (negative is sooner), by renumbering
+/// the priorities of all queued videos
+pub fn move_in_queue(db: &Database, video: &DBVideoInfo, offset: i64) -> Result<()> {
+    let mut queue = queued_videos(db)?;
+    let pos = queue
+        .iter()
+        .position(|v| v.id == video.id)
+        .ok_or_else(|| anyhow::anyhow!("Video {} is not queued", video.id))?;
+    let new_pos = (pos as i64 + offset).clamp(0, queue.len() as i64 - 1) as usize;
+    let moved = queue.remove(pos);
+    queue.insert(new_pos, moved);
+
+    let len = queue.len() as i64;
+    for (i, v) in queue.iter().enumerate() {
+        v.set_priority(db, len - i as i64)?;
+    }
+    Ok(())
+}
+
+/// Seconds without a heartbeat after which a `Downloading` video's download is taken to have
+/// been interrupted. Running downloads update it every `worker::DOWNLOAD_HEARTBEAT`
+pub const DOWNLOAD_STALE_AFTER: i64 = 120;
+
+/// Change videos left `Downloading` by a process which stopped or was killed back to `Queued`,
+/// returning them. Downloads whose process is still updating their heartbeat are left alone,
+/// so this is safe to call while other processes are downloading
+pub fn requeue_interrupted(db: &Database) -> Result<Vec<DBVideoInfo>> {
+    let mut q = db.conn.prepare(
+        "SELECT id, partial_path, download_pid FROM video
+            WHERE status=?1 AND (download_heartbeat IS NULL
+                OR datetime(download_heartbeat) < datetime('now', ?2))",
+    )?;
+    let mapped = q.query_map(
+        params![
+            VideoStatus::Downloading,
+            format!("-{} seconds", DOWNLOAD_STALE_AFTER)
+        ],
+        |row| {
+            Ok((
+                row.get("id")?,
+                row.get("partial_path")?,
+                row.get("download_pid")?,
+            ))
+        },
+    )?;
+
+    let mut ret = vec![];
+    for m in mapped {
+        let (id, partial_path, pid): (i64, Option<String>, Option<u32>) = m?;
+        match partial_path {
+            Some(p) if std::path::Path::new(&format!("{}.part", p)).exists() => {
+                info!(
+                    "Resuming interrupted download of {:?} (process {:?})",
+                    &p, pid
+                )
+            }
+            _ => info!(
+                "Restarting interrupted download of video {} (process {:?})",
+                id, pid
+            ),
+        }
+        // Checked again, in case it was requeued and claimed since being listed
+        let changed = db
+            .conn
+            .execute(
+                "UPDATE video SET status=?1, download_pid=NULL, download_heartbeat=NULL
+                    WHERE id=?2 AND status=?3 AND download_pid IS ?4",
+                params![VideoStatus::Queued, id, VideoStatus::Downloading, pid],
+            )
+            .context("Failed to requeue interrupted video")?;
+        if changed == 0 {
+            continue;
+        }
+        crate::events::publish(Event::VideoStatus {
+            video: id,
+            status: VideoStatus::Queued,
+        });
+        ret.push(DBVideoInfo::get_by_sqlid(db, id)?);
+    }
+    Ok(ret)
+}
+
+/// Change `GrabError` videos which are due a retry back to `Queued`, returning them
+pub fn requeue_due_retries(db: &Database) -> Result<Vec<DBVideoInfo>> {
+    let mut q = db
+        .conn
+        .prepare("SELECT id, retry_at FROM video WHERE status=?1 AND retry_at IS NOT NULL")?;
+    let mapped = q.query_map(params![VideoStatus::GrabError], |row| {
+        Ok((row.get("id")?, row.get("retry_at")?))
+    })?;
+
+    let now = chrono::Utc::now();
+    let mut due: Vec<i64> = vec![];
+    for m in mapped {
+        let (id, retry_at): (i64, chrono::DateTime<chrono::Utc>) = m?;
+        if retry_at <= now {
+            due.push(id);
+        }
+    }
+
+    let mut ret = vec![];
+    for id in due {
+        db.conn
+            .execute(
+                "UPDATE video SET status=?1, retry_at=NULL WHERE id=?2",
+                params![VideoStatus::Queued, id],
+            )
+            .context("Failed to requeue video")?;
+        crate::events::publish(Event::VideoStatus {
+            video: id,
+            status: VideoStatus::Queued,
+        });
+        ret.push(DBVideoInfo::get_by_sqlid(db, id)?);
+    }
+    Ok(ret)
+}
+
+/// Position in a list of videos (ordered newest first) for continuing the list after a video,
+/// which stays valid when videos are added
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoCursor {
+    pub published_at: chrono::DateTime<chrono::Utc>,
+    /// SQL ID of the video, ordering videos published at the same time
+    pub id: i64,
+}
+
+impl VideoCursor {
+    /// Cursor continuing after `video`
+    pub fn after(video: &DBVideoInfo) -> VideoCursor {
+        VideoCursor {
+            published_at: video.info.published_at,
+            id: video.id,
+        }
+    }
+
+    /// Opaque string form, for URLs
+    pub fn encode(&self) -> String {
+        format!("{:x}-{:x}", self.published_at.timestamp() as u64, self.id)
+    }
+
+    pub fn decode(cursor: &str) -> Result<VideoCursor> {
+        use chrono::TimeZone;
+
+        let parse = || -> Option<VideoCursor> {
+            let (time, id) = cursor.split_once('-')?;
+            let time = u64::from_str_radix(time, 16).ok()? as i64;
+            Some(VideoCursor {
+                published_at: chrono::Utc.timestamp_opt(time, 0).single()?,
+                id: i64::from_str_radix(id, 16).ok()?,
+            })
+        };
+        parse().ok_or_else(|| anyhow::anyhow!("Invalid cursor {:?}", cursor))
+    }
+}
+
+pub fn all_videos(
+    db: &Database,
+    limit: i64,
+    page: i64,
+    filter: Option<FilterParams>,
+) -> Result<Vec<DBVideoInfo>> {
+    query_videos(db, limit, page * limit, None, filter, false)
+}
+
+/// Videos with all the words in `text` (or words starting with them) in their title, alternative
+/// title or description, or whose channel's name contains it, best matches first. Uses the
+/// full-text index, so is fast even with many videos
+pub fn search(
+    db: &Database,
+    text: &str,
+    limit: i64,
+    page: i64,
+    filter: Option<FilterParams>,
+) -> Result<Vec<DBVideoInfo>> {
+    let filter = FilterParams {
+        search: Some(text.into()),
+        ..filter.unwrap_or_default()
+    };
+    query_videos(db, limit, page * limit, None, Some(filter), true)
+}
+
+/// Like `all_videos`, but continuing after the video `cursor` points to (from the start if `None`)
+pub fn all_videos_after(
+    db: &Database,
+    limit: i64,
+    cursor: Option<&VideoCursor>,
+    filter: Option<FilterParams>,
+) -> Result<Vec<DBVideoInfo>> {
+    query_videos(db, limit, 0, cursor, filter, false)
+}
+
+/// Videos matching `filter`, in its order (or if `by_relevance`, best matches for the filter's
+/// search first), skipping `offset` videos after `cursor`
+fn query_videos(
+    db: &Database,
+    limit: i64,
+    offset: i64,
+    cursor: Option<&VideoCursor>,
+    filter: Option<FilterParams>,
+    by_relevance: bool,
+) -> Result<Vec<DBVideoInfo>> {
+    let order = filter.as_ref().map(|f| f.order_by).unwrap_or_default();
+    if cursor.is_some() && order != VideoOrder::Published {
+        anyhow::bail!("Cursors can only be used with videos ordered by published date");
+    }
+    let (filter_sql, filter_params) = filter_sql(filter);
+    // Formatted into the query like the status and channel filters, as all parameters are used
+    let cursor_pred: String = match cursor {
+        Some(c) => format!(
+            r#"(datetime(published_at) < datetime("{t}")
+                OR (datetime(published_at) = datetime("{t}") AND id < {id}))"#,
+            t = c.published_at.format("%Y-%m-%d %H:%M:%S"),
+            id = c.id,
+        ),
+        None => "1".into(),
+    };
+    let mapper = |row: &rusqlite::Row| {
+        Ok(DBVideoInfo {
+            id: row.get("id")?,
+            status: row.get("status")?,
+            date_added: row.get("date_added")?,
+            info: VideoInfo {
+                id: row.get("video_id")?,
+                url: row.get("url")?,
+                title: row.get("title")?,
+                title_alt: row.get("title_alt")?,
+                description: row.get("description")?,
+                description_alt: row.get("description_alt")?,
+                thumbnail_url: row.get("thumbnail")?,
+                thumbnails: vec![],
+                published_at: row.get("published_at")?,
+                duration: row.get("duration")?,
+                live_status: row.get("live_status")?,
+                view_count: row.get("view_count")?,
+                captions: split_captions(row.get("captions")?),
+                scheduled_at: row.get("scheduled_at")?,
+                members_only: row.get("members_only")?,
+            },
+            chanid: row.get("channel")?,
+            watched: row.get("watched")?,
+            watched_at: row.get("watched_at")?,
+            notes: row.get("notes")?,
+            error_message: row.get("error_message")?,
+            last_seen: row.get("last_seen")?,
+            removed_at: row.get("removed_at")?,
+        })
+    };
+
+    // Lower ranks are better matches. Videos only matched by channel name come after the rest
+    let relevance = if by_relevance {
+        "CASE WHEN ?3 IS NULL THEN 0
+            ELSE IFNULL((SELECT rank FROM video_fts WHERE video_fts MATCH ?3 AND rowid = video.id), 0)
+        END,"
+    } else {
+        ""
+    };
+
+    let sql = format!(
+        r#"SELECT id, status, video_id, url, title, title_alt, description, description_alt, thumbnail, published_at, channel, duration, date_added, live_status, view_count, captions, scheduled_at, members_only, watched, watched_at, notes, error_message, last_seen, removed_at
+        FROM video
+        WHERE {}
+            AND {}
+        ORDER BY {} {}
+        LIMIT ?7
+        OFFSET ?8
+        "#,
+        filter_sql,
+        cursor_pred,
+        relevance,
+        order.sql(),
+    );
+
+    trace!("all_videos query SQL {}", &sql);
+
+    let mut q = db.conn.prepare(&sql)?;
+    let [name_contains, tag, search_query, search, after, before] = filter_params;
+    let mapped = q.query_map(
+        params![
+            name_contains,
+            tag,
+            search_query,
+            search,
+            after,
+            before,
+            limit,
+            offset
+        ],
+        mapper,
+    )?;
+    let mut ret: Vec<DBVideoInfo> = vec![];
+    for r in mapped {
+        ret.push(r?);
+    }
+    Ok(ret)
+}
+
+/// Number of videos matching `filter`, i.e the total `all_videos` pages through
+pub fn count_videos(db: &Database, filter: Option<FilterParams>) -> Result<i64> {
+    let (filter_sql, filter_params) = filter_sql(filter);
+    let sql = format!("SELECT COUNT(*) FROM video WHERE {}", filter_sql);
+    db.conn
+        .query_row(&sql, rusqlite::params_from_iter(filter_params), |row| {
+            row.get(0)
+        })
+        .context("Failed to count videos")
+}
+
+/// SQL condition selecting videos matching `filter`, using parameters `?1` to `?6` which are returned
+fn filter_sql(filter: Option<FilterParams>) -> (String, [Option<String>; 6]) {
+    // Create query snippet like:
+    // (status = "NE" OR status = "GE")
+    // Or `1` as placeholder if no statuses are set.
+    let status_pred: String = if let Some(ref filter) = filter {
+        if let Some(status) = &filter.status {
+            let s = status
+                .iter()
+                .map(|s| format!(r#"status = "{}""#, s.as_str()))
+                .collect::<Vec<String>>()
+                .join(" OR ");
+
+            if status.len() > 1 {
+                format!("({})", s)
+            } else {
+                s
+            }
+        } else {
+            "1".into() // 1 i.e true
+        }
+    } else {
+        "1".into() // 1 i.e true
+    };
+
+    // Like `status NOT IN ("IG", "GR")`
+    let status_not_pred: String = match filter.as_ref().and_then(|f| f.status_not.as_ref()) {
+        Some(status) if !status.is_empty() => format!(
+            "status NOT IN ({})",
+            status
+                .iter()
+                .map(|s| format!(r#""{}""#, s.as_str()))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        _ => "1".into(),
+    };
+
+    let chanid_pred: String = if let Some(ref filter) = filter {
+        if let Some(cid) = filter.chanid {
+            // Including videos of other channels which this channel also lists
+            format!(
+                "(channel = {0} OR id IN (SELECT video FROM video_duplicate WHERE channel = {0}))",
+                cid
+            )
+        } else {
+            "1".into()
+        }
+    } else {
+        "1".into()
+    };
+
+    let collection_pred: String = match filter.as_ref().and_then(|f| f.collection) {
+        Some(c) => format!(
+            "id IN (SELECT video FROM collection_video WHERE collection = {})",
+            c
+        ),
+        None => "1".into(),
+    };
+
+    // Tags from the search text, quoted as SQL strings
+    let (search, search_tags) = match filter.as_ref().and_then(|f| f.search.as_deref()) {
+        Some(s) => {
+            let (text, tags) = split_search_tags(s);
+            (Some(text).filter(|t| !t.is_empty()), tags)
+        }
+        None => (None, vec![]),
+    };
+    let search_tags_pred: String = if search_tags.is_empty() {
+        "1".into()
+    } else {
+        search_tags
+            .iter()
+            .map(|t| {
+                format!(
+                    "id IN (SELECT video FROM video_tag JOIN tag ON tag.id = video_tag.tag WHERE tag.name = '{}')",
+                    t.replace('\'', "''")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    };
+
+    let watched_pred: String = match filter.as_ref().and_then(|f| f.watched) {
+        Some(w) => format!("watched = {}", w as i32),
+        None => "1".into(),
+    };
+
+    let sql = format!(
+        r#"(?1 IS NULL OR title LIKE ("%" || ?1 || "%"))
+            AND (?2 IS NULL OR id IN (
+                SELECT video FROM video_tag JOIN tag ON tag.id = video_tag.tag WHERE tag.name = ?2))
+            AND (?3 IS NULL
+                OR id IN (SELECT rowid FROM video_fts WHERE video_fts MATCH ?3)
+                OR channel IN (SELECT id FROM channel WHERE title LIKE ("%" || ?4 || "%")))
+            AND (?5 IS NULL OR datetime(published_at) >= datetime(?5))
+            AND (?6 IS NULL OR datetime(published_at) < datetime(?6))
+            AND {}
+            AND {}
+            AND {}
+            AND {}
+            AND {}
+            AND {}"#,
+        status_pred, status_not_pred, chanid_pred, collection_pred, watched_pred, search_tags_pred,
+    );
+
+    let (name_contains, tag, after, before) = match filter {
+        Some(f) => (
+            f.name_contains,
+            f.tag,
+            f.published_after,
+            f.published_before,
+        ),
+        None => (None, None, None, None),
+    };
+    let search_query = search.as_deref().and_then(fts_query);
+    // Compared with `datetime()`, as stored times may be formatted differently
+    let time = |t: chrono::DateTime<chrono::Utc>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+    (
+        sql,
+        [
+            name_contains.filter(|n| !n.is_empty()),
+            tag,
+            search_query,
+            search.map(|s| s.trim().to_string()),
+            after.map(time),
+            before.map(time),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_channels() -> Result<()> {
+        // Create database in memory
+        let mdb = Database::create_in_memory(true)?;
+
+        // Check no channels exist
+        {
+            let chans = list_channels(&mdb)?;
+
+            // Setup latest schema
+            assert_eq!(chans.len(), 0);
+        }
+
+        // Create channel
+        {
+            let cid = ChannelID::Youtube(crate::common::YoutubeID {
+                id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+                handle: None,
+            });
+
+            Channel::create(
+                &mdb,
+                &cid,
+                "test channel",
+                "http://example.com/thumbnail.jpg",
+            )?;
+        }
+
+        let chans = list_channels(&mdb)?;
+        assert_eq!(chans.len(), 1);
+        assert_eq!(chans[0].id, 1);
+        assert_eq!(chans[0].chanid, "UCUBfKCp83QT19JCUekEdxOQ");
+        assert_eq!(chans[0].title, "test channel");
+        assert_eq!(chans[0].thumbnail, "http://example.com/thumbnail.jpg");
+
+        let c = &chans[0];
+
+        // Check no videos exist
+        {
+            let vids = c.all_videos(&mdb, 50, 0, None)?;
+            assert_eq!(vids.len(), 0);
+        }
+
+        // ..and no latest video
+        {
+            let latest = c.last_n_video_urls(&mdb, 50)?;
+            assert_eq!(latest.len(), 0);
+        }
+
+        // Create new video
+        {
+            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
+                .with_timezone(&chrono::Utc);
+
+            let new_video = VideoInfo {
+                id: "an id".into(),
+                url: "http://example.com/watch?v=abc123".into(),
+                title: "A title!".into(),
+                title_alt: None,
+                description: "A ficticious video.\nIt is quite good".into(),
+                description_alt: None,
+                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
+                published_at: when,
+                duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            };
+            c.add_video(&mdb, &new_video)?;
+        }
+
+        // Check video now exists
+        {
+            let vids = c.all_videos(&mdb, 50, 0, None)?;
+            assert_eq!(vids.len(), 1);
+            let first = &vids[0].info;
+            assert_eq!(first.id, "an id");
+            assert_eq!(first.url, "http://example.com/watch?v=abc123");
+            assert_eq!(first.title, "A title!");
+            assert_eq!(first.description, "A ficticious video.\nIt is quite good");
+            assert_eq!(first.thumbnail_url, "http://example.com/vidthumb.jpg");
+            assert_eq!(
+                first.published_at,
+                chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
+                    .with_timezone(&chrono::Utc)
+            );
+            assert_eq!(first.duration, 12341);
+        }
+
+        // Create "older" video for latest_video test
+        {
+            let when = chrono::DateTime::parse_from_rfc3339("1999-04-01T12:30:01Z")?
+                .with_timezone(&chrono::Utc);
+
+            let new_video = VideoInfo {
+                id: "old id".into(),
+                url: "http://example.com/watch?v=old".into(),
+                title: "Old video".into(),
+                title_alt: None,
+                description: "Was created a while ago".into(),
+                description_alt: None,
+                thumbnail_url: "http://example.com/oldvid.jpg".into(),
+                thumbnails: vec![],
+                published_at: when,
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            };
+            c.add_video(&mdb, &new_video)?;
+        }
+
+        // Check latest video method returns the older video, not oldest-inserted
+        {
+            let latest = c.last_n_video_urls(&mdb, 50)?;
+            assert_eq!(latest.len(), 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter() -> Result<()> {
+        use crate::common::VideoStatus;
+
+        let mdb = Database::create_in_memory(true)?;
+
+        let cid = ChannelID::Youtube(crate::common::YoutubeID {
+            id: "testchannel".into(),
+            handle: None,
+        });
+
+        let c = Channel::create(
+            &mdb,
+            &cid,
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+
+        // Create new video
+        {
+            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
+                .with_timezone(&chrono::Utc);
+
+            let new_video = VideoInfo {
+                id: "an id".into(),
+                url: "http://example.com/watch?v=abc123".into(),
+                title: "Good video!".into(),
+                title_alt: None,
+                description: "A ficticious video.\nIt is quite good".into(),
+                description_alt: None,
+                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
+                published_at: when,
+                duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            };
+            c.add_video(&mdb, &new_video)?;
+        }
+
+        // Video 2
+        {
+            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
+                .with_timezone(&chrono::Utc);
+
+            let new_video = VideoInfo {
+                id: "an id".into(),
+                url: "http://example.com/watch?v=def321".into(),
+                title: "Another good video!".into(),
+                title_alt: None,
+                description: "A ficticious video.\nIt is quite good".into(),
+                description_alt: None,
+                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
+                published_at: when,
+                duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            };
+            c.add_video(&mdb, &new_video)?;
+        }
+
+        // Video 3
+        {
+            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
+                .with_timezone(&chrono::Utc);
+
+            let new_video = VideoInfo {
+                id: "an id".into(),
+                url: "http://example.com/watch?v=xyz789".into(),
+                title: "A grab error".into(),
+                title_alt: None,
+                description: "A ficticious video.\nIt is quite good".into(),
+                description_alt: None,
+                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
+                published_at: when,
+                duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            };
+            let v = c.add_video(&mdb, &new_video)?;
+            v.set_status(&mdb, crate::common::VideoStatus::GrabError)?;
+        }
+
+        // Searching by status only
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::GrabError);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: Some(st),
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                1
+            );
+        }
+
+        // Searching by another status
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::New);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: Some(st),
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                2
+            );
+        }
+
+        // Searching by status which has no videos
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::Downloading);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: Some(st),
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                0
+            );
+        }
+
+        // Searching by title only
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::New);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: Some("Another".into()),
+                        status: Some(st),
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                1
+            );
+        }
+
+        // Another search by title
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::New);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: Some("A".into()),
+                        status: None,
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                2
+            );
+        }
+
+        // Search by title finds nothing
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::New);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: Some("Blahblah".into()),
+                        status: None,
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                0
+            );
+        }
+
+        // No filtering
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::New);
+            assert_eq!(all_videos(&mdb, 99, 0, None)?.len(), 3);
+        }
+
+        // Filtering with no specified parameters
+        {
+            let mut st = HashSet::new();
+            st.insert(VideoStatus::New);
+            assert_eq!(
+                all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: None,
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?
+                .len(),
+                3
+            );
+        }
+
+        // Full-text search of title and description, by word prefix, or of channel name
+        {
+            let search = |text: &str, status: Option<VideoStatus>| -> Result<usize> {
+                Ok(all_videos(
+                    &mdb,
+                    99,
+                    0,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: status.map(|s| std::iter::once(s).collect()),
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: Some(text.into()),
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    }),
+                )?
+                .len())
+            };
+            assert_eq!(search("ficti", None)?, 3);
+            assert_eq!(search("grab error", None)?, 1);
+            assert_eq!(search("ficti", Some(VideoStatus::GrabError))?, 1);
+            assert_eq!(search("test channel", None)?, 3);
+            assert_eq!(search("zebra", None)?, 0);
+            assert_eq!(search("\"good (video", None)?, 3);
+
+            // Index is updated when the alternative title is set
+            let v = all_videos(&mdb, 1, 0, None)?.remove(0);
+            v.set_title_alt(&mdb, "Zebras".into())?;
+            assert_eq!(search("zebra", None)?, 1);
+
+            // Best matches first
+            let other = all_videos(&mdb, 1, 1, None)?.remove(0);
+            other.set_title_alt(&mdb, "Zebras, zebras and more zebras".into())?;
+            let found: Vec<i64> = super::search(&mdb, "zebra", 10, 0, None)?
+                .iter()
+                .map(|v| v.id)
+                .collect();
+            assert_eq!(found, vec![other.id, v.id]);
+            assert_eq!(super::search(&mdb, "zebra", 1, 1, None)?[0].id, v.id);
+            assert_eq!(super::search(&mdb, "test channel", 10, 0, None)?.len(), 3);
+        }
+
+        // Counting, for paging through
+        {
+            assert_eq!(count_videos(&mdb, None)?, 3);
+            assert_eq!(c.count_videos(&mdb, None)?, 3);
+            assert_eq!(
+                count_videos(
+                    &mdb,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: Some(std::iter::once(VideoStatus::GrabError).collect()),
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: Some("ficti".into()),
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?,
+                1
+            );
+            assert_eq!(
+                count_videos(
+                    &mdb,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: None,
+                        status_not: Some(std::iter::once(VideoStatus::GrabError).collect()),
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: None,
+                        published_before: None,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    })
+                )?,
+                2
+            );
+            assert_eq!(all_videos(&mdb, 2, 0, None)?.len(), 2);
+            assert_eq!(all_videos(&mdb, 2, 1, None)?.len(), 1);
+        }
+
+        // Continuing from a cursor, with videos published at the same time ordered by ID
+        {
+            let first = all_videos_after(&mdb, 2, None, None)?;
+            assert_eq!(first.iter().map(|v| v.id).collect::<Vec<i64>>(), vec![3, 2]);
+            let cursor = VideoCursor::decode(&VideoCursor::after(&first[1]).encode())?;
+            assert_eq!(cursor, VideoCursor::after(&first[1]));
+            let rest = c.all_videos_after(&mdb, 2, Some(&cursor), None)?;
+            assert_eq!(rest.iter().map(|v| v.id).collect::<Vec<i64>>(), vec![1]);
+            assert_eq!(
+                all_videos_after(&mdb, 2, Some(&VideoCursor::after(&rest[0])), None)?.len(),
+                0
+            );
+            assert!(VideoCursor::decode("nonsense").is_err());
+        }
+
+        // Published date range, including the start but not the end
+        {
+            let between = |after: &str, before: &str| -> Result<i64> {
+                let parse = |t: &str| -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+                    if t.is_empty() {
+                        return Ok(None);
+                    }
+                    Ok(Some(
+                        chrono::DateTime::parse_from_rfc3339(t)?.with_timezone(&chrono::Utc),
+                    ))
+                };
+                count_videos(
+                    &mdb,
+                    Some(FilterParams {
+                        name_contains: None,
+                        status: None,
+                        status_not: None,
+                        chanid: None,
+                        collection: None,
+                        tag: None,
+                        search: None,
+                        published_after: parse(after)?,
+                        published_before: parse(before)?,
+                        watched: None,
+                        order_by: VideoOrder::default(),
+                    }),
+                )
+            };
+            assert_eq!(between("2001-12-01T00:00:00Z", "2002-01-01T00:00:00Z")?, 3);
+            assert_eq!(between("2001-12-30T16:39:57Z", "")?, 3);
+            assert_eq!(between("2001-12-30T16:39:58Z", "")?, 0);
+            assert_eq!(between("", "2001-12-30T16:39:57Z")?, 0);
+            assert_eq!(between("", "2001-12-30T17:00:00+01:00")?, 0);
+        }
+
+        // Good
+        Ok(())
+    }
+
+    #[test]
+    fn test_channel_handle() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let cid = ChannelID::Youtube(crate::common::YoutubeID {
+            id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: Some("@thegreatsd".into()),
+        });
+        let c = Channel::create(&mdb, &cid, "test channel", "")?;
+        assert_eq!(c.chanid, "UCUBfKCp83QT19JCUekEdxOQ");
+        assert_eq!(c.handle.as_deref(), Some("@thegreatsd"));
+
+        // Channel is found by ID alone
+        let found = Channel::get(&mdb, &Service::Youtube.get_channel_id(&c.chanid))?;
+        assert_eq!(found.id, c.id);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_metadata() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert_eq!(c.description, "");
+        assert_eq!(c.banner, None);
+        assert_eq!(c.subscriber_count, None);
+
+        c.update_metadata(
+            &mdb,
+            &ChannelMetadata {
+                title: "Renamed channel".into(),
+                thumbnail: "http://example.com/new.jpg".into(),
+                description: "About the channel".into(),
+                banner: Some("http://example.com/banner.jpg".into()),
+                subscriber_count: Some(1234),
+            },
+        )?;
+
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.title, "Renamed channel");
+        assert_eq!(c.description, "About the channel");
+        assert_eq!(c.banner.as_deref(), Some("http://example.com/banner.jpg"));
+        assert_eq!(c.subscriber_count, Some(1234));
+        Ok(())
+    }
+
+    #[test]
+    fn test_channel_backend() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert_eq!(c.backend, None);
+
+        c.set_backend(&mdb, Some(Backend::Ytdlp))?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.backend, Some(Backend::Ytdlp));
+
+        c.set_backend(&mdb, None)?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.backend, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_channel_format() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert_eq!(c.format, None);
+
+        c.set_format(&mdb, Some("bestvideo[height<=480]+bestaudio"))?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(
+            c.format.as_deref(),
+            Some("bestvideo[height<=480]+bestaudio")
+        );
+
+        c.set_format(&mdb, None)?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.format, None);
+
+        c.set_download_dir(&mdb, Some("/music"))?;
+        c.set_filename_format(&mdb, Some("%(title)s.%(ext)s"))?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.download_dir.as_deref(), Some("/music"));
+        assert_eq!(c.filename_format.as_deref(), Some("%(title)s.%(ext)s"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_sql() -> Result<()> {
+        let mdb = Database::create_in_memory(false)?;
+        let (service, status): (Service, VideoStatus) = mdb.conn.query_row(
+            "SELECT ?1, ?2",
+            params![Service::Vimeo, VideoStatus::GrabError],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(service, Service::Vimeo);
+        assert_eq!(status, VideoStatus::GrabError);
+        Ok(())
+    }
+
+    #[test]
+    fn test_maintain() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        // As can be left behind when foreign keys aren't enforced
+        mdb.conn.execute_batch(
+            r#"PRAGMA foreign_keys = OFF;
+            INSERT INTO video (id, channel, video_id, status, url, title, description, thumbnail, published_at)
+                VALUES (100, 999, "abc", "NE", "http://example.com/abc", "Orphan", "", "", "2020-01-01");
+            INSERT INTO tag (id, name) VALUES (100, "orphaned");
+            INSERT INTO video_tag (video, tag) VALUES (100, 100);
+            PRAGMA foreign_keys = ON;"#,
+        )?;
+
+        let report = mdb.maintain(true)?;
+        assert!(report.integrity_errors.is_empty());
+        assert_eq!(
+            report.orphans,
+            vec![
+                ("video".to_string(), 1),
+                ("video_history".to_string(), 1),
+                ("video_tag".to_string(), 1)
+            ]
+        );
+        // Nothing removed by a dry run
+        assert_eq!(mdb.maintain(true)?.orphans.len(), 3);
+
+        let report = mdb.maintain(false)?;
+        assert_eq!(
+            report.orphans,
+            vec![
+                ("video".to_string(), 1),
+                ("video_history".to_string(), 1),
+                ("video_tag".to_string(), 1)
+            ]
+        );
+        assert!(mdb.maintain(false)?.orphans.is_empty());
+        assert_eq!(list_channels(&mdb)?.len(), 1);
+        assert!(all_videos(&mdb, 50, 0, None)?.is_empty());
+
+        // Foreign key enforcement is left as it was
+        mdb.conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+        mdb.maintain(true)?;
+        let enforced: bool = mdb
+            .conn
+            .query_row("PRAGMA foreign_keys", params![], |row| row.get(0))?;
+        assert!(!enforced);
+        c.delete(&mdb)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicates() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let chan = |id: &str| {
+            Channel::create(
+                &mdb,
+                &ChannelID::Youtube(crate::common::YoutubeID {
+                    id: id.into(),
+                    handle: None,
+                }),
+                id,
+                "http://example.com/thumbnail.jpg",
+            )
+        };
+        let a = chan("channel a")?;
+        let b = chan("channel b")?;
+        let video = |id: &str, url: &str| VideoInfo {
+            id: id.into(),
+            url: url.into(),
+            title: format!("Video {}", id),
+            title_alt: None,
+            description: "".into(),
+            description_alt: None,
+            thumbnail_url: "".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc::now(),
+            duration: 0,
+            live_status: LiveStatus::NotLive,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: None,
+            members_only: false,
+        };
+
+        let orig = a.add_video(&mdb, &video("abc", "http://example.com/watch?v=abc"))?;
+        let again = a.add_video(&mdb, &video("abc", "http://example.com/watch?v=abc"));
+        assert!(matches!(
+            again.unwrap_err().downcast_ref::<DatabaseError>(),
+            Some(DatabaseError::VideoExists(_))
+        ));
+        // Same URL, or same ID with a different URL, is linked to the original
+        let dup = b.add_video(&mdb, &video("abc", "http://example.com/watch?v=abc"))?;
+        assert_eq!(dup.id, orig.id);
+        let dup = b.add_video(&mdb, &video("abc", "http://example.com/abc"))?;
+        assert_eq!(dup.id, orig.id);
+        b.add_video(&mdb, &video("def", "http://example.com/watch?v=def"))?;
+        let (dup, added) = b.upsert_video(&mdb, &video("abc", "http://example.com/abc"))?;
+        assert_eq!((dup.id, added), (orig.id, false));
+
+        assert_eq!(all_videos(&mdb, 50, 0, None)?.len(), 2);
+        assert_eq!(a.all_videos(&mdb, 50, 0, None)?.len(), 1);
+        assert_eq!(b.all_videos(&mdb, 50, 0, None)?.len(), 2);
+        assert!(b
+            .last_n_video_urls(&mdb, 10)?
+            .contains("http://example.com/watch?v=abc"));
+
+        let dups = orig.duplicate_channels(&mdb)?;
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].id, b.id);
+
+        b.delete(&mdb)?;
+        assert!(orig.duplicate_channels(&mdb)?.is_empty());
+        assert_eq!(a.all_videos(&mdb, 50, 0, None)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_doctor() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert!(mdb.doctor(false)?.is_empty());
+
+        mdb.conn.execute_batch(
+            r#"PRAGMA foreign_keys = OFF;
+            INSERT INTO video (id, channel, video_id, status, url, title, description, thumbnail, published_at, date_added)
+                VALUES (100, 999, "abc", "NE", "http://example.com/abc", "Orphan", "", "", "2020-01-01 00:00:00", NULL);
+            INSERT INTO video (id, channel, video_id, status, url, title, description, thumbnail, published_at, date_added)
+                VALUES (101, 1, "def", "XX", "http://example.com/def", "Odd", "", "", "2020-01-01 00:00:00", NULL);
+            INSERT INTO channel (id, chanid, service, title, thumbnail) VALUES (50, "x", "myspace", "", "");
+            PRAGMA foreign_keys = ON;"#,
+        )?;
+
+        let report = mdb.doctor(false)?;
+        assert_eq!(report.unknown_statuses, vec![(101, "XX".to_string())]);
+        assert_eq!(report.unknown_services, vec![(50, "myspace".to_string())]);
+        assert_eq!(report.missing_values, vec![("video", "date_added", 2)]);
+        assert_eq!(report.foreign_key_errors, vec![("video".to_string(), 100)]);
+        // Nothing changed without fixing
+        assert_eq!(mdb.doctor(false)?.foreign_key_errors.len(), 1);
+
+        mdb.doctor(true)?;
+        let report = mdb.doctor(false)?;
+        assert!(report.foreign_key_errors.is_empty());
+        assert!(report.unknown_statuses.is_empty());
+        assert!(report.missing_values.is_empty());
+        assert_eq!(report.unknown_services.len(), 1);
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, 101)?.status,
+            VideoStatus::New
+        );
+        // Its history was left referring to it
+        assert!(mdb.maintain(true)?.orphans.is_empty());
+        assert_eq!(c.all_videos(&mdb, 50, 0, None)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("vidl.sqlite3.bak");
+
+        backup_to(&mdb.conn, &path)?;
+        let backup = Connection::open(&path)?;
+        let mig = crate::db_migration::get_migrator(&backup);
+        assert!(mig.is_db_current()?);
+
+        // Never overwrites an earlier backup
+        assert!(backup_to(&mdb.conn, &path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_database_key() -> Result<()> {
+        let mut cfg = Config::load();
+        cfg.db_key = None;
+        cfg.db_key_command = None;
+        assert_eq!(database_key(&cfg)?, None);
+
+        // Only the trailing newline is removed
+        cfg.db_key_command = Some("printf ' secret \\n'".into());
+        assert_eq!(database_key(&cfg)?, Some(" secret ".into()));
+
+        cfg.db_key = Some("other".into());
+        assert_eq!(database_key(&cfg)?, Some("other".into()));
+
+        cfg.db_key = None;
+        cfg.db_key_command = Some("exit 1".into());
+        assert!(database_key(&cfg).is_err());
+        cfg.db_key_command = Some("true".into());
+        assert!(database_key(&cfg).is_err());
+
+        // Refuses to leave the database unencrypted
+        cfg.db_key = Some("secret".into());
+        assert_eq!(
+            ConnectionSettings::new(&cfg).is_ok(),
+            cfg!(feature = "sqlcipher")
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_encrypted() -> Result<()> {
+        let mut cfg = Config::load();
+        cfg.db_key = Some("secret".into());
+        let settings = ConnectionSettings::new(&cfg)?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("vidl.sqlite3");
+        let conn = Connection::open(&path)?;
+        settings.apply(&conn)?;
+        conn.execute("CREATE TABLE t (x)", params![])?;
+        drop(conn);
+
+        assert!(!std::fs::read(&path)?
+            .windows(b"CREATE TABLE".len())
+            .any(|w| w == b"CREATE TABLE"));
+        let conn = Connection::open(&path)?;
+        assert!(conn.prepare("SELECT * FROM t").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_settings() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        assert_eq!(mdb.setting::<i64>(Setting::UpdateInterval)?, None);
+        assert_eq!(mdb.default_update_interval()?, chrono::Duration::hours(1));
+
+        mdb.set_setting(Setting::UpdateInterval, Some(30))?;
+        assert_eq!(mdb.setting::<i64>(Setting::UpdateInterval)?, Some(30));
+        assert_eq!(
+            mdb.default_update_interval()?,
+            chrono::Duration::minutes(30)
+        );
+        assert!(mdb.set_setting(Setting::UpdateInterval, Some(0)).is_err());
+        assert!(mdb
+            .set_setting(Setting::UpdateInterval, Some("soon"))
+            .is_err());
+        assert_eq!(mdb.setting::<i64>(Setting::UpdateInterval)?, Some(30));
+
+        // Channel's own interval is used over the setting
+        let settings = ChannelSettings {
+            update_interval_minutes: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.update_interval(mdb.default_update_interval()?),
+            chrono::Duration::minutes(5)
+        );
+
+        mdb.set_setting(Setting::Format, Some("worst"))?;
+        assert_eq!(
+            mdb.settings()?,
+            vec![
+                (Setting::UpdateInterval, Some("30".into())),
+                (Setting::Format, Some("worst".into()))
+            ]
+        );
+
+        mdb.set_setting::<i64>(Setting::UpdateInterval, None)?;
+        assert_eq!(mdb.setting::<i64>(Setting::UpdateInterval)?, None);
+
+        // Settings unknown to this version are kept, but not listed
+        mdb.conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('future', 'x')",
+            params![],
+        )?;
+        assert_eq!(mdb.settings()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_order() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        // Added in a different order to when they were published
+        for (id, title, duration, days_ago) in [
+            ("a", "b video", 10, 3),
+            ("b", "A video", 30, 1),
+            ("c", "c video", 20, 2),
+        ] {
+            c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.into(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: title.into(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now() - chrono::Duration::days(days_ago),
+                    duration,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+        }
+        mdb.conn.execute(
+            "UPDATE video SET date_added = datetime('now', '-' || id || ' hours')",
+            params![],
+        )?;
+
+        let ids = |order_by| -> Result<Vec<String>> {
+            let filter = FilterParams {
+                order_by,
+                ..Default::default()
+            };
+            Ok(c.all_videos(&mdb, 50, 0, Some(filter))?
+                .into_iter()
+                .map(|v| v.info.id)
+                .collect())
+        };
+        assert_eq!(ids(VideoOrder::Published)?, vec!["b", "c", "a"]);
+        assert_eq!(ids(VideoOrder::Added)?, vec!["a", "b", "c"]);
+        assert_eq!(ids(VideoOrder::Duration)?, vec!["b", "c", "a"]);
+        assert_eq!(ids(VideoOrder::Title)?, vec!["b", "a", "c"]);
+
+        // Ordering by date added uses its index rather than sorting
+        let plan: String = mdb.conn.query_row(
+            &format!(
+                "EXPLAIN QUERY PLAN SELECT id FROM video ORDER BY {}",
+                VideoOrder::Added.sql()
+            ),
+            params![],
+            |row| row.get("detail"),
+        )?;
+        assert!(plan.contains("idx_video_date_added"), "{}", plan);
+
+        let cursor = VideoCursor {
+            published_at: chrono::Utc::now(),
+            id: 10,
+        };
+        let filter = FilterParams {
+            order_by: VideoOrder::Title,
+            ..Default::default()
+        };
+        assert!(c
+            .all_videos_after(&mdb, 50, Some(&cursor), Some(filter))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "abc".into(),
+                url: "http://example.com/watch?v=abc".into(),
+                title: "Video".into(),
+                title_alt: None,
+                description: "".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+
+        set_origin(Origin::Web);
+        v.set_status(&mdb, VideoStatus::Queued)?;
+        // Setting the same status again isn't a change
+        v.set_status(&mdb, VideoStatus::Queued)?;
+        set_origin(Origin::Cli);
+
+        let history = v.history(&mdb)?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].old, None);
+        assert_eq!(history[0].new, VideoStatus::New);
+        assert_eq!(history[0].origin, "cli");
+        assert_eq!(history[1].old, Some(VideoStatus::New));
+        assert_eq!(history[1].new, VideoStatus::Queued);
+        assert_eq!(history[1].origin, "web");
+
+        c.delete(&mdb)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_alt_edits() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "abc".into(),
+                url: "http://example.com/watch?v=abc".into(),
+                title: "Video".into(),
+                title_alt: None,
+                description: "Description".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+        assert_eq!(v.alt_edits(&mdb)?, vec![]);
+
+        v.set_title_alt(&mdb, "Better title".into())?;
+        v.set_title_alt(&mdb, "Oops".into())?;
+        // Setting the same text again isn't an edit
+        v.set_title_alt(&mdb, "Oops".into())?;
+        v.set_description_alt(&mdb, "Better description".into())?;
+
+        let edits = v.alt_edits(&mdb)?;
+        assert_eq!(edits.len(), 3);
+        assert_eq!(edits[0].field, AltField::Description);
+        assert_eq!(edits[0].old_value, None);
+        assert_eq!(edits[1].field, AltField::Title);
+        assert_eq!(edits[1].old_value.as_deref(), Some("Better title"));
+        assert_eq!(edits[2].old_value, None);
+
+        // Undo the accidental edit, which can itself be undone
+        v.revert_alt_edit(&mdb, edits[1].id)?;
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.info.title_alt.as_deref(), Some("Better title"));
+        assert_eq!(
+            v.info.description_alt.as_deref(),
+            Some("Better description")
+        );
+        let edits = v.alt_edits(&mdb)?;
+        assert_eq!(edits.len(), 4);
+        assert_eq!(edits[0].old_value.as_deref(), Some("Oops"));
+
+        // Back to the source's description
+        v.revert_alt_edit(&mdb, edits[1].id)?;
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.info.description_alt, None);
+
+        assert!(v.revert_alt_edit(&mdb, 1234).is_err());
+
+        c.delete(&mdb)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_thumbnails() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let thumb = |name: &str, width: i64| Thumbnail {
+            url: format!("http://example.com/{}.jpg", name),
+            width,
+            height: width * 9 / 16,
+        };
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "abc".into(),
+                url: "http://example.com/watch?v=abc".into(),
+                title: "Video".into(),
+                title_alt: None,
+                description: "".into(),
+                description_alt: None,
+                thumbnail_url: "http://example.com/default.jpg".into(),
+                thumbnails: vec![
+                    thumb("maxres", 1280),
+                    thumb("small", 120),
+                    thumb("medium", 320),
+                ],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+
+        // Sorted smallest first
+        assert_eq!(
+            v.thumbnails(&mdb)?,
+            vec![
+                thumb("small", 120),
+                thumb("medium", 320),
+                thumb("maxres", 1280)
+            ]
+        );
+        assert_eq!(
+            v.thumbnail_url_for(&mdb, Some(200))?,
+            "http://example.com/medium.jpg"
+        );
+        assert_eq!(
+            v.thumbnail_url_for(&mdb, Some(320))?,
+            "http://example.com/medium.jpg"
+        );
+        assert_eq!(
+            v.thumbnail_url_for(&mdb, Some(4000))?,
+            "http://example.com/maxres.jpg"
+        );
+        assert_eq!(
+            v.thumbnail_url_for(&mdb, None)?,
+            "http://example.com/maxres.jpg"
+        );
+
+        // Without any sizes, the single thumbnail is used
+        v.set_thumbnails(&mdb, &[])?;
+        assert_eq!(
+            v.thumbnail_url_for(&mdb, Some(200))?,
+            "http://example.com/default.jpg"
+        );
+
+        c.delete(&mdb)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "abc123".into(),
+                url: "http://example.com/watch?v=abc123".into(),
+                title: "A video".into(),
+                title_alt: None,
+                description: "".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+        v.set_status(&mdb, VideoStatus::Grabbed)?;
+        assert!(!c.archived);
+
+        assert_eq!(count_channels(&mdb)?, 1);
+        c.set_archived(&mdb, true)?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert!(c.archived);
+        assert_eq!(count_channels(&mdb)?, 0);
+        assert!(!c.update_required(&mdb)?);
+        assert!(list_channels(&mdb)?.is_empty());
+        assert_eq!(list_archived_channels(&mdb)?.len(), 1);
+        assert_eq!(list_all_channels(&mdb)?.len(), 1);
+
+        // Videos are kept with their status
+        let videos = c.all_videos(&mdb, 50, 0, None)?;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].status, VideoStatus::Grabbed);
+
+        c.set_archived(&mdb, false)?;
+        assert_eq!(list_channels(&mdb)?.len(), 1);
+        assert!(list_archived_channels(&mdb)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_channel_settings() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert_eq!(c.settings(&mdb)?, ChannelSettings::default());
+        assert!(c.update_required(&mdb)?);
+
+        let settings = ChannelSettings {
+            auto_download: true,
+            update_interval_minutes: Some(24 * 60),
+            enabled: true,
+            prune_after_days: Some(30),
+            prune_delete: true,
+        };
+        c.set_settings(&mdb, &settings)?;
+        assert_eq!(c.settings(&mdb)?, settings);
+        assert_eq!(
+            c.settings(&mdb)?
+                .update_interval(mdb.default_update_interval()?),
+            chrono::Duration::days(1)
+        );
+
+        c.set_last_update(&mdb)?;
+        assert!(!c.update_required(&mdb)?);
+
+        // Disabled channels are never due an update
+        let other = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "otherchannel".into(),
+                handle: None,
+            }),
+            "other channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        other.set_settings(
+            &mdb,
+            &ChannelSettings {
+                enabled: false,
+                ..Default::default()
+            },
+        )?;
+        assert!(!other.update_required(&mdb)?);
+
+        assert!(c
+            .set_settings(
+                &mdb,
+                &ChannelSettings {
+                    update_interval_minutes: Some(0),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+        assert!(c
+            .set_settings(
+                &mdb,
+                &ChannelSettings {
+                    prune_after_days: Some(0),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+        assert_eq!(c.settings(&mdb)?, settings);
+
+        c.delete(&mdb)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_subtitles() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert_eq!(c.subtitles, None);
+        c.set_subtitles(&mdb, Some(false))?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.subtitles, Some(false));
+
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "a".into(),
+                url: "http://example.com/watch?v=a".into(),
+                title: "Video a".into(),
+                title_alt: None,
+                description: "".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec!["en".into(), "de".into()],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+        assert_eq!(v.subtitle_files(&mdb)?, vec![]);
+
+        let files = vec![
+            SubtitleFile {
+                lang: "de".into(),
+                path: "download/a.de.vtt".into(),
+            },
+            SubtitleFile {
+                lang: "en".into(),
+                path: "download/a.en.vtt".into(),
+            },
+        ];
+        v.set_subtitle_files(&mdb, &files)?;
+        // Re-downloading replaces rather than duplicates
+        v.set_subtitle_files(&mdb, &files)?;
+        assert_eq!(v.subtitle_files(&mdb)?, files);
+
+        assert_eq!(v.file_path(&mdb)?, None);
+        assert_eq!(v.downloaded_file(&mdb)?, None);
+        assert_eq!(c.disk_usage(&mdb)?, 0);
+        v.set_downloaded_file(&mdb, "/videos/download/a.mkv", Some(1234))?;
+        assert_eq!(
+            v.file_path(&mdb)?.as_deref(),
+            Some("/videos/download/a.mkv")
+        );
+        let file = v.downloaded_file(&mdb)?.expect("File should be recorded");
+        assert_eq!(file.path, "/videos/download/a.mkv");
+        assert_eq!(file.size, Some(1234));
+        assert!(file.downloaded_at.is_some());
+        assert_eq!(c.disk_usage(&mdb)?, 1234);
+
+        c.delete(&mdb)?;
+        let remaining: i64 =
+            mdb.conn
+                .query_row("SELECT COUNT(*) FROM video_subtitle", [], |row| row.get(0))?;
+        assert_eq!(remaining, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_by_video_id() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let playlist = Channel::create(
+            &mdb,
+            &ChannelID::YoutubePlaylist(crate::common::YoutubePlaylistID { id: "PL1".into() }),
+            "playlist",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let vimeo = Channel::create(
+            &mdb,
+            &ChannelID::Vimeo(crate::common::VimeoID {
+                id: "vimeochan".into(),
+            }),
+            "vimeo",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let video = |url: &str| VideoInfo {
+            id: "123".into(),
+            url: url.into(),
+            title: "Video".into(),
+            title_alt: None,
+            description: "".into(),
+            description_alt: None,
+            thumbnail_url: "".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc::now(),
+            duration: 0,
+            live_status: LiveStatus::NotLive,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: None,
+            members_only: false,
+        };
+        // Same ID on different services
+        let vv = vimeo.add_video(&mdb, &video("http://vimeo.com/123"))?;
+        let yv = playlist.add_video(&mdb, &video("http://www.youtube.com/watch?v=123"))?;
+
+        let found = |service, id| -> Result<Option<i64>> {
+            Ok(DBVideoInfo::get_by_video_id(&mdb, service, id)?.map(|v| v.id))
+        };
+        assert_eq!(found(Service::Vimeo, "123")?, Some(vv.id));
+        assert_eq!(found(Service::Youtube, "123")?, Some(yv.id));
+        assert_eq!(found(Service::YoutubePlaylist, "123")?, Some(yv.id));
+        assert_eq!(found(Service::Soundcloud, "123")?, None);
+        assert_eq!(found(Service::Vimeo, "456")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upsert_video() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let mut info = VideoInfo {
+            id: "abc".into(),
+            url: "http://example.com/watch?v=abc".into(),
+            title: "Video".into(),
+            title_alt: None,
+            description: "Short".into(),
+            description_alt: None,
+            thumbnail_url: "".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc::now(),
+            duration: 60,
+            live_status: LiveStatus::NotLive,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: None,
+            members_only: false,
+        };
+        let (orig, added) = c.upsert_video(&mdb, &info)?;
+        assert!(added);
+        orig.set_description(&mdb, "Short description, in full")?;
+        orig.set_status(&mdb, VideoStatus::Grabbed)?;
+
+        // Re-published with a new title, listed without the full description or duration
+        info.title = "Video (re-upload)".into();
+        info.duration = 0;
+        let (v, added) = c.upsert_video(&mdb, &info)?;
+        assert!(!added);
+        assert_eq!(v.id, orig.id);
+        assert_eq!(v.status, VideoStatus::Grabbed);
+        assert_eq!(v.info.title, "Video (re-upload)");
+        assert_eq!(v.info.description, "Short description, in full");
+        assert_eq!(v.info.duration, 60);
+
+        info.description = "New description".into();
+        info.duration = 90;
+        let (v, _) = c.upsert_video(&mdb, &info)?;
+        assert_eq!(v.info.description, "New description");
+        assert_eq!(v.info.duration, 90);
+        assert_eq!(c.all_videos(&mdb, 50, 0, None)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_stale() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let now = chrono::Utc::now();
+        let mut videos = vec![];
+        for (id, age_days) in &[("old", 30), ("older", 60), ("grabbed", 30), ("new", 1)] {
+            videos.push(c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: id.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: now - chrono::Duration::days(*age_days),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?);
+        }
+        videos[1].add_tags(&mdb, &["tag".to_string()])?;
+        videos[2].set_status(&mdb, VideoStatus::Grabbed)?;
+        let status = |i: usize| DBVideoInfo::get_by_sqlid(&mdb, videos[i].id).map(|v| v.status);
+
+        // Nothing is pruned without a policy
+        assert_eq!(c.prune_stale(&mdb)?, 0);
+
+        let mut settings = ChannelSettings {
+            prune_after_days: Some(45),
+            ..Default::default()
+        };
+        c.set_settings(&mdb, &settings)?;
+        assert_eq!(c.prune_stale(&mdb)?, 1);
+        assert_eq!(status(0)?, VideoStatus::New);
+        assert_eq!(status(1)?, VideoStatus::Ignore);
+
+        settings.prune_after_days = Some(7);
+        settings.prune_delete = true;
+        c.set_settings(&mdb, &settings)?;
+        assert_eq!(c.prune_stale(&mdb)?, 1);
+        assert!(DBVideoInfo::get_by_sqlid(&mdb, videos[0].id).is_err());
+        assert_eq!(status(1)?, VideoStatus::Ignore);
+        assert_eq!(status(2)?, VideoStatus::Grabbed);
+        assert_eq!(status(3)?, VideoStatus::New);
+
+        // Deleting leaves nothing referring to the videos
+        videos[1].set_status(&mdb, VideoStatus::New)?;
+        assert_eq!(c.prune_stale(&mdb)?, 1);
+        assert!(mdb.maintain(true)?.orphans.is_empty());
+
+        // Videos published earlier on the day of the cutoff are pruned, with either policy
+        let cutoff_day = (chrono::Utc::now() - chrono::Duration::days(7))
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+        for (id, delete) in &[("deleted", true), ("ignored", false)] {
+            let v = c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: id.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: cutoff_day,
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+            settings.prune_delete = *delete;
+            c.set_settings(&mdb, &settings)?;
+            assert_eq!(c.prune_stale(&mdb)?, 1);
+            match DBVideoInfo::get_by_sqlid(&mdb, v.id) {
+                Ok(v) => assert!(!delete && v.status == VideoStatus::Ignore),
+                Err(_) => assert!(delete),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_status() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let now = chrono::Utc::now();
+        let mut videos = vec![];
+        for (id, age_days) in &[("old", 30), ("grabbed", 30), ("new1", 1), ("new2", 0)] {
+            videos.push(c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: id.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: now - chrono::Duration::days(*age_days),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?);
+        }
+        videos[1].set_status(&mdb, VideoStatus::Grabbed)?;
+
+        // Only new videos are changed
+        assert_eq!(
+            c.ignore_older_than(&mdb, now - chrono::Duration::days(7))?,
+            1
+        );
+        let queued: Vec<String> = c.queue_new(&mdb)?.into_iter().map(|v| v.info.id).collect();
+        assert_eq!(queued.len(), 2);
+        assert!(queued.contains(&"new1".to_string()) && queued.contains(&"new2".to_string()));
+
+        let status = |i: usize| DBVideoInfo::get_by_sqlid(&mdb, videos[i].id).map(|v| v.status);
+        assert_eq!(status(0)?, VideoStatus::Ignore);
+        assert_eq!(status(1)?, VideoStatus::Grabbed);
+        assert_eq!(status(2)?, VideoStatus::Queued);
+        assert!(c.queue_new(&mdb)?.is_empty());
+
+        use chrono::TimeZone;
+
+        // Videos published earlier on the same day as the cutoff are ignored
+        let morning = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "morning".into(),
+                url: "http://example.com/watch?v=morning".into(),
+                title: "morning".into(),
                 title_alt: None,
-                description: "Was created a while ago".into(),
+                description: "".into(),
                 description_alt: None,
-                thumbnail_url: "http://example.com/oldvid.jpg".into(),
-                published_at: when,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc.with_ymd_and_hms(2020, 1, 1, 9, 0, 0).unwrap(),
                 duration: 0,
-            };
-            c.add_video(&mdb, &new_video)?;
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+        let noon = chrono::Utc.with_ymd_and_hms(2020, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(c.ignore_older_than(&mdb, noon)?, 1);
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, morning.id)?.status,
+            VideoStatus::Ignore
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_requeue_interrupted() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let mut videos = vec![];
+        for id in &["a", "b"] {
+            videos.push(c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: id.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?);
         }
+        // Left by a version which didn't record the downloading process
+        videos[0].set_status(&mdb, VideoStatus::Downloading)?;
+        videos[0].set_partial_path(&mdb, Some("download/a.mp4"))?;
+        // Being downloaded by a running process
+        videos[1].set_status(&mdb, VideoStatus::Queued)?;
+        assert!(videos[1].claim_download(&mdb)?);
+        videos[1].download_heartbeat(&mdb)?;
+
+        let requeued = requeue_interrupted(&mdb)?;
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].id, videos[0].id);
+        assert_eq!(requeued[0].status, VideoStatus::Queued);
+        assert_eq!(requeue_interrupted(&mdb)?.len(), 0);
+
+        // Process stopped updating the heartbeat, e.g it was killed
+        mdb.conn.execute(
+            "UPDATE video SET download_heartbeat=datetime('now', '-1 hour') WHERE id=?1",
+            params![videos[1].id],
+        )?;
+        let requeued = requeue_interrupted(&mdb)?;
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].id, videos[1].id);
+        assert_eq!(requeued[0].status, VideoStatus::Queued);
+        Ok(())
+    }
 
-        // Check latest video method returns the older video, not oldest-inserted
-        {
-            let latest = c.last_n_video_urls(&mdb, 50)?;
-            assert_eq!(latest.len(), 2);
+    #[test]
+    fn test_ignore_rules() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        assert!(c
+            .set_ignore_rules(
+                &mdb,
+                &IgnoreRules {
+                    title: Some("(unclosed".into()),
+                    ..Default::default()
+                }
+            )
+            .is_err());
+
+        let rules = IgnoreRules {
+            min_duration: Some(61),
+            max_duration: Some(3600),
+            title: Some("(?i)#shorts|trailer".into()),
+        };
+        c.set_ignore_rules(&mdb, &rules)?;
+        let c = Channel::get_by_sqlid(&mdb, c.id)?;
+        assert_eq!(c.ignore_rules, rules);
+
+        for (title, duration, expected) in &[
+            ("Normal video", 600, VideoStatus::New),
+            ("Short", 30, VideoStatus::Ignore),
+            ("Stream VOD", 7200, VideoStatus::Ignore),
+            ("Unknown duration", 0, VideoStatus::New),
+            ("Official Trailer", 120, VideoStatus::Ignore),
+            ("Funny #Shorts", 600, VideoStatus::Ignore),
+        ] {
+            let v = c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: title.to_string(),
+                    url: format!("http://example.com/watch?v={}", title),
+                    title: title.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: *duration,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+            assert_eq!(&v.status, expected, "{}", title);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_priority() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let mut videos = vec![];
+        for (i, id) in ["a", "b", "c"].iter().enumerate() {
+            let v = c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: id.to_string(),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now() - chrono::Duration::days(i as i64),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+            v.set_status(&mdb, VideoStatus::Queued)?;
+            videos.push(v);
+        }
+        let order = |mdb: &Database| -> Result<Vec<String>> {
+            Ok(queued_videos(mdb)?.into_iter().map(|v| v.info.id).collect())
+        };
+
+        // Oldest first
+        assert_eq!(order(&mdb)?, vec!["c", "b", "a"]);
+
+        videos[0].bump_priority(&mdb)?;
+        assert_eq!(videos[0].priority(&mdb)?, 1);
+        assert_eq!(order(&mdb)?, vec!["a", "c", "b"]);
+
+        move_in_queue(&mdb, &videos[1], -1)?;
+        assert_eq!(order(&mdb)?, vec!["a", "b", "c"]);
+        move_in_queue(&mdb, &videos[0], 5)?;
+        assert_eq!(order(&mdb)?, vec!["b", "c", "a"]);
+
+        assert!(videos[0].claim_download(&mdb)?);
+        assert!(!videos[0].claim_download(&mdb)?);
+        assert_eq!(order(&mdb)?, vec!["b", "c"]);
+        assert!(move_in_queue(&mdb, &videos[0], 1).is_err());
+
+        assert!(videos[1].unqueue(&mdb)?);
+        assert!(!videos[1].unqueue(&mdb)?);
+        assert_eq!(order(&mdb)?, vec!["c"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "a".into(),
+                url: "http://example.com/watch?v=a".into(),
+                title: "Video a".into(),
+                title_alt: None,
+                description: "".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+        assert_eq!(v.retry_state(&mdb)?, (0, None));
+
+        // Zero delay so the retry is due immediately
+        let zero = chrono::Duration::zero();
+        assert!(v
+            .record_grab_error(&mdb, DownloadErrorKind::Network, "failed", 2, zero)?
+            .is_some());
+        let requeued = requeue_due_retries(&mdb)?;
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].status, VideoStatus::Queued);
+        assert_eq!(requeue_due_retries(&mdb)?.len(), 0);
+
+        // Backoff doubles the delay each time
+        let hour = chrono::Duration::hours(1);
+        let at = v
+            .record_grab_error(&mdb, DownloadErrorKind::Network, "failed", 2, hour)?
+            .unwrap();
+        assert!(at > chrono::Utc::now() + chrono::Duration::minutes(119));
+        assert_eq!(requeue_due_retries(&mdb)?.len(), 0);
+
+        // Out of retries
+        assert_eq!(
+            v.record_grab_error(&mdb, DownloadErrorKind::Network, "failed", 2, zero)?,
+            None
+        );
+        assert_eq!(v.retry_state(&mdb)?, (3, None));
+        assert_eq!(requeue_due_retries(&mdb)?.len(), 0);
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.status, VideoStatus::GrabError);
+        assert_eq!(v.error_kind(&mdb)?, Some(DownloadErrorKind::Network));
+        assert_eq!(v.error_message.as_deref(), Some("failed"));
+
+        // Never retried if the video is gone
+        v.set_status(&mdb, VideoStatus::Queued)?;
+        let v2 = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(
+            v2.record_grab_error(&mdb, DownloadErrorKind::Unavailable, "failed", 10, zero)?,
+            None
+        );
+
+        // Manual retry resets the retry count
+        assert!(v2.retry(&mdb)?);
+        assert!(!v2.retry(&mdb)?);
+        assert_eq!(v2.retry_state(&mdb)?, (0, None));
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, v.id)?.status,
+            VideoStatus::Queued
+        );
+
+        v2.record_grab_error(&mdb, DownloadErrorKind::Unavailable, "failed", 10, zero)?;
+        let retried = c.retry_failed(&mdb)?;
+        assert_eq!(retried.len(), 1);
+        assert_eq!(retried[0].status, VideoStatus::Queued);
+        assert_eq!(c.retry_failed(&mdb)?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_watched() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        for id in &["a", "b"] {
+            c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: format!("Video {}", id),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+        }
+        let filter = |watched| FilterParams {
+            name_contains: None,
+            status: None,
+            status_not: None,
+            chanid: None,
+            collection: None,
+            tag: None,
+            search: None,
+            published_after: None,
+            published_before: None,
+            watched,
+            order_by: VideoOrder::default(),
+        };
+
+        let videos = c.all_videos(&mdb, 10, 0, None)?;
+        assert!(videos.iter().all(|v| !v.watched));
+        assert_eq!(c.count_videos(&mdb, Some(filter(Some(true))))?, 0);
+
+        videos[0].set_watched(&mdb, true)?;
+        let v = DBVideoInfo::get_by_sqlid(&mdb, videos[0].id)?;
+        assert!(v.watched);
+        let watched_at = v.watched_at.expect("Time watched should be recorded");
+        assert!(chrono::Utc::now() - watched_at < chrono::Duration::minutes(1));
+        // Marking again keeps the time it was first watched
+        videos[0].set_watched(&mdb, true)?;
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, videos[0].id)?.watched_at,
+            Some(watched_at)
+        );
+        let watched = c.all_videos(&mdb, 10, 0, Some(filter(Some(true))))?;
+        assert_eq!(watched.len(), 1);
+        assert_eq!(watched[0].id, videos[0].id);
+        let unwatched = c.all_videos(&mdb, 10, 0, Some(filter(Some(false))))?;
+        assert_eq!(unwatched.len(), 1);
+        assert_eq!(unwatched[0].id, videos[1].id);
+        assert_eq!(count_videos(&mdb, Some(filter(None)))?, 2);
+
+        videos[0].set_watched(&mdb, false)?;
+        assert_eq!(c.count_videos(&mdb, Some(filter(Some(true))))?, 0);
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, videos[0].id)?.watched_at,
+            None
+        );
+
+        // Notes are stored with the video too
+        assert_eq!(videos[0].notes, None);
+        videos[0].set_notes(&mdb, " Watch the second half \n")?;
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, videos[0].id)?.notes,
+            Some("Watch the second half".into())
+        );
+        videos[0].set_notes(&mdb, "  ")?;
+        assert_eq!(DBVideoInfo::get_by_sqlid(&mdb, videos[0].id)?.notes, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        for id in &["a", "b", "c"] {
+            c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: format!("Video {}", id),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+        }
+        let videos = c.all_videos(&mdb, 10, 0, None)?;
+
+        let mut later = Collection::create(&mdb, " Watch later ")?;
+        assert_eq!(later.name, "Watch later");
+        let talks = Collection::create(&mdb, "Conference talks")?;
+        // Names are unique
+        assert!(Collection::create(&mdb, "Watch later").is_err());
+        assert!(Collection::create(&mdb, "").is_err());
+        assert_eq!(list_collections(&mdb)?, vec![talks.clone(), later.clone()]);
+
+        assert!(later.add_video(&mdb, &videos[0])?);
+        assert!(!later.add_video(&mdb, &videos[0])?);
+        later.add_video(&mdb, &videos[1])?;
+        talks.add_video(&mdb, &videos[1])?;
+        assert_eq!(later.count_videos(&mdb)?, 2);
+        let ids: Vec<i64> = later.videos(&mdb, 10, 0)?.iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![videos[0].id, videos[1].id]);
+        assert_eq!(
+            videos[1].collections(&mdb)?,
+            vec![talks.clone(), later.clone()]
+        );
+        assert!(videos[2].collections(&mdb)?.is_empty());
+
+        assert!(later.remove_video(&mdb, &videos[0])?);
+        assert!(!later.remove_video(&mdb, &videos[0])?);
+        assert_eq!(later.count_videos(&mdb)?, 1);
+
+        later.rename(&mdb, "Later")?;
+        assert_eq!(Collection::get_by_sqlid(&mdb, later.id)?.name, "Later");
+
+        // Deleting a collection leaves its videos
+        talks.delete(&mdb)?;
+        assert_eq!(list_collections(&mdb)?, vec![later.clone()]);
+        assert_eq!(c.count_videos(&mdb, None)?, 3);
+
+        // Deleting the channel removes its videos from collections
+        c.delete(&mdb)?;
+        assert_eq!(later.count_videos(&mdb)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chapters() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "a".into(),
+                url: "http://example.com/watch?v=a".into(),
+                title: "Video a".into(),
+                title_alt: None,
+                description: "Truncated...".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc::now(),
+                duration: 0,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+
+        let chapters = vec![
+            Chapter {
+                start: 0,
+                title: "Intro".into(),
+            },
+            Chapter {
+                start: 90,
+                title: "Main".into(),
+            },
+        ];
+        v.set_chapters(&mdb, &chapters)?;
+        // Setting again replaces rather than duplicates
+        v.set_chapters(&mdb, &chapters)?;
+        assert_eq!(v.chapters(&mdb)?, chapters);
+
+        v.set_description(&mdb, "Truncated... but now complete")?;
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.info.description, "Truncated... but now complete");
+
+        c.delete(&mdb)?;
+        let remaining: i64 =
+            mdb.conn
+                .query_row("SELECT COUNT(*) FROM video_chapter", [], |row| row.get(0))?;
+        assert_eq!(remaining, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+
+        for (id, tags) in &[
+            ("a", vec!["music", "live"]),
+            ("b", vec!["music"]),
+            ("c", vec![]),
+        ] {
+            let v = c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: format!("Video {}", id),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+            let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+            v.add_tags(&mdb, &tags)?;
+            // Adding again is harmless
+            v.add_tags(&mdb, &tags)?;
         }
+
+        let filtered = |tag: &str| -> Result<Vec<String>> {
+            Ok(all_videos(
+                &mdb,
+                99,
+                0,
+                Some(FilterParams {
+                    name_contains: None,
+                    status: None,
+                    status_not: None,
+                    chanid: None,
+                    collection: None,
+                    tag: Some(tag.into()),
+                    search: None,
+                    published_after: None,
+                    published_before: None,
+                    watched: None,
+                    order_by: VideoOrder::default(),
+                }),
+            )?
+            .into_iter()
+            .map(|v| v.info.id)
+            .collect())
+        };
+        let mut music = filtered("music")?;
+        music.sort();
+        assert_eq!(music, vec!["a", "b"]);
+        assert_eq!(filtered("live")?, vec!["a"]);
+        assert_eq!(filtered("nope")?.len(), 0);
+
+        let a = DBVideoInfo::get_by_sqlid(&mdb, 1)?;
+        assert_eq!(a.tags(&mdb)?, vec!["live", "music"]);
+        assert!(a.info.captions.is_empty());
+
+        a.set_captions(&mdb, &["en".to_string(), "de".to_string()])?;
+        let a = DBVideoInfo::get_by_sqlid(&mdb, 1)?;
+        assert_eq!(a.info.captions, vec!["en", "de"]);
+
+        // No tag filter returns everything
+        assert_eq!(all_videos(&mdb, 99, 0, None)?.len(), 3);
+
+        // Tags added by hand, and found by `tag:` in search text
+        let b = DBVideoInfo::get_by_sqlid(&mdb, 2)?;
+        assert!(b.add_tag(&mdb, " favourite's ")?);
+        assert!(!b.add_tag(&mdb, "favourite's")?);
+        assert!(b.add_tag(&mdb, "  ").is_err());
+        assert_eq!(b.tags(&mdb)?, vec!["favourite's", "music"]);
+        assert_eq!(filtered("favourite's")?, vec!["b"]);
+        let found = |text: &str| -> Result<Vec<String>> {
+            Ok(search(&mdb, text, 99, 0, None)?
+                .into_iter()
+                .map(|v| v.info.id)
+                .collect())
+        };
+        assert_eq!(found("tag:favourite's")?, vec!["b"]);
+        assert_eq!(found("tag:music tag:live")?, vec!["a"]);
+        assert_eq!(found("video tag:live")?, vec!["a"]);
+        assert_eq!(found("other tag:live")?.len(), 0);
+
+        assert!(b.remove_tag(&mdb, "favourite's")?);
+        assert!(!b.remove_tag(&mdb, "favourite's")?);
+        assert_eq!(b.tags(&mdb)?, vec!["music"]);
         Ok(())
     }
 
     #[test]
-    fn test_filter() -> Result<()> {
-        use crate::common::VideoStatus;
-
+    fn test_upcoming() -> Result<()> {
         let mdb = Database::create_in_memory(true)?;
 
-        let cid = ChannelID::Youtube(crate::common::YoutubeID {
-            id: "testchannel".into(),
-        });
-
         let c = Channel::create(
             &mdb,
-            &cid,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
             "test channel",
             "http://example.com/thumbnail.jpg",
         )?;
 
-        // Create new video
-        {
-            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
-                .with_timezone(&chrono::Utc);
-
-            let new_video = VideoInfo {
-                id: "an id".into(),
-                url: "http://example.com/watch?v=abc123".into(),
-                title: "Good video!".into(),
-                title_alt: None,
-                description: "A ficticious video.\nIt is quite good".into(),
-                description_alt: None,
-                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
-                published_at: when,
-                duration: 12341,
-            };
-            c.add_video(&mdb, &new_video)?;
-        }
-
-        // Video 2
-        {
-            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
-                .with_timezone(&chrono::Utc);
-
-            let new_video = VideoInfo {
-                id: "an id".into(),
-                url: "http://example.com/watch?v=def321".into(),
-                title: "Another good video!".into(),
-                title_alt: None,
-                description: "A ficticious video.\nIt is quite good".into(),
-                description_alt: None,
-                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
-                published_at: when,
-                duration: 12341,
-            };
-            c.add_video(&mdb, &new_video)?;
-        }
-
-        // Video 3
-        {
-            let when = chrono::DateTime::parse_from_rfc3339("2001-12-30T16:39:57Z")?
-                .with_timezone(&chrono::Utc);
+        let when = chrono::DateTime::parse_from_rfc3339("2030-01-01T12:00:00Z")?
+            .with_timezone(&chrono::Utc);
+        let premiere = VideoInfo {
+            id: "premiere".into(),
+            url: "http://example.com/watch?v=premiere".into(),
+            title: "Coming soon".into(),
+            title_alt: None,
+            description: "".into(),
+            description_alt: None,
+            thumbnail_url: "".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc::now(),
+            duration: 0,
+            live_status: LiveStatus::Upcoming,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: Some(when),
+            members_only: false,
+        };
+        let v = c.add_video(&mdb, &premiere)?;
+        assert_eq!(v.status, VideoStatus::Upcoming);
+        assert_eq!(v.info.scheduled_at, Some(when));
+
+        // Still upcoming, so nothing changes
+        assert_eq!(c.release_upcoming(&mdb)?, 0);
+
+        // Premiere has started
+        c.set_video_live_status(&mdb, &premiere.url, LiveStatus::NotLive)?;
+        assert_eq!(c.release_upcoming(&mdb)?, 1);
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.status, VideoStatus::New);
+        Ok(())
+    }
 
-            let new_video = VideoInfo {
-                id: "an id".into(),
-                url: "http://example.com/watch?v=xyz789".into(),
-                title: "A grab error".into(),
-                title_alt: None,
-                description: "A ficticious video.\nIt is quite good".into(),
-                description_alt: None,
-                thumbnail_url: "http://example.com/vidthumb.jpg".into(),
-                published_at: when,
-                duration: 12341,
-            };
-            let v = c.add_video(&mdb, &new_video)?;
-            v.set_status(&mdb, crate::common::VideoStatus::GrabError)?;
-        }
+    #[test]
+    fn test_members_only() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
 
-        // Searching by status only
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::GrabError);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: None,
-                        status: Some(st),
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                1
-            );
-        }
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
 
-        // Searching by another status
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::New);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: None,
-                        status: Some(st),
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                2
-            );
-        }
+        let mut info = VideoInfo {
+            id: "members".into(),
+            url: "http://example.com/watch?v=members".into(),
+            title: "For members".into(),
+            title_alt: None,
+            description: "".into(),
+            description_alt: None,
+            thumbnail_url: "".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc::now(),
+            duration: 0,
+            live_status: LiveStatus::NotLive,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: None,
+            members_only: true,
+        };
+        let v = c.add_video(&mdb, &info)?;
+        assert_eq!(v.status, VideoStatus::Unavailable);
+        assert!(v.info.members_only);
+
+        // Previously added before being detected as members-only
+        info.id = "later".into();
+        info.url = "http://example.com/watch?v=later".into();
+        info.members_only = false;
+        let v = c.add_video(&mdb, &info)?;
+        assert_eq!(v.status, VideoStatus::New);
+        c.mark_unavailable(&mdb, &info.url)?;
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.status, VideoStatus::Unavailable);
+        Ok(())
+    }
 
-        // Searching by status which has no videos
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::Downloading);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: None,
-                        status: Some(st),
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                0
-            );
-        }
+    #[test]
+    fn test_mark_removed() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
 
-        // Searching by title only
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::New);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: Some("Another".into()),
-                        status: Some(st),
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                1
-            );
-        }
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
 
-        // Another search by title
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::New);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: Some("A".into()),
-                        status: None,
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                2
-            );
+        let mut ids = vec![];
+        for id in &["a", "b", "c"] {
+            let v = c.add_video(
+                &mdb,
+                &VideoInfo {
+                    id: id.to_string(),
+                    url: format!("http://example.com/watch?v={}", id),
+                    title: format!("Video {}", id),
+                    title_alt: None,
+                    description: "".into(),
+                    description_alt: None,
+                    thumbnail_url: "".into(),
+                    thumbnails: vec![],
+                    published_at: chrono::Utc::now(),
+                    duration: 0,
+                    live_status: LiveStatus::NotLive,
+                    view_count: None,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                },
+            )?;
+            ids.push(v.id);
         }
+        DBVideoInfo::get_by_sqlid(&mdb, ids[1])?.set_status(&mdb, VideoStatus::Grabbed)?;
+
+        // Source only lists "a"
+        let listed: HashSet<String> = ["http://example.com/watch?v=a".to_string()].into();
+        c.mark_seen(&mdb, &listed)?;
+        assert!(DBVideoInfo::get_by_sqlid(&mdb, ids[0])?.last_seen.is_some());
+        assert!(DBVideoInfo::get_by_sqlid(&mdb, ids[1])?.last_seen.is_none());
+        // Only marked once missing from two lists in a row
+        assert_eq!(c.mark_removed(&mdb, &listed, 2)?, 0);
+        assert_eq!(
+            DBVideoInfo::get_by_sqlid(&mdb, ids[1])?.status,
+            VideoStatus::Grabbed
+        );
+        assert_eq!(c.mark_removed(&mdb, &listed, 2)?, 2);
+        let v = DBVideoInfo::get_by_sqlid(&mdb, ids[0])?;
+        assert_eq!(v.status, VideoStatus::New);
+        assert!(v.removed_at.is_none());
+        // Downloaded video keeps its status
+        let v = DBVideoInfo::get_by_sqlid(&mdb, ids[1])?;
+        assert_eq!(v.status, VideoStatus::Grabbed);
+        assert!(v.removed_at.is_some());
+        let v = DBVideoInfo::get_by_sqlid(&mdb, ids[2])?;
+        assert_eq!(v.status, VideoStatus::Removed);
+        assert!(v.removed_at.is_some());
+
+        // Already removed videos aren't counted again
+        assert_eq!(c.mark_removed(&mdb, &listed, 2)?, 0);
+        Ok(())
+    }
 
-        // Search by title finds nothing
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::New);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: Some("Blahblah".into()),
-                        status: None,
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                0
-            );
-        }
+    #[test]
+    fn test_live_status() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
 
-        // No filtering
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::New);
-            assert_eq!(all_videos(&mdb, 99, 0, None)?.len(), 3);
-        }
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "test channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
 
-        // Filtering with no specified parameters
-        {
-            let mut st = HashSet::new();
-            st.insert(VideoStatus::New);
-            assert_eq!(
-                all_videos(
-                    &mdb,
-                    99,
-                    0,
-                    Some(FilterParams {
-                        name_contains: None,
-                        status: None,
-                        chanid: None,
-                    })
-                )?
-                .len(),
-                3
-            );
-        }
+        let stream = VideoInfo {
+            id: "stream".into(),
+            url: "http://example.com/watch?v=stream".into(),
+            title: "Live now".into(),
+            title_alt: None,
+            description: "".into(),
+            description_alt: None,
+            thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc::now(),
+            duration: 0,
+            live_status: LiveStatus::Live,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: None,
+            members_only: false,
+        };
+        let v = c.add_video(&mdb, &stream)?;
+        assert_eq!(v.info.live_status, LiveStatus::Live);
 
-        // Good
+        // Stream finishes
+        c.set_video_live_status(&mdb, &stream.url, LiveStatus::NotLive)?;
+        let v = DBVideoInfo::get_by_sqlid(&mdb, v.id)?;
+        assert_eq!(v.info.live_status, LiveStatus::NotLive);
         Ok(())
     }
 
@@ -1088,6 +6086,7 @@ mod tests {
             &mdb,
             &ChannelID::Youtube(crate::common::YoutubeID {
                 id: "testchannel".into(),
+                handle: None,
             }),
             "test channel",
             "http://example.com/thumbnail.jpg",
@@ -1097,6 +6096,7 @@ mod tests {
             &mdb,
             &ChannelID::Youtube(crate::common::YoutubeID {
                 id: "secondchannel".into(),
+                handle: None,
             }),
             "second channel",
             "http://example.com/second.jpg",
@@ -1115,8 +6115,14 @@ mod tests {
                 description: "A ficticious video.\nIt is quite good".into(),
                 description_alt: None,
                 thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
                 published_at: when,
                 duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
             };
             dbg!("first");
             c.add_video(&mdb, &new_video)?;
@@ -1135,8 +6141,14 @@ mod tests {
                 description: "A ficticious video.\nIt is quite good".into(),
                 description_alt: None,
                 thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
                 published_at: when,
                 duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
             };
             dbg!("second");
             c.add_video(&mdb, &new_video)?;
@@ -1155,8 +6167,14 @@ mod tests {
                 description: "A third video".into(),
                 description_alt: None,
                 thumbnail_url: "http://example.com/vidthumb.jpg".into(),
+                thumbnails: vec![],
                 published_at: when,
                 duration: 12341,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
             };
             c2.add_video(&mdb, &new_video)?;
         }
@@ -143,6 +143,1240 @@ impl Migration for M05AddAltDescription {
         Ok(())
     }
 }
+
+#[derive(Debug)]
+struct M06AddChannelBackend;
+
+impl Migration for M06AddChannelBackend {
+    fn get_name(&self) -> &str {
+        "Add backend to channels"
+    }
+    fn get_version(&self) -> i64 {
+        6
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Null value means use the configured default
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN backend TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M07AddLiveStatus;
+
+impl Migration for M07AddLiveStatus {
+    fn get_name(&self) -> &str {
+        "Add live_status to videos"
+    }
+    fn get_version(&self) -> i64 {
+        7
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Existing videos are assumed to be normal uploads
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN live_status TEXT NOT NULL DEFAULT 'none'
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M08AddViewCount;
+
+impl Migration for M08AddViewCount {
+    fn get_name(&self) -> &str {
+        "Add view_count to videos"
+    }
+    fn get_version(&self) -> i64 {
+        8
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Null value means the source didn't provide a count
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN view_count INTEGER
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M09CreateVideoTag;
+
+impl Migration for M09CreateVideoTag {
+    fn get_name(&self) -> &str {
+        "Create video_tag table"
+    }
+    fn get_version(&self) -> i64 {
+        9
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE video_tag (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                tag           TEXT NOT NULL,
+                FOREIGN KEY(video) REFERENCES video(id),
+                UNIQUE(video, tag)
+            );
+            CREATE INDEX idx_video_tag_tag ON video_tag (
+                tag
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M10AddCaptions;
+
+impl Migration for M10AddCaptions {
+    fn get_name(&self) -> &str {
+        "Add captions to videos"
+    }
+    fn get_version(&self) -> i64 {
+        10
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Comma separated list of language codes
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN captions TEXT NOT NULL DEFAULT ''
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M11AddChannelDetails;
+
+impl Migration for M11AddChannelDetails {
+    fn get_name(&self) -> &str {
+        "Add description, banner and subscriber_count to channels"
+    }
+    fn get_version(&self) -> i64 {
+        11
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Filled in on next update
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN description TEXT NOT NULL DEFAULT '';
+            ALTER TABLE channel
+            ADD COLUMN banner TEXT;
+            ALTER TABLE channel
+            ADD COLUMN subscriber_count INTEGER;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M12AddScheduledAt;
+
+impl Migration for M12AddScheduledAt {
+    fn get_name(&self) -> &str {
+        "Add scheduled_at to videos"
+    }
+    fn get_version(&self) -> i64 {
+        12
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Null for normal videos
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN scheduled_at DATETIME NULL
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M13AddMembersOnly;
+
+impl Migration for M13AddMembersOnly {
+    fn get_name(&self) -> &str {
+        "Add members_only to videos"
+    }
+    fn get_version(&self) -> i64 {
+        13
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN members_only BOOLEAN NOT NULL DEFAULT 0
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M14AddChannelHandle;
+
+impl Migration for M14AddChannelHandle {
+    fn get_name(&self) -> &str {
+        "Add handle to channels"
+    }
+    fn get_version(&self) -> i64 {
+        14
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN handle TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M15CreateVideoChapter;
+
+impl Migration for M15CreateVideoChapter {
+    fn get_name(&self) -> &str {
+        "Create video_chapter table"
+    }
+    fn get_version(&self) -> i64 {
+        15
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE video_chapter (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                start         INTEGER NOT NULL,
+                title         TEXT NOT NULL,
+                FOREIGN KEY(video) REFERENCES video(id)
+            );
+            CREATE INDEX idx_video_chapter_video ON video_chapter (
+                video
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M16AddChannelFormat;
+
+impl Migration for M16AddChannelFormat {
+    fn get_name(&self) -> &str {
+        "Add format to channels"
+    }
+    fn get_version(&self) -> i64 {
+        16
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN format TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M17AddChannelSubtitles;
+
+impl Migration for M17AddChannelSubtitles {
+    fn get_name(&self) -> &str {
+        "Add subtitles flag to channels"
+    }
+    fn get_version(&self) -> i64 {
+        17
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN subtitles BOOLEAN
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M18CreateVideoSubtitle;
+
+impl Migration for M18CreateVideoSubtitle {
+    fn get_name(&self) -> &str {
+        "Create video_subtitle table"
+    }
+    fn get_version(&self) -> i64 {
+        18
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE video_subtitle (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                lang          TEXT NOT NULL,
+                path          TEXT NOT NULL,
+                FOREIGN KEY(video) REFERENCES video(id)
+            );
+            CREATE INDEX idx_video_subtitle_video ON video_subtitle (
+                video
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M19AddVideoRetry;
+
+impl Migration for M19AddVideoRetry {
+    fn get_name(&self) -> &str {
+        "Add retry count and time to videos"
+    }
+    fn get_version(&self) -> i64 {
+        19
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN retry_count INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE video
+            ADD COLUMN retry_at DATETIME;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M20AddChannelOutput;
+
+impl Migration for M20AddChannelOutput {
+    fn get_name(&self) -> &str {
+        "Add download directory and filename format to channels"
+    }
+    fn get_version(&self) -> i64 {
+        20
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN download_dir TEXT;
+            ALTER TABLE channel
+            ADD COLUMN filename_format TEXT;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M21AddVideoPartialPath;
+
+impl Migration for M21AddVideoPartialPath {
+    fn get_name(&self) -> &str {
+        "Add partial download path to videos"
+    }
+    fn get_version(&self) -> i64 {
+        21
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN partial_path TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M22AddChannelIgnoreRules;
+
+impl Migration for M22AddChannelIgnoreRules {
+    fn get_name(&self) -> &str {
+        "Add auto-ignore rules to channels"
+    }
+    fn get_version(&self) -> i64 {
+        22
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN ignore_min_duration INTEGER;
+            ALTER TABLE channel
+            ADD COLUMN ignore_max_duration INTEGER;
+            ALTER TABLE channel
+            ADD COLUMN ignore_title TEXT;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M23AddVideoPriority;
+
+impl Migration for M23AddVideoPriority {
+    fn get_name(&self) -> &str {
+        "Add download priority to videos"
+    }
+    fn get_version(&self) -> i64 {
+        23
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN priority INTEGER NOT NULL DEFAULT 0
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M24AddVideoErrorKind;
+
+impl Migration for M24AddVideoErrorKind {
+    fn get_name(&self) -> &str {
+        "Add download error kind to videos"
+    }
+    fn get_version(&self) -> i64 {
+        24
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN error_kind TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M25CreateVideoSearch;
+
+impl Migration for M25CreateVideoSearch {
+    fn get_name(&self) -> &str {
+        "Create full-text search index of videos"
+    }
+    fn get_version(&self) -> i64 {
+        25
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Index of the video table's text, kept up to date by triggers
+        conn.execute_batch(
+            "
+            CREATE VIRTUAL TABLE video_fts USING fts5(
+                title, title_alt, description, description_alt,
+                content='video', content_rowid='id'
+            );
+            INSERT INTO video_fts(video_fts) VALUES('rebuild');
+
+            CREATE TRIGGER video_fts_insert AFTER INSERT ON video BEGIN
+                INSERT INTO video_fts(rowid, title, title_alt, description, description_alt)
+                VALUES (new.id, new.title, new.title_alt, new.description, new.description_alt);
+            END;
+            CREATE TRIGGER video_fts_delete AFTER DELETE ON video BEGIN
+                INSERT INTO video_fts(video_fts, rowid, title, title_alt, description, description_alt)
+                VALUES ('delete', old.id, old.title, old.title_alt, old.description, old.description_alt);
+            END;
+            CREATE TRIGGER video_fts_update
+            AFTER UPDATE OF title, title_alt, description, description_alt ON video BEGIN
+                INSERT INTO video_fts(video_fts, rowid, title, title_alt, description, description_alt)
+                VALUES ('delete', old.id, old.title, old.title_alt, old.description, old.description_alt);
+                INSERT INTO video_fts(rowid, title, title_alt, description, description_alt)
+                VALUES (new.id, new.title, new.title_alt, new.description, new.description_alt);
+            END;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M26AddVideoFilePath;
+
+impl Migration for M26AddVideoFilePath {
+    fn get_name(&self) -> &str {
+        "Add downloaded file path to videos"
+    }
+    fn get_version(&self) -> i64 {
+        26
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN file_path TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M27AddVideoWatched;
+
+impl Migration for M27AddVideoWatched {
+    fn get_name(&self) -> &str {
+        "Add watched flag to videos"
+    }
+    fn get_version(&self) -> i64 {
+        27
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN watched INTEGER NOT NULL DEFAULT 0
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M28AddVideoNotes;
+
+impl Migration for M28AddVideoNotes {
+    fn get_name(&self) -> &str {
+        "Add notes to videos"
+    }
+    fn get_version(&self) -> i64 {
+        28
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN notes TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M29CreateTag;
+
+impl Migration for M29CreateTag {
+    fn get_name(&self) -> &str {
+        "Move tag names to tag table"
+    }
+    fn get_version(&self) -> i64 {
+        29
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Each name is stored once, with video_tag rebuilt to refer to it
+        conn.execute_batch(
+            "
+            CREATE TABLE tag (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                name          TEXT NOT NULL UNIQUE
+            );
+            INSERT INTO tag (name) SELECT DISTINCT tag FROM video_tag ORDER BY tag;
+
+            CREATE TABLE video_tag_new (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                tag           INTEGER NOT NULL,
+                FOREIGN KEY(video) REFERENCES video(id),
+                FOREIGN KEY(tag) REFERENCES tag(id),
+                UNIQUE(video, tag)
+            );
+            INSERT INTO video_tag_new (video, tag)
+                SELECT video_tag.video, tag.id FROM video_tag JOIN tag ON tag.name = video_tag.tag;
+            DROP TABLE video_tag;
+            ALTER TABLE video_tag_new RENAME TO video_tag;
+            CREATE INDEX idx_video_tag_tag ON video_tag (
+                tag
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M30CreateCollection;
+
+impl Migration for M30CreateCollection {
+    fn get_name(&self) -> &str {
+        "Create collection tables"
+    }
+    fn get_version(&self) -> i64 {
+        30
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE collection (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                name          TEXT NOT NULL UNIQUE,
+                date_created  DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE collection_video (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                collection    INTEGER NOT NULL,
+                video         INTEGER NOT NULL,
+                date_added    DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(collection) REFERENCES collection(id),
+                FOREIGN KEY(video) REFERENCES video(id),
+                UNIQUE(collection, video)
+            );
+            CREATE INDEX idx_collection_video_video ON collection_video (
+                video
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M31AddVideoWatchedAt;
+
+impl Migration for M31AddVideoWatchedAt {
+    fn get_name(&self) -> &str {
+        "Add time videos were watched"
+    }
+    fn get_version(&self) -> i64 {
+        31
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Unknown for videos already marked as watched
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN watched_at DATETIME
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M32AddVideoFileInfo;
+
+impl Migration for M32AddVideoFileInfo {
+    fn get_name(&self) -> &str {
+        "Add size and download time of video files"
+    }
+    fn get_version(&self) -> i64 {
+        32
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video ADD COLUMN file_size INTEGER;
+            ALTER TABLE video ADD COLUMN downloaded_at DATETIME;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M33CreateChannelSettings;
+
+impl Migration for M33CreateChannelSettings {
+    fn get_name(&self) -> &str {
+        "Create channel settings table"
+    }
+    fn get_version(&self) -> i64 {
+        33
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Channels without a row use the defaults
+        conn.execute_batch(
+            "
+            CREATE TABLE channel_settings (
+                channel                  INTEGER PRIMARY KEY,
+                auto_download            BOOLEAN NOT NULL DEFAULT 0,
+                update_interval_minutes  INTEGER,
+                enabled                  BOOLEAN NOT NULL DEFAULT 1,
+                FOREIGN KEY(channel) REFERENCES channel(id)
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M34AddChannelArchived;
+
+impl Migration for M34AddChannelArchived {
+    fn get_name(&self) -> &str {
+        "Add archived flag to channel"
+    }
+    fn get_version(&self) -> i64 {
+        34
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel
+            ADD COLUMN archived BOOLEAN NOT NULL DEFAULT 0
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M35CreateVideoDuplicate;
+
+impl Migration for M35CreateVideoDuplicate {
+    fn get_name(&self) -> &str {
+        "Create video duplicate table"
+    }
+    fn get_version(&self) -> i64 {
+        35
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Channels which also list a video belonging to another channel
+        conn.execute_batch(
+            "
+            CREATE TABLE video_duplicate (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                channel       INTEGER NOT NULL,
+                date_added    DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(video) REFERENCES video(id),
+                FOREIGN KEY(channel) REFERENCES channel(id),
+                UNIQUE(video, channel)
+            );
+            CREATE INDEX idx_video_duplicate_channel ON video_duplicate (
+                channel
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M36AddVideoOrderIndex;
+
+impl Migration for M36AddVideoOrderIndex {
+    fn get_name(&self) -> &str {
+        "Index video list order"
+    }
+    fn get_version(&self) -> i64 {
+        36
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Matches the `ORDER BY` of video lists and the cursor they continue from, so pages
+        // are found without sorting the whole table
+        conn.execute_batch(
+            "
+            CREATE INDEX idx_video_published_at_id ON video (
+                datetime(published_at), id
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M37CreateVideoHistory;
+
+impl Migration for M37CreateVideoHistory {
+    fn get_name(&self) -> &str {
+        "Create video status history table"
+    }
+    fn get_version(&self) -> i64 {
+        37
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Kept up to date by triggers, using the `vidl_origin()` function vidl registers on
+        // each connection
+        conn.execute_batch(
+            "
+            CREATE TABLE video_history (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                old_status    TEXT,
+                new_status    TEXT NOT NULL,
+                origin        TEXT NOT NULL,
+                changed_at    DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(video) REFERENCES video(id)
+            );
+            CREATE INDEX idx_video_history_video ON video_history (
+                video
+            );
+
+            CREATE TRIGGER video_history_insert AFTER INSERT ON video BEGIN
+                INSERT INTO video_history (video, new_status, origin)
+                VALUES (new.id, new.status, vidl_origin());
+            END;
+            CREATE TRIGGER video_history_update
+            AFTER UPDATE OF status ON video WHEN old.status != new.status BEGIN
+                INSERT INTO video_history (video, old_status, new_status, origin)
+                VALUES (new.id, old.status, new.status, vidl_origin());
+            END;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M38AddVideoIndexes;
+
+impl Migration for M38AddVideoIndexes {
+    fn get_name(&self) -> &str {
+        "Add indexes for filtering videos by status, channel and date added"
+    }
+    fn get_version(&self) -> i64 {
+        38
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // idx_video_channel is covered by the new channel index, which also orders a channel's
+        // latest videos
+        conn.execute_batch(
+            "
+            CREATE INDEX idx_video_status ON video (
+                status
+            );
+            CREATE INDEX idx_video_channel_published_at ON video (
+                channel, published_at
+            );
+            CREATE INDEX idx_video_date_added ON video (
+                date_added
+            );
+            DROP INDEX idx_video_channel;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M39AddVideoErrorMessage;
+
+impl Migration for M39AddVideoErrorMessage {
+    fn get_name(&self) -> &str {
+        "Add error_message to videos"
+    }
+    fn get_version(&self) -> i64 {
+        39
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video
+            ADD COLUMN error_message TEXT
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M40AddVideoLastSeen;
+
+impl Migration for M40AddVideoLastSeen {
+    fn get_name(&self) -> &str {
+        "Add last_seen and missed_updates to videos"
+    }
+    fn get_version(&self) -> i64 {
+        40
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE video ADD COLUMN last_seen DATETIME;
+            ALTER TABLE video ADD COLUMN missed_updates INTEGER NOT NULL DEFAULT 0;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M41CreateVideoThumbnail;
+
+impl Migration for M41CreateVideoThumbnail {
+    fn get_name(&self) -> &str {
+        "Create video thumbnail sizes table"
+    }
+    fn get_version(&self) -> i64 {
+        41
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE video_thumbnail (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                url           TEXT NOT NULL,
+                width         INTEGER NOT NULL,
+                height        INTEGER NOT NULL,
+                FOREIGN KEY(video) REFERENCES video(id),
+                UNIQUE(video, url)
+            );
+            CREATE INDEX idx_video_thumbnail_video ON video_thumbnail (
+                video
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M42CreateSettings;
+
+impl Migration for M42CreateSettings {
+    fn get_name(&self) -> &str {
+        "Create settings table"
+    }
+    fn get_version(&self) -> i64 {
+        42
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE TABLE settings (
+                key           TEXT PRIMARY KEY,
+                value         TEXT NOT NULL
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M43CreateVideoAltEdit;
+
+impl Migration for M43CreateVideoAltEdit {
+    fn get_name(&self) -> &str {
+        "Create video alternative title and description edit history table"
+    }
+    fn get_version(&self) -> i64 {
+        43
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Kept up to date by triggers, like video_history
+        conn.execute_batch(
+            "
+            CREATE TABLE video_alt_edit (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                video         INTEGER NOT NULL,
+                field         TEXT NOT NULL,
+                old_value     TEXT,
+                changed_at    DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(video) REFERENCES video(id)
+            );
+            CREATE INDEX idx_video_alt_edit_video ON video_alt_edit (
+                video
+            );
+
+            CREATE TRIGGER video_alt_edit_title
+            AFTER UPDATE OF title_alt ON video WHEN old.title_alt IS NOT new.title_alt BEGIN
+                INSERT INTO video_alt_edit (video, field, old_value)
+                VALUES (new.id, 'title_alt', old.title_alt);
+            END;
+            CREATE TRIGGER video_alt_edit_description
+            AFTER UPDATE OF description_alt ON video
+            WHEN old.description_alt IS NOT new.description_alt BEGIN
+                INSERT INTO video_alt_edit (video, field, old_value)
+                VALUES (new.id, 'description_alt', old.description_alt);
+            END;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M44AddChannelRetention;
+
+impl Migration for M44AddChannelRetention {
+    fn get_name(&self) -> &str {
+        "Add retention policy to channel settings"
+    }
+    fn get_version(&self) -> i64 {
+        44
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            ALTER TABLE channel_settings ADD COLUMN prune_after_days INTEGER;
+            ALTER TABLE channel_settings ADD COLUMN prune_delete BOOLEAN NOT NULL DEFAULT 0;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M45AddVideoIdIndex;
+
+impl Migration for M45AddVideoIdIndex {
+    fn get_name(&self) -> &str {
+        "Add index for finding videos by their service's ID"
+    }
+    fn get_version(&self) -> i64 {
+        45
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "
+            CREATE INDEX idx_video_video_id ON video (
+                video_id
+            );
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M46NormaliseDateAdded;
+
+impl Migration for M46NormaliseDateAdded {
+    fn get_name(&self) -> &str {
+        "Store video date_added in one format"
+    }
+    fn get_version(&self) -> i64 {
+        46
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Written as `CURRENT_TIMESTAMP`, RFC 3339 or with fractional seconds and a timezone,
+        // which don't sort together as text
+        conn.execute_batch(
+            "
+            UPDATE video SET date_added = datetime(date_added)
+                WHERE datetime(date_added) IS NOT NULL;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M47AddDownloadHeartbeat;
+
+impl Migration for M47AddDownloadHeartbeat {
+    fn get_name(&self) -> &str {
+        "Add download_pid and download_heartbeat to videos"
+    }
+    fn get_version(&self) -> i64 {
+        47
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Process downloading the video, and when it last showed it was still doing so
+        conn.execute_batch(
+            "
+            ALTER TABLE video ADD COLUMN download_pid INTEGER NULL;
+            ALTER TABLE video ADD COLUMN download_heartbeat DATETIME NULL;
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct M48AddRemovedAt;
+
+impl Migration for M48AddRemovedAt {
+    fn get_name(&self) -> &str {
+        "Add removed_at to videos"
+    }
+    fn get_version(&self) -> i64 {
+        48
+    }
+
+    fn up(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Removal from the source was recorded as the `RM` status, replacing whatever it was.
+        // Downloaded or queued videos get their status back from the history
+        conn.execute_batch(
+            "
+            ALTER TABLE video ADD COLUMN removed_at DATETIME NULL;
+            UPDATE video SET removed_at = CURRENT_TIMESTAMP WHERE status = 'RM';
+            UPDATE video SET status = (
+                SELECT CASE old_status WHEN 'DL' THEN 'QU' ELSE old_status END
+                FROM video_history
+                WHERE video_history.video = video.id AND new_status = 'RM'
+                ORDER BY video_history.id DESC LIMIT 1
+            )
+            WHERE status = 'RM' AND (
+                SELECT old_status FROM video_history
+                WHERE video_history.video = video.id AND new_status = 'RM'
+                ORDER BY video_history.id DESC LIMIT 1
+            ) IN ('GR', 'QU', 'DL');
+            ",
+        )
+        .map(|_| ())?;
+
+        Ok(())
+    }
+}
+
 pub fn get_migrator(db: &rusqlite::Connection) -> Migrator {
     Migrator {
         migs: vec![
@@ -151,6 +1385,49 @@ pub fn get_migrator(db: &rusqlite::Connection) -> Migrator {
             Box::new(M03AddInsertionDate {}),
             Box::new(M04AddAltTitle {}),
             Box::new(M05AddAltDescription {}),
+            Box::new(M06AddChannelBackend {}),
+            Box::new(M07AddLiveStatus {}),
+            Box::new(M08AddViewCount {}),
+            Box::new(M09CreateVideoTag {}),
+            Box::new(M10AddCaptions {}),
+            Box::new(M11AddChannelDetails {}),
+            Box::new(M12AddScheduledAt {}),
+            Box::new(M13AddMembersOnly {}),
+            Box::new(M14AddChannelHandle {}),
+            Box::new(M15CreateVideoChapter {}),
+            Box::new(M16AddChannelFormat {}),
+            Box::new(M17AddChannelSubtitles {}),
+            Box::new(M18CreateVideoSubtitle {}),
+            Box::new(M19AddVideoRetry {}),
+            Box::new(M20AddChannelOutput {}),
+            Box::new(M21AddVideoPartialPath {}),
+            Box::new(M22AddChannelIgnoreRules {}),
+            Box::new(M23AddVideoPriority {}),
+            Box::new(M24AddVideoErrorKind {}),
+            Box::new(M25CreateVideoSearch {}),
+            Box::new(M26AddVideoFilePath {}),
+            Box::new(M27AddVideoWatched {}),
+            Box::new(M28AddVideoNotes {}),
+            Box::new(M29CreateTag {}),
+            Box::new(M30CreateCollection {}),
+            Box::new(M31AddVideoWatchedAt {}),
+            Box::new(M32AddVideoFileInfo {}),
+            Box::new(M33CreateChannelSettings {}),
+            Box::new(M34AddChannelArchived {}),
+            Box::new(M35CreateVideoDuplicate {}),
+            Box::new(M36AddVideoOrderIndex {}),
+            Box::new(M37CreateVideoHistory {}),
+            Box::new(M38AddVideoIndexes {}),
+            Box::new(M39AddVideoErrorMessage {}),
+            Box::new(M40AddVideoLastSeen {}),
+            Box::new(M41CreateVideoThumbnail {}),
+            Box::new(M42CreateSettings {}),
+            Box::new(M43CreateVideoAltEdit {}),
+            Box::new(M44AddChannelRetention {}),
+            Box::new(M45AddVideoIdIndex {}),
+            Box::new(M46NormaliseDateAdded {}),
+            Box::new(M47AddDownloadHeartbeat {}),
+            Box::new(M48AddRemovedAt {}),
         ],
         db: &db,
     }
@@ -1,19 +1,855 @@
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
 
 use anyhow::{Context, Result};
-use log::debug;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use serde_derive::Serialize;
 
+use crate::common::{DownloadErrorKind, FilenameStyle, Service};
 use crate::config::Config;
+use crate::db::Channel;
 use crate::source::base::VideoInfo;
 
-pub fn download(vid: &VideoInfo) -> Result<()> {
+lazy_static! {
+    /// Progress of downloads currently running, keyed by video SQL ID
+    pub(crate) static ref PROGRESS: Mutex<HashMap<i64, DownloadProgress>> = Mutex::new(HashMap::new());
+    /// Downloads running in this process, keyed by video ID, so they can be cancelled
+    static ref RUNNING: Mutex<HashMap<String, RunningDownload>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct RunningDownload {
+    cancelled: bool,
+    /// Downloader process, once started
+    child: Option<Arc<Mutex<Child>>>,
+}
+
+impl RunningDownload {
+    fn cancel(&mut self, video_id: &str) {
+        self.cancelled = true;
+        if let Some(child) = &self.child {
+            if let Err(e) = child.lock().unwrap().kill() {
+                warn!("Failed to stop downloader for {} - {:?}", video_id, e);
+            }
+        }
+    }
+}
+
+/// Removes download from `RUNNING` when it finishes
+struct RunningGuard<'a>(&'a str);
+
+impl<'a> RunningGuard<'a> {
+    fn new(video_id: &'a str) -> Self {
+        RUNNING
+            .lock()
+            .unwrap()
+            .insert(video_id.into(), RunningDownload::default());
+        RunningGuard(video_id)
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        RUNNING.lock().unwrap().remove(self.0);
+    }
+}
+
+/// Download was stopped by `cancel`
+#[derive(Debug, thiserror::Error)]
+#[error("Download of {0} was cancelled")]
+pub struct Cancelled(pub String);
+
+/// Stop the download of video with ID `video_id`, killing the downloader. Returns false if
+/// it isn't being downloaded by this process
+pub fn cancel(video_id: &str) -> bool {
+    let mut running = RUNNING.lock().unwrap();
+    let Some(dl) = running.get_mut(video_id) else {
+        return false;
+    };
+    dl.cancel(video_id);
+    true
+}
+
+/// Stop all downloads running in this process, returning how many there were
+pub fn cancel_all() -> usize {
+    let mut running = RUNNING.lock().unwrap();
+    for (video_id, dl) in running.iter_mut() {
+        dl.cancel(video_id);
+    }
+    running.len()
+}
+
+fn is_cancelled(video_id: &str) -> bool {
+    RUNNING
+        .lock()
+        .unwrap()
+        .get(video_id)
+        .is_some_and(|dl| dl.cancelled)
+}
+
+/// Downloader exited unsuccessfully
+#[derive(Debug, thiserror::Error)]
+#[error("{} failed ({}): {}", .binary, .kind.as_str(), .message)]
+pub struct DownloadError {
+    pub binary: String,
+    pub kind: DownloadErrorKind,
+    /// Last error message from the downloader, or its exit status if there wasn't one
+    pub message: String,
+}
+
+/// Something which happened during a download
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadEvent {
+    Progress(DownloadProgress),
+    /// File (media, or a fragment of it) being written to
+    Destination(String),
+    Subtitle(SubtitleFile),
+    Warning(String),
+    Error(String),
+    /// Other output from the downloader
+    Output(String),
+}
+
+/// State of a running download, parsed from the downloader's `--newline` output
+#[derive(Debug, Clone, Default, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct DownloadProgress {
+    pub title: String,
+    pub percent: f32,
+    /// Like `2.34MiB/s`
+    pub speed: Option<String>,
+    /// Like `00:31`
+    pub eta: Option<String>,
+}
+
+/// Subtitle file written alongside a downloaded video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleFile {
+    /// Language code, like `en`
+    pub lang: String,
+    pub path: String,
+}
+
+/// Files written by a successful download
+#[derive(Debug, Clone, Default)]
+pub struct Downloaded {
+    /// Final video (or audio) file, if it could be found in the downloader's output
+    pub path: Option<std::path::PathBuf>,
+    pub subtitles: Vec<SubtitleFile>,
+}
+
+/// Parse the path from lines reporting where media is written, like
+/// `[Merger] Merging formats into "some_video.mkv"`. Later lines supersede earlier ones
+fn parse_media_path(line: &str) -> Option<String> {
+    let line = line.trim();
+    if let Some(p) = line.strip_prefix("[Merger] Merging formats into ") {
+        return Some(p.trim_matches('"').to_string());
+    }
+    if let Some(p) = line
+        .strip_prefix("[download] Destination: ")
+        .or_else(|| line.strip_prefix("[ExtractAudio] Destination: "))
+    {
+        return Some(p.to_string());
+    }
+    line.strip_prefix("[download] ")
+        .and_then(|p| p.strip_suffix(" has already been downloaded"))
+        .map(|p| p.to_string())
+}
+
+/// Parse a line like `[info] Writing video subtitles to: some_video.en.vtt`
+fn parse_subtitle_file(line: &str) -> Option<SubtitleFile> {
+    let path = line
+        .trim()
+        .strip_prefix("[info] Writing video subtitles to: ")?
+        .to_string();
+    // Language is the second extension, as in `title__id.en.vtt`
+    let lang = std::path::Path::new(&path)
+        .file_stem()
+        .map(std::path::Path::new)
+        .and_then(|s| s.extension())?
+        .to_string_lossy()
+        .to_string();
+    Some(SubtitleFile { lang, path })
+}
+
+/// Parse a progress line like `[download]  45.3% of 123.45MiB at  2.34MiB/s ETA 00:31`,
+/// updating the percent/speed/ETA of `progress`. Returns false if it isn't a progress line
+fn parse_progress(line: &str, progress: &mut DownloadProgress) -> bool {
+    let Some(rest) = line.trim().strip_prefix("[download]") else {
+        return false;
+    };
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let Some(percent) = words
+        .first()
+        .and_then(|w| w.strip_suffix('%'))
+        .and_then(|p| p.parse::<f32>().ok())
+    else {
+        return false;
+    };
+
+    let after = |key: &str| -> Option<String> {
+        words
+            .iter()
+            .position(|w| *w == key)
+            .and_then(|i| words.get(i + 1))
+            .filter(|w| !w.starts_with("Unknown"))
+            .map(|w| w.to_string())
+    };
+    progress.percent = percent;
+    progress.speed = after("at");
+    progress.eta = after("ETA");
+    true
+}
+
+/// Which program is used to download videos, as their arguments differ slightly
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownloaderKind {
+    YtDlp,
+    /// The original youtube-dl, which lacks some newer options
+    YoutubeDl,
+}
+
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    /// Executable name or path
+    pub binary: String,
+    pub kind: DownloaderKind,
+}
+
+/// If an executable with the given name is in `PATH`
+fn in_path(name: &str) -> bool {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&paths)
+        .any(|dir| dir.join(name).is_file() || dir.join(format!("{}.exe", name)).is_file())
+}
+
+impl Downloader {
+    /// Use the configured downloader, otherwise yt-dlp, falling back to youtube-dl if only it is installed
+    pub fn from_config(cfg: &Config) -> Downloader {
+        let binary = match &cfg.downloader {
+            Some(b) => b.clone(),
+            None if !in_path("yt-dlp") && in_path("youtube-dl") => "youtube-dl".into(),
+            None => "yt-dlp".into(),
+        };
+        Downloader::new(binary)
+    }
+
+    fn new(binary: String) -> Downloader {
+        let name = std::path::Path::new(&binary)
+            .file_name()
+            .and_then(|x| x.to_str())
+            .unwrap_or(&binary);
+        let kind = if name.starts_with("youtube-dl") {
+            DownloaderKind::YoutubeDl
+        } else {
+            DownloaderKind::YtDlp
+        };
+        Downloader { binary, kind }
+    }
+
+    /// Run with `--version`, returning the version string
+    pub fn version(&self) -> Result<String> {
+        let out = Command::new(&self.binary)
+            .arg("--version")
+            .output()
+            .with_context(|| format!("Failed to run {}", &self.binary))?;
+        if !out.status.success() {
+            anyhow::bail!("{} --version exited with {}", &self.binary, out.status);
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    }
+
+    /// Adjust arguments written for yt-dlp to suit this downloader
+    fn adapt_args<'a>(&self, args: Vec<&'a str>) -> Vec<&'a str> {
+        match self.kind {
+            DownloaderKind::YtDlp => args,
+            DownloaderKind::YoutubeDl => {
+                let mut ret = vec![];
+                let mut args = args.into_iter();
+                while let Some(a) = args.next() {
+                    // SponsorBlock and concurrent fragment downloads are yt-dlp only
+                    if a.starts_with("--sponsorblock") {
+                        continue;
+                    }
+                    if a == "--concurrent-fragments" || a == "-N" {
+                        args.next();
+                        continue;
+                    }
+                    ret.push(match a {
+                        "--write-subs" => "--write-sub",
+                        "--sub-langs" => "--sub-lang",
+                        other => other,
+                    });
+                }
+                ret
+            }
+        }
+    }
+}
+
+/// Log the downloader version, so a missing or broken install is noticed before any downloads fail
+pub fn check_downloader() {
+    let dl = Downloader::from_config(&Config::load());
+    match dl.version() {
+        Ok(v) => info!("Using {} version {}", &dl.binary, v),
+        Err(e) => warn!(
+            "Downloader {:?} not working, downloads will fail - {:?}",
+            &dl.binary, e
+        ),
+    }
+}
+
+/// If a file's `actual` duration in seconds is close enough to the video's `expected` duration.
+/// Files may be slightly shorter, e.g when SponsorBlock segments are marked, but not truncated
+fn duration_matches(actual: f64, expected: i32) -> bool {
+    let expected = expected as f64;
+    let tolerance = (expected * 0.02).max(5.0);
+    actual >= expected - tolerance
+}
+
+/// Duration of media file in seconds, from ffprobe
+fn probe_duration(path: &std::path::Path) -> Result<f64> {
+    let out = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .output()
+        .context("Failed to run ffprobe")?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "ffprobe exited with {} - {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    text.trim()
+        .parse()
+        .with_context(|| format!("Unexpected ffprobe output {:?}", text))
+}
+
+/// Check the downloaded file isn't empty and, if `VIDL_VERIFY_DURATION` is set and the
+/// video's duration is known, isn't shorter than the video
+pub fn verify(path: &std::path::Path, expected_duration: i32, cfg: &Config) -> Result<()> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("Downloaded file {:?} not found", path))?
+        .len();
+    if size == 0 {
+        anyhow::bail!("Downloaded file {:?} is empty", path);
+    }
+
+    if cfg.verify_duration && expected_duration > 0 {
+        let actual = probe_duration(path)?;
+        if !duration_matches(actual, expected_duration) {
+            anyhow::bail!(
+                "Downloaded file {:?} is {:.0} seconds long, but the video is {} seconds",
+                path,
+                actual,
+                expected_duration
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replace the `-f`/`--format` value in `args`, or add one if there isn't one
+fn set_format<'a>(args: &mut Vec<&'a str>, format: &'a str) {
+    match args.iter().position(|a| *a == "-f" || *a == "--format") {
+        Some(i) if i + 1 < args.len() => args[i + 1] = format,
+        _ => {
+            args.push("-f");
+            args.push(format);
+        }
+    }
+}
+
+/// Subtitle languages to download, as a comma separated list. Any of the wanted languages which
+/// the video has, or all of them if the channel has subtitles enabled but no languages are configured
+fn subtitle_langs(vid: &VideoInfo, chan: &Channel, cfg: &Config) -> String {
+    if !chan.subtitles.unwrap_or(!cfg.subtitle_langs.is_empty()) {
+        return "".into();
+    }
+    vid.captions
+        .iter()
+        .filter(|c| cfg.subtitle_langs.is_empty() || cfg.subtitle_langs.contains(c))
+        .map(|c| c.as_str())
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+/// Directory and filename template for channel's downloads, using the channel's overrides if set
+fn output_location<'a>(chan: &'a Channel, cfg: &'a Config) -> (std::path::PathBuf, &'a str) {
+    let dir = match &chan.download_dir {
+        Some(d) => std::path::PathBuf::from(d),
+        None => cfg.download_dir.clone(),
+    };
+    let filename_format = chan
+        .filename_format
+        .as_deref()
+        .unwrap_or(&cfg.filename_format);
+    (dir, filename_format)
+}
+
+/// Directory the downloader writes channel's videos to - a folder for the channel in the
+/// staging dir if one is configured, otherwise the output directory
+fn working_dir(chan: &Channel, cfg: &Config) -> std::path::PathBuf {
+    match &cfg.staging_dir {
+        // Channels can share filenames but not output directories
+        Some(staging) => staging.join(chan.id.to_string()),
+        None => output_location(chan, cfg).0,
+    }
+}
+
+/// Move finished file `path` from `staging` to the same place relative to `dir`, returning
+/// its new path. Files are copied if they're on different filesystems, under a temporary
+/// name so the file only appears in `dir` once complete
+fn move_into(
+    path: &std::path::Path,
+    staging: &std::path::Path,
+    dir: &std::path::Path,
+) -> Result<std::path::PathBuf> {
+    let relative = match path.strip_prefix(staging) {
+        Ok(r) => r,
+        // Not staged, e.g already downloaded
+        Err(_) => return Ok(path.to_path_buf()),
+    };
+    let dest = dir.join(relative);
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).context("Failed to make output folder")?;
+    }
+
+    debug!("Moving {:?} to {:?}", path, &dest);
+    if std::fs::rename(path, &dest).is_err() {
+        let tmp = dest.with_file_name(format!(
+            ".{}.vidl-tmp",
+            dest.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        std::fs::copy(path, &tmp).with_context(|| format!("Failed to copy {:?}", path))?;
+        std::fs::rename(&tmp, &dest)?;
+        std::fs::remove_file(path)?;
+    }
+    Ok(dest)
+}
+
+/// Move a download's files from the staging dir into the channel's output directory
+fn move_to_output(downloaded: Downloaded, chan: &Channel, cfg: &Config) -> Result<Downloaded> {
+    let staging = working_dir(chan, cfg);
+    let (dir, _) = output_location(chan, cfg);
+    let path = match &downloaded.path {
+        Some(p) => Some(move_into(p, &staging, &dir)?),
+        None => {
+            warn!(
+                "Unknown output filename, leaving download in {:?}",
+                &staging
+            );
+            None
+        }
+    };
+    let subtitles = downloaded
+        .subtitles
+        .into_iter()
+        .map(|s| {
+            let moved = move_into(std::path::Path::new(&s.path), &staging, &dir)?;
+            Ok(SubtitleFile {
+                lang: s.lang,
+                path: moved.to_string_lossy().into(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Downloaded { path, subtitles })
+}
+
+/// Fill in a yt-dlp style output template like `%(title)s__%(id)s.%(ext)s` with values
+/// made safe for filenames. `%(ext)s`, and any fields vidl doesn't know, are left for the
+/// downloader, so `%` in values is escaped as `%%`
+fn output_filename(
+    template: &str,
+    vid: &VideoInfo,
+    chan: &Channel,
+    style: FilenameStyle,
+) -> String {
+    let value = |v: &str| style.sanitize(v).replace('%', "%%");
+    let title_alt = vid.title_alt.as_deref().unwrap_or(&vid.title);
+    template
+        .replace("%(uploader)s", &value(&chan.title))
+        .replace("%(channel)s", &value(&chan.title))
+        .replace(
+            "%(upload_date)s",
+            &vid.published_at.format("%Y%m%d").to_string(),
+        )
+        .replace("%(title)s", &value(&vid.title))
+        .replace("%(title_alt)s", &value(title_alt))
+        .replace("%(id)s", &value(&vid.id))
+}
+
+/// If `name` is an unfinished download, or a format which is yet to be merged like `title.f137.mp4`
+fn is_partial_file(name: &str) -> bool {
+    lazy_static! {
+        static ref FORMAT_FILE: regex::Regex = regex::Regex::new(r"\.f\d+\.[^.]+$").unwrap();
+    }
+    [".part", ".ytdl", ".temp"]
+        .iter()
+        .any(|s| name.ends_with(s))
+        || name.contains(".part-Frag")
+        || FORMAT_FILE.is_match(name)
+}
+
+/// Filename (with `%(ext)s` still to be filled in) for video in `dir`. If a finished file
+/// with the same name is already there, which could be another video's as the name doesn't
+/// include the ID, the video ID is appended
+fn unique_filename(dir: &std::path::Path, filename: &str, vid: &VideoInfo, cfg: &Config) -> String {
+    let Some(stem) = filename.strip_suffix(".%(ext)s") else {
+        return filename.into();
+    };
+    let path = dir.join(stem);
+    let (Some(parent), Some(base)) = (path.parent(), path.file_name()) else {
+        return filename.into();
+    };
+    let prefix = format!("{}.", base.to_string_lossy());
+    let id_suffix = format!("__{}", cfg.filename_style.sanitize(&vid.id));
+    if stem.ends_with(&id_suffix) {
+        // Already unique
+        return filename.into();
+    }
+
+    let collides = std::fs::read_dir(parent)
+        .map(|entries| {
+            entries.filter_map(|e| e.ok()).any(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                name.starts_with(&prefix) && !is_partial_file(&name)
+            })
+        })
+        .unwrap_or(false);
+    if collides {
+        info!(
+            "{:?} already exists in {:?}, adding video ID to filename",
+            stem, parent
+        );
+        format!("{}{}.%(ext)s", stem, id_suffix)
+    } else {
+        filename.into()
+    }
+}
+
+/// Path of video's file in `dir`, relative to the channel's output directory, with `%(ext)s`
+/// left for the downloader
+fn output_path(vid: &VideoInfo, chan: &Channel, cfg: &Config) -> String {
+    let (out_dir, filename_format) = output_location(chan, cfg);
+    let filename = output_filename(filename_format, vid, chan, cfg.filename_style);
+    // Filenames containing the ID can only clash with the same video
+    if filename_format.contains("%(id)s") {
+        filename
+    } else {
+        unique_filename(&out_dir, &filename, vid, cfg)
+    }
+}
+
+/// Download `url` to `path`, via a `.part` file which is resumed if it already exists.
+/// `on_progress` is called with the bytes downloaded so far, and the total if known, and can
+/// stop the download by returning an error
+fn fetch_resumable(
+    url: &str,
+    path: &std::path::Path,
+    mut on_progress: impl FnMut(u64, Option<u64>) -> Result<()>,
+) -> Result<()> {
+    use std::io::{Read, Write};
+
+    let part = path.with_extension(format!(
+        "{}.part",
+        path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+    ));
+    let mut done = std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+
+    let mut req = attohttpc::get(url);
+    if done > 0 {
+        debug!("Resuming {:?} from byte {}", &part, done);
+        req = req.header(attohttpc::header::RANGE, format!("bytes={}-", done));
+    }
+    let resp = req.send()?;
+    let status = resp.status();
+    if status == attohttpc::StatusCode::RANGE_NOT_SATISFIABLE && done > 0 {
+        // Part file already complete
+        std::fs::rename(&part, path)?;
+        return Ok(());
+    }
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, status);
+    }
+    let resumed = status == attohttpc::StatusCode::PARTIAL_CONTENT;
+    if !resumed {
+        done = 0;
+    }
+    let total = resp
+        .headers()
+        .get(attohttpc::header::CONTENT_LENGTH)
+        .and_then(|l| l.to_str().ok())
+        .and_then(|l| l.parse::<u64>().ok())
+        .map(|l| l + done);
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part)
+        .with_context(|| format!("Failed to open {:?}", &part))?;
+    let (_, _, mut reader) = resp.split();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])?;
+        done += n as u64;
+        on_progress(done, total)?;
+    }
+    if let Some(total) = total {
+        if done < total {
+            anyhow::bail!(
+                "Download of {} ended after {} of {} bytes",
+                &url,
+                done,
+                total
+            );
+        }
+    }
+    drop(file);
+    std::fs::rename(&part, path)?;
+    Ok(())
+}
+
+/// Download a Youtube video's best single-file stream from Invidious, without an external downloader
+fn native_download(
+    vid: &VideoInfo,
+    chan: &Channel,
+    cfg: &Config,
+    mut on_destination: impl FnMut(&str),
+    on_progress: impl FnMut(u64, Option<u64>) -> Result<()>,
+) -> Result<Downloaded> {
+    let streams = crate::source::invidious::direct_streams(&vid.id)?;
+    let best = streams
+        .into_iter()
+        .filter(|s| s.height.unwrap_or(0) <= 1080)
+        .max_by_key(|s| s.height.unwrap_or(0))
+        .ok_or_else(|| anyhow::anyhow!("No direct streams available for {}", &vid.id))?;
+
+    let (out_dir, _) = output_location(chan, cfg);
+    let filename = output_path(vid, chan, cfg)
+        .replace("%(ext)s", &best.ext)
+        .replace("%%", "%");
+    let dir = working_dir(chan, cfg);
+    std::fs::create_dir_all(&dir).context("Failed to make output folder")?;
+    let mut path = dir.join(&filename);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to make output folder")?;
+    }
+    if out_dir.join(&filename).exists() {
+        path = out_dir.join(&filename);
+        info!("{:?} has already been downloaded", &path);
+    } else {
+        info!("Downloading {:?} to {:?}", &vid.url, &path);
+        on_destination(&path.to_string_lossy());
+        fetch_resumable(&best.url, &path, on_progress)?;
+    }
+    Ok(Downloaded {
+        path: Some(path),
+        subtitles: vec![],
+    })
+}
+
+/// If the video can be downloaded natively - the internal downloader only handles Youtube
+/// videos using the default format, without subtitles
+fn can_download_natively(vid: &VideoInfo, chan: &Channel, cfg: &Config) -> bool {
+    cfg.native_download
+        && matches!(chan.service, Service::Youtube | Service::YoutubePlaylist)
+        && chan.format.is_none()
+        && subtitle_langs(vid, chan, cfg).is_empty()
+}
+
+/// Download video using the channel's format (or `default_format`, the `format` setting) and
+/// subtitle settings, reporting progress, files being written to (which are resumed from their
+/// `.part` file if interrupted) and messages from the downloader as `events`
+pub fn download(
+    vid: &VideoInfo,
+    chan: &Channel,
+    default_format: Option<&str>,
+    events: &Sender<DownloadEvent>,
+) -> Result<Downloaded> {
+    // The receiver may have stopped listening, which shouldn't stop the download
+    let send = |e: DownloadEvent| {
+        let _ = events.send(e);
+    };
     let cfg = Config::load();
+    let mut progress = DownloadProgress::default();
+    let _running = RunningGuard::new(&vid.id);
+
+    // Files only appear in the output directory once complete
+    let finish = |downloaded: Downloaded| {
+        if cfg.staging_dir.is_some() {
+            move_to_output(downloaded, chan, &cfg)
+        } else {
+            Ok(downloaded)
+        }
+    };
+
+    if can_download_natively(vid, chan, &cfg) {
+        let result = native_download(
+            vid,
+            chan,
+            &cfg,
+            |path| send(DownloadEvent::Destination(path.into())),
+            |done, total| {
+                if is_cancelled(&vid.id) {
+                    return Err(Cancelled(vid.id.clone()).into());
+                }
+                if let Some(total) = total.filter(|t| *t > 0) {
+                    progress.percent = (done as f64 / total as f64 * 100.0) as f32;
+                    send(DownloadEvent::Progress(progress.clone()));
+                }
+                Ok(())
+            },
+        );
+        match result {
+            Ok(downloaded) => return finish(downloaded),
+            Err(e) if e.is::<Cancelled>() => return Err(e),
+            Err(e) => send(DownloadEvent::Warning(format!(
+                "Native download of {:?} failed, falling back to {:?} - {:?}",
+                &vid.url, &cfg.downloader, e
+            ))),
+        }
+    }
+
+    let mut downloaded = Downloaded::default();
+    let mut last_error: Option<String> = None;
+    let result = run_download(
+        vid,
+        chan,
+        default_format,
+        false,
+        |line| {
+            if let Some(s) = parse_subtitle_file(line) {
+                downloaded.subtitles.push(s.clone());
+                send(DownloadEvent::Subtitle(s));
+            } else if let Some(p) = parse_media_path(line) {
+                // Subtitles are also reported as download destinations
+                if !downloaded.subtitles.iter().any(|s| s.path == p) {
+                    downloaded.path = Some(p.clone().into());
+                    send(DownloadEvent::Destination(p));
+                }
+            } else if parse_progress(line, &mut progress) {
+                send(DownloadEvent::Progress(progress.clone()));
+            } else {
+                send(DownloadEvent::Output(line.into()));
+            }
+        },
+        |line| {
+            let event = parse_stderr(line);
+            if let DownloadEvent::Error(msg) = &event {
+                last_error = Some(msg.clone());
+            }
+            send(event)
+        },
+    );
+
+    if is_cancelled(&vid.id) {
+        return Err(Cancelled(vid.id.clone()).into());
+    }
+
+    // Classify the failure from the downloader's last error message
+    result.map_err(|e| match e.downcast::<DownloadError>() {
+        Ok(mut de) => {
+            if let Some(msg) = last_error {
+                de.kind = DownloadErrorKind::classify(&msg);
+                de.message = msg;
+            }
+            de.into()
+        }
+        Err(e) => e,
+    })?;
+    finish(downloaded)
+}
+
+/// Classify a line the downloader wrote to stderr
+fn parse_stderr(line: &str) -> DownloadEvent {
+    if let Some(msg) = line.strip_prefix("ERROR:") {
+        DownloadEvent::Error(msg.trim().into())
+    } else if let Some(msg) = line.strip_prefix("WARNING:") {
+        DownloadEvent::Warning(msg.trim().into())
+    } else {
+        DownloadEvent::Output(line.into())
+    }
+}
+
+/// Work out where video would be downloaded to, by running the downloader with `--simulate`
+/// so nothing is written
+pub fn simulate(
+    vid: &VideoInfo,
+    chan: &Channel,
+    default_format: Option<&str>,
+) -> Result<Option<std::path::PathBuf>> {
+    let cfg = Config::load();
+    let mut filename = None;
+    run_download(
+        vid,
+        chan,
+        default_format,
+        true,
+        |line| {
+            // `--get-filename` prints the path on its own
+            if !line.starts_with('[') && !line.trim().is_empty() {
+                filename = Some(std::path::PathBuf::from(line.trim()));
+            }
+        },
+        |line| {
+            if let DownloadEvent::Error(msg) = parse_stderr(line) {
+                warn!("Simulating download of {:?}: {}", &vid.title, msg);
+            }
+        },
+    )?;
+    // Report the final location, rather than where it would be staged
+    let staging = working_dir(chan, &cfg);
+    let (dir, _) = output_location(chan, &cfg);
+    Ok(filename.map(|f| match f.strip_prefix(&staging) {
+        Ok(relative) => dir.join(relative),
+        Err(_) => f,
+    }))
+}
+
+/// Run the downloader, calling `on_line` for each line of output and `on_err_line` for each
+/// line of errors. `default_format` is used if the channel has no format set. If `simulate`
+/// is set, nothing is downloaded and the filename is printed
+fn run_download(
+    vid: &VideoInfo,
+    chan: &Channel,
+    default_format: Option<&str>,
+    simulate: bool,
+    mut on_line: impl FnMut(&str),
+    mut on_err_line: impl FnMut(&str),
+) -> Result<()> {
+    let cfg = Config::load();
+    let dl = Downloader::from_config(&cfg);
 
     // Ensure output folder exists
-    std::fs::create_dir_all(&cfg.download_dir).context("Failed to make output folder")?;
+    let dir = working_dir(chan, &cfg);
+    if !simulate {
+        std::fs::create_dir_all(&dir).context("Failed to make output folder")?;
+    }
 
-    let output_template = &cfg.download_dir.join(cfg.filename_format);
+    let output_template = &dir.join(output_path(vid, chan, &cfg));
 
     // Prepare command arguments
     let mut args: Vec<&str> = vec![];
@@ -24,54 +860,390 @@ pub fn download(vid: &VideoInfo) -> Result<()> {
     args.push(output_template.to_str().unwrap());
 
     // Then options from config
-    args.extend(
-        cfg.extra_youtubedl_args
-            .iter()
-            .map(|x: &String| -> &str { x.as_ref() }),
-    );
+    let extra_args = if chan.service.is_audio_only() {
+        &cfg.audio_youtubedl_args
+    } else {
+        &cfg.extra_youtubedl_args
+    };
+    args.extend(extra_args.iter().map(|x: &String| -> &str { x.as_ref() }));
+    // Resume from `.part` files left by an interrupted download
+    if !args.contains(&"--continue") {
+        args.push("--continue");
+    }
+    // Speed up downloads of large (e.g 4K) videos split into many fragments
+    let concurrent_fragments = cfg.concurrent_fragments.map(|n| n.to_string());
+    if let Some(n) = &concurrent_fragments {
+        args.push("--concurrent-fragments");
+        args.push(n);
+    }
+    if let Some(size) = &cfg.http_chunk_size {
+        args.push("--http-chunk-size");
+        args.push(size);
+    }
+    if let Some(archive) = &cfg.download_archive {
+        args.push("--download-archive");
+        args.push(archive.to_str().unwrap());
+    }
+    // The `format` setting isn't used for audio, as it is for video formats
+    let default_format = default_format.filter(|_| !chan.service.is_audio_only());
+    if let Some(format) = chan.format.as_deref().or(default_format) {
+        set_format(&mut args, format);
+    }
+
+    let sub_langs = subtitle_langs(vid, chan, &cfg);
+    if !sub_langs.is_empty() {
+        args.push("--write-subs");
+        args.push("--sub-langs");
+        args.push(&sub_langs);
+    }
+
+    if simulate {
+        args.push("--simulate");
+        args.push("--get-filename");
+    }
 
     // Final arg is video URL
     args.push(&vid.url);
 
-    debug!("Running youtube-dl with args {:#?}", args);
+    let args = dl.adapt_args(args);
+    debug!("Running {} with args {:#?}", &dl.binary, args);
 
-    let mut child = Command::new("yt-dlp")
+    let mut child = Command::new(&dl.binary)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .args(args)
-        .spawn()?;
+        .spawn()
+        .with_context(|| format!("Failed to run {}", &dl.binary))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    // Made available to `cancel`, which may already have been called
+    let child = Arc::new(Mutex::new(child));
+    if let Some(running) = RUNNING.lock().unwrap().get_mut(&vid.id) {
+        running.child = Some(child.clone());
+        if running.cancelled {
+            child.lock().unwrap().kill()?;
+        }
+    }
 
     {
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or(anyhow::anyhow!("Failed to find thing"))?;
+        let stdout = stdout.ok_or(anyhow::anyhow!("Failed to find thing"))?;
 
         let reader = BufReader::new(stdout);
 
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or(anyhow::anyhow!("Failed to find thing"))?;
+        let stderr = stderr.ok_or(anyhow::anyhow!("Failed to find thing"))?;
         let reader_err = BufReader::new(stderr);
 
         reader
             .lines()
             .filter_map(|line| line.ok())
-            .for_each(|line| println!("{}", line));
+            .for_each(|line| on_line(&line));
 
         reader_err
             .lines()
             .filter_map(|line| line.ok())
-            .for_each(|line| println!("ERR: {}", line));
+            .for_each(|line| on_err_line(&line));
     }
-    let exit = child.wait()?;
+    // Output has ended so the downloader is exiting, and won't need killed while waiting
+    let exit = child.lock().unwrap().wait()?;
     if !exit.success() {
-        return Err(anyhow::anyhow!(
-            "youtube-dl exited with non-zero exit status {}",
-            exit
-        ));
+        return Err(DownloadError {
+            binary: dl.binary.clone(),
+            kind: DownloadErrorKind::Other,
+            message: format!("exited with non-zero exit status {}", exit),
+        }
+        .into());
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancel_all() {
+        let _a = RunningGuard::new("test_cancel_all_a");
+        let _b = RunningGuard::new("test_cancel_all_b");
+        assert!(!is_cancelled("test_cancel_all_a"));
+        // Other tests may be running downloads too
+        assert!(cancel_all() >= 2);
+        assert!(is_cancelled("test_cancel_all_a"));
+        assert!(is_cancelled("test_cancel_all_b"));
+        assert!(!cancel("test_cancel_all_c"));
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        let mut p = DownloadProgress::default();
+        assert!(parse_progress(
+            "[download]  45.3% of 123.45MiB at  2.34MiB/s ETA 00:31",
+            &mut p
+        ));
+        assert_eq!(p.percent, 45.3);
+        assert_eq!(p.speed.as_deref(), Some("2.34MiB/s"));
+        assert_eq!(p.eta.as_deref(), Some("00:31"));
+
+        assert!(parse_progress(
+            "[download]   0.0% of ~  1.00GiB at Unknown B/s ETA Unknown",
+            &mut p
+        ));
+        assert_eq!(p.percent, 0.0);
+        assert_eq!(p.speed, None);
+        assert_eq!(p.eta, None);
+
+        assert!(parse_progress(
+            "[download] 100% of 123.45MiB in 00:52",
+            &mut p
+        ));
+        assert_eq!(p.percent, 100.0);
+
+        assert!(!parse_progress(
+            "[download] Destination: some_video.mp4",
+            &mut p
+        ));
+        assert!(!parse_progress(
+            "[youtube] abc: Downloading webpage",
+            &mut p
+        ));
+    }
+
+    #[test]
+    fn test_parse_subtitle_file() {
+        assert_eq!(
+            parse_subtitle_file(
+                "[info] Writing video subtitles to: download/chan__20200101_title__abc.en-GB.vtt"
+            ),
+            Some(SubtitleFile {
+                lang: "en-GB".into(),
+                path: "download/chan__20200101_title__abc.en-GB.vtt".into(),
+            })
+        );
+        assert_eq!(
+            parse_subtitle_file("[download] Destination: some_video.mp4"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_media_path() {
+        assert_eq!(
+            parse_media_path("[download] Destination: download/a__abc.f137.mp4").as_deref(),
+            Some("download/a__abc.f137.mp4")
+        );
+        assert_eq!(
+            parse_media_path(r#"[Merger] Merging formats into "download/a__abc.mkv""#).as_deref(),
+            Some("download/a__abc.mkv")
+        );
+        assert_eq!(
+            parse_media_path("[download] download/a__abc.mkv has already been downloaded")
+                .as_deref(),
+            Some("download/a__abc.mkv")
+        );
+        assert_eq!(
+            parse_media_path("[download]  45.3% of 123.45MiB at  2.34MiB/s ETA 00:31"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_output_filename() -> Result<()> {
+        use chrono::TimeZone;
+
+        let mdb = crate::db::Database::create_in_memory(true)?;
+        let chan = Channel::create(
+            &mdb,
+            &crate::common::ChannelID::Youtube(crate::common::YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "Test Channel!",
+            "",
+        )?;
+        let vid = VideoInfo {
+            id: "abc-123".into(),
+            url: "https://www.youtube.com/watch?v=abc-123".into(),
+            title: "A video: part 1/2".into(),
+            title_alt: None,
+            description: "".into(),
+            description_alt: None,
+            thumbnail_url: "".into(),
+            thumbnails: vec![],
+            published_at: chrono::Utc.with_ymd_and_hms(2020, 1, 31, 0, 0, 0).unwrap(),
+            duration: 0,
+            live_status: crate::common::LiveStatus::NotLive,
+            view_count: None,
+            captions: vec![],
+            scheduled_at: None,
+            members_only: false,
+        };
+        assert_eq!(
+            output_filename(
+                "%(uploader)s__%(upload_date)s_%(title)s__%(id)s.%(ext)s",
+                &vid,
+                &chan,
+                FilenameStyle::Restrict
+            ),
+            "Test_Channel__20200131_A_video_part_1_2__abc-123.%(ext)s"
+        );
+        let vid = VideoInfo {
+            title: "100% real".into(),
+            title_alt: Some("Alternative".into()),
+            ..vid
+        };
+        assert_eq!(
+            output_filename(
+                "%(channel)s/%(title)s - %(title_alt)s [%(resolution)s].%(ext)s",
+                &vid,
+                &chan,
+                FilenameStyle::Unicode
+            ),
+            "Test Channel!/100%% real - Alternative [%(resolution)s].%(ext)s"
+        );
+
+        // Clashing with a finished file from another video, but not an unfinished one
+        let cfg = Config::load();
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("100_real.f137.mp4.part"), "")?;
+        assert_eq!(
+            unique_filename(dir.path(), "100_real.%(ext)s", &vid, &cfg),
+            "100_real.%(ext)s"
+        );
+        std::fs::write(dir.path().join("100_real.mkv"), "")?;
+        assert_eq!(
+            unique_filename(dir.path(), "100_real.%(ext)s", &vid, &cfg),
+            "100_real__abc-123.%(ext)s"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_resumable() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("video.mp4");
+
+        let _m = mockito::mock("GET", "/full")
+            .with_body("0123456789")
+            .create();
+        let mut seen = vec![];
+        fetch_resumable(&format!("{}/full", mockito::server_url()), &path, |d, t| {
+            seen.push((d, t));
+            Ok(())
+        })?;
+        assert_eq!(std::fs::read_to_string(&path)?, "0123456789");
+        assert_eq!(seen.last(), Some(&(10, Some(10))));
+
+        // Resume from partially downloaded file
+        let path = dir.path().join("resumed.mp4");
+        std::fs::write(dir.path().join("resumed.mp4.part"), "0123")?;
+        let _m = mockito::mock("GET", "/resume")
+            .match_header("range", "bytes=4-")
+            .with_status(206)
+            .with_body("456789")
+            .create();
+        fetch_resumable(
+            &format!("{}/resume", mockito::server_url()),
+            &path,
+            |_, _| Ok(()),
+        )?;
+        assert_eq!(std::fs::read_to_string(&path)?, "0123456789");
+        assert!(!dir.path().join("resumed.mp4.part").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_into() -> Result<()> {
+        let staging = tempfile::tempdir()?;
+        let out = tempfile::tempdir()?;
+        let nested = staging.path().join("Uploader").join("video.mkv");
+        std::fs::create_dir_all(nested.parent().unwrap())?;
+        std::fs::write(&nested, "video")?;
+
+        let moved = move_into(&nested, staging.path(), out.path())?;
+        assert_eq!(moved, out.path().join("Uploader").join("video.mkv"));
+        assert_eq!(std::fs::read_to_string(&moved)?, "video");
+        assert!(!nested.exists());
+
+        // Files outside the staging dir are left where they are
+        assert_eq!(move_into(&moved, staging.path(), out.path())?, moved);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        assert!(duration_matches(600.0, 600));
+        assert!(duration_matches(590.0, 600));
+        assert!(!duration_matches(300.0, 600));
+        assert!(duration_matches(20.0, 24));
+        assert!(!duration_matches(0.0, 24));
+
+        let cfg = Config::load();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("video.mp4");
+        assert!(verify(&path, 0, &cfg).is_err());
+        std::fs::write(&path, "")?;
+        assert!(verify(&path, 0, &cfg).is_err());
+        std::fs::write(&path, "data")?;
+        assert!(verify(&path, 0, &cfg).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_stderr() {
+        assert_eq!(
+            parse_stderr("ERROR: [youtube] abc: Video unavailable"),
+            DownloadEvent::Error("[youtube] abc: Video unavailable".into())
+        );
+        assert_eq!(
+            parse_stderr("WARNING: [youtube] Falling back to generic n function search"),
+            DownloadEvent::Warning("[youtube] Falling back to generic n function search".into())
+        );
+        assert_eq!(
+            parse_stderr("Deprecated Feature: something"),
+            DownloadEvent::Output("Deprecated Feature: something".into())
+        );
+    }
+
+    #[test]
+    fn test_set_format() {
+        let mut args = vec!["--continue", "-f", "best", "--newline"];
+        set_format(&mut args, "worst");
+        assert_eq!(args, vec!["--continue", "-f", "worst", "--newline"]);
+
+        let mut args = vec!["--continue"];
+        set_format(&mut args, "worst");
+        assert_eq!(args, vec!["--continue", "-f", "worst"]);
+    }
+
+    #[test]
+    fn test_downloader_args() {
+        let ytdlp = Downloader::new("/usr/local/bin/yt-dlp".into());
+        assert_eq!(ytdlp.kind, DownloaderKind::YtDlp);
+
+        let ytdl = Downloader::new("/usr/bin/youtube-dl".into());
+        assert_eq!(ytdl.kind, DownloaderKind::YoutubeDl);
+
+        let args = vec![
+            "--sponsorblock-mark=sponsor",
+            "--concurrent-fragments",
+            "4",
+            "--http-chunk-size",
+            "10M",
+            "--write-subs",
+            "--sub-langs",
+            "en",
+        ];
+        assert_eq!(ytdlp.adapt_args(args.clone()), args);
+        assert_eq!(
+            ytdl.adapt_args(args),
+            vec![
+                "--http-chunk-size",
+                "10M",
+                "--write-sub",
+                "--sub-lang",
+                "en"
+            ]
+        );
+    }
+}
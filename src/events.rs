@@ -0,0 +1,72 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::common::VideoStatus;
+use crate::download::DownloadProgress;
+
+lazy_static! {
+    /// Receivers of events, e.g open `/events` streams in the web interface
+    static ref SUBSCRIBERS: Mutex<Vec<Sender<Event>>> = Mutex::new(vec![]);
+}
+
+/// Something which changed, which the web interface can update to show
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Video with SQL ID `video` changed to `status`
+    VideoStatus { video: i64, status: VideoStatus },
+    Progress {
+        video: i64,
+        progress: DownloadProgress,
+    },
+    /// Finished updating channel with SQL ID `channel`, finding `added` new videos
+    ChannelUpdated { channel: i64, added: usize },
+}
+
+impl Event {
+    /// Name of the event, like `video_status`
+    pub fn name(&self) -> &str {
+        match self {
+            Event::VideoStatus { .. } => "video_status",
+            Event::Progress { .. } => "progress",
+            Event::ChannelUpdated { .. } => "channel_updated",
+        }
+    }
+}
+
+/// Send event to all subscribers. Does nothing if there are none (e.g when run from the CLI)
+pub fn publish(event: Event) {
+    // Subscribers which have gone away are dropped
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|s| s.send(event.clone()).is_ok());
+}
+
+/// Receive all events published from now on, until the receiver is dropped
+pub fn subscribe() -> Receiver<Event> {
+    let (send, recv) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(send);
+    recv
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_publish() {
+        let recv = subscribe();
+        let gone = subscribe();
+        drop(gone);
+
+        let event = Event::ChannelUpdated {
+            channel: 1,
+            added: 2,
+        };
+        publish(event.clone());
+        // Other tests may be publishing too
+        assert!(recv.try_iter().any(|e| e == event));
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt::Display;
+
+use lazy_static::lazy_static;
+
+use crate::config::Config;
+
+lazy_static! {
+    static ref LOCALE: Locale = Config::load().ui_language;
+}
+
+/// Language the web interface is shown in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    English,
+    German,
+}
+
+impl Locale {
+    /// From a language code like `de`, ignoring any region like `de-AT`
+    pub fn from_str(code: &str) -> anyhow::Result<Locale> {
+        let lang = code.split(['-', '_']).next().unwrap_or_default();
+        match lang.to_lowercase().as_str() {
+            "en" => Ok(Locale::English),
+            "de" => Ok(Locale::German),
+            _ => Err(anyhow::anyhow!("Unsupported interface language {:?}", code)),
+        }
+    }
+
+    /// Language code, as used by the `lang` attribute and for formatting dates in the browser
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::German => "de",
+        }
+    }
+
+    /// Translations from English, or `None` for English itself
+    fn table(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        match self {
+            Locale::English => None,
+            Locale::German => Some(GERMAN),
+        }
+    }
+}
+
+/// Configured language of the web interface
+pub fn locale() -> Locale {
+    *LOCALE
+}
+
+/// English `text` in the configured language, or unchanged if it hasn't been translated
+pub fn tr(text: &'static str) -> &'static str {
+    translate(locale(), text)
+}
+
+/// Like `tr`, replacing each `{}` in the translation with the next of `args`
+pub fn tr_args(text: &'static str, args: &[&dyn Display]) -> String {
+    fill(translate(locale(), text), args)
+}
+
+/// `tr_args` with a single argument, which is simpler to call from templates
+pub fn tr_with(text: &'static str, arg: impl Display) -> String {
+    tr_args(text, &[&arg])
+}
+
+fn translate(locale: Locale, text: &'static str) -> &'static str {
+    locale
+        .table()
+        .and_then(|t| t.iter().find(|(en, _)| *en == text))
+        .map(|(_, translated)| *translated)
+        .unwrap_or(text)
+}
+
+fn fill(text: &str, args: &[&dyn Display]) -> String {
+    let mut parts = text.split("{}");
+    let mut ret = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(a) = args.get(i) {
+            ret.push_str(&a.to_string());
+        }
+        ret.push_str(part);
+    }
+    ret
+}
+
+const GERMAN: &[(&str, &str)] = &[
+    // Navigation
+    ("Channels", "Kanäle"),
+    ("Add", "Hinzufügen"),
+    ("Update", "Aktualisieren"),
+    ("Queue", "Warteschlange"),
+    ("Search", "Suche"),
+    // Channel list
+    ("All channels", "Alle Kanäle"),
+    ("Last week", "Letzte Woche"),
+    ("All time", "Insgesamt"),
+    ("Backend", "Quelle"),
+    ("Format", "Format"),
+    ("Subtitles", "Untertitel"),
+    ("Status history", "Statusverlauf"),
+    ("Edit history", "Bearbeitungsverlauf"),
+    ("Title was", "Titel war"),
+    ("Description was", "Beschreibung war"),
+    ("the original", "das Original"),
+    ("Revert", "Zurücksetzen"),
+    ("Download failed:", "Download fehlgeschlagen:"),
+    ("Newest first", "Neueste zuerst"),
+    ("Recently added", "Zuletzt hinzugefügt"),
+    ("Longest first", "Längste zuerst"),
+    ("By title", "Nach Titel"),
+    ("Last seen in source", "Zuletzt in der Quelle gesehen"),
+    ("Removed from source", "Aus der Quelle entfernt"),
+    ("Output", "Ausgabe"),
+    ("Auto-ignore", "Automatisch ignorieren"),
+    ("default", "Standard"),
+    ("default directory", "Standardordner"),
+    ("default filename", "Standarddateiname"),
+    ("min secs", "min. Sek."),
+    ("max secs", "max. Sek."),
+    ("title regex", "Titel-Regex"),
+    // Queue
+    ("Youtube video URL", "Youtube-Video-URL"),
+    ("Download video", "Video herunterladen"),
+    ("Stop downloading", "Download abbrechen"),
+    ("Cancel", "Abbrechen"),
+    ("Download sooner", "Früher herunterladen"),
+    ("Download later", "Später herunterladen"),
+    ("Download next", "Als Nächstes herunterladen"),
+    ("Bump", "Vorziehen"),
+    ("Remove from queue", "Aus der Warteschlange entfernen"),
+    ("Remove", "Entfernen"),
+    (
+        "Nothing queued for download",
+        "Nichts zum Herunterladen vorgemerkt",
+    ),
+    ("Starting", "Startet"),
+    ("Queued", "Vorgemerkt"),
+    // Playlists
+    ("Playlists", "Playlists"),
+    ("{} videos", "{} Videos"),
+    ("Tracked", "Verfolgt"),
+    ("Track", "Verfolgen"),
+    // Video lists
+    ("First", "Erste"),
+    ("Prev page", "Vorherige Seite"),
+    ("Next page", "Nächste Seite"),
+    ("Last", "Letzte"),
+    ("Page {} of {}, videos {}", "Seite {} von {}, Videos {}"),
+    ("{}-{} of {}", "{}-{} von {}"),
+    ("0 of {}", "0 von {}"),
+    (
+        "Title, description or channel",
+        "Titel, Beschreibung oder Kanal",
+    ),
+    ("Any status", "Jeder Status"),
+    ("New", "Neu"),
+    ("Grabbed", "Heruntergeladen"),
+    ("Failed", "Fehlgeschlagen"),
+    ("Ignored", "Ignoriert"),
+    ("Published from", "Veröffentlicht ab"),
+    ("until before", "bis vor"),
+    ("Watched or not", "Gesehen oder nicht"),
+    ("Watched", "Gesehen"),
+    ("Unwatched", "Ungesehen"),
+    ("Filter", "Filtern"),
+    ("{} subscribers", "{} Abonnenten"),
+    ("Feed", "Feed"),
+    ("RSS feed", "RSS-Feed"),
+    (
+        "Download all new videos in this channel?",
+        "Alle neuen Videos dieses Kanals herunterladen?",
+    ),
+    ("Download all new", "Alle neuen herunterladen"),
+    (
+        "Retry all {} failed videos in this channel?",
+        "Alle {} fehlgeschlagenen Videos dieses Kanals erneut versuchen?",
+    ),
+    ("Retry all failed", "Alle fehlgeschlagenen wiederholen"),
+    (
+        "Ignore new videos older than",
+        "Neue Videos ignorieren, älter als",
+    ),
+    ("Scheduled for", "Geplant für"),
+    // Before the channel a video is from
+    ("on", "von"),
+    ("{} views.", "{} Aufrufe."),
+    ("Original description:", "Ursprüngliche Beschreibung:"),
+    ("Chapters:", "Kapitel:"),
+    ("Notes:", "Notizen:"),
+    ("Info", "Info"),
+    ("Watch", "Ansehen"),
+    ("View", "Öffnen"),
+    ("Click to toggle", "Zum Umschalten klicken"),
+    ("Retry", "Wiederholen"),
+    ("Ignore", "Ignorieren"),
+    ("No more!", "Keine weiteren!"),
+    // Video page
+    (
+        "The downloaded file could not be found.",
+        "Die heruntergeladene Datei wurde nicht gefunden.",
+    ),
+    ("Not downloaded yet.", "Noch nicht heruntergeladen."),
+    ("View original", "Original öffnen"),
+    ("Mark as watched", "Als gesehen markieren"),
+    ("Mark as unwatched", "Als ungesehen markieren"),
+    ("Notes", "Notizen"),
+    ("Save notes", "Notizen speichern"),
+    ("downloaded {}", "heruntergeladen am {}"),
+    ("New tag", "Neues Schlagwort"),
+    ("Add tag", "Schlagwort hinzufügen"),
+    ("Remove tag", "Schlagwort entfernen"),
+    ("In collections:", "In Sammlungen:"),
+    ("Add to collection", "Zur Sammlung hinzufügen"),
+    // Collections
+    ("Collections", "Sammlungen"),
+    ("Collection name", "Name der Sammlung"),
+    ("Create collection", "Sammlung erstellen"),
+    (
+        "No collections yet. Videos can be added to them from their video page",
+        "Noch keine Sammlungen. Videos können auf ihrer Videoseite hinzugefügt werden",
+    ),
+    ("Rename", "Umbenennen"),
+    (
+        "Delete this collection? The videos in it are kept",
+        "Diese Sammlung löschen? Die Videos darin bleiben erhalten",
+    ),
+    ("Delete collection", "Sammlung löschen"),
+    ("Remove from collection", "Aus der Sammlung entfernen"),
+    ("Saved", "Gespeichert"),
+    ("Error saving", "Fehler beim Speichern"),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(translate(Locale::English, "Watched"), "Watched");
+        assert_eq!(translate(Locale::German, "Watched"), "Gesehen");
+        // Untranslated text is shown in English
+        assert_eq!(translate(Locale::German, "Something new"), "Something new");
+
+        assert_eq!(
+            fill(translate(Locale::German, "{}-{} of {}"), &[&1, &50, &"123"]),
+            "1-50 von 123"
+        );
+        assert_eq!(fill("{} and {}", &[&1]), "1 and ");
+
+        assert_eq!(Locale::from_str("de").unwrap(), Locale::German);
+        assert_eq!(Locale::from_str("de_AT").unwrap(), Locale::German);
+        assert_eq!(Locale::from_str("EN-gb").unwrap(), Locale::English);
+        assert!(Locale::from_str("xx").is_err());
+
+        // Each text is only translated once
+        for (i, (en, _)) in GERMAN.iter().enumerate() {
+            assert!(
+                !GERMAN[i + 1..].iter().any(|(other, _)| other == en),
+                "{:?} translated twice",
+                en
+            );
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use log::{debug, trace, warn};
+
+/// Number of most recently used images also kept in memory
+const HOT_IMAGES: usize = 200;
+
+/// Widths thumbnails are resized to, so only a few sizes of each are cached
+const WIDTHS: &[u32] = &[64, 128, 256, 512];
+
+lazy_static! {
+    pub(crate) static ref IMG_CACHE: Mutex<ImageCache> = {
+        let cfg = crate::config::Config::load();
+        Mutex::new(ImageCache::new(
+            cfg.thumbnail_cache_dir,
+            cfg.thumbnail_cache_size,
+        ))
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Image {
+    pub(crate) data: Vec<u8>,
+    pub(crate) content_type: String,
+}
+
+/// Thumbnails stored on disk up to a total size, removing the least recently used first
+pub(crate) struct ImageCache {
+    /// `None` to only keep images in memory
+    dir: Option<PathBuf>,
+    /// Total bytes of files in `dir`
+    max_size: u64,
+    /// Size of files in `dir`, once they have been counted
+    disk_size: Option<u64>,
+    hot: HashMap<String, Image>,
+    /// Keys of `hot`, least recently used first
+    hot_order: VecDeque<String>,
+}
+
+impl ImageCache {
+    pub(crate) fn new(dir: Option<PathBuf>, max_size: u64) -> Self {
+        ImageCache {
+            dir,
+            max_size,
+            disk_size: None,
+            hot: HashMap::new(),
+            hot_order: VecDeque::new(),
+        }
+    }
+
+    /// File image from `url` is stored in
+    fn path(&self, url: &str) -> Option<PathBuf> {
+        use std::hash::{Hash, Hasher};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        url.hash(&mut h);
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{:016x}.img", h.finish())))
+    }
+
+    pub(crate) fn contains(&self, url: &str) -> bool {
+        self.hot.contains_key(url) || self.path(url).is_some_and(|p| p.is_file())
+    }
+
+    pub(crate) fn get(&mut self, url: &str) -> Option<Image> {
+        if let Some(img) = self.hot.get(url).cloned() {
+            self.use_hot(url);
+            return Some(img);
+        }
+
+        let path = self.path(url)?;
+        match read_image(&path, url) {
+            Ok(Some(img)) => {
+                mark_used(&path);
+                self.add_hot(url, img.clone());
+                Some(img)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                debug!("Failed to read cached image {:?} - {:?}", &path, e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn add(&mut self, url: &str, img: Image) {
+        if let Some(path) = self.path(url) {
+            if let Err(e) = self.write(&path, url, &img) {
+                warn!("Failed to cache image {} - {:?}", url, e);
+            }
+        }
+        self.add_hot(url, img);
+    }
+
+    fn write(&mut self, path: &Path, url: &str, img: &Image) -> Result<()> {
+        let dir = path.parent().context("Cache path has no parent")?;
+        std::fs::create_dir_all(dir)?;
+        // Written to a temporary file then renamed, so a partial image is never served
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        writeln!(tmp, "{}", url)?;
+        writeln!(tmp, "{}", img.content_type)?;
+        tmp.write_all(&img.data)?;
+        let written = tmp.as_file().metadata()?.len();
+        tmp.persist(path)?;
+
+        let size = match self.disk_size {
+            Some(s) => s + written,
+            // Counted after writing, so includes the new file
+            None => dir_size(dir)?,
+        };
+        self.disk_size = Some(size);
+        if size > self.max_size {
+            self.prune()?;
+        }
+        Ok(())
+    }
+
+    /// Remove least recently used files, leaving some space so this isn't needed after every
+    /// new image
+    fn prune(&mut self) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let mut files = vec![];
+        for e in std::fs::read_dir(dir)? {
+            let e = e?;
+            if e.path().extension().is_some_and(|x| x == "img") {
+                let m = e.metadata()?;
+                files.push((m.modified()?, m.len(), e.path()));
+            }
+        }
+        files.sort();
+
+        let target = self.max_size / 10 * 9;
+        let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in files {
+            if size <= target {
+                break;
+            }
+            trace!("Removing least recently used image {:?}", &path);
+            std::fs::remove_file(&path)?;
+            size -= len;
+        }
+        self.disk_size = Some(size);
+        Ok(())
+    }
+
+    fn add_hot(&mut self, url: &str, img: Image) {
+        if self.hot.insert(url.into(), img).is_some() {
+            self.use_hot(url);
+            return;
+        }
+        self.hot_order.push_back(url.into());
+        while self.hot_order.len() > HOT_IMAGES {
+            if let Some(old) = self.hot_order.pop_front() {
+                self.hot.remove(&old);
+            }
+        }
+    }
+
+    /// Move to the most recently used end
+    fn use_hot(&mut self, url: &str) {
+        if let Some(pos) = self.hot_order.iter().position(|u| u == url) {
+            if let Some(u) = self.hot_order.remove(pos) {
+                self.hot_order.push_back(u);
+            }
+        }
+    }
+}
+
+/// Smallest standard width which is at least `requested`, or `None` (the original size) if
+/// larger than all of them
+pub(crate) fn thumbnail_width(requested: u32) -> Option<u32> {
+    WIDTHS.iter().copied().find(|w| *w >= requested)
+}
+
+/// Cache key of the image from `url` resized to `width` and/or re-encoded as WebP, or `None`
+/// if the original is used unchanged
+pub(crate) fn variant_key(url: &str, width: Option<u32>, webp: bool) -> Option<String> {
+    if width.is_none() && !webp {
+        return None;
+    }
+    Some(format!(
+        "{}#w={}{}",
+        url,
+        width.map(|w| w.to_string()).unwrap_or_default(),
+        if webp { "&webp" } else { "" }
+    ))
+}
+
+/// Resize image to `width` (without enlarging it) and/or re-encode it as WebP, with ffmpeg
+pub(crate) fn transcode(img: &Image, width: Option<u32>, webp: bool) -> Result<Image> {
+    let mut input = tempfile::NamedTempFile::new()?;
+    input.write_all(&img.data)?;
+
+    let mut cmd = std::process::Command::new("ffmpeg");
+    cmd.args(["-v", "error", "-i"]).arg(input.path());
+    if let Some(w) = width {
+        cmd.arg("-vf").arg(format!("scale=w='min({},iw)':h=-2", w));
+    }
+    cmd.args(["-frames:v", "1", "-f", "image2pipe", "-c:v"]);
+    let content_type = if webp {
+        cmd.args(["libwebp", "-quality", "80"]);
+        "image/webp"
+    } else {
+        cmd.args(["mjpeg", "-q:v", "3"]);
+        "image/jpeg"
+    };
+    let out = cmd.arg("pipe:1").output().context("Failed to run ffmpeg")?;
+    if !out.status.success() || out.stdout.is_empty() {
+        anyhow::bail!(
+            "ffmpeg exited with {} - {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(Image {
+        data: out.stdout,
+        content_type: content_type.into(),
+    })
+}
+
+/// Read image file, which starts with lines of the URL and content type. `None` if the file
+/// doesn't exist, or is for a different URL
+fn read_image(path: &Path, url: &str) -> Result<Option<Image>> {
+    let f = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = BufReader::new(f);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != url {
+        return Ok(None);
+    }
+    let mut content_type = String::new();
+    reader.read_line(&mut content_type)?;
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    Ok(Some(Image {
+        data,
+        content_type: content_type.trim_end().into(),
+    }))
+}
+
+/// Record file as just used, by its modification time
+fn mark_used(path: &Path) {
+    let touched = std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()));
+    if let Err(e) = touched {
+        debug!("Failed to mark {:?} as used - {:?}", path, e);
+    }
+}
+
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for e in std::fs::read_dir(dir)? {
+        size += e?.metadata()?.len();
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_image_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let img = |data: &str| Image {
+            data: data.as_bytes().to_vec(),
+            content_type: "image/jpeg".into(),
+        };
+        // Each file is 32 bytes, so only two fit
+        let size = 80;
+
+        let mut cache = ImageCache::new(Some(dir.path().into()), size);
+        assert_eq!(cache.get("http://x/a"), None);
+        cache.add("http://x/a", img("aaaaaaaaaa"));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache.add("http://x/b", img("bbbbbbbbbb"));
+        assert_eq!(cache.get("http://x/a"), Some(img("aaaaaaaaaa")));
+
+        // Kept after restarting, and reading marks it as used
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut cache = ImageCache::new(Some(dir.path().into()), size);
+        assert!(cache.contains("http://x/a"));
+        assert_eq!(cache.get("http://x/a"), Some(img("aaaaaaaaaa")));
+
+        // So the least recently used is removed when full
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache.add("http://x/c", img("cccccccccc"));
+        let cache = ImageCache::new(Some(dir.path().into()), size);
+        assert!(cache.contains("http://x/a"));
+        assert!(!cache.contains("http://x/b"));
+        assert!(cache.contains("http://x/c"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_variant_key() {
+        assert_eq!(thumbnail_width(32), Some(64));
+        assert_eq!(thumbnail_width(256), Some(256));
+        assert_eq!(thumbnail_width(1000), None);
+
+        assert_eq!(variant_key("http://x/a.jpg", None, false), None);
+        assert_eq!(
+            variant_key("http://x/a.jpg", Some(64), false).as_deref(),
+            Some("http://x/a.jpg#w=64")
+        );
+        assert_eq!(
+            variant_key("http://x/a.jpg", None, true).as_deref(),
+            Some("http://x/a.jpg#w=&webp")
+        );
+    }
+}
@@ -4,6 +4,7 @@ extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 
+mod archive;
 mod backup;
 mod cli;
 mod common;
@@ -11,7 +12,11 @@ mod config;
 mod db;
 mod db_migration;
 mod download;
+mod events;
+mod i18n;
+mod image_cache;
 mod libmig;
+mod nfo;
 mod source;
 mod web;
 mod worker;
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use askama::Template;
+use log::debug;
+
+use crate::db::{Channel, DBVideoInfo};
+
+/// Kodi/Jellyfin `.nfo` metadata for a downloaded video
+#[derive(Template)]
+#[template(path = "video.nfo", escape = "html")]
+struct NfoTemplate<'a> {
+    title: &'a str,
+    channel: &'a str,
+    description: &'a str,
+    /// Like `2020-01-31`
+    published: String,
+    year: String,
+    runtime_minutes: i32,
+    duration: i32,
+    service: &'a str,
+    id: &'a str,
+    /// URL of the largest thumbnail, or empty
+    thumb: &'a str,
+}
+
+fn render(video: &DBVideoInfo, chan: &Channel, thumb: &str) -> Result<String> {
+    let v = &video.info;
+    let t = NfoTemplate {
+        title: &v.title,
+        channel: &chan.title,
+        description: &v.description,
+        published: v.published_at.format("%Y-%m-%d").to_string(),
+        year: v.published_at.format("%Y").to_string(),
+        // Rounded up so short videos don't show as 0 minutes
+        runtime_minutes: (v.duration + 59) / 60,
+        duration: v.duration,
+        service: chan.service.as_str(),
+        id: &v.id,
+        thumb,
+    };
+    Ok(t.render()?)
+}
+
+/// Write `.nfo` file next to the downloaded `media_path`, so media servers can index it, with
+/// `thumb` as the URL of its artwork
+pub fn write_nfo(
+    video: &DBVideoInfo,
+    chan: &Channel,
+    media_path: &Path,
+    thumb: &str,
+) -> Result<PathBuf> {
+    let nfo_path = media_path.with_extension("nfo");
+    debug!("Writing {:?}", &nfo_path);
+    std::fs::write(&nfo_path, render(video, chan, thumb)?)
+        .with_context(|| format!("Failed to write {:?}", &nfo_path))?;
+    Ok(nfo_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{ChannelID, LiveStatus, YoutubeID};
+    use crate::db::Database;
+    use crate::source::base::VideoInfo;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_write_nfo() -> Result<()> {
+        let mdb = Database::create_in_memory(true)?;
+        let c = Channel::create(
+            &mdb,
+            &ChannelID::Youtube(YoutubeID {
+                id: "testchannel".into(),
+                handle: None,
+            }),
+            "Test & channel",
+            "http://example.com/thumbnail.jpg",
+        )?;
+        let v = c.add_video(
+            &mdb,
+            &VideoInfo {
+                id: "abc".into(),
+                url: "http://example.com/watch?v=abc".into(),
+                title: "A <great> video".into(),
+                title_alt: None,
+                description: "Description".into(),
+                description_alt: None,
+                thumbnail_url: "".into(),
+                thumbnails: vec![],
+                published_at: chrono::Utc.with_ymd_and_hms(2020, 1, 31, 12, 0, 0).unwrap(),
+                duration: 61,
+                live_status: LiveStatus::NotLive,
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            },
+        )?;
+
+        let dir = tempfile::tempdir()?;
+        let path = write_nfo(
+            &v,
+            &c,
+            &dir.path().join("chan__20200131_video__abc.mkv"),
+            "http://example.com/maxres.jpg",
+        )?;
+        assert_eq!(path, dir.path().join("chan__20200131_video__abc.nfo"));
+
+        let nfo = std::fs::read_to_string(path)?;
+        assert!(nfo.contains("<title>A &lt;great&gt; video</title>"));
+        assert!(nfo.contains("<showtitle>Test &amp; channel</showtitle>"));
+        assert!(nfo.contains("<aired>2020-01-31</aired>"));
+        assert!(nfo.contains("<runtime>2</runtime>"));
+        assert!(nfo.contains(r#"<uniqueid type="youtube" default="true">abc</uniqueid>"#));
+        assert!(nfo.contains("<thumb>http://example.com/maxres.jpg</thumb>"));
+        Ok(())
+    }
+}
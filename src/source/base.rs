@@ -1,11 +1,16 @@
 use anyhow::Result;
 
+use crate::common::LiveStatus;
+
 /// Important info about channel
 #[derive(Debug)]
 pub struct ChannelMetadata {
     pub title: String,
     pub thumbnail: String,
     pub description: String,
+    /// URL to wide banner image, if the channel has one
+    pub banner: Option<String>,
+    pub subscriber_count: Option<i64>,
 }
 
 /// Important info about a video
@@ -17,8 +22,102 @@ pub struct VideoInfo {
     pub description: String,
     pub description_alt: Option<String>,
     pub thumbnail_url: String,
+    /// Every size of the thumbnail the source lists, which may be none besides `thumbnail_url`.
+    /// Stored separately, so empty when loaded from the database (see `DBVideoInfo::thumbnails`)
+    pub thumbnails: Vec<Thumbnail>,
     pub published_at: chrono::DateTime<chrono::Utc>,
     pub duration: i32,
+    pub live_status: LiveStatus,
+    /// Number of views, if the source provides it
+    pub view_count: Option<i64>,
+    /// Language codes of available captions/subtitles (e.g `en`, `de`)
+    pub captions: Vec<String>,
+    /// When a premiere or stream is scheduled to start, if it is upcoming
+    pub scheduled_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Requires a channel membership or payment to watch, so can't be downloaded
+    pub members_only: bool,
+}
+
+/// Additional info about a single video which isn't included when listing a channel
+#[derive(Debug, Default)]
+pub struct VideoDetails {
+    /// Keywords, category etc
+    pub tags: Vec<String>,
+    /// Language codes of available captions
+    pub captions: Vec<String>,
+    /// Full description, where the channel listing only contains a truncated one
+    pub description: Option<String>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// A playlist belonging to a channel, which can be added as a channel of its own
+#[derive(Debug)]
+pub struct PlaylistInfo {
+    pub id: String,
+    pub title: String,
+    pub thumbnail: String,
+    pub video_count: Option<i64>,
+}
+
+/// One size of a video's thumbnail
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: i64,
+    pub height: i64,
+}
+
+/// Section of a video, as listed with timestamps in the description
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    /// Start time in seconds
+    pub start: i32,
+    pub title: String,
+}
+
+/// Parse timestamp like `1:23` or `1:02:03` into seconds
+fn parse_timestamp(ts: &str) -> Option<i32> {
+    let mut total = 0;
+    let mut parts = 0;
+    for part in ts.split(':') {
+        if part.is_empty() || (parts > 0 && part.len() > 2) {
+            return None;
+        }
+        total = total * 60 + part.parse::<i32>().ok()?;
+        parts += 1;
+    }
+    if parts < 2 {
+        return None;
+    }
+    Some(total)
+}
+
+/// Find chapters in a video description. Following Youtube's rules, these
+/// are lines starting with a timestamp, the first at `0:00`, with at least
+/// three chapters in ascending order - otherwise no chapters are returned
+pub fn parse_chapters(description: &str) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = vec![];
+    for line in description.lines() {
+        let line = line.trim();
+        let (ts, title) = match line.split_once(char::is_whitespace) {
+            Some(x) => x,
+            None => continue,
+        };
+        let ts = ts.trim_start_matches('(').trim_end_matches(')');
+        if let Some(start) = parse_timestamp(ts) {
+            let title = title.trim().trim_start_matches(['-', ':', '|']).trim();
+            chapters.push(Chapter {
+                start,
+                title: title.into(),
+            });
+        }
+    }
+
+    let ascending = chapters.windows(2).all(|w| w[0].start < w[1].start);
+    if chapters.len() < 3 || chapters[0].start != 0 || !ascending {
+        return vec![];
+    }
+    chapters
 }
 
 impl std::fmt::Debug for VideoInfo {
@@ -32,8 +131,9 @@ impl std::fmt::Debug for VideoInfo {
 }
 
 /// Source for info on a channel (collection of related videos - e.g a YouTube
-/// channel, Vimeo user, etc), and access to videos within.
-pub trait ChannelData {
+/// channel, Vimeo user, etc), and access to videos within. Shared between threads
+/// so requests can be made concurrently.
+pub trait ChannelData: Sync {
     /// Get basic info on channel like title, icon URL etc
     fn get_metadata(&self) -> Result<ChannelMetadata>;
 
@@ -41,4 +141,50 @@ pub trait ChannelData {
     /// should, ideally, lazily load videos from the source as the iterator will
     /// only be used until the most recently seen video
     fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i>;
+
+    /// Get tags, captions, full description etc for a single video. These usually require an
+    /// additional request per video, so are only retrieved for new videos.
+    /// Sources which don't support this return empty details.
+    fn video_details(&self, _video_id: &str) -> Result<VideoDetails> {
+        Ok(VideoDetails::default())
+    }
+
+    /// List the playlists created by the channel
+    fn playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        anyhow::bail!("Listing playlists is not supported for this channel or backend")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_chapters() {
+        let desc = "A video\n\n0:00 Intro\n1:23 - The middle bit\n1:02:03 Outro\n\nLink at 12:00 somewhere";
+        assert_eq!(
+            parse_chapters(desc),
+            vec![
+                Chapter {
+                    start: 0,
+                    title: "Intro".into()
+                },
+                Chapter {
+                    start: 83,
+                    title: "The middle bit".into()
+                },
+                Chapter {
+                    start: 3723,
+                    title: "Outro".into()
+                },
+            ]
+        );
+
+        // Not starting at zero
+        assert_eq!(parse_chapters("0:10 One\n1:00 Two\n2:00 Three"), vec![]);
+        // Too few
+        assert_eq!(parse_chapters("0:00 One\n1:00 Two"), vec![]);
+        // Out of order
+        assert_eq!(parse_chapters("0:00 One\n2:00 Two\n1:00 Three"), vec![]);
+    }
 }
@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use log::{debug, trace};
+
+use crate::common::{BitchuteID, LiveStatus};
+use crate::source::base::{ChannelMetadata, VideoInfo};
+use crate::source::{paged, RateLimit};
+
+fn api_prefix() -> String {
+    #[cfg(test)]
+    let prefix: String = mockito::server_url();
+
+    #[cfg(not(test))]
+    let prefix: String = "https://api.bitchute.com".into();
+
+    prefix
+}
+
+/// Number of videos requested per page
+const PAGE_SIZE: i64 = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct BCChannel {
+    channel_id: String,
+    channel_name: String,
+    description: Option<String>,
+    thumbnail_url: Option<String>,
+    /// Sometimes a number, sometimes a string
+    subscriber_count: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct BCVideo {
+    video_id: String,
+    video_name: String,
+    description: Option<String>,
+    thumbnail_url: Option<String>,
+    date_published: String,
+    /// Like `12:34` or `1:02:03`
+    duration: Option<String>,
+    view_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct BCVideoPage {
+    videos: Vec<BCVideo>,
+}
+
+/// The API takes POST'd JSON parameters rather than query strings
+fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+    path: &str,
+    body: serde_json::Value,
+) -> Result<T> {
+    let url = format!(
+        "{prefix}/api/beta/{path}",
+        prefix = api_prefix(),
+        path = path
+    );
+    debug!("Retrieving URL {} with {}", &url, &body);
+    let resp = attohttpc::post(&url)
+        .header(attohttpc::header::CONTENT_TYPE, "application/json")
+        .text(body.to_string())
+        .send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let text = resp.text()?;
+    trace!("Raw response: {}", &text);
+    let data: T = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    trace!("Raw deserialisation: {:?}", &data);
+    Ok(data)
+}
+
+/// Convert `H:MM:SS` or `MM:SS` to seconds
+fn parse_duration(d: &str) -> Option<i32> {
+    let mut total = 0;
+    for part in d.split(':') {
+        total = total * 60 + part.trim().parse::<i32>().ok()?;
+    }
+    Some(total)
+}
+
+/// Find channel ID from a URL like `https://www.bitchute.com/channel/abcDEF123/`, or the ID itself
+pub fn find_channel_id(url_or_id: &str) -> Result<BitchuteID> {
+    let id = match url_or_id.find("/channel/") {
+        Some(idx) => url_or_id[idx + "/channel/".len()..]
+            .split(['/', '?'])
+            .next()
+            .unwrap_or(""),
+        None => url_or_id.trim_matches('/'),
+    };
+    if id.is_empty() {
+        anyhow::bail!("Could not find BitChute channel ID in {:?}", url_or_id);
+    }
+    Ok(BitchuteID { id: id.into() })
+}
+
+/// Object to query videos on a BitChute channel
+#[derive(Debug)]
+pub struct BitchuteQuery<'a> {
+    chan_id: &'a BitchuteID,
+    rate_limit: RateLimit,
+}
+
+impl<'a> BitchuteQuery<'a> {
+    pub fn new(chan_id: &BitchuteID) -> BitchuteQuery<'_> {
+        BitchuteQuery {
+            chan_id,
+            rate_limit: RateLimit::default(),
+        }
+    }
+
+    fn get_page(&self, offset: i64) -> Result<Vec<VideoInfo>> {
+        self.rate_limit.wait();
+        let data: BCVideoPage = request_data(
+            "channel/videos",
+            serde_json::json!({
+                "channel_id": self.chan_id.id,
+                "offset": offset,
+                "limit": PAGE_SIZE,
+            }),
+        )?;
+
+        let mut ret: Vec<VideoInfo> = vec![];
+        for d in data.videos {
+            let published_at = chrono::DateTime::parse_from_rfc3339(&d.date_published)
+                .with_context(|| format!("Invalid date_published {:?}", &d.date_published))?
+                .with_timezone(&chrono::Utc);
+            ret.push(VideoInfo {
+                url: format!("https://www.bitchute.com/video/{}/", d.video_id),
+                id: d.video_id,
+                title: d.video_name,
+                title_alt: None,
+                description: d.description.unwrap_or_default(),
+                description_alt: None,
+                thumbnail_url: d.thumbnail_url.unwrap_or_default(),
+                thumbnails: vec![],
+                published_at,
+                duration: d.duration.as_deref().and_then(parse_duration).unwrap_or(0),
+                live_status: LiveStatus::NotLive,
+                view_count: d.view_count,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            });
+        }
+        Ok(ret)
+    }
+}
+
+impl<'a> crate::source::base::ChannelData for BitchuteQuery<'a> {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        self.rate_limit.wait();
+        let d: BCChannel = request_data(
+            "channel",
+            serde_json::json!({ "channel_id": self.chan_id.id }),
+        )?;
+
+        let subscriber_count = match d.subscriber_count {
+            Some(serde_json::Value::Number(n)) => n.as_i64(),
+            Some(serde_json::Value::String(s)) => s.parse().ok(),
+            _ => None,
+        };
+
+        Ok(ChannelMetadata {
+            title: d.channel_name,
+            thumbnail: d.thumbnail_url.unwrap_or_default(),
+            description: d.description.unwrap_or_default(),
+            banner: None,
+            subscriber_count,
+        })
+    }
+
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        // POST /api/beta/channel/videos {"channel_id": .., "offset": .., "limit": ..}
+
+        // Pages continue until one is empty
+        Box::new(paged(0, move |offset: i64| {
+            Ok((self.get_page(offset)?, Some(offset + PAGE_SIZE)))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::base::ChannelData;
+
+    #[test]
+    fn test_find_channel_id() -> Result<()> {
+        assert_eq!(
+            find_channel_id("https://www.bitchute.com/channel/abcDEF123/")?.id,
+            "abcDEF123"
+        );
+        assert_eq!(find_channel_id("abcDEF123")?.id, "abcDEF123");
+        assert!(find_channel_id("https://www.bitchute.com/channel/").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("12:34"), Some(754));
+        assert_eq!(parse_duration("1:02:03"), Some(3723));
+        assert_eq!(parse_duration("live"), None);
+    }
+
+    #[test]
+    fn test_bitchute_videos() -> Result<()> {
+        let _m1 = mockito::mock("POST", "/api/beta/channel/videos")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"offset": 0}),
+            ))
+            .with_body_from_file("testdata/bitchute_videos.json")
+            .create();
+        let _m2 = mockito::mock("POST", "/api/beta/channel/videos")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"offset": PAGE_SIZE}),
+            ))
+            .with_body(r#"{"videos": []}"#)
+            .create();
+
+        let cid = BitchuteID {
+            id: "abcDEF123".into(),
+        };
+        let bc = BitchuteQuery::new(&cid);
+        let result = bc.videos().collect::<Result<Vec<VideoInfo>>>()?;
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, "vid00000001");
+        assert_eq!(result[0].url, "https://www.bitchute.com/video/vid00000001/");
+        assert_eq!(result[0].duration, 754);
+        assert_eq!(result[0].view_count, Some(321));
+        assert_eq!(result[1].title, "An older video");
+        Ok(())
+    }
+}
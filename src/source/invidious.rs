@@ -1,14 +1,13 @@
-use std::collections::VecDeque;
-
 use anyhow::{Context, Result};
 use chrono::offset::TimeZone;
 
 use log::{debug, trace};
 
-use crate::common::{Service, YoutubeID};
-use crate::source::base::{ChannelMetadata, VideoInfo};
-
-use ratelimit_meter::{DirectRateLimiter, GCRA};
+use crate::common::{LiveStatus, YoutubeID, YoutubeName, YoutubePlaylistID};
+use crate::source::base::{
+    parse_chapters, ChannelMetadata, PlaylistInfo, Thumbnail, VideoDetails, VideoInfo,
+};
+use crate::source::{paged, RateLimit};
 
 fn api_prefix() -> String {
     #[cfg(test)]
@@ -38,6 +37,47 @@ struct YTVideoInfo {
     description: String,
     length_seconds: i32,
     published: i64,
+    view_count: Option<i64>,
+    /// Start time of scheduled premieres
+    premiere_timestamp: Option<i64>,
+    /// Members-only or paid video
+    #[serde(default)]
+    premium: bool,
+    #[serde(default)]
+    live_now: bool,
+    #[serde(default)]
+    is_upcoming: bool,
+}
+
+impl YTVideoInfo {
+    fn to_video_info(&self) -> VideoInfo {
+        VideoInfo {
+            id: self.video_id.clone(),
+            url: format!("http://youtube.com/watch?v={id}", id = self.video_id),
+            title: self.title.clone(),
+            title_alt: None,
+            description: self.description.clone(),
+            description_alt: None,
+            thumbnail_url: choose_best_thumbnail(&self.video_thumbnails).url.clone(),
+            thumbnails: self.video_thumbnails.iter().map(|t| t.into()).collect(),
+            published_at: chrono::Utc.timestamp(self.published, 0),
+            duration: self.length_seconds,
+            view_count: self.view_count,
+            captions: vec![],
+            scheduled_at: self
+                .premiere_timestamp
+                .filter(|_| self.is_upcoming)
+                .and_then(|ts| chrono::Utc.timestamp_opt(ts, 0).single()),
+            members_only: self.premium,
+            live_status: if self.live_now {
+                LiveStatus::Live
+            } else if self.is_upcoming {
+                LiveStatus::Upcoming
+            } else {
+                LiveStatus::NotLive
+            },
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,18 +97,225 @@ struct YTChannelInfo {
     description: String,
     author_thumbnails: Vec<YTThumbnailInfo>,
     author_banners: Vec<YTThumbnailInfo>,
+    sub_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTPlaylistPage {
+    playlists: Vec<YTPlaylist>,
+    continuation: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTPlaylist {
+    title: String,
+    playlist_id: String,
+    playlist_thumbnail: Option<String>,
+    video_count: Option<i64>,
+}
+
+/// Subset of the per-video info from `/api/v1/videos/:id`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTVideoDetails {
+    #[serde(default)]
+    keywords: Vec<String>,
+    genre: Option<String>,
+    #[serde(default)]
+    captions: Vec<YTCaption>,
+    description: Option<String>,
+}
+
+/// Formats from `/api/v1/videos/:id` containing both video and audio
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTVideoStreams {
+    #[serde(default)]
+    format_streams: Vec<YTFormatStream>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTFormatStream {
+    url: String,
+    container: Option<String>,
+    /// Like `720p`
+    resolution: Option<String>,
+}
+
+/// Directly downloadable media file for a video
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectStream {
+    pub url: String,
+    /// File extension, like `mp4`
+    pub ext: String,
+    pub height: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTCaption {
+    label: String,
+    language_code: String,
+}
+
+/// Cached API response, stored as JSON in the cache directory
+#[derive(Serialize, Deserialize, Debug)]
+struct CacheEntry {
+    key: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Unix timestamp of when the response was retrieved, or last confirmed unchanged
+    fetched_at: i64,
+    body: String,
+}
+
+/// Cache entries which haven't been used for this long are deleted
+const CACHE_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Cache directory and TTL, or `None` if caching is disabled
+fn http_cache(cfg: &crate::config::Config) -> Option<(std::path::PathBuf, u64)> {
+    if cfg!(test) || cfg.http_cache_ttl == 0 {
+        return None;
+    }
+
+    // Clear out old entries once per process
+    static PRUNE: std::sync::Once = std::sync::Once::new();
+    PRUNE.call_once(|| prune_cache(&cfg.http_cache_dir));
+
+    Some((cfg.http_cache_dir.clone(), cfg.http_cache_ttl))
+}
+
+fn prune_cache(dir: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for e in entries.flatten() {
+        let expired = e
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| m.elapsed().ok())
+            .map(|age| age > CACHE_MAX_AGE)
+            .unwrap_or(false);
+        if expired {
+            trace!("Removing old cache entry {:?}", e.path());
+            let _ = std::fs::remove_file(e.path());
+        }
+    }
+}
+
+fn cache_path(dir: &std::path::Path, key: &str) -> std::path::PathBuf {
+    use std::hash::{Hash, Hasher};
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut h);
+    dir.join(format!("{:016x}.json", h.finish()))
+}
+
+fn write_cache(path: &std::path::Path, entry: &CacheEntry) -> Result<()> {
+    let dir = path.parent().context("Cache path has no parent")?;
+    std::fs::create_dir_all(dir)?;
+    // Write to temporary file then rename, as other workers may be reading the same entry
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(&mut tmp, entry)?;
+    tmp.persist(path)?;
+    Ok(())
+}
+
+/// Send request, using a cached response if it is under `ttl` seconds old. Older cached
+/// responses are revalidated with `If-None-Match`/`If-Modified-Since`, so unchanged responses
+/// don't need downloaded again
+fn cached_send(
+    req: attohttpc::RequestBuilder,
+    key: &str,
+    dir: &std::path::Path,
+    ttl: u64,
+) -> Result<String> {
+    let path = cache_path(dir, key);
+    let cached: Option<CacheEntry> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .filter(|e: &CacheEntry| e.key == key);
+
+    let now = chrono::Utc::now().timestamp();
+    let mut req = req;
+    if let Some(c) = &cached {
+        if now - c.fetched_at < ttl as i64 {
+            debug!("Using cached response for {}", key);
+            return Ok(c.body.clone());
+        }
+        if let Some(etag) = &c.etag {
+            req = req.header(attohttpc::header::IF_NONE_MATCH, etag.as_str());
+        }
+        if let Some(lm) = &c.last_modified {
+            req = req.header(attohttpc::header::IF_MODIFIED_SINCE, lm.as_str());
+        }
+    }
+
+    let resp = req.send()?;
+    if resp.status() == attohttpc::StatusCode::NOT_MODIFIED {
+        if let Some(mut c) = cached {
+            debug!("Cached response still current for {}", key);
+            c.fetched_at = now;
+            write_cache(&path, &c)?;
+            return Ok(c.body);
+        }
+    }
+
+    let header = |name: attohttpc::header::HeaderName| {
+        resp.headers()
+            .get(name)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.to_string())
+    };
+    let etag = header(attohttpc::header::ETAG);
+    let last_modified = header(attohttpc::header::LAST_MODIFIED);
+    let success = resp.is_success();
+    let body = resp.text()?;
+
+    // Only cache successful responses
+    if success {
+        let entry = CacheEntry {
+            key: key.into(),
+            etag,
+            last_modified,
+            fetched_at: now,
+            body,
+        };
+        if let Err(e) = write_cache(&path, &entry) {
+            debug!("Failed to write cache entry for {} - {:?}", key, e);
+        }
+        return Ok(entry.body);
+    }
+    Ok(body)
 }
 
 fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(url: &str) -> Result<T> {
     fn subreq<T: serde::de::DeserializeOwned + std::fmt::Debug>(url: &str) -> Result<T> {
         debug!("Retrieving URL {}", &url);
-        let resp = attohttpc::get(&url)
-        .header(
+        let cfg = crate::config::Config::load();
+        let mut req = attohttpc::get(&url).header(
             attohttpc::header::USER_AGENT,
             "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:78.0) Gecko/20100101 Firefox/78.0",
-        )
-        .send()?;
-        let text = resp.text()?;
+        );
+        // Ask for titles etc in the configured locale, rather than the instance's default
+        if let Some(lang) = &cfg.language {
+            req = req
+                .header(attohttpc::header::ACCEPT_LANGUAGE, lang.as_str())
+                .param("hl", lang);
+        }
+        if let Some(region) = &cfg.region {
+            req = req.param("region", region);
+        }
+        let text = match http_cache(&cfg) {
+            Some((dir, ttl)) => {
+                let key = format!("{} {:?} {:?}", url, &cfg.language, &cfg.region);
+                cached_send(req, &key, &dir, ttl)?
+            }
+            None => req.send()?.text()?,
+        };
         trace!("Raw response: {}", &text);
         let data: T = serde_json::from_str(&text)
             .with_context(|| format!("Failed to parse response from {}", &url))?;
@@ -91,6 +338,93 @@ fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(url: &str) ->
     ret
 }
 
+/// Get keywords and category (genre) of a video as a list of tags, along with caption languages,
+/// the full description and chapters from it
+fn get_video_details(video_id: &str) -> Result<VideoDetails> {
+    let url = format!(
+        "{prefix}/api/v1/videos/{id}?fields=keywords,genre,captions,description",
+        prefix = api_prefix(),
+        id = video_id,
+    );
+    let d: YTVideoDetails = request_data(&url)?;
+
+    let mut tags = d.keywords;
+    if let Some(genre) = d.genre {
+        if !genre.is_empty() && !tags.contains(&genre) {
+            tags.push(genre);
+        }
+    }
+
+    let mut captions: Vec<String> = vec![];
+    for c in d.captions {
+        // Auto-generated captions have the same language code as a manual one may
+        if !captions.contains(&c.language_code) {
+            captions.push(c.language_code);
+        }
+    }
+
+    let description = d.description.filter(|x| !x.is_empty());
+    let chapters = description
+        .as_deref()
+        .map(parse_chapters)
+        .unwrap_or_default();
+
+    Ok(VideoDetails {
+        tags,
+        captions,
+        description,
+        chapters,
+    })
+}
+
+/// Get info on a single video, as listed for a channel
+pub fn get_video_info(video_id: &str) -> Result<VideoInfo> {
+    let url = format!(
+        "{prefix}/api/v1/videos/{id}?fields=title,videoId,videoThumbnails,description,lengthSeconds,published,viewCount,premiereTimestamp,premium,liveNow,isUpcoming",
+        prefix = api_prefix(),
+        id = video_id,
+    );
+    let d: YTVideoInfo = request_data(&url)?;
+    Ok(d.to_video_info())
+}
+
+/// Get single-file (combined audio and video) streams of a video, proxied through the
+/// Invidious instance as the original URLs only work from the instance's address
+pub fn direct_streams(video_id: &str) -> Result<Vec<DirectStream>> {
+    let url = format!(
+        "{prefix}/api/v1/videos/{id}?fields=formatStreams&local=true",
+        prefix = api_prefix(),
+        id = video_id,
+    );
+    let d: YTVideoStreams = request_data(&url)?;
+
+    Ok(d.format_streams
+        .into_iter()
+        .map(|s| DirectStream {
+            // Proxied URLs are relative to the instance
+            url: if s.url.starts_with('/') {
+                format!("{}{}", api_prefix(), s.url)
+            } else {
+                s.url
+            },
+            ext: s.container.unwrap_or_else(|| "mp4".into()),
+            height: s
+                .resolution
+                .and_then(|r| r.trim_end_matches('p').parse().ok()),
+        })
+        .collect())
+}
+
+impl From<&YTThumbnailInfo> for Thumbnail {
+    fn from(t: &YTThumbnailInfo) -> Self {
+        Thumbnail {
+            url: t.url.clone(),
+            width: t.width.into(),
+            height: t.height.into(),
+        }
+    }
+}
+
 /// Return the "default" quality thumbnail (falling back to the first)
 fn choose_best_thumbnail(thumbs: &Vec<YTThumbnailInfo>) -> &YTThumbnailInfo {
     for t in thumbs {
@@ -105,17 +439,14 @@ fn choose_best_thumbnail(thumbs: &Vec<YTThumbnailInfo>) -> &YTThumbnailInfo {
 #[derive(Debug)]
 pub struct YoutubeQuery<'a> {
     chan_id: &'a YoutubeID,
-    rate_limit: std::cell::RefCell<DirectRateLimiter<GCRA>>,
+    rate_limit: RateLimit,
 }
 
 impl<'a> YoutubeQuery<'a> {
     pub fn new(chan_id: &YoutubeID) -> YoutubeQuery {
         YoutubeQuery {
             chan_id,
-            rate_limit: std::cell::RefCell::new(DirectRateLimiter::<GCRA>::new(
-                std::num::NonZeroU32::new(10).unwrap(),
-                std::time::Duration::from_secs(60),
-            )),
+            rate_limit: RateLimit::default(),
         }
     }
 }
@@ -123,20 +454,12 @@ impl<'a> YoutubeQuery<'a> {
 impl<'a> crate::source::base::ChannelData for YoutubeQuery<'a> {
     fn get_metadata(&self) -> Result<ChannelMetadata> {
         let url = format!(
-            "{prefix}/api/v1/channels/{chanid}?fields=author,authorId,description,authorThumbnails,authorBanners",
+            "{prefix}/api/v1/channels/{chanid}?fields=author,authorId,description,authorThumbnails,authorBanners,subCount",
             prefix = api_prefix(),
             chanid = self.chan_id.id
         );
 
-        match self.rate_limit.borrow_mut().check() {
-            Ok(_) => {
-                // good
-            }
-            Err(_) => {
-                trace!("Waiting for rate limit");
-                std::thread::sleep(std::time::Duration::from_secs(1));
-            }
-        }
+        self.rate_limit.wait_for(std::time::Duration::from_secs(1));
         let d: YTChannelInfo = request_data(&url)?;
 
         let thumbnail = choose_best_thumbnail(&d.author_thumbnails).url.clone();
@@ -145,26 +468,22 @@ impl<'a> crate::source::base::ChannelData for YoutubeQuery<'a> {
             title: d.author.clone(),
             thumbnail: thumbnail,
             description: d.description.clone(),
+            // Banners are listed largest first
+            banner: d.author_banners.first().map(|b| b.url.clone()),
+            subscriber_count: d.sub_count,
         })
     }
 
     fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
         // GET /api/v1/channels/:ucid/videos?page=1
 
-        enum Token {
-            /// More pages to check
-            Value(String),
-            /// Nothing more
-            End,
-        }
-
         fn get_page(
             chanid: &str,
-            continuation: &Option<Token>,
+            continuation: Option<&str>,
         ) -> Result<(Vec<VideoInfo>, Option<String>)> {
             let ct_arg = match continuation {
-                Some(Token::Value(v)) => format!("?continuation={}", v),
-                Some(Token::End) | None => "".into(),
+                Some(v) => format!("?continuation={}", v),
+                None => "".into(),
             };
 
             let url = format!(
@@ -175,186 +494,325 @@ impl<'a> crate::source::base::ChannelData for YoutubeQuery<'a> {
             );
             let data: YtVideoPage = request_data(&url)?;
 
-            let ret: Vec<VideoInfo> = data
-                .videos
-                .iter()
-                .map(|d| VideoInfo {
-                    id: d.video_id.clone(),
-                    url: format!("http://youtube.com/watch?v={id}", id = d.video_id),
-                    title: d.title.clone(),
-                    title_alt: None,
-                    description: d.description.clone(),
-                    description_alt: None,
-                    thumbnail_url: choose_best_thumbnail(&d.video_thumbnails).url.clone(),
-                    published_at: chrono::Utc.timestamp(d.published, 0),
-                    duration: d.length_seconds,
-                })
-                .collect();
+            let ret: Vec<VideoInfo> = data.videos.iter().map(|d| d.to_video_info()).collect();
 
             Ok((ret, data.continuation))
         }
 
-        let mut cont_token: Option<Token> = None;
-        let mut completed = false;
-        let mut current_items: VecDeque<VideoInfo> = VecDeque::new();
+        // The first page has no continuation token
+        Box::new(paged(None, move |continuation: Option<String>| {
+            self.rate_limit.wait();
+            let (items, next) = get_page(&self.chan_id.id, continuation.as_deref())?;
+            Ok((items, next.map(Some)))
+        }))
+    }
 
-        let it = std::iter::from_fn(move || -> Option<Result<VideoInfo>> {
-            if completed {
-                return None;
-            }
-            if let Some(cur) = current_items.pop_front() {
-                // Iterate through previously stored items
-                Some(Ok(cur))
-            } else {
-                match self.rate_limit.borrow_mut().check() {
-                    Ok(_) => {}
-                    Err(_) => {
-                        debug!("Waiting for rate limit");
-                        std::thread::sleep(std::time::Duration::from_secs(10));
-                    }
-                }
-
-                if let Some(Token::End) = cont_token {
-                    // No more videos queued up,
-                    // and no token for next page - done
-                    completed = true;
-                    return None;
-                }
-
-                // If nothing is stored, get next page of videos
-                let data: Result<(Vec<VideoInfo>, Option<String>)> =
-                    get_page(&self.chan_id.id, &cont_token);
-
-                let nextup: Option<Result<VideoInfo>> = match data {
-                    Err(e) => {
-                        // Prevent future iteration
-                        completed = true;
-                        // Something went wrong, return an error item
-                        Some(Err(e))
-                    }
-                    Ok((new_items, ct)) => {
-                        match ct {
-                            None => {
-                                // No subsequent continuation, so future requests needed
-                                cont_token = Some(Token::End)
-                            }
-                            Some(ct) => {
-                                // Token in returned API response, so more pages to check later
-                                cont_token = Some(Token::Value(ct));
-                            }
-                        }
-                        if new_items.len() == 0 {
-                            // No more items, stop iterator
-                            None
-                        } else {
-                            current_items.extend(new_items);
-                            Some(Ok(current_items.pop_front().unwrap()))
-                        }
-                    }
-                };
-                nextup
-            }
-        });
-        Box::new(it)
-    }
-}
-
-/// Find channel ID (`UC..` string) based on either a user or channel name
-pub(crate) fn find_channel_id_workaround(id: &str) -> anyhow::Result<String> {
-    fn post_json(url: String, target_url: &str) -> anyhow::Result<serde_json::Value> {
-        let req = attohttpc::get(&url)
-            .header("Content-Type", "application/json; charset=UTF-8")
-            .header("Accept-Encoding", "gzip")
-            .param("url", &target_url)
-            .send();
-        let resp = req.unwrap();
-        if resp.is_success() {
-            let text = resp.text()?;
-            let parsed: serde_json::Value = serde_json::from_str(&text)?;
-            Ok(parsed)
-        } else {
-            anyhow::bail!("Error from {} - status {}", &url, resp.status())
+    fn video_details(&self, video_id: &str) -> Result<VideoDetails> {
+        self.rate_limit.wait_for(std::time::Duration::from_secs(1));
+        get_video_details(video_id)
+    }
+
+    fn playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        // GET /api/v1/channels/:ucid/playlists?continuation=..
+        // Stops on an empty page, or one giving the same continuation again
+        paged(None, |continuation: Option<String>| {
+            let ct_arg = match &continuation {
+                Some(ct) => format!("?continuation={}", ct),
+                None => "".into(),
+            };
+            let url = format!(
+                "{prefix}/api/v1/channels/{chanid}/playlists{continuation}",
+                prefix = api_prefix(),
+                chanid = self.chan_id.id,
+                continuation = ct_arg,
+            );
+            let data: YTPlaylistPage = request_data(&url)?;
+            let playlists = data.playlists.into_iter().map(|p| PlaylistInfo {
+                id: p.playlist_id,
+                title: p.title,
+                thumbnail: p.playlist_thumbnail.unwrap_or_default(),
+                video_count: p.video_count,
+            });
+            let next = data
+                .continuation
+                .filter(|ct| Some(ct) != continuation.as_ref());
+            Ok((playlists.collect(), next.map(Some)))
+        })
+        .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTPlaylistVideo {
+    title: String,
+    video_id: String,
+    video_thumbnails: Vec<YTThumbnailInfo>,
+    index: i64,
+    length_seconds: i32,
+    #[serde(default)]
+    live_now: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct YTPlaylistInfo {
+    title: String,
+    playlist_id: String,
+    description: String,
+    playlist_thumbnail: Option<String>,
+    author_thumbnails: Vec<YTThumbnailInfo>,
+    videos: Vec<YTPlaylistVideo>,
+}
+
+/// Object to query data about given playlist
+#[derive(Debug)]
+pub struct YoutubePlaylistQuery<'a> {
+    playlist_id: &'a YoutubePlaylistID,
+    rate_limit: RateLimit,
+}
+
+impl<'a> YoutubePlaylistQuery<'a> {
+    pub fn new(playlist_id: &YoutubePlaylistID) -> YoutubePlaylistQuery<'_> {
+        YoutubePlaylistQuery {
+            playlist_id,
+            rate_limit: RateLimit::default(),
         }
     }
 
-    if id.starts_with("UC") {
-        return Ok(id.into());
+    fn get_page(&self, page: i64) -> Result<YTPlaylistInfo> {
+        self.rate_limit.wait();
+
+        let url = format!(
+            "{prefix}/api/v1/playlists/{plid}?page={page}",
+            prefix = api_prefix(),
+            plid = self.playlist_id.id,
+            page = page,
+        );
+        request_data(&url)
     }
+}
 
-    // Look up in various formats
-    let urls = vec![
-        format!("https://www.youtube.com/@{}", id),
-        format!("https://www.youtube.com/user/{}", id),
-        format!("https://www.youtube.com/c/{}", id),
-    ];
+impl<'a> crate::source::base::ChannelData for YoutubePlaylistQuery<'a> {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        let d = self.get_page(1)?;
 
-    for u in &urls {
-        if let Ok(data) = post_json(format!("{}/api/v1/resolveurl", api_prefix()), u) {
-            // Got response as user
-            if let Some(browse_id) = data.pointer("/ucid").and_then(|x| x.as_str()) {
-                return Ok(browse_id.into());
-            } else {
-                anyhow::bail!("Failed to find browseId for username");
+        let thumbnail = match d.playlist_thumbnail {
+            Some(t) => t,
+            None if !d.author_thumbnails.is_empty() => {
+                choose_best_thumbnail(&d.author_thumbnails).url.clone()
             }
-        }
+            None => "".into(),
+        };
+
+        Ok(ChannelMetadata {
+            title: d.title,
+            thumbnail,
+            description: d.description,
+            banner: None,
+            subscriber_count: None,
+        })
+    }
+
+    /// Playlists are ordered oldest-first and new items are appended to the
+    /// end, so all pages are retrieved up front and returned in reverse order.
+    /// The playlist endpoint does not include a publish date, so the time the
+    /// video was first seen is used instead.
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        // GET /api/v1/playlists/:plid?page=1
+
+        // Pages can overlap, so stop once a page contains nothing new
+        let mut seen_indexes: std::collections::HashSet<i64> = std::collections::HashSet::new();
+        let all: Result<Vec<YTPlaylistVideo>> = paged(1, |page: i64| {
+            let new_items = self
+                .get_page(page)?
+                .videos
+                .into_iter()
+                .filter(|v| seen_indexes.insert(v.index))
+                .collect();
+            Ok((new_items, Some(page + 1)))
+        })
+        .collect();
+        let mut all = match all {
+            Ok(all) => all,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+
+        all.sort_by_key(|v| std::cmp::Reverse(v.index));
+
+        let now = chrono::Utc::now();
+        Box::new(all.into_iter().map(move |d| {
+            Ok(VideoInfo {
+                url: format!("http://youtube.com/watch?v={id}", id = d.video_id),
+                id: d.video_id,
+                title: d.title,
+                title_alt: None,
+                description: "".into(),
+                description_alt: None,
+                thumbnail_url: if d.video_thumbnails.is_empty() {
+                    "".into()
+                } else {
+                    choose_best_thumbnail(&d.video_thumbnails).url.clone()
+                },
+                thumbnails: d.video_thumbnails.iter().map(|t| t.into()).collect(),
+                published_at: now,
+                duration: d.length_seconds,
+                live_status: if d.live_now {
+                    LiveStatus::Live
+                } else {
+                    LiveStatus::NotLive
+                },
+                view_count: None,
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+            })
+        }))
+    }
+
+    fn video_details(&self, video_id: &str) -> Result<VideoDetails> {
+        self.rate_limit.wait();
+        get_video_details(video_id)
     }
+}
 
-    anyhow::bail!("Failed to find any of {:?}", urls)
+/// Find playlist ID from either a playlist URL (`...?list=PL..`) or the ID itself
+pub fn find_playlist_id(url_or_id: &str) -> Result<YoutubePlaylistID> {
+    let id = match url_or_id.split_once("list=") {
+        Some((_, rest)) => rest.split('&').next().unwrap_or(rest),
+        None => url_or_id,
+    };
+    if id.is_empty() {
+        anyhow::bail!("Failed to find playlist ID in {:?}", url_or_id);
+    }
+    Ok(YoutubePlaylistID { id: id.into() })
+}
+
+/// Find channel ID (`UC..` string) for a handle, username or custom URL, using Invidious to resolve the channel URL
+pub fn resolve_channel_id(name: &YoutubeName) -> Result<String> {
+    if let YoutubeName::ChannelID(id) = name {
+        return Ok(id.clone());
+    }
+
+    let target_url = name.url();
+    let url = format!("{}/api/v1/resolveurl", api_prefix());
+    debug!("Resolving {} with {}", &target_url, &url);
+    let resp = attohttpc::get(&url).param("url", &target_url).send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let data: serde_json::Value = serde_json::from_str(&resp.text()?)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    match data.pointer("/ucid").and_then(|x| x.as_str()) {
+        Some(ucid) => Ok(ucid.into()),
+        None => anyhow::bail!("No channel ID found for {}", &target_url),
+    }
+}
+
+/// Check the Invidious instance responds, returning its software version
+pub fn check_instance(timeout: std::time::Duration) -> Result<String> {
+    let url = format!("{}/api/v1/stats", api_prefix());
+    let resp = attohttpc::get(&url).timeout(timeout).send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let data: serde_json::Value = serde_json::from_str(&resp.text()?)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    Ok(data
+        .pointer("/software/version")
+        .and_then(|x| x.as_str())
+        .unwrap_or("unknown")
+        .into())
+}
+
+#[test]
+fn test_check_instance() {
+    let timeout = std::time::Duration::from_secs(5);
+    let _m1 = mockito::mock("GET", "/api/v1/stats")
+        .with_body(
+            r#"{"version": "2.0", "software": {"name": "invidious", "version": "2024.01.01"}}"#,
+        )
+        .create();
+    assert_eq!(check_instance(timeout).unwrap(), "2024.01.01");
+    drop(_m1);
+
+    let _m2 = mockito::mock("GET", "/api/v1/stats")
+        .with_status(502)
+        .create();
+    assert!(check_instance(timeout).is_err());
 }
 
 #[test]
 fn test_basic() {
+    let _m1 = mockito::mock("GET", "/api/v1/resolveurl")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "url".into(),
+            "https://www.youtube.com/@onceuponaclimb".into(),
+        ))
+        .with_body(r#"{"ucid": "UCOYYX1Ucvx87A7CSy5M99yw", "pageType": "WebPageType.CHANNEL"}"#)
+        .create();
+    let _m2 = mockito::mock("GET", "/api/v1/resolveurl")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "url".into(),
+            "https://www.youtube.com/user/thegreatsd".into(),
+        ))
+        .with_body(r#"{"ucid": "UCUBfKCp83QT19JCUekEdxOQ", "pageType": "WebPageType.CHANNEL"}"#)
+        .create();
+
     // Look up directly by channel ID
     assert_eq!(
-        &find_channel_id_workaround("UCOYYX1Ucvx87A7CSy5M99yw").unwrap(),
+        &resolve_channel_id(&YoutubeName::ChannelID("UCOYYX1Ucvx87A7CSy5M99yw".into())).unwrap(),
         "UCOYYX1Ucvx87A7CSy5M99yw"
     );
-    // By channel name
+    // By handle
     assert_eq!(
-        &find_channel_id_workaround("onceuponaclimb").unwrap(),
+        &resolve_channel_id(&YoutubeName::Handle("onceuponaclimb".into())).unwrap(),
         "UCOYYX1Ucvx87A7CSy5M99yw"
     );
 
     // By username
     assert_eq!(
-        &find_channel_id_workaround("thegreatsd").unwrap(),
+        &resolve_channel_id(&YoutubeName::Username("thegreatsd".into())).unwrap(),
         "UCUBfKCp83QT19JCUekEdxOQ"
     );
 }
 
-/// Find channel ID either from a username or ID
-use crate::common::ChannelID;
-pub fn find_channel_id(name: &str, service: &Service) -> Result<ChannelID> {
-    match service {
-        Service::Youtube => {
-            let id = find_channel_id_workaround(name)?;
-            Ok(ChannelID::Youtube(YoutubeID { id }))
-        }
-        Service::Vimeo => Err(anyhow::anyhow!("Not yet implemented!")), // FIXME: This method belongs outside of youtube.rs
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::source::base::ChannelData;
+    use crate::source::find_channel_id;
 
     #[test]
     fn test_basic_find() -> Result<()> {
-        let _m1 = mockito::mock("GET", "/api/v1/channels/thegreatsd")
-            .with_body_from_file("testdata/channel_thegreatsd.json")
-            .create();
-        let _m2 = mockito::mock("GET", "/api/v1/channels/UCUBfKCp83QT19JCUekEdxOQ")
-            .with_body_from_file("testdata/channel_thegreatsd.json") // Same content
+        let _m1 = mockito::mock("GET", "/api/v1/resolveurl")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "url".into(),
+                "https://www.youtube.com/user/thegreatsd".into(),
+            ))
+            .with_body(r#"{"ucid": "UCUBfKCp83QT19JCUekEdxOQ"}"#)
             .create();
 
-        let c = find_channel_id("thegreatsd", &crate::common::Service::Youtube)?;
+        let c = find_channel_id(
+            "https://www.youtube.com/user/thegreatsd",
+            &crate::common::Service::Youtube,
+        )?;
         assert_eq!(c.id_str(), "UCUBfKCp83QT19JCUekEdxOQ");
         assert_eq!(c.service(), crate::common::Service::Youtube);
+        match &c {
+            crate::common::ChannelID::Youtube(x) => {
+                assert_eq!(x.handle.as_deref(), Some("thegreatsd"))
+            }
+            _ => panic!("Expected Youtube channel"),
+        }
 
-        // Check same `ChannelID` is found by ID as by username
+        // Check same channel is found by ID as by username, without a handle
         let by_id = find_channel_id("UCUBfKCp83QT19JCUekEdxOQ", &crate::common::Service::Youtube)?;
-        assert_eq!(by_id, c);
+        assert_eq!(by_id.id_str(), c.id_str());
+        match &by_id {
+            crate::common::ChannelID::Youtube(x) => assert_eq!(x.handle, None),
+            _ => panic!("Expected Youtube channel"),
+        }
 
         Ok(())
     }
@@ -377,6 +835,7 @@ mod test {
 
         let cid = crate::common::YoutubeID {
             id: "UCOYYX1Ucvx87A7CSy5M99yw".into(),
+            handle: None,
         };
         let yt = YoutubeQuery::new(&cid);
         let vids = yt.videos();
@@ -415,6 +874,7 @@ mod test {
 
         let cid = crate::common::YoutubeID {
             id: "UCOYYX1Ucvx87A7CSy5M99yw".into(),
+            handle: None,
         };
         let yt = YoutubeQuery::new(&cid);
         let mut vids = yt.videos();
@@ -424,6 +884,138 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_playlist() -> Result<()> {
+        let _m1 = mockito::mock("GET", "/api/v1/playlists/PLtestplaylist?page=1")
+            .with_body_from_file("testdata/playlist_page1.json")
+            .create();
+        let _m2 = mockito::mock("GET", "/api/v1/playlists/PLtestplaylist?page=2")
+            .with_body_from_file("testdata/playlist_page1.json") // Overlapping page ends iteration
+            .create();
+
+        let plid = find_playlist_id("https://www.youtube.com/playlist?list=PLtestplaylist&si=abc")?;
+        assert_eq!(plid.id, "PLtestplaylist");
+
+        let pl = YoutubePlaylistQuery::new(&plid);
+        let meta = pl.get_metadata()?;
+        assert_eq!(meta.title, "A test playlist");
+
+        let result = pl.videos().collect::<Result<Vec<super::VideoInfo>>>()?;
+        assert_eq!(result.len(), 2);
+        // Most recently appended first
+        assert_eq!(result[0].id, "second00001");
+        assert_eq!(result[1].id, "first000001");
+        assert_eq!(result[1].duration, 300);
+        Ok(())
+    }
+
+    #[test]
+    fn test_video_details() -> Result<()> {
+        let _m1 = mockito::mock("GET", "/api/v1/videos/abc123?fields=keywords,genre,captions,description")
+            .with_body(r#"{"description": "A full description\n\n0:00 Start\n0:30 Middle\n1:00 End", "keywords": ["stop motion", "animation"], "genre": "Film & Animation", "captions": [{"label": "English", "languageCode": "en", "url": "/api/v1/captions/abc123?label=English"}, {"label": "English (auto-generated)", "languageCode": "en", "url": "/api/v1/captions/abc123?label=English+%28auto-generated%29"}, {"label": "German", "languageCode": "de", "url": "/api/v1/captions/abc123?label=German"}]}"#)
+            .create();
+
+        let cid = YoutubeID {
+            id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: None,
+        };
+        let yt = YoutubeQuery::new(&cid);
+        let details = yt.video_details("abc123")?;
+        assert_eq!(
+            details.tags,
+            vec!["stop motion", "animation", "Film & Animation"]
+        );
+        assert_eq!(details.captions, vec!["en", "de"]);
+        assert!(details
+            .description
+            .as_deref()
+            .unwrap()
+            .starts_with("A full description"));
+        assert_eq!(details.chapters.len(), 3);
+        assert_eq!(details.chapters[1].start, 30);
+        assert_eq!(details.chapters[1].title, "Middle");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_video_info() -> Result<()> {
+        let _m1 = mockito::mock("GET", "/api/v1/videos/abc123?fields=title,videoId,videoThumbnails,description,lengthSeconds,published,viewCount,premiereTimestamp,premium,liveNow,isUpcoming")
+            .with_body(r#"{"title": "A video", "videoId": "abc123", "videoThumbnails": [{"quality": "medium", "url": "https://example.com/mq.jpg", "width": 320, "height": 180}], "description": "About it", "lengthSeconds": 125, "published": 1577836800, "viewCount": 42, "premium": false, "liveNow": false, "isUpcoming": false}"#)
+            .create();
+
+        let v = get_video_info("abc123")?;
+        assert_eq!(v.id, "abc123");
+        assert_eq!(v.url, "http://youtube.com/watch?v=abc123");
+        assert_eq!(v.title, "A video");
+        assert_eq!(v.duration, 125);
+        assert_eq!(v.view_count, Some(42));
+        assert_eq!(v.thumbnail_url, "https://example.com/mq.jpg");
+        assert_eq!(v.published_at.timestamp(), 1577836800);
+        assert_eq!(v.live_status, LiveStatus::NotLive);
+        Ok(())
+    }
+
+    #[test]
+    fn test_playlists() -> Result<()> {
+        let _m1 = mockito::mock("GET", "/api/v1/channels/UCUBfKCp83QT19JCUekEdxOQ/playlists")
+            .with_body_from_file("testdata/channel_thegreatsd_playlists.json")
+            .create();
+        let _m2 = mockito::mock(
+            "GET",
+            "/api/v1/channels/UCUBfKCp83QT19JCUekEdxOQ/playlists?continuation=4qmFsgI",
+        )
+        .with_body(r#"{"playlists": []}"#)
+        .create();
+
+        let cid = YoutubeID {
+            id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: None,
+        };
+        let yt = YoutubeQuery::new(&cid);
+        let playlists = yt.playlists()?;
+        assert_eq!(playlists.len(), 2);
+        assert_eq!(playlists[0].id, "PLxQD0gxlRfbqXgvEQe3ppu3Kt5C_0jqpZ");
+        assert_eq!(playlists[0].title, "Stop motion");
+        assert_eq!(playlists[0].video_count, Some(12));
+        assert_eq!(playlists[1].thumbnail, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_send() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = format!("{}/api/v1/cachetest", api_prefix());
+
+        let first = mockito::mock("GET", "/api/v1/cachetest")
+            .with_header("etag", "\"abc\"")
+            .with_body(r#"{"a": 1}"#)
+            .expect(1)
+            .create();
+        assert_eq!(
+            cached_send(attohttpc::get(&url), &url, dir.path(), 60)?,
+            r#"{"a": 1}"#
+        );
+        // Within TTL so no request made
+        assert_eq!(
+            cached_send(attohttpc::get(&url), &url, dir.path(), 60)?,
+            r#"{"a": 1}"#
+        );
+        first.assert();
+
+        // Outside TTL, revalidated with the ETag
+        let revalidate = mockito::mock("GET", "/api/v1/cachetest")
+            .match_header("if-none-match", "\"abc\"")
+            .with_status(304)
+            .expect(1)
+            .create();
+        assert_eq!(
+            cached_send(attohttpc::get(&url), &url, dir.path(), 0)?,
+            r#"{"a": 1}"#
+        );
+        revalidate.assert();
+        Ok(())
+    }
+
     #[test]
     fn test_metadata() -> Result<()> {
         let _m1 = mockito::mock("GET", "/api/v1/channels/UCUBfKCp83QT19JCUekEdxOQ")
@@ -432,6 +1024,7 @@ mod test {
 
         let cid = crate::common::YoutubeID {
             id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: None,
         };
         let yt = YoutubeQuery::new(&cid);
         let meta = yt.get_metadata()?;
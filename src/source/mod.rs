@@ -1,2 +1,191 @@
 pub mod base;
+pub mod bitchute;
 pub mod invidious;
+pub mod piped;
+pub mod rumble;
+pub mod soundcloud;
+pub mod vimeo;
+pub mod youtube_api;
+pub mod ytdlp;
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use log::{debug, warn};
+use ratelimit_meter::{DirectRateLimiter, GCRA};
+
+use crate::common::{Backend, ChannelID, Service, YoutubeID, YoutubeName};
+use base::ChannelData;
+
+/// Limits requests to a source to 10 a minute, shared by threads making requests at once
+#[derive(Debug)]
+pub struct RateLimit(std::sync::Mutex<DirectRateLimiter<GCRA>>);
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit(std::sync::Mutex::new(DirectRateLimiter::<GCRA>::new(
+            std::num::NonZeroU32::new(10).unwrap(),
+            std::time::Duration::from_secs(60),
+        )))
+    }
+}
+
+impl RateLimit {
+    /// Call before each request, waiting if over the limit
+    pub fn wait(&self) {
+        self.wait_for(std::time::Duration::from_secs(10));
+    }
+
+    /// Like `wait`, but only pausing for `pause` if over the limit
+    pub fn wait_for(&self, pause: std::time::Duration) {
+        // Checked separately, so other threads aren't held up by the pause
+        let limited = self.0.lock().unwrap().check().is_err();
+        if limited {
+            debug!("Waiting for rate limit");
+            std::thread::sleep(pause);
+        }
+    }
+}
+
+/// Iterate over the items of a paged listing, starting from page `first`. `get_page` returns
+/// the items on a page and the next page, or `None` for the last. Pages are only requested
+/// once the previous one's items have been used, and iteration stops after an error or an
+/// empty page
+pub fn paged<'i, T: 'i, P: 'i>(
+    first: P,
+    mut get_page: impl FnMut(P) -> Result<(Vec<T>, Option<P>)> + 'i,
+) -> impl Iterator<Item = Result<T>> + 'i {
+    let mut next_page = Some(first);
+    let mut completed = false;
+    let mut current_items: VecDeque<T> = VecDeque::new();
+
+    std::iter::from_fn(move || -> Option<Result<T>> {
+        if completed {
+            return None;
+        }
+        if let Some(cur) = current_items.pop_front() {
+            // Iterate through previously stored items
+            return Some(Ok(cur));
+        }
+
+        let page = match next_page.take() {
+            Some(p) => p,
+            None => {
+                // Nothing stored and no further pages - done
+                completed = true;
+                return None;
+            }
+        };
+
+        match get_page(page) {
+            Err(e) => {
+                // Prevent future iteration, and return the error
+                completed = true;
+                Some(Err(e))
+            }
+            Ok((new_items, next)) => {
+                next_page = next;
+                current_items.extend(new_items);
+                match current_items.pop_front() {
+                    Some(cur) => Some(Ok(cur)),
+                    None => {
+                        // Empty page, so no more items
+                        completed = true;
+                        None
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Find channel ID either from a username, handle, URL or ID
+pub fn find_channel_id(name: &str, service: &Service) -> Result<ChannelID> {
+    match service {
+        Service::Youtube => {
+            let yt_name = YoutubeName::parse(name)?;
+            let id = invidious::resolve_channel_id(&yt_name)?;
+            Ok(ChannelID::Youtube(YoutubeID {
+                id,
+                handle: yt_name.display(),
+            }))
+        }
+        Service::Vimeo => Ok(ChannelID::Vimeo(vimeo::find_user_id(name)?)),
+        Service::YoutubePlaylist => Ok(ChannelID::YoutubePlaylist(invidious::find_playlist_id(
+            name,
+        )?)),
+        Service::Soundcloud => Ok(ChannelID::Soundcloud(soundcloud::find_user_id(name)?)),
+        Service::Rumble => Ok(ChannelID::Rumble(rumble::find_channel_id(name)?)),
+        Service::Bitchute => Ok(ChannelID::Bitchute(bitchute::find_channel_id(name)?)),
+    }
+}
+
+/// Get the `ChannelData` implementation for the given channel. The `backend`
+/// selects between Invidious, Piped, yt-dlp and the YouTube Data API for Youtube
+/// channels (playlists are only supported by Invidious and yt-dlp), and
+/// BitChute can also use yt-dlp. Rumble always uses yt-dlp, and other services
+/// always use their own API.
+pub fn channel_data<'a>(cid: &'a ChannelID, backend: Backend) -> Box<dyn ChannelData + 'a> {
+    match (cid, backend) {
+        (
+            ChannelID::Youtube(_) | ChannelID::YoutubePlaylist(_) | ChannelID::Bitchute(_),
+            Backend::Ytdlp,
+        )
+        | (ChannelID::Rumble(_), _) => Box::new(ytdlp::YtdlpQuery::new(cid)),
+        (ChannelID::Youtube(x), Backend::Api) => {
+            match crate::config::Config::load().youtube_api_key {
+                Some(key) => Box::new(youtube_api::YoutubeApiQuery::new(x, key)),
+                None => {
+                    warn!("No YouTube API key configured, falling back to Invidious");
+                    Box::new(invidious::YoutubeQuery::new(x))
+                }
+            }
+        }
+        (ChannelID::Youtube(x), Backend::Invidious) => Box::new(invidious::YoutubeQuery::new(x)),
+        (ChannelID::Youtube(x), Backend::Piped) => Box::new(piped::PipedQuery::new(x)),
+        (ChannelID::YoutubePlaylist(x), Backend::Invidious | Backend::Api | Backend::Piped) => {
+            Box::new(invidious::YoutubePlaylistQuery::new(x))
+        }
+        (ChannelID::Vimeo(x), _) => Box::new(vimeo::VimeoQuery::new(x)),
+        (ChannelID::Soundcloud(x), _) => Box::new(soundcloud::SoundcloudQuery::new(x)),
+        (ChannelID::Bitchute(x), _) => Box::new(bitchute::BitchuteQuery::new(x)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_paged() {
+        // Pages are requested as they're needed, ending after the last page
+        let mut requested = vec![];
+        let items: Vec<i32> = paged(1, |p: i32| {
+            requested.push(p);
+            Ok((vec![p * 10, p * 10 + 1], (p < 3).then_some(p + 1)))
+        })
+        .take(3)
+        .collect::<Result<_>>()
+        .unwrap();
+        assert_eq!(items, vec![10, 11, 20]);
+        assert_eq!(requested, vec![1, 2]);
+
+        let all: Vec<i32> = paged(1, |p: i32| Ok((vec![p], (p < 3).then_some(p + 1))))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(all, vec![1, 2, 3]);
+
+        // Stops after an error, or an empty page
+        let mut it = paged(1, |p: i32| match p {
+            1 => Ok((vec![1], Some(2))),
+            _ => Err(anyhow::anyhow!("failed")),
+        });
+        assert_eq!(it.next().unwrap().unwrap(), 1);
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+        assert_eq!(
+            paged(1, |p: i32| Ok((Vec::<i32>::new(), Some(p + 1)))).count(),
+            0
+        );
+    }
+}
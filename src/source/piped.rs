@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use chrono::offset::TimeZone;
+use log::{debug, trace};
+
+use crate::common::{LiveStatus, YoutubeID};
+use crate::source::base::{ChannelMetadata, VideoInfo};
+use crate::source::{paged, RateLimit};
+
+fn api_prefix() -> String {
+    #[cfg(test)]
+    let prefix: String = mockito::server_url();
+
+    #[cfg(not(test))]
+    let prefix: String = std::env::var("VIDL_PIPED_URL")
+        .ok()
+        .unwrap_or_else(|| "https://pipedapi.kavin.rocks".into());
+
+    prefix
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PipedChannel {
+    id: String,
+    name: String,
+    avatar_url: Option<String>,
+    banner_url: Option<String>,
+    description: Option<String>,
+    subscriber_count: Option<i64>,
+    nextpage: Option<String>,
+    related_streams: Vec<PipedStream>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PipedPage {
+    nextpage: Option<String>,
+    related_streams: Vec<PipedStream>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PipedStream {
+    /// Like `/watch?v=abc`
+    url: String,
+    title: String,
+    thumbnail: String,
+    short_description: Option<String>,
+    /// Seconds, or -1 for live streams
+    duration: i32,
+    views: Option<i64>,
+    /// Milliseconds since epoch
+    uploaded: i64,
+}
+
+fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+    url: &str,
+    params: &[(&str, &str)],
+) -> Result<T> {
+    debug!("Retrieving URL {} with {:?}", &url, &params);
+    let resp = attohttpc::get(url).params(params).send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let text = resp.text()?;
+    trace!("Raw response: {}", &text);
+    let data: T = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    trace!("Raw deserialisation: {:?}", &data);
+    Ok(data)
+}
+
+/// Convert a stream from a channel page into a `VideoInfo`
+fn convert_stream(s: PipedStream) -> Result<VideoInfo> {
+    let id = match s.url.split_once("v=") {
+        Some((_, rest)) => rest.split('&').next().unwrap_or(rest).to_string(),
+        None => anyhow::bail!("Could not find video ID in {:?}", &s.url),
+    };
+    let live = s.duration < 0;
+    Ok(VideoInfo {
+        url: format!("https://www.youtube.com/watch?v={}", id),
+        id,
+        title: s.title,
+        title_alt: None,
+        description: s.short_description.unwrap_or_default(),
+        description_alt: None,
+        thumbnail_url: s.thumbnail,
+        thumbnails: vec![],
+        published_at: chrono::Utc.timestamp_millis_opt(s.uploaded).unwrap(),
+        duration: if live { 0 } else { s.duration },
+        live_status: if live {
+            LiveStatus::Live
+        } else {
+            LiveStatus::NotLive
+        },
+        view_count: s.views.filter(|v| *v >= 0),
+        captions: vec![],
+        scheduled_at: None,
+        members_only: false,
+    })
+}
+
+/// Object to query a Youtube channel through a Piped instance
+#[derive(Debug)]
+pub struct PipedQuery<'a> {
+    chan_id: &'a YoutubeID,
+    rate_limit: RateLimit,
+}
+
+impl<'a> PipedQuery<'a> {
+    pub fn new(chan_id: &YoutubeID) -> PipedQuery<'_> {
+        PipedQuery {
+            chan_id,
+            rate_limit: RateLimit::default(),
+        }
+    }
+
+    fn get_channel(&self) -> Result<PipedChannel> {
+        self.rate_limit.wait();
+        let url = format!(
+            "{prefix}/channel/{chanid}",
+            prefix = api_prefix(),
+            chanid = self.chan_id.id
+        );
+        request_data(&url, &[])
+    }
+
+    fn get_page(&self, nextpage: &str) -> Result<PipedPage> {
+        self.rate_limit.wait();
+        let url = format!(
+            "{prefix}/nextpage/channel/{chanid}",
+            prefix = api_prefix(),
+            chanid = self.chan_id.id
+        );
+        request_data(&url, &[("nextpage", nextpage)])
+    }
+}
+
+impl<'a> crate::source::base::ChannelData for PipedQuery<'a> {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        let d = self.get_channel()?;
+        Ok(ChannelMetadata {
+            title: d.name,
+            thumbnail: d.avatar_url.unwrap_or_default(),
+            description: d.description.unwrap_or_default(),
+            banner: d.banner_url.filter(|b| !b.is_empty()),
+            subscriber_count: d.subscriber_count.filter(|c| *c >= 0),
+        })
+    }
+
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        // GET /channel/:id for the first page, then /nextpage/channel/:id?nextpage=..
+
+        // The first page has no `nextpage` token
+        let pages = paged(None, move |nextpage: Option<String>| {
+            let page = match nextpage {
+                None => self.get_channel().map(|c| PipedPage {
+                    nextpage: c.nextpage,
+                    related_streams: c.related_streams,
+                })?,
+                Some(np) => self.get_page(&np)?,
+            };
+            Ok((page.related_streams, page.nextpage.map(Some)))
+        });
+        Box::new(pages.map(|s| s.and_then(convert_stream)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::base::ChannelData;
+
+    #[test]
+    fn test_piped_video_list() -> Result<()> {
+        let _m1 = mockito::mock("GET", "/channel/UCUBfKCp83QT19JCUekEdxOQ")
+            .with_body_from_file("testdata/piped_channel.json")
+            .create();
+        let _m2 = mockito::mock("GET", "/nextpage/channel/UCUBfKCp83QT19JCUekEdxOQ")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "nextpage".into(),
+                r#"{"token":"page2"}"#.into(),
+            ))
+            .with_body(r#"{"nextpage": null, "relatedStreams": [{"url": "/watch?v=oldvideo123", "title": "Old video", "thumbnail": "https://example.com/old.jpg", "shortDescription": null, "duration": 60, "views": 5, "uploaded": 1577836800000}]}"#)
+            .create();
+
+        let cid = YoutubeID {
+            id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: None,
+        };
+        let piped = PipedQuery::new(&cid);
+
+        let meta = piped.get_metadata()?;
+        assert_eq!(meta.title, "thegreatsd");
+        assert_eq!(meta.subscriber_count, Some(61));
+
+        let result = piped.videos().collect::<Result<Vec<VideoInfo>>>()?;
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].id, "fBIcDz9uO2g");
+        assert_eq!(result[0].url, "https://www.youtube.com/watch?v=fBIcDz9uO2g");
+        assert_eq!(result[0].duration, 1234);
+        assert_eq!(result[0].view_count, Some(100));
+        assert_eq!(result[1].live_status, LiveStatus::Live);
+        assert_eq!(result[2].title, "Old video");
+        assert_eq!(
+            result[2].published_at,
+            chrono::Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+//! Rumble has no public API for listing a channel's videos, so channel data is
+//! retrieved with `yt-dlp` (see `crate::source::ytdlp`). This module only
+//! handles working out the channel ID.
+
+use anyhow::Result;
+
+use crate::common::RumbleID;
+
+/// Find channel path from a URL like `https://rumble.com/c/SomeChannel`, or a plain channel name
+pub fn find_channel_id(url_or_name: &str) -> Result<RumbleID> {
+    let path = match url_or_name.find("rumble.com/") {
+        Some(idx) => &url_or_name[idx + "rumble.com/".len()..],
+        None => url_or_name,
+    };
+    let parts: Vec<&str> = path.split(['/', '?']).filter(|x| !x.is_empty()).collect();
+
+    let id = match parts.as_slice() {
+        // Channels live under `/c/` and users under `/user/`
+        [kind @ ("c" | "user"), name, ..] => format!("{}/{}", kind, name),
+        // Assume plain name is a channel
+        [name] => format!("c/{}", name),
+        _ => anyhow::bail!("Could not find Rumble channel in {:?}", url_or_name),
+    };
+    Ok(RumbleID { id })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_channel_id() -> Result<()> {
+        assert_eq!(
+            find_channel_id("https://rumble.com/c/SomeChannel?page=2")?.id,
+            "c/SomeChannel"
+        );
+        assert_eq!(
+            find_channel_id("https://rumble.com/user/someone/videos")?.id,
+            "user/someone"
+        );
+        assert_eq!(find_channel_id("SomeChannel")?.id, "c/SomeChannel");
+        assert!(find_channel_id("https://rumble.com/").is_err());
+        Ok(())
+    }
+}
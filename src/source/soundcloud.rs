@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use log::{debug, trace};
+
+use crate::common::{LiveStatus, SoundcloudID};
+use crate::source::base::{ChannelMetadata, VideoInfo};
+use crate::source::{paged, RateLimit};
+
+fn api_prefix() -> String {
+    #[cfg(test)]
+    let prefix: String = mockito::server_url();
+
+    #[cfg(not(test))]
+    let prefix: String = "https://api-v2.soundcloud.com".into();
+
+    prefix
+}
+
+/// Client ID used by the SoundCloud web player, required for all API requests
+fn client_id() -> Result<String> {
+    crate::config::Config::load()
+        .soundcloud_client_id
+        .ok_or_else(|| anyhow::anyhow!("SoundCloud client ID not set (VIDL_SOUNDCLOUD_CLIENT_ID)"))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SCUser {
+    id: i64,
+    username: String,
+    avatar_url: Option<String>,
+    description: Option<String>,
+    followers_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SCTrack {
+    id: i64,
+    title: String,
+    description: Option<String>,
+    permalink_url: String,
+    /// Duration in milliseconds
+    duration: i64,
+    created_at: String,
+    artwork_url: Option<String>,
+    playback_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SCTrackPage {
+    collection: Vec<SCTrack>,
+    next_href: Option<String>,
+}
+
+fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(url: &str) -> Result<T> {
+    let client_id = client_id()?;
+
+    debug!("Retrieving URL {}", &url);
+    let resp = attohttpc::get(url).param("client_id", client_id).send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let text = resp.text()?;
+    trace!("Raw response: {}", &text);
+    let data: T = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    trace!("Raw deserialisation: {:?}", &data);
+    Ok(data)
+}
+
+/// Find numeric SoundCloud user ID from a profile URL or permalink name (e.g `forss`)
+pub fn find_user_id(name: &str) -> Result<SoundcloudID> {
+    let profile_url = if name.starts_with("http") {
+        name.to_string()
+    } else {
+        format!("https://soundcloud.com/{}", name)
+    };
+    let url = format!("{prefix}/resolve", prefix = api_prefix());
+    let client_id = client_id()?;
+
+    debug!("Resolving SoundCloud URL {}", &profile_url);
+    let resp = attohttpc::get(&url)
+        .param("url", &profile_url)
+        .param("client_id", client_id)
+        .send()?;
+    if !resp.is_success() {
+        anyhow::bail!(
+            "Failed to resolve {} - status {}",
+            &profile_url,
+            resp.status()
+        );
+    }
+    let d: SCUser = serde_json::from_str(&resp.text()?)
+        .with_context(|| format!("Failed to parse user from {}", &profile_url))?;
+    Ok(SoundcloudID {
+        id: d.id.to_string(),
+    })
+}
+
+/// Object to query tracks uploaded by a SoundCloud user
+#[derive(Debug)]
+pub struct SoundcloudQuery<'a> {
+    chan_id: &'a SoundcloudID,
+    rate_limit: RateLimit,
+}
+
+impl<'a> SoundcloudQuery<'a> {
+    pub fn new(chan_id: &SoundcloudID) -> SoundcloudQuery<'_> {
+        SoundcloudQuery {
+            chan_id,
+            rate_limit: RateLimit::default(),
+        }
+    }
+}
+
+impl<'a> crate::source::base::ChannelData for SoundcloudQuery<'a> {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        let url = format!(
+            "{prefix}/users/{chanid}",
+            prefix = api_prefix(),
+            chanid = self.chan_id.id
+        );
+
+        self.rate_limit.wait();
+        let d: SCUser = request_data(&url)?;
+
+        Ok(ChannelMetadata {
+            title: d.username,
+            thumbnail: d.avatar_url.unwrap_or_default(),
+            description: d.description.unwrap_or_default(),
+            banner: None,
+            subscriber_count: d.followers_count,
+        })
+    }
+
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        // GET /users/:id/tracks?limit=50
+
+        fn get_page(url: &str) -> Result<(Vec<VideoInfo>, Option<String>)> {
+            let data: SCTrackPage = request_data(url)?;
+
+            let mut ret: Vec<VideoInfo> = vec![];
+            for d in data.collection {
+                let published_at = chrono::DateTime::parse_from_rfc3339(&d.created_at)
+                    .with_context(|| format!("Invalid created_at {:?}", &d.created_at))?
+                    .with_timezone(&chrono::Utc);
+                ret.push(VideoInfo {
+                    id: d.id.to_string(),
+                    url: d.permalink_url,
+                    title: d.title,
+                    title_alt: None,
+                    description: d.description.unwrap_or_default(),
+                    description_alt: None,
+                    thumbnail_url: d.artwork_url.unwrap_or_default(),
+                    thumbnails: vec![],
+                    published_at,
+                    duration: (d.duration / 1000) as i32,
+                    live_status: LiveStatus::NotLive,
+                    view_count: d.playback_count,
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                });
+            }
+
+            Ok((ret, data.next_href))
+        }
+
+        let first = format!(
+            "{prefix}/users/{chanid}/tracks?limit=50",
+            prefix = api_prefix(),
+            chanid = self.chan_id.id
+        );
+        Box::new(paged(first, move |url: String| {
+            self.rate_limit.wait();
+            get_page(&url)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::base::ChannelData;
+
+    #[test]
+    fn test_soundcloud_tracks() -> Result<()> {
+        std::env::set_var("VIDL_SOUNDCLOUD_CLIENT_ID", "testclient");
+
+        let _m1 = mockito::mock("GET", "/users/4242/tracks")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_file("testdata/soundcloud_tracks.json")
+            .create();
+
+        let cid = SoundcloudID { id: "4242".into() };
+        let sc = SoundcloudQuery::new(&cid);
+        let result = sc.videos().collect::<Result<Vec<VideoInfo>>>()?;
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, "123456");
+        assert_eq!(result[0].title, "Flickermood");
+        assert_eq!(result[0].url, "https://soundcloud.com/forss/flickermood");
+        // Milliseconds converted to seconds
+        assert_eq!(result[0].duration, 213);
+        assert_eq!(result[0].view_count, Some(3210));
+        assert_eq!(result[1].thumbnail_url, "");
+        Ok(())
+    }
+}
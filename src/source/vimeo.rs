@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use log::{debug, trace};
+
+use crate::common::{LiveStatus, VimeoID};
+use crate::source::base::{ChannelMetadata, VideoInfo};
+use crate::source::{paged, RateLimit};
+
+fn api_prefix() -> String {
+    #[cfg(test)]
+    let prefix: String = mockito::server_url();
+
+    #[cfg(not(test))]
+    let prefix: String = "https://api.vimeo.com".into();
+
+    prefix
+}
+
+/// Personal access token used to authenticate with the Vimeo API
+fn access_token() -> Result<String> {
+    crate::config::Config::load()
+        .vimeo_access_token
+        .ok_or_else(|| anyhow::anyhow!("Vimeo access token not set (VIDL_VIMEO_TOKEN)"))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoPicture {
+    width: i32,
+    height: i32,
+    link: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoPictures {
+    sizes: Vec<VimeoPicture>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoUser {
+    uri: String,
+    name: String,
+    bio: Option<String>,
+    pictures: Option<VimeoPictures>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoVideo {
+    uri: String,
+    name: String,
+    description: Option<String>,
+    link: String,
+    duration: i32,
+    release_time: String,
+    pictures: Option<VimeoPictures>,
+    stats: Option<VimeoStats>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoStats {
+    plays: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoPaging {
+    next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VimeoVideoPage {
+    data: Vec<VimeoVideo>,
+    paging: VimeoPaging,
+}
+
+fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(url: &str) -> Result<T> {
+    let token = access_token()?;
+
+    debug!("Retrieving URL {}", &url);
+    let resp = attohttpc::get(url)
+        .header(
+            attohttpc::header::AUTHORIZATION,
+            format!("bearer {}", token),
+        )
+        .header(
+            attohttpc::header::ACCEPT,
+            "application/vnd.vimeo.*+json;version=3.4",
+        )
+        .send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let text = resp.text()?;
+    trace!("Raw response: {}", &text);
+    let data: T = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    trace!("Raw deserialisation: {:?}", &data);
+    Ok(data)
+}
+
+/// Return the smallest picture at least 100px wide (falling back to the largest)
+fn choose_best_thumbnail(pictures: &Option<VimeoPictures>) -> String {
+    let sizes = match pictures {
+        Some(p) => &p.sizes,
+        None => return "".into(),
+    };
+    sizes
+        .iter()
+        .filter(|p| p.width >= 100)
+        .min_by_key(|p| p.width)
+        .or_else(|| sizes.iter().max_by_key(|p| p.width))
+        .map(|p| p.link.clone())
+        .unwrap_or_default()
+}
+
+/// Strip the `/users/` or `/videos/` prefix from a Vimeo API `uri`
+fn id_from_uri(uri: &str) -> String {
+    uri.rsplit('/').next().unwrap_or(uri).into()
+}
+
+/// Find numeric Vimeo user ID from either a vanity name (e.g `staff`) or existing ID
+pub fn find_user_id(name: &str) -> Result<VimeoID> {
+    let url = format!(
+        "{prefix}/users/{name}?fields=uri",
+        prefix = api_prefix(),
+        name = name
+    );
+    let d: VimeoUser = request_data(&url)?;
+    Ok(VimeoID {
+        id: id_from_uri(&d.uri),
+    })
+}
+
+/// Object to query data about given Vimeo user
+#[derive(Debug)]
+pub struct VimeoQuery<'a> {
+    chan_id: &'a VimeoID,
+    rate_limit: RateLimit,
+}
+
+impl<'a> VimeoQuery<'a> {
+    pub fn new(chan_id: &VimeoID) -> VimeoQuery<'_> {
+        VimeoQuery {
+            chan_id,
+            rate_limit: RateLimit::default(),
+        }
+    }
+}
+
+impl<'a> crate::source::base::ChannelData for VimeoQuery<'a> {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        let url = format!(
+            "{prefix}/users/{chanid}?fields=uri,name,bio,pictures.sizes",
+            prefix = api_prefix(),
+            chanid = self.chan_id.id
+        );
+
+        self.rate_limit.wait();
+        let d: VimeoUser = request_data(&url)?;
+
+        Ok(ChannelMetadata {
+            title: d.name,
+            thumbnail: choose_best_thumbnail(&d.pictures),
+            description: d.bio.unwrap_or_default(),
+            banner: None,
+            subscriber_count: None,
+        })
+    }
+
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        // GET /users/:id/videos?sort=date&direction=desc
+
+        fn get_page(path: &str) -> Result<(Vec<VideoInfo>, Option<String>)> {
+            let url = format!("{prefix}{path}", prefix = api_prefix(), path = path);
+            let data: VimeoVideoPage = request_data(&url)?;
+
+            let mut ret: Vec<VideoInfo> = vec![];
+            for d in data.data {
+                let published_at = chrono::DateTime::parse_from_rfc3339(&d.release_time)
+                    .with_context(|| format!("Invalid release_time {:?}", &d.release_time))?
+                    .with_timezone(&chrono::Utc);
+                ret.push(VideoInfo {
+                    id: id_from_uri(&d.uri),
+                    url: d.link,
+                    title: d.name,
+                    title_alt: None,
+                    description: d.description.unwrap_or_default(),
+                    description_alt: None,
+                    thumbnail_url: choose_best_thumbnail(&d.pictures),
+                    thumbnails: vec![],
+                    published_at,
+                    duration: d.duration,
+                    live_status: LiveStatus::NotLive,
+                    view_count: d.stats.and_then(|s| s.plays),
+                    captions: vec![],
+                    scheduled_at: None,
+                    members_only: false,
+                });
+            }
+
+            Ok((ret, data.paging.next))
+        }
+
+        let first = format!(
+            "/users/{chanid}/videos?sort=date&direction=desc&per_page=50&fields=uri,name,description,link,duration,release_time,pictures.sizes,stats.plays",
+            chanid = self.chan_id.id
+        );
+        Box::new(paged(first, move |path: String| {
+            self.rate_limit.wait();
+            get_page(&path)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::base::ChannelData;
+
+    #[test]
+    fn test_thumbnail_choice() {
+        let pics = Some(VimeoPictures {
+            sizes: vec![
+                VimeoPicture {
+                    width: 640,
+                    height: 360,
+                    link: "large".into(),
+                },
+                VimeoPicture {
+                    width: 100,
+                    height: 75,
+                    link: "small".into(),
+                },
+                VimeoPicture {
+                    width: 30,
+                    height: 30,
+                    link: "tiny".into(),
+                },
+            ],
+        });
+        assert_eq!(choose_best_thumbnail(&pics), "small");
+        assert_eq!(choose_best_thumbnail(&None), "");
+    }
+
+    #[test]
+    fn test_vimeo_video_list() -> Result<()> {
+        std::env::set_var("VIDL_VIMEO_TOKEN", "testtoken");
+
+        let _m1 = mockito::mock("GET", "/users/12345/videos")
+            .match_query(mockito::Matcher::Regex("fields=.*stats.plays".into()))
+            .with_body_from_file("testdata/vimeo_videos.json")
+            .create();
+
+        let cid = VimeoID { id: "12345".into() };
+        let vq = VimeoQuery::new(&cid);
+        let result = vq.videos().collect::<Result<Vec<VideoInfo>>>()?;
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, "76979871");
+        assert_eq!(result[0].title, "The New Vimeo Player");
+        assert_eq!(result[0].url, "https://vimeo.com/76979871");
+        assert_eq!(result[0].duration, 62);
+        assert_eq!(result[0].view_count, Some(2463));
+        assert_eq!(result[1].description, "");
+        assert_eq!(result[1].view_count, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use log::{debug, trace};
+
+use crate::common::{LiveStatus, YoutubeID};
+use crate::source::base::{ChannelMetadata, VideoInfo};
+use crate::source::{paged, RateLimit};
+
+fn api_prefix() -> String {
+    #[cfg(test)]
+    let prefix: String = mockito::server_url();
+
+    #[cfg(not(test))]
+    let prefix: String = "https://www.googleapis.com".into();
+
+    prefix
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ApiThumbnail {
+    url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ApiThumbnails {
+    default: Option<ApiThumbnail>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiChannelSnippet {
+    title: String,
+    description: String,
+    thumbnails: ApiThumbnails,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiRelatedPlaylists {
+    uploads: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiChannelContentDetails {
+    related_playlists: ApiRelatedPlaylists,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiChannelStatistics {
+    /// Number as a string, absent if the channel hides it
+    subscriber_count: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiBrandingImage {
+    banner_external_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiBrandingSettings {
+    image: Option<ApiBrandingImage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiChannel {
+    snippet: ApiChannelSnippet,
+    content_details: ApiChannelContentDetails,
+    statistics: Option<ApiChannelStatistics>,
+    branding_settings: Option<ApiBrandingSettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ApiChannelList {
+    #[serde(default)]
+    items: Vec<ApiChannel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiResourceId {
+    video_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiPlaylistItemSnippet {
+    published_at: String,
+    title: String,
+    description: String,
+    thumbnails: ApiThumbnails,
+    resource_id: ApiResourceId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiPlaylistItemContentDetails {
+    video_published_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiPlaylistItem {
+    snippet: ApiPlaylistItemSnippet,
+    content_details: Option<ApiPlaylistItemContentDetails>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiPlaylistItemList {
+    next_page_token: Option<String>,
+    items: Vec<ApiPlaylistItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ApiVideoContentDetails {
+    duration: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiVideoSnippet {
+    /// One of `none`, `live` or `upcoming`
+    live_broadcast_content: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiVideoStatistics {
+    /// Number as a string, e.g `"1547"`
+    view_count: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiVideo {
+    id: String,
+    content_details: ApiVideoContentDetails,
+    snippet: Option<ApiVideoSnippet>,
+    statistics: Option<ApiVideoStatistics>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ApiVideoList {
+    #[serde(default)]
+    items: Vec<ApiVideo>,
+}
+
+fn request_data<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+    key: &str,
+    path: &str,
+    params: &[(&str, &str)],
+) -> Result<T> {
+    let url = format!(
+        "{prefix}/youtube/v3/{path}",
+        prefix = api_prefix(),
+        path = path
+    );
+    debug!("Retrieving URL {} with {:?}", &url, params);
+    let resp = attohttpc::get(&url)
+        .params(params)
+        .param("key", key)
+        .send()?;
+    if !resp.is_success() {
+        anyhow::bail!("Error from {} - status {}", &url, resp.status());
+    }
+    let text = resp.text()?;
+    trace!("Raw response: {}", &text);
+    let data: T = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse response from {}", &url))?;
+    trace!("Raw deserialisation: {:?}", &data);
+    Ok(data)
+}
+
+/// Parse an ISO 8601 duration like `PT1H2M3S` into seconds
+fn parse_duration(iso: &str) -> Option<i32> {
+    let rest = iso.strip_prefix('P')?;
+    let mut total = 0;
+    let mut num = String::new();
+    for c in rest.chars() {
+        match c {
+            'T' => {}
+            '0'..='9' => num.push(c),
+            unit => {
+                let n: i32 = num.parse().ok()?;
+                num.clear();
+                total += n * match unit {
+                    'W' => 7 * 24 * 3600,
+                    'D' => 24 * 3600,
+                    'H' => 3600,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total)
+}
+
+/// Object to query data about a channel using the official YouTube Data API
+#[derive(Debug)]
+pub struct YoutubeApiQuery<'a> {
+    chan_id: &'a YoutubeID,
+    key: String,
+    rate_limit: RateLimit,
+}
+
+impl<'a> YoutubeApiQuery<'a> {
+    pub fn new(chan_id: &YoutubeID, key: String) -> YoutubeApiQuery<'_> {
+        YoutubeApiQuery {
+            chan_id,
+            key,
+            rate_limit: RateLimit::default(),
+        }
+    }
+
+    fn get_channel(&self) -> Result<ApiChannel> {
+        self.rate_limit.wait();
+        let d: ApiChannelList = request_data(
+            &self.key,
+            "channels",
+            &[
+                ("part", "snippet,contentDetails,statistics,brandingSettings"),
+                ("id", &self.chan_id.id),
+            ],
+        )?;
+        d.items
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Channel {} not found", &self.chan_id.id))
+    }
+
+    /// Get one page of the uploads playlist, with durations looked up from the videos endpoint
+    fn get_page(
+        &self,
+        uploads: &str,
+        page_token: Option<&str>,
+    ) -> Result<(Vec<VideoInfo>, Option<String>)> {
+        self.rate_limit.wait();
+        let mut params = vec![
+            ("part", "snippet,contentDetails"),
+            ("playlistId", uploads),
+            ("maxResults", "50"),
+        ];
+        if let Some(t) = page_token {
+            params.push(("pageToken", t));
+        }
+        let page: ApiPlaylistItemList = request_data(&self.key, "playlistItems", &params)?;
+
+        // Durations, live status and view counts are not part of the playlist items, so look them up in one batch
+        let ids = page
+            .items
+            .iter()
+            .map(|i| i.snippet.resource_id.video_id.as_str())
+            .collect::<Vec<&str>>()
+            .join(",");
+        let details: HashMap<String, ApiVideo> = if ids.is_empty() {
+            HashMap::new()
+        } else {
+            let vids: ApiVideoList = request_data(
+                &self.key,
+                "videos",
+                &[("part", "contentDetails,snippet,statistics"), ("id", &ids)],
+            )?;
+            vids.items.into_iter().map(|v| (v.id.clone(), v)).collect()
+        };
+
+        let mut ret: Vec<VideoInfo> = vec![];
+        for item in page.items {
+            let s = item.snippet;
+            let published_str = item
+                .content_details
+                .and_then(|c| c.video_published_at)
+                .unwrap_or(s.published_at);
+            let published_at = chrono::DateTime::parse_from_rfc3339(&published_str)
+                .with_context(|| format!("Invalid publishedAt {:?}", &published_str))?
+                .with_timezone(&chrono::Utc);
+            let id = s.resource_id.video_id;
+            let detail = details.get(&id);
+            let live_status = match detail
+                .and_then(|v| v.snippet.as_ref())
+                .and_then(|s| s.live_broadcast_content.as_deref())
+            {
+                Some("live") => LiveStatus::Live,
+                Some("upcoming") => LiveStatus::Upcoming,
+                _ => LiveStatus::NotLive,
+            };
+            ret.push(VideoInfo {
+                url: format!("http://youtube.com/watch?v={id}", id = id),
+                duration: detail
+                    .and_then(|v| parse_duration(&v.content_details.duration))
+                    .unwrap_or(0),
+                live_status,
+                view_count: detail
+                    .and_then(|v| v.statistics.as_ref())
+                    .and_then(|s| s.view_count.as_ref())
+                    .and_then(|n| n.parse().ok()),
+                captions: vec![],
+                scheduled_at: None,
+                members_only: false,
+                id,
+                title: s.title,
+                title_alt: None,
+                description: s.description,
+                description_alt: None,
+                thumbnail_url: s.thumbnails.default.map(|t| t.url).unwrap_or_default(),
+                thumbnails: vec![],
+                published_at,
+            });
+        }
+
+        Ok((ret, page.next_page_token))
+    }
+}
+
+impl<'a> crate::source::base::ChannelData for YoutubeApiQuery<'a> {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        let c = self.get_channel()?;
+        Ok(ChannelMetadata {
+            title: c.snippet.title,
+            thumbnail: c
+                .snippet
+                .thumbnails
+                .default
+                .map(|t| t.url)
+                .unwrap_or_default(),
+            description: c.snippet.description,
+            banner: c
+                .branding_settings
+                .and_then(|b| b.image)
+                .and_then(|i| i.banner_external_url),
+            subscriber_count: c
+                .statistics
+                .and_then(|s| s.subscriber_count)
+                .and_then(|n| n.parse().ok()),
+        })
+    }
+
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        // GET /youtube/v3/playlistItems?playlistId=UU..
+
+        let uploads = match self.get_channel() {
+            Ok(c) => c.content_details.related_playlists.uploads,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+
+        // The first page has no page token
+        Box::new(paged(None, move |token: Option<String>| {
+            let (items, next) = self.get_page(&uploads, token.as_deref())?;
+            Ok((items, next.map(Some)))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::base::ChannelData;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT1H2M3S"), Some(3723));
+        assert_eq!(parse_duration("PT10M"), Some(600));
+        assert_eq!(parse_duration("P1DT1S"), Some(86401));
+        assert_eq!(parse_duration("PT0S"), Some(0));
+        assert_eq!(parse_duration("garbage"), None);
+    }
+
+    #[test]
+    fn test_api_video_list() -> Result<()> {
+        let _m1 = mockito::mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_file("testdata/ytapi_channel.json")
+            .create();
+        let _m2 = mockito::mock("GET", "/youtube/v3/playlistItems")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_file("testdata/ytapi_playlistitems.json")
+            .create();
+        let _m3 = mockito::mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_file("testdata/ytapi_videos.json")
+            .create();
+
+        let cid = YoutubeID {
+            id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: None,
+        };
+        let api = YoutubeApiQuery::new(&cid, "testkey".into());
+
+        let meta = api.get_metadata()?;
+        assert_eq!(meta.title, "thegreatsd");
+        assert_eq!(meta.subscriber_count, Some(61));
+        assert_eq!(
+            meta.banner.as_deref(),
+            Some("https://yt3.googleusercontent.com/examplebanner")
+        );
+
+        let result = api.videos().collect::<Result<Vec<VideoInfo>>>()?;
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, "abcdefghijk");
+        assert_eq!(result[0].url, "http://youtube.com/watch?v=abcdefghijk");
+        assert_eq!(result[0].duration, 754);
+        assert_eq!(result[0].live_status, LiveStatus::Live);
+        assert_eq!(result[0].view_count, Some(1547));
+        assert_eq!(result[1].title, "An older upload");
+        assert_eq!(result[1].duration, 61);
+        assert_eq!(result[1].live_status, LiveStatus::NotLive);
+        assert_eq!(result[1].view_count, None);
+        Ok(())
+    }
+}
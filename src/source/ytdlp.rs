@@ -0,0 +1,301 @@
+use std::io::{BufRead, BufReader, Lines};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use anyhow::{Context, Result};
+use chrono::offset::TimeZone;
+use log::{debug, trace};
+
+use crate::common::{ChannelID, LiveStatus};
+use crate::source::base::{ChannelMetadata, VideoInfo};
+
+/// yt-dlp executable - the configured downloader if it is yt-dlp, as youtube-dl lacks some options used here
+fn ytdlp_binary() -> String {
+    let dl = crate::download::Downloader::from_config(&crate::config::Config::load());
+    match dl.kind {
+        crate::download::DownloaderKind::YtDlp => dl.binary,
+        crate::download::DownloaderKind::YoutubeDl => "yt-dlp".into(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct YtdlpThumbnail {
+    id: Option<String>,
+    url: String,
+    width: Option<i32>,
+}
+
+/// A single line of `--flat-playlist --dump-json` output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct YtdlpEntry {
+    id: String,
+    url: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    duration: Option<f64>,
+    timestamp: Option<i64>,
+    /// e.g `is_live`, `is_upcoming`, `was_live` or `not_live`
+    live_status: Option<String>,
+    view_count: Option<i64>,
+    /// Scheduled start time of upcoming streams/premieres
+    release_timestamp: Option<i64>,
+    /// e.g `public`, `subscriber_only` or `premium_only`
+    availability: Option<String>,
+    #[serde(default)]
+    thumbnails: Vec<YtdlpThumbnail>,
+}
+
+/// Output of `--dump-single-json` on a channel/playlist
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct YtdlpPlaylist {
+    title: Option<String>,
+    channel: Option<String>,
+    uploader: Option<String>,
+    description: Option<String>,
+    channel_follower_count: Option<i64>,
+    #[serde(default)]
+    thumbnails: Vec<YtdlpThumbnail>,
+}
+
+/// URL which yt-dlp understands for the given channel
+fn channel_url(cid: &ChannelID) -> String {
+    match cid {
+        ChannelID::Youtube(x) => format!("https://www.youtube.com/channel/{}/videos", x.id),
+        ChannelID::YoutubePlaylist(x) => format!("https://www.youtube.com/playlist?list={}", x.id),
+        ChannelID::Vimeo(x) => format!("https://vimeo.com/user{}", x.id),
+        ChannelID::Soundcloud(x) => format!("https://api.soundcloud.com/users/{}", x.id),
+        ChannelID::Rumble(x) => format!("https://rumble.com/{}", x.id),
+        ChannelID::Bitchute(x) => format!("https://www.bitchute.com/channel/{}/", x.id),
+    }
+}
+
+/// Choose the smallest thumbnail of a video (which is the most similar to Invidious' "default" quality)
+fn choose_video_thumbnail(thumbs: &[YtdlpThumbnail]) -> String {
+    thumbs
+        .iter()
+        .min_by_key(|t| t.width.unwrap_or(i32::MAX))
+        .map(|t| t.url.clone())
+        .unwrap_or_default()
+}
+
+/// Choose the channel avatar from the channel's thumbnails (which also contain banners)
+fn choose_channel_thumbnail(thumbs: &[YtdlpThumbnail]) -> String {
+    thumbs
+        .iter()
+        .find(|t| t.id.as_deref().unwrap_or("").contains("avatar"))
+        .or_else(|| thumbs.first())
+        .map(|t| t.url.clone())
+        .unwrap_or_default()
+}
+
+/// Choose the widest banner from the channel's thumbnails, if there are any
+fn choose_channel_banner(thumbs: &[YtdlpThumbnail]) -> Option<String> {
+    thumbs
+        .iter()
+        .filter(|t| t.id.as_deref().unwrap_or("").contains("banner"))
+        .max_by_key(|t| t.width.unwrap_or(0))
+        .map(|t| t.url.clone())
+}
+
+/// Convert one line of JSON output into a `VideoInfo`
+fn parse_entry(line: &str) -> Result<VideoInfo> {
+    let d: YtdlpEntry = serde_json::from_str(line)
+        .with_context(|| format!("Failed to parse yt-dlp output {}", line))?;
+    trace!("Raw deserialisation: {:?}", &d);
+
+    // Flat playlists only have an approximate date, if any
+    let published_at = match d.timestamp {
+        Some(ts) => chrono::Utc
+            .timestamp_opt(ts, 0)
+            .single()
+            .unwrap_or_else(chrono::Utc::now),
+        None => chrono::Utc::now(),
+    };
+
+    let url = match d.url {
+        Some(u) => u,
+        None => format!("http://youtube.com/watch?v={}", d.id),
+    };
+
+    let scheduled_at = match d.live_status.as_deref() {
+        Some("is_upcoming") => d
+            .release_timestamp
+            .and_then(|ts| chrono::Utc.timestamp_opt(ts, 0).single()),
+        _ => None,
+    };
+
+    Ok(VideoInfo {
+        url,
+        thumbnail_url: choose_video_thumbnail(&d.thumbnails),
+        thumbnails: vec![],
+        id: d.id,
+        title: d.title.unwrap_or_default(),
+        title_alt: None,
+        description: d.description.unwrap_or_default(),
+        description_alt: None,
+        published_at,
+        duration: d.duration.unwrap_or(0.0) as i32,
+        live_status: match d.live_status.as_deref() {
+            Some("is_live") => LiveStatus::Live,
+            Some("is_upcoming") => LiveStatus::Upcoming,
+            _ => LiveStatus::NotLive,
+        },
+        view_count: d.view_count,
+        captions: vec![],
+        scheduled_at,
+        members_only: matches!(
+            d.availability.as_deref(),
+            Some("subscriber_only" | "premium_only" | "needs_auth")
+        ),
+    })
+}
+
+/// Lazily reads lines from a running yt-dlp process, killing it if dropped early
+struct YtdlpLines {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl Iterator for YtdlpLines {
+    type Item = std::io::Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next()
+    }
+}
+
+impl Drop for YtdlpLines {
+    fn drop(&mut self) {
+        // Process is probably still paginating if iteration stopped early
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Object to query a channel by running yt-dlp, useful when no Invidious instance is working
+#[derive(Debug)]
+pub struct YtdlpQuery {
+    url: String,
+    binary: String,
+}
+
+impl YtdlpQuery {
+    pub fn new(cid: &ChannelID) -> YtdlpQuery {
+        YtdlpQuery {
+            url: channel_url(cid),
+            binary: ytdlp_binary(),
+        }
+    }
+
+    fn spawn(&self, args: &[&str]) -> Result<YtdlpLines> {
+        debug!(
+            "Running {} with args {:?} {}",
+            &self.binary, args, &self.url
+        );
+        let mut child = Command::new(&self.binary)
+            .args(args)
+            .arg(&self.url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", &self.binary))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to read stdout of {}", &self.binary))?;
+        Ok(YtdlpLines {
+            child,
+            lines: BufReader::new(stdout).lines(),
+        })
+    }
+}
+
+impl crate::source::base::ChannelData for YtdlpQuery {
+    fn get_metadata(&self) -> Result<ChannelMetadata> {
+        let mut lines = self.spawn(&[
+            "--flat-playlist",
+            "--dump-single-json",
+            "--playlist-items",
+            "1",
+        ])?;
+        let line = lines.next().ok_or_else(|| {
+            anyhow::anyhow!("No output from {} for {}", &self.binary, &self.url)
+        })??;
+        let d: YtdlpPlaylist = serde_json::from_str(&line)
+            .with_context(|| format!("Failed to parse yt-dlp output for {}", &self.url))?;
+
+        Ok(ChannelMetadata {
+            title: d.channel.or(d.uploader).or(d.title).unwrap_or_default(),
+            thumbnail: choose_channel_thumbnail(&d.thumbnails),
+            description: d.description.unwrap_or_default(),
+            banner: choose_channel_banner(&d.thumbnails),
+            subscriber_count: d.channel_follower_count,
+        })
+    }
+
+    fn videos<'i>(&'i self) -> Box<dyn Iterator<Item = Result<VideoInfo>> + 'i> {
+        let lines = match self.spawn(&[
+            "--flat-playlist",
+            "--dump-json",
+            "--extractor-args",
+            "youtubetab:approximate_date",
+        ]) {
+            Ok(l) => l,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+
+        Box::new(lines.map(|line| parse_entry(&line?)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_entry() -> Result<()> {
+        let line = r#"{"_type": "url", "ie_key": "Youtube", "id": "dQw4w9WgXcQ", "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ", "title": "A video", "description": null, "duration": 212.0, "timestamp": 1256453880, "thumbnails": [{"url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg", "height": 270, "width": 480}, {"url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/default.jpg", "height": 90, "width": 120}]}"#;
+        let v = parse_entry(line)?;
+        assert_eq!(v.id, "dQw4w9WgXcQ");
+        assert_eq!(v.url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(v.title, "A video");
+        assert_eq!(v.description, "");
+        assert_eq!(v.duration, 212);
+        assert_eq!(
+            v.thumbnail_url,
+            "https://i.ytimg.com/vi/dQw4w9WgXcQ/default.jpg"
+        );
+        assert_eq!(v.published_at.to_rfc3339(), "2009-10-25T06:58:00+00:00");
+        assert_eq!(v.live_status, LiveStatus::NotLive);
+
+        let live = parse_entry(r#"{"id": "abc", "live_status": "is_live"}"#)?;
+        assert_eq!(live.live_status, LiveStatus::Live);
+        assert_eq!(live.scheduled_at, None);
+        assert!(!live.members_only);
+
+        let members = parse_entry(r#"{"id": "abc", "availability": "subscriber_only"}"#)?;
+        assert!(members.members_only);
+
+        let upcoming = parse_entry(
+            r#"{"id": "abc", "live_status": "is_upcoming", "release_timestamp": 1256453880}"#,
+        )?;
+        assert_eq!(upcoming.live_status, LiveStatus::Upcoming);
+        assert_eq!(
+            upcoming.scheduled_at.map(|x| x.to_rfc3339()).as_deref(),
+            Some("2009-10-25T06:58:00+00:00")
+        );
+
+        assert!(parse_entry("garbagenonsense").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_channel_url() {
+        let cid = ChannelID::Youtube(crate::common::YoutubeID {
+            id: "UCUBfKCp83QT19JCUekEdxOQ".into(),
+            handle: None,
+        });
+        assert_eq!(
+            channel_url(&cid),
+            "https://www.youtube.com/channel/UCUBfKCp83QT19JCUekEdxOQ/videos"
+        );
+    }
+}
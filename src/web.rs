@@ -1,105 +1,178 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::io::Write;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::Result;
 use askama::Template;
 use lazy_static::lazy_static;
-use log::info;
+use log::{debug, info, warn};
+use ratelimit_meter::{KeyedRateLimiter, NonConformance, GCRA};
 use rouille::{router, Request, Response};
 use serde_derive::Serialize;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
-use crate::common::VideoStatus;
+use crate::common::{Backend, Service, VideoStatus};
 use crate::config::Config;
-use crate::db::{Channel, DBVideoInfo, FilterParams};
+use crate::db::{Channel, DBVideoInfo, FilterParams, VideoCursor, VideoOrder};
+use crate::events::Event;
+use crate::source::base::Chapter;
 use crate::worker::WorkerPool;
 
-#[derive(Clone)]
-pub(crate) struct Image {
-    pub(crate) data: Vec<u8>,
-    pub(crate) content_type: String,
-}
+/// Period `VIDL_POST_RATE_LIMIT` requests are allowed in
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
 
-pub(crate) struct ImageCache {
-    images: HashMap<String, Image>,
+lazy_static! {
+    static ref BASE_PATH: String = Config::load().base_path;
+    static ref CSRF_TOKEN: String = random_token();
+    /// POST requests from each client address, or `None` if not limited
+    static ref POST_LIMITER: Mutex<Option<KeyedRateLimiter<std::net::IpAddr, GCRA>>> =
+        Mutex::new(
+            std::num::NonZeroU32::new(Config::load().post_rate_limit)
+                .map(|n| KeyedRateLimiter::new(n, RATE_LIMIT_WINDOW))
+        );
 }
 
-#[derive(Clone)]
-enum ImageCacheResponse {
-    Redirect(String),
-    Image(Image),
+/// Path the web interface is served under (like `/vidl`, or empty), which all links start with
+pub fn base_path() -> &'static str {
+    &BASE_PATH
 }
 
-impl ImageCache {
-    fn new() -> Self {
-        ImageCache {
-            images: HashMap::new(),
-        }
-    }
-
-    pub(crate) fn contains(&self, url: &str) -> bool {
-        self.images.contains_key(url)
-    }
-
-    fn get(
-        &mut self,
-        url: String,
-        worker: Arc<Mutex<crate::worker::WorkerPool>>,
-    ) -> Result<ImageCacheResponse> {
-        if self.images.contains_key(&url) {
-            let cached = self.images.get(&url);
-            Ok(ImageCacheResponse::Image((*cached.unwrap()).clone()))
-        } else {
-            let thready_url: String = url.clone();
-            let pool = worker.lock().unwrap();
-            pool.enqueue(crate::worker::WorkItem::ThumbnailCache(thready_url));
-
-            Ok(ImageCacheResponse::Redirect(url.into()))
-        }
-    }
+/// Token which pages include in their POST requests, so requests from other sites (which can't
+/// read the pages) can be told apart. Changes each time vidl is started
+pub fn csrf_token() -> &'static str {
+    &CSRF_TOKEN
+}
 
-    pub(crate) fn add(&mut self, url: &str, img: Image) {
-        self.images.insert(url.into(), img);
-    }
+/// 128 random bits as hex, from the OS-seeded keys `HashMap` uses
+fn random_token() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    (0..2)
+        .map(|_| {
+            let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+            h.write_u128(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            format!("{:016x}", h.finish())
+        })
+        .collect()
 }
 
-lazy_static! {
-    pub(crate) static ref IMG_CACHE: Mutex<ImageCache> = Mutex::new(ImageCache::new());
+/// Link to `path` of the web interface
+fn link(path: &str) -> String {
+    format!("{}{}", base_path(), path)
 }
 
-#[derive(Debug, Serialize)]
+/// Number of videos with each status
+#[derive(Debug, Serialize, ToSchema)]
 pub struct WebChannelStats {
     grabbed: usize,
     new: usize,
+    /// Ignored, queued, failed etc
     other: usize,
+    /// Failed downloads, also counted in `other`
+    failed: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct WebChannel {
     id: i64,
     chanid: String,
     service: String,
     title: String,
     icon: String,
+    /// Backend name, or empty string if using the configured default
+    backend: String,
+    description: String,
+    /// Banner URL, or empty string if there is none
+    banner: String,
+    subscriber_count: Option<i64>,
+    /// Youtube handle or username, or empty string
+    handle: String,
+    /// Download format, or empty string to use the configured default
+    format: String,
+    /// `on`, `off`, or empty string to use the configured default
+    subtitles: String,
+    /// Download directory, or empty string to use the configured default
+    download_dir: String,
+    /// Output template, or empty string to use the configured default
+    filename_format: String,
+    /// Auto-ignore rules, as empty strings when not set
+    ignore_min_duration: String,
+    ignore_max_duration: String,
+    ignore_title: String,
+    /// Videos published in the last week
     stats_1w: WebChannelStats,
     stats_all: WebChannelStats,
+    /// Total size in bytes of downloaded files, where known
+    disk_usage: u64,
 }
 
 impl WebChannel {
     fn new(src: Channel, db: &crate::db::Database) -> Result<WebChannel> {
         let stats_1w = src.stats_1w(db)?.into();
         let stats_all = src.stats_all(db)?.into();
+        let disk_usage = src.disk_usage(db)?;
         Ok(WebChannel {
             id: src.id,
             chanid: src.chanid,
             service: src.service.as_str().into(),
             title: src.title,
             icon: src.thumbnail,
+            backend: src.backend.map(|b| b.as_str().into()).unwrap_or_default(),
+            description: src.description,
+            banner: src.banner.unwrap_or_default(),
+            subscriber_count: src.subscriber_count,
+            handle: src.handle.unwrap_or_default(),
+            format: src.format.unwrap_or_default(),
+            ignore_min_duration: src
+                .ignore_rules
+                .min_duration
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            ignore_max_duration: src
+                .ignore_rules
+                .max_duration
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            ignore_title: src.ignore_rules.title.clone().unwrap_or_default(),
+            download_dir: src.download_dir.unwrap_or_default(),
+            filename_format: src.filename_format.unwrap_or_default(),
+            subtitles: match src.subtitles {
+                Some(true) => "on".into(),
+                Some(false) => "off".into(),
+                None => "".into(),
+            },
             stats_1w,
             stats_all,
+            disk_usage,
         })
     }
+
+    /// Like `1.5 GiB`
+    pub fn disk_usage_str(&self) -> String {
+        size_str(self.disk_usage)
+    }
+}
+
+/// Size in bytes as a binary multiple, like `123.4 MiB`
+fn size_str(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
 }
 
 impl From<crate::db::ChannelStats> for WebChannelStats {
@@ -108,13 +181,16 @@ impl From<crate::db::ChannelStats> for WebChannelStats {
             grabbed: src.grabbed,
             new: src.new,
             other: src.other,
+            failed: src.failed,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct WebChannelList {
     channels: Vec<WebChannel>,
+    /// Number of channels, not including archived ones
+    total: i64,
 }
 
 impl WebChannelList {
@@ -124,11 +200,12 @@ impl WebChannelList {
             let c = WebChannel::new(p, db)?;
             channels.push(c);
         }
-        Ok(WebChannelList { channels })
+        let total = crate::db::count_channels(db)?;
+        Ok(WebChannelList { channels, total })
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct WebVideoInfo<'a> {
     id: i64,
     video_id: String,
@@ -142,6 +219,54 @@ pub struct WebVideoInfo<'a> {
     status_class: String,
     channel: &'a WebChannel,
     duration: i32,
+    /// `live` or `upcoming` for streams, empty for normal videos
+    live_status: String,
+    view_count: Option<i64>,
+    tags: Vec<String>,
+    /// Language codes of available captions
+    captions: Vec<String>,
+    /// Start time of upcoming premieres/streams, or empty string
+    scheduled_at: String,
+    chapters: Vec<WebChapter>,
+    /// Downloaded, so can be watched from vidl
+    grabbed: bool,
+    /// Download failed, so can be retried
+    failed: bool,
+    /// Marked as watched, independent of the status
+    watched: bool,
+    /// When it was marked as watched, or empty string if unknown or not watched
+    watched_at: String,
+    /// Notes written in vidl, or empty string
+    notes: String,
+    /// Why the download failed, or empty string if it didn't
+    error_message: String,
+    /// Date the source last listed the video, like `2023-01-31`, or empty string if unknown
+    last_seen: String,
+    /// Date the video was found to be no longer listed by the source, like `2023-01-31`, or
+    /// empty string if it still is
+    removed_at: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebChapter {
+    /// Start time like `1:02:03`
+    start: String,
+    title: String,
+}
+
+impl From<Chapter> for WebChapter {
+    fn from(src: Chapter) -> WebChapter {
+        let (h, m, s) = (src.start / 3600, src.start / 60 % 60, src.start % 60);
+        let start = if h > 0 {
+            format!("{}:{:02}:{:02}", h, m, s)
+        } else {
+            format!("{}:{:02}", m, s)
+        };
+        WebChapter {
+            start,
+            title: src.title,
+        }
+    }
 }
 
 impl<'a> WebVideoInfo<'a> {
@@ -149,6 +274,13 @@ impl<'a> WebVideoInfo<'a> {
         format!("{}m{}", self.duration / 60, self.duration % 60)
     }
 
+    pub fn view_count_str(&self) -> String {
+        match self.view_count {
+            Some(n) => crate::i18n::tr_args("{} views.", &[&n]),
+            None => "".into(),
+        }
+    }
+
     pub fn get_title(&self) -> &str {
         if let Some(t) = &self.title_alt {
             &t
@@ -173,6 +305,10 @@ fn status_css_class(status: VideoStatus) -> String {
         VideoStatus::Grabbed => "ytdl-grabbed",
         VideoStatus::GrabError => "ytdl-graberror",
         VideoStatus::Ignore => "ytdl-ignore",
+        VideoStatus::Upcoming => "ytdl-upcoming",
+        VideoStatus::Unavailable => "ytdl-unavailable",
+        VideoStatus::Removed => "ytdl-removed",
+        VideoStatus::Corrupt => "ytdl-corrupt",
     }
     .into()
 }
@@ -193,27 +329,512 @@ impl<'a> From<(DBVideoInfo, &'a WebChannel)> for WebVideoInfo<'a> {
             status_class: status_css_class(src.status),
             channel: chan,
             duration: src.info.duration,
+            live_status: if src.info.live_status.is_ongoing() {
+                src.info.live_status.as_str().into()
+            } else {
+                "".into()
+            },
+            view_count: src.info.view_count,
+            tags: vec![],
+            captions: src.info.captions,
+            scheduled_at: src
+                .info
+                .scheduled_at
+                .map(|x| x.to_rfc3339())
+                .unwrap_or_default(),
+            chapters: vec![],
+            grabbed: src.status == VideoStatus::Grabbed,
+            failed: src.status == VideoStatus::GrabError,
+            watched: src.watched,
+            watched_at: src.watched_at.map(|x| x.to_rfc3339()).unwrap_or_default(),
+            notes: src.notes.unwrap_or_default(),
+            error_message: if src.status == VideoStatus::GrabError {
+                src.error_message.unwrap_or_default()
+            } else {
+                "".into()
+            },
+            last_seen: src
+                .last_seen
+                .map(|x| x.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            removed_at: src
+                .removed_at
+                .map(|x| x.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct WebChannelVideos<'a> {
     videos: Vec<(String, Vec<WebVideoInfo<'a>>)>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct WebPlaylist {
+    id: String,
+    title: String,
+    thumbnail: String,
+    video_count: Option<i64>,
+    /// SQL ID of the channel if this playlist has already been added
+    tracked: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "playlist_list.html")]
+struct PlaylistListTemplate<'a> {
+    chan: &'a WebChannel,
+    playlists: &'a [WebPlaylist],
+}
+
+fn page_chan_playlists(chanid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Channel::get_by_sqlid(&db, chanid)?;
+
+    let cid = c.service.get_channel_id(&c.chanid);
+    let api = crate::source::channel_data(&cid, c.backend.unwrap_or(cfg.backend));
+    let mut playlists: Vec<WebPlaylist> = vec![];
+    for p in api.playlists()? {
+        let tracked = crate::db::Channel::get(
+            &db,
+            &crate::common::Service::YoutubePlaylist.get_channel_id(&p.id),
+        )
+        .ok()
+        .map(|t| t.id);
+        playlists.push(WebPlaylist {
+            id: p.id,
+            title: p.title,
+            thumbnail: p.thumbnail,
+            video_count: p.video_count,
+            tracked,
+        });
+    }
+
+    let chan = WebChannel::new(c, &db)?;
+    let t = PlaylistListTemplate {
+        chan: &chan,
+        playlists: &playlists,
+    };
+    Ok(Response::html(t.render()?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/playlist/add",
+    tag = "channels",
+    params(("id" = String, Query, description = "Playlist ID or URL")),
+    responses((status = 303, description = "Added, redirecting to the new channel")),
+)]
+fn page_add_playlist(playlist_id: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let cid =
+        crate::source::find_channel_id(playlist_id, &crate::common::Service::YoutubePlaylist)?;
+    let c = crate::db::Channel::create_from_source(&db, &cid, None)?;
+
+    // Redirect to the new channel
+    Ok(Response::redirect_303(link(&format!("/channel/{}", c.id))))
+}
+
+/// Collection of videos chosen by hand, with the number of videos in it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebCollection {
+    id: i64,
+    name: String,
+    video_count: i64,
+}
+
+impl WebCollection {
+    fn new(c: crate::db::Collection, db: &crate::db::Database) -> Result<WebCollection> {
+        let video_count = c.count_videos(db)?;
+        Ok(WebCollection {
+            id: c.id,
+            name: c.name,
+            video_count,
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "collection_list.html")]
+struct CollectionListTemplate<'a> {
+    collections: &'a [WebCollection],
+}
+
+/// All collections, by name
+#[utoipa::path(
+    get,
+    path = "/api/v1/collections",
+    tag = "collections",
+    responses((status = 200, description = "Collections", body = [WebCollection])),
+)]
+fn page_collection_list(as_json: bool) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let mut collections = vec![];
+    for c in crate::db::list_collections(&db)? {
+        collections.push(WebCollection::new(c, &db)?);
+    }
+
+    if as_json {
+        return Ok(Response::json(&collections));
+    }
+    let t = CollectionListTemplate {
+        collections: &collections,
+    };
+    Ok(Response::html(t.render()?))
+}
+
+/// Create empty collection
+#[utoipa::path(
+    post,
+    path = "/collections/create",
+    tag = "collections",
+    params(("name" = String, Query, description = "Name, which must be unique. Also accepted as a form field")),
+    responses((status = 303, description = "Created, redirecting to the collection")),
+)]
+fn page_create_collection(name: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Collection::create(&db, name)?;
+    Ok(Response::redirect_303(link(&format!(
+        "/collection/{}",
+        c.id
+    ))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/collection/{collid}/rename",
+    tag = "collections",
+    params(
+        ("collid" = i64, Path, description = "Collection ID"),
+        ("name" = String, Query, description = "New name. Also accepted as a form field"),
+    ),
+    responses((status = 303, description = "Renamed, redirecting to the collection")),
+)]
+fn page_rename_collection(collid: i64, name: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    crate::db::Collection::get_by_sqlid(&db, collid)?.rename(&db, name)?;
+    Ok(Response::redirect_303(link(&format!(
+        "/collection/{}",
+        collid
+    ))))
+}
+
+/// Delete collection, leaving the videos in it
+#[utoipa::path(
+    post,
+    path = "/collection/{collid}/delete",
+    tag = "collections",
+    params(("collid" = i64, Path, description = "Collection ID")),
+    responses((status = 303, description = "Deleted, redirecting to the collections")),
+)]
+fn page_delete_collection(collid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    crate::db::Collection::get_by_sqlid(&db, collid)?.delete(&db)?;
+    Ok(Response::redirect_303(link("/collections")))
+}
+
+/// Add video to collection, or remove it
+#[utoipa::path(
+    post,
+    path = "/collection/{collid}/{action}",
+    tag = "collections",
+    params(
+        ("collid" = i64, Path, description = "Collection ID"),
+        ("action" = String, Path, description = "`add` or `remove`"),
+        ("video" = i64, Query, description = "Video ID. Also accepted as a form field"),
+    ),
+    responses((status = 303, description = "Changed, redirecting to the video when adding or the collection when removing")),
+)]
+fn page_collection_video(collid: i64, videoid: i64, add: bool) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Collection::get_by_sqlid(&db, collid)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    if add {
+        c.add_video(&db, &v)?;
+        Ok(Response::redirect_303(link(&format!("/video/{}", videoid))))
+    } else {
+        c.remove_video(&db, &v)?;
+        Ok(Response::redirect_303(link(&format!(
+            "/collection/{}",
+            collid
+        ))))
+    }
+}
+
+/// Video waiting for, or being, downloaded
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebQueueItem {
+    id: i64,
+    title: String,
+    /// False if still waiting for a worker
+    downloading: bool,
+    /// `None` until the download reports progress
+    progress: Option<crate::download::DownloadProgress>,
+}
+
+/// Like `45.3% at 2.34MiB/s, ETA 00:31`
+fn progress_text(p: &crate::download::DownloadProgress) -> String {
+    let mut ret = format!("{:.1}%", p.percent);
+    if let Some(speed) = &p.speed {
+        ret.push_str(&format!(" at {}", speed));
+    }
+    if let Some(eta) = &p.eta {
+        ret.push_str(&format!(", ETA {}", eta));
+    }
+    ret
+}
+
+impl WebQueueItem {
+    pub fn progress_str(&self) -> String {
+        match &self.progress {
+            None if self.downloading => crate::i18n::tr("Starting").into(),
+            None => crate::i18n::tr("Queued").into(),
+            Some(p) => progress_text(p),
+        }
+    }
+}
+
+/// Running downloads (with their progress) followed by queued videos
+fn download_queue(db: &crate::db::Database) -> Result<Vec<WebQueueItem>> {
+    let progress = crate::download::PROGRESS.lock().unwrap().clone();
+
+    let mut statuses = HashSet::new();
+    statuses.insert(VideoStatus::Downloading);
+    let mut videos = crate::db::all_videos(
+        db,
+        i64::MAX,
+        0,
+        Some(FilterParams {
+            name_contains: None,
+            status: Some(statuses),
+            status_not: None,
+            chanid: None,
+            collection: None,
+            tag: None,
+            search: None,
+            published_after: None,
+            published_before: None,
+            watched: None,
+            order_by: VideoOrder::default(),
+        }),
+    )?;
+    // Then queued videos, in the order they will be downloaded
+    videos.extend(crate::db::queued_videos(db)?);
+
+    let ret: Vec<WebQueueItem> = videos
+        .into_iter()
+        .map(|v| WebQueueItem {
+            progress: progress.get(&v.id).cloned(),
+            downloading: v.status == VideoStatus::Downloading,
+            id: v.id,
+            title: v.info.title,
+        })
+        .collect();
+    Ok(ret)
+}
+
+#[derive(Template)]
+#[template(path = "queue.html")]
+struct QueueTemplate<'a> {
+    queue: &'a [WebQueueItem],
+}
+
+/// Download queue, as JSON
+#[derive(Debug, Serialize, ToSchema)]
+struct QueueResponse<'a> {
+    /// Running downloads, then queued videos in the order they will be downloaded
+    queue: &'a [WebQueueItem],
+}
+
+/// Download queue, which is also shown as a page at `/queue`
+#[utoipa::path(
+    get,
+    path = "/api/queue",
+    tag = "queue",
+    responses((status = 200, description = "Download queue", body = QueueResponse)),
+)]
+fn page_queue(as_json: bool) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let queue = download_queue(&db)?;
+
+    if as_json {
+        return Ok(Response::json(&QueueResponse { queue: &queue }));
+    }
+    let t = QueueTemplate { queue: &queue };
+    Ok(Response::html(t.render()?))
+}
+
+/// What each worker is doing, how much it has done and its last error, and the number of work
+/// items waiting, to see whether downloads are actually happening
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers",
+    tag = "status",
+    responses((status = 200, description = "Worker pool state", body = crate::worker::PoolState)),
+)]
+fn page_workers() -> Response {
+    Response::json(&crate::worker::pool_state())
+}
+
+/// Format event as a server-sent event, with JSON data
+fn sse_message(event: &Event) -> String {
+    let data = match event {
+        Event::VideoStatus { video, status } => serde_json::json!({
+            "video": video,
+            "status": status.as_str(),
+            "status_class": status_css_class(*status),
+        }),
+        Event::Progress { video, progress } => serde_json::json!({
+            "video": video,
+            "percent": progress.percent,
+            "text": progress_text(progress),
+        }),
+        Event::ChannelUpdated { channel, added } => serde_json::json!({
+            "channel": channel,
+            "added": added,
+        }),
+    };
+    format!("event: {}\ndata: {}\n\n", event.name(), data)
+}
+
+/// Streams events to a client until it disconnects
+struct EventStream(Option<Receiver<Event>>);
+
+impl rouille::Upgrade for EventStream {
+    fn build(&mut self, mut socket: Box<dyn rouille::ReadWrite + Send>) {
+        let Some(recv) = self.0.take() else {
+            return;
+        };
+        loop {
+            let msg = match recv.recv_timeout(Duration::from_secs(15)) {
+                Ok(event) => sse_message(&event),
+                // Comment, so disconnected clients are noticed
+                Err(RecvTimeoutError::Timeout) => ":\n\n".into(),
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            if socket
+                .write_all(msg.as_bytes())
+                .and_then(|_| socket.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Server-sent events stream of video status changes, download progress and channel updates
+fn page_events() -> Response {
+    // The socket is taken over instead of using a response body, as chunked bodies are
+    // buffered. Without a length, the body is read until the connection closes
+    Response {
+        status_code: 200,
+        headers: vec![
+            ("Content-Type".into(), "text/event-stream".into()),
+            ("Cache-Control".into(), "no-cache".into()),
+        ],
+        data: rouille::ResponseBody::empty(),
+        upgrade: Some(Box::new(EventStream(Some(crate::events::subscribe())))),
+    }
+}
+
+/// How often the dashboard websocket is sent its state when nothing has changed
+const DASHBOARD_KEEPALIVE: Duration = Duration::from_secs(10);
+
+/// Send worker pool state and the download queue whenever they change, until the client disconnects
+fn push_dashboard(ws: &mut rouille::websocket::Websocket) -> Result<()> {
+    let events = crate::events::subscribe();
+    let cfg = Config::load();
+
+    let mut last_sent = String::new();
+    let mut last_send_at = std::time::Instant::now();
+    loop {
+        let pool = crate::worker::pool_state();
+        let queue = {
+            // Only held while reading, so idle sockets don't use up the pool
+            let db = crate::db::Database::pooled(&cfg)?;
+            download_queue(&db)?
+        };
+        let state = serde_json::json!({
+            "workers": pool.workers,
+            "pending": pool.pending,
+            "queue": queue,
+        })
+        .to_string();
+        // Also resent regularly, as the socket is never read from so a closed connection is
+        // only noticed when sending fails
+        if state != last_sent || last_send_at.elapsed() > DASHBOARD_KEEPALIVE {
+            ws.send_text(&state)
+                .map_err(|e| anyhow::anyhow!("Failed to send to websocket - {:?}", e))?;
+            last_sent = state;
+            last_send_at = std::time::Instant::now();
+        }
+
+        // Check again after anything changes, and regularly as workers start and finish
+        match events.recv_timeout(Duration::from_secs(1)) {
+            Ok(_) | Err(RecvTimeoutError::Timeout) => while events.try_recv().is_ok() {},
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        if ws.is_closed() {
+            return Ok(());
+        }
+    }
+}
+
+/// Websocket pushing what the workers are doing and the download queue with progress, as JSON
+fn page_ws(request: &Request) -> Result<Response> {
+    // Browsers let any site open a websocket, so check it's this one like a POST request
+    if let Some(source) = cross_site_source(request) {
+        warn!("Refused websocket from {:?}, which is another site", source);
+        return Ok(Response::text("Cross-site request refused").with_status_code(403));
+    }
+    let (response, websocket) = match rouille::websocket::start(request, None::<&str>) {
+        Ok(r) => r,
+        Err(e) => return Ok(Response::text(format!("{:?}", e)).with_status_code(400)),
+    };
+    std::thread::spawn(move || {
+        // Received once the response has been sent
+        let Ok(mut ws) = websocket.recv() else {
+            return;
+        };
+        if let Err(e) = push_dashboard(&mut ws) {
+            debug!("Websocket closed - {:?}", e);
+        }
+    });
+    Ok(response)
+}
+
 #[derive(Template)]
 #[template(path = "channel_list.html")]
 struct ChannelListTemplate<'a> {
     chans: &'a WebChannelList,
 }
 
-fn page_chan_list() -> Result<Response> {
+/// All channels with their settings and video counts, which are also shown as the main page
+#[utoipa::path(
+    get,
+    path = "/api/v1/channels",
+    tag = "channels",
+    responses((status = 200, description = "Channels", body = WebChannelList)),
+)]
+fn page_chan_list(as_json: bool) -> Result<Response> {
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    let db = crate::db::Database::pooled(&cfg)?;
     let chans = crate::db::list_channels(&db)?;
     let ret = WebChannelList::new(chans, &db)?;
 
+    if as_json {
+        return Ok(Response::json(&ret));
+    }
+
     let t = ChannelListTemplate { chans: &ret };
 
     let html = t.render()?;
@@ -225,24 +846,413 @@ fn page_chan_list() -> Result<Response> {
 struct VideoListTemplate<'a> {
     videos: &'a WebChannelVideos<'a>,
     page: i64,
+    /// Set when listing a single channel
+    channel: Option<&'a WebChannel>,
+    /// Set when listing a collection
+    collection: Option<WebCollection>,
+    /// Search text, set on the search page
+    search: Option<String>,
+    /// Comma-separated status codes being filtered by, like `NE,GR`
+    status: String,
+    per_page: i64,
+    /// Number of videos on all pages
+    total: i64,
+    /// Query string parameters other than the page number, like `&status=NE`
+    query: String,
+    /// Date range filtered by, like `2023-01-31`, or empty
+    published_after: String,
+    published_before: String,
+    /// `1` or `0` when filtering by watched flag, otherwise empty
+    watched: String,
+    /// Like `published`, as from `VideoOrder::as_str`
+    order_by: String,
+    /// Query parameters other than the page, dates, watched filter and order
+    hidden_params: Vec<(&'static str, String)>,
+}
+
+impl VideoListTemplate<'_> {
+    /// Link to another page of the same list
+    pub fn page_url(&self, page: i64) -> String {
+        format!("?page={}{}", page, self.query)
+    }
+
+    pub fn prev_page_url(&self) -> String {
+        self.page_url(self.page - 1)
+    }
+
+    pub fn next_page_url(&self) -> String {
+        self.page_url(self.page + 1)
+    }
+
+    pub fn last_page_url(&self) -> String {
+        self.page_url(self.last_page())
+    }
+
+    pub fn last_page(&self) -> i64 {
+        last_page(self.total, self.per_page)
+    }
+
+    /// Like `Page 1 of 3, videos 1-50 of 123`
+    pub fn page_str(&self) -> String {
+        crate::i18n::tr_args(
+            "Page {} of {}, videos {}",
+            &[
+                &(self.page + 1),
+                &(self.last_page() + 1),
+                &self.showing_str(),
+            ],
+        )
+    }
+
+    /// Like `51-100 of 321`
+    pub fn showing_str(&self) -> String {
+        let first = self.page * self.per_page;
+        let last = (first + self.per_page).min(self.total);
+        if first >= last {
+            crate::i18n::tr_args("0 of {}", &[&self.total])
+        } else {
+            crate::i18n::tr_args("{}-{} of {}", &[&(first + 1), &last, &self.total])
+        }
+    }
+}
+
+/// Default number of videos per page
+const PER_PAGE: i64 = 50;
+
+/// Number of the last page (numbered from 0) of `total` videos
+fn last_page(total: i64, per_page: i64) -> i64 {
+    ((total - 1) / per_page).max(0)
+}
+
+/// Page size from `per_page` query parameter
+fn per_page_param(request: &Request) -> i64 {
+    request
+        .get_param("per_page")
+        .and_then(|x| x.parse::<i64>().ok())
+        .unwrap_or(PER_PAGE)
+        .clamp(1, 500)
+}
+
+/// Query parameters reproducing `filter`, for links to other pages
+fn filter_params(filter: &Option<FilterParams>, per_page: i64) -> Vec<(&'static str, String)> {
+    let mut params = vec![];
+    if let Some(f) = filter {
+        if let Some(s) = &f.search {
+            params.push(("q", s.clone()));
+        }
+        if let Some(s) = status_param(f) {
+            params.push(("status", s));
+        }
+        if let Some(t) = &f.name_contains {
+            params.push(("title", t.clone()));
+        }
+        if let Some(t) = &f.tag {
+            params.push(("tag", t.clone()));
+        }
+        if let Some(t) = &f.published_after {
+            params.push(("published_after", t.format("%Y-%m-%d").to_string()));
+        }
+        if let Some(t) = &f.published_before {
+            params.push(("published_before", t.format("%Y-%m-%d").to_string()));
+        }
+        if let Some(w) = f.watched {
+            params.push(("watched", if w { "1" } else { "0" }.into()));
+        }
+        if f.order_by != VideoOrder::default() {
+            params.push(("order_by", f.order_by.as_str().into()));
+        }
+    }
+    if per_page != PER_PAGE {
+        params.push(("per_page", per_page.to_string()));
+    }
+    params
+}
+
+/// Query string like `&status=NE&tag=music`
+fn query_string(params: &[(&str, String)]) -> String {
+    use rouille::percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    params
+        .iter()
+        .map(|(k, v)| format!("&{}={}", k, utf8_percent_encode(v, NON_ALPHANUMERIC)))
+        .collect()
+}
+
+/// Page of a video list, as JSON
+#[derive(Debug, Serialize, ToSchema)]
+struct VideoListResponse<'a> {
+    /// Videos grouped by the date they're ordered by (published, or added), or in one group
+    /// with an empty date when ordered by duration or title
+    videos: &'a WebChannelVideos<'a>,
+    /// Page number, starting from 0
+    page: i64,
+    per_page: i64,
+    /// Number of videos in the list, on all pages
+    total: i64,
+    last_page: i64,
+    /// Given as `cursor` to get the videos after this page, or null if there are none
+    next_cursor: Option<String>,
+    /// Whether there are more videos after this page
+    has_more: bool,
+}
+
+/// Query parameters of all video lists
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListQuery {
+    /// Page number, starting from 0
+    #[param(value_type = Option<i64>)]
+    page: i64,
+    /// Videos per page, from 1 to 500. Defaults to 50
+    #[param(value_type = Option<i64>)]
+    per_page: i64,
+    /// Comma separated status codes, like `NE,GR`. Codes prefixed with `!` are excluded, like `!IG,!GR`
+    #[param(value_type = Option<String>)]
+    status: Option<HashSet<VideoStatus>>,
+    #[param(ignore)]
+    status_not: Option<HashSet<VideoStatus>>,
+    /// Only videos published on or after this date, like `2024-01-31`
+    #[param(value_type = Option<String>)]
+    published_after: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only videos published on or before this date
+    #[param(value_type = Option<String>)]
+    published_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// `next_cursor` of the previous page, to continue after its last video instead of using
+    /// `page`. Videos aren't skipped or repeated when new ones are added between requests
+    cursor: Option<String>,
+    /// `1` for only watched videos, or `0` for only unwatched
+    #[param(value_type = Option<String>)]
+    watched: Option<bool>,
+    /// `published` (newest first, the default), `added` (most recently added first), `duration`
+    /// (longest first) or `title`. Cursors can only be used when ordered by published date
+    #[param(value_type = Option<String>)]
+    order_by: VideoOrder,
+    /// Return JSON instead of a page, if set to any value
+    #[param(value_type = Option<bool>)]
+    json: bool,
+}
+
+impl ListQuery {
+    fn from_request(request: &Request) -> ListQuery {
+        let (status, status_not) = status_query(request);
+        ListQuery {
+            page: request
+                .get_param("page")
+                .and_then(|x| x.parse::<i64>().ok())
+                .unwrap_or(0),
+            per_page: per_page_param(request),
+            status,
+            status_not,
+            published_after: date_param(request, "published_after"),
+            published_before: date_param(request, "published_before"),
+            cursor: request.get_param("cursor").filter(|c| !c.is_empty()),
+            watched: request.get_param("watched").and_then(|x| match x.as_str() {
+                "1" | "true" => Some(true),
+                "0" | "false" => Some(false),
+                _ => None,
+            }),
+            order_by: request
+                .get_param("order_by")
+                .and_then(|x| VideoOrder::from_str(&x).ok())
+                .unwrap_or_default(),
+            json: request.get_param("json").is_some(),
+        }
+    }
+}
+
+/// Videos from all channels
+#[utoipa::path(
+    get,
+    path = "/channel/_all",
+    tag = "videos",
+    params(
+        ("title" = Option<String>, Query, description = "Only videos with titles containing this"),
+        ("tag" = Option<String>, Query, description = "Only videos with this tag"),
+        ListQuery,
+    ),
+    responses((status = 200, description = "Videos, as JSON with `?json`", body = VideoListResponse)),
+)]
+fn page_all_videos(request: &Request) -> Result<Response> {
+    let q = ListQuery::from_request(request);
+    let filter = FilterParams {
+        name_contains: request.get_param("title"),
+        status: q.status,
+        status_not: q.status_not,
+        chanid: None,
+        collection: None,
+        tag: request.get_param("tag"),
+        search: None,
+        published_after: q.published_after,
+        published_before: q.published_before,
+        watched: q.watched,
+        order_by: q.order_by,
+    };
+    page_list_videos(None, q.page, q.per_page, q.cursor, Some(filter), q.json)
+}
+
+/// Videos in a collection
+#[utoipa::path(
+    get,
+    path = "/collection/{collid}",
+    tag = "collections",
+    params(
+        ("collid" = i64, Path, description = "Collection ID"),
+        ListQuery,
+    ),
+    responses((status = 200, description = "Videos, as JSON with `?json`", body = VideoListResponse)),
+)]
+fn page_collection_videos(request: &Request, collid: i64) -> Result<Response> {
+    let q = ListQuery::from_request(request);
+    let filter = FilterParams {
+        name_contains: None,
+        status: q.status,
+        status_not: q.status_not,
+        chanid: None,
+        collection: Some(collid),
+        tag: None,
+        search: None,
+        published_after: q.published_after,
+        published_before: q.published_before,
+        watched: q.watched,
+        order_by: q.order_by,
+    };
+    page_list_videos(None, q.page, q.per_page, q.cursor, Some(filter), q.json)
+}
+
+/// Full text search of video titles, descriptions and tags
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "videos",
+    params(
+        ("q" = Option<String>, Query, description = "Search query"),
+        ListQuery,
+    ),
+    responses((status = 200, description = "Matching videos, as JSON with `?json`", body = VideoListResponse)),
+)]
+fn page_search(request: &Request) -> Result<Response> {
+    let q = ListQuery::from_request(request);
+    let filter = FilterParams {
+        name_contains: None,
+        status: q.status,
+        status_not: q.status_not,
+        chanid: None,
+        collection: None,
+        tag: None,
+        search: Some(request.get_param("q").unwrap_or_default()),
+        published_after: q.published_after,
+        published_before: q.published_before,
+        watched: q.watched,
+        order_by: q.order_by,
+    };
+    page_list_videos(None, q.page, q.per_page, q.cursor, Some(filter), q.json)
+}
+
+/// Videos from one channel
+#[utoipa::path(
+    get,
+    path = "/channel/{chanid}",
+    tag = "videos",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("title" = Option<String>, Query, description = "Only videos with titles containing this"),
+        ("tag" = Option<String>, Query, description = "Only videos with this tag"),
+        ListQuery,
+    ),
+    responses((status = 200, description = "Videos, as JSON with `?json`", body = VideoListResponse)),
+)]
+fn page_channel_videos(request: &Request, chanid: i64) -> Result<Response> {
+    let q = ListQuery::from_request(request);
+    let filter = FilterParams {
+        name_contains: request.get_param("title"),
+        status: q.status,
+        status_not: q.status_not,
+        chanid: None, // TODO: Can set this to chanid and remove branching here
+        collection: None,
+        tag: request.get_param("tag"),
+        search: None,
+        published_after: q.published_after,
+        published_before: q.published_before,
+        watched: q.watched,
+        order_by: q.order_by,
+    };
+    page_list_videos(
+        Some(chanid),
+        q.page,
+        q.per_page,
+        q.cursor,
+        Some(filter),
+        q.json,
+    )
 }
 
 fn page_list_videos(
     id: Option<i64>,
     page: i64,
+    per_page: i64,
+    cursor: Option<String>,
     filter: Option<FilterParams>,
     as_json: bool,
 ) -> Result<Response> {
+    let cursor = cursor.as_deref().map(VideoCursor::decode).transpose()?;
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
-    let (c, videos): (Option<Channel>, Vec<DBVideoInfo>) = if let Some(id) = id {
+    let db = crate::db::Database::pooled(&cfg)?;
+    let search = filter.as_ref().and_then(|f| f.search.clone());
+    let collection = match filter.as_ref().and_then(|f| f.collection) {
+        Some(cid) => Some(WebCollection::new(
+            crate::db::Collection::get_by_sqlid(&db, cid)?,
+            &db,
+        )?),
+        None => None,
+    };
+    let status = filter.as_ref().and_then(status_param).unwrap_or_default();
+    let params = filter_params(&filter, per_page);
+    let query = query_string(&params);
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default()
+    };
+    let published_after = param("published_after");
+    let published_before = param("published_before");
+    let watched = param("watched");
+    let order = filter.as_ref().map(|f| f.order_by).unwrap_or_default();
+    // Other parameters, kept when changing the dates, watched filter or order
+    let hidden_params = params
+        .iter()
+        .filter(|(k, _)| !k.starts_with("published_") && *k != "watched" && *k != "order_by")
+        .cloned()
+        .collect();
+    // With a cursor, one extra video is retrieved to find if there are more
+    let (c, mut videos, total): (Option<Channel>, Vec<DBVideoInfo>, i64) = if let Some(id) = id {
         let c = crate::db::Channel::get_by_sqlid(&db, id)?;
-        let videos = c.all_videos(&db, 50, page, filter)?;
-        (Some(c), videos)
+        let total = c.count_videos(&db, filter.clone())?;
+        let videos = match &cursor {
+            Some(cur) => c.all_videos_after(&db, per_page + 1, Some(cur), filter)?,
+            None => c.all_videos(&db, per_page, page, filter)?,
+        };
+        (Some(c), videos, total)
+    } else {
+        let total = crate::db::count_videos(&db, filter.clone())?;
+        let videos = match &cursor {
+            Some(cur) => crate::db::all_videos_after(&db, per_page + 1, Some(cur), filter)?,
+            None => crate::db::all_videos(&db, per_page, page, filter)?,
+        };
+        (None, videos, total)
+    };
+    let has_more = if cursor.is_some() {
+        let more = videos.len() as i64 > per_page;
+        videos.truncate(per_page as usize);
+        more
     } else {
-        let videos = crate::db::all_videos(&db, 50, page, filter)?;
-        (None, videos)
+        (page + 1) * per_page < total
     };
+    let next_cursor = videos
+        .last()
+        .filter(|_| has_more && order == VideoOrder::Published)
+        .map(|v| VideoCursor::after(v).encode());
 
     // Construct a map of WebChannel's to be referenced by each video
     let mut chans: HashMap<i64, WebChannel> = HashMap::new();
@@ -255,80 +1265,890 @@ fn page_list_videos(
         }
     }
 
-    // Group by date
-    let mut by_date_step1: BTreeMap<String, Vec<WebVideoInfo>> = BTreeMap::new();
+    // Group consecutive videos by the date they're ordered by, or all in one group with no
+    // date when not ordered by date
+    let mut by_date: Vec<(String, Vec<WebVideoInfo>)> = vec![];
     for v in videos {
-        let timestamp = v.info.published_at.date().format("%Y-%m-%d").to_string();
+        let timestamp = match order {
+            VideoOrder::Published => v
+                .info
+                .published_at
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string(),
+            VideoOrder::Added => v.date_added.date_naive().format("%Y-%m-%d").to_string(),
+            VideoOrder::Duration | VideoOrder::Title => "".into(),
+        };
         let wc = &chans[&v.chanid];
-        by_date_step1
-            .entry(timestamp)
-            .or_insert_with(Vec::new)
-            .push((v, wc).into());
+        let tags = v.tags(&db)?;
+        let chapters = v.chapters(&db)?;
+        let mut wv: WebVideoInfo = (v, wc).into();
+        wv.tags = tags;
+        wv.chapters = chapters.into_iter().map(|c| c.into()).collect();
+        match by_date.last_mut() {
+            Some((date, day_videos)) if *date == timestamp => day_videos.push(wv),
+            _ => by_date.push((timestamp, vec![wv])),
+        }
     }
     // Each WebChannelVideo is VideoInfo plus a reference to the channel it belongs to
 
-    // Reverse ordering of days - workaround for https://github.com/djc/askama/issues/333
-    let by_date: Vec<(String, Vec<WebVideoInfo>)> = by_date_step1.into_iter().rev().collect();
-
     let ret: WebChannelVideos = WebChannelVideos { videos: by_date };
 
     if as_json {
-        // Serialize the data into JSON
-        let json_data = serde_json::json!({
-            "videos": &ret,
-            "page": page,
-        });
-
-        // Return JSON response
+        let json_data = VideoListResponse {
+            videos: &ret,
+            page,
+            per_page,
+            total,
+            last_page: last_page(total, per_page),
+            next_cursor,
+            has_more,
+        };
         Ok(Response::json(&json_data))
     } else {
         let t = VideoListTemplate {
             videos: &ret,
             page: page,
+            channel: id.and_then(|id| chans.get(&id)),
+            collection,
+            search,
+            status,
+            per_page,
+            total,
+            query,
+            published_after,
+            published_before,
+            watched,
+            order_by: order.as_str().into(),
+            hidden_params,
         };
         let html = t.render()?;
         Ok(Response::html(html))
     }
 }
 
-fn page_set_title_alt(videoid: i64, title: String) -> Result<Response> {
-    let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
-    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
-    v.set_title_alt(&db, title)?;
-    Ok(Response::text("ok"))
+#[derive(Template)]
+#[template(path = "video.html")]
+struct VideoTemplate<'a> {
+    video: &'a WebVideoInfo<'a>,
+    /// Collections the video is in
+    collections: Vec<crate::db::Collection>,
+    /// Collections it could be added to
+    other_collections: Vec<crate::db::Collection>,
+    /// Whether the downloaded file can be played
+    has_file: bool,
+    /// Like `123.4 MiB, downloaded 2023-01-31 12:00`, or empty if unknown
+    file_info: String,
+    /// Languages of WebVTT subtitles, the only format browsers show
+    subtitles: Vec<String>,
+    /// Status changes, oldest first
+    history: Vec<crate::db::StatusChange>,
+    /// Changes of the alternative title and description, newest first
+    alt_edits: Vec<crate::db::AltEdit>,
 }
 
-fn page_set_description_alt(videoid: i64, title: String) -> Result<Response> {
+/// Video details, with a player if downloaded
+fn page_video(videoid: i64) -> Result<Response> {
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    let db = crate::db::Database::pooled(&cfg)?;
     let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
-    v.set_description_alt(&db, title)?;
-    Ok(Response::text("ok"))
+    let file = v.downloaded_file(&db)?;
+    let has_file = file
+        .as_ref()
+        .is_some_and(|f| std::path::Path::new(&f.path).is_file());
+    let file_info = file
+        .map(|f| {
+            let mut info = vec![];
+            if let Some(size) = f.size {
+                info.push(size_str(size));
+            }
+            if let Some(at) = f.downloaded_at {
+                info.push(crate::i18n::tr_with(
+                    "downloaded {}",
+                    at.format("%Y-%m-%d %H:%M"),
+                ));
+            }
+            info.join(", ")
+        })
+        .unwrap_or_default();
+    let subtitles = v
+        .subtitle_files(&db)?
+        .into_iter()
+        .filter(|s| s.path.ends_with(".vtt"))
+        .map(|s| s.lang)
+        .collect();
+    let chan = WebChannel::new(v.channel(&db)?, &db)?;
+    let chapters = v.chapters(&db)?;
+    let tags = v.tags(&db)?;
+    let collections = v.collections(&db)?;
+    let history = v.history(&db)?;
+    let alt_edits = v.alt_edits(&db)?;
+    let other_collections = crate::db::list_collections(&db)?
+        .into_iter()
+        .filter(|c| !collections.contains(c))
+        .collect();
+    let mut video: WebVideoInfo = (v, &chan).into();
+    video.chapters = chapters.into_iter().map(|c| c.into()).collect();
+    video.tags = tags;
+
+    let t = VideoTemplate {
+        video: &video,
+        collections,
+        other_collections,
+        has_file,
+        file_info,
+        subtitles,
+        history,
+        alt_edits,
+    };
+    Ok(Response::html(t.render()?))
 }
 
-fn page_download_video(videoid: i64, workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
-    let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
-    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
-    let chanid = v.chanid;
+/// Parse `Range` header like `bytes=100-199` into the first and last byte requested of a file
+/// of `size` bytes. `None` if it can't be satisfied, or asks for multiple ranges
+fn parse_range(header: &str, size: u64) -> Option<(u64, u64)> {
+    let (start, end) = header.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = if start.is_empty() {
+        // Suffix like `-500`, the last 500 bytes
+        let len: u64 = end.parse().ok()?;
+        (size.checked_sub(len.min(size))?, size.checked_sub(1)?)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size.checked_sub(1)?
+        } else {
+            end.parse::<u64>().ok()?.min(size.checked_sub(1)?)
+        };
+        (start, end)
+    };
+    if start > end {
+        return None;
+    }
+    Some((start, end))
+}
 
-    // Mark video as queued
-    v.set_status(&db, VideoStatus::Queued)?;
+/// Serve file, honouring `Range` requests so players can seek
+fn serve_file(request: &Request, path: &std::path::Path) -> Result<Response> {
+    use std::io::{Read, Seek, SeekFrom};
 
-    // Then add it to the work queue
-    {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let content_type = rouille::extension_to_mime(ext);
+
+    let Some(range) = request.header("Range") else {
+        return Ok(Response::from_file(content_type, file)
+            .with_additional_header("Accept-Ranges", "bytes"));
+    };
+    let Some((start, end)) = parse_range(range, size) else {
+        return Ok(Response::empty_400()
+            .with_status_code(416)
+            .with_additional_header("Content-Range", format!("bytes */{}", size)));
+    };
+    file.seek(SeekFrom::Start(start))?;
+    let len = end - start + 1;
+    Ok(Response {
+        status_code: 206,
+        headers: vec![
+            ("Content-Type".into(), content_type.into()),
+            ("Accept-Ranges".into(), "bytes".into()),
+            (
+                "Content-Range".into(),
+                format!("bytes {}-{}/{}", start, end, size).into(),
+            ),
+        ],
+        data: rouille::ResponseBody::from_reader_and_size(file.take(len), len as usize),
+        upgrade: None,
+    })
+}
+
+/// Stream the downloaded video file
+#[utoipa::path(
+    get,
+    path = "/video/{videoid}/file",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("Range" = Option<String>, Header, description = "Byte range, like `bytes=0-1023`"),
+    ),
+    responses(
+        (status = 200, description = "Downloaded file"),
+        (status = 206, description = "Requested range of the file"),
+        (status = 404, description = "Video hasn't been downloaded"),
+        (status = 416, description = "Range is outside the file"),
+    ),
+)]
+fn page_video_file(request: &Request, videoid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    match v.file_path(&db)? {
+        Some(p) if std::path::Path::new(&p).is_file() => serve_file(request, p.as_ref()),
+        _ => Ok(Response::empty_404()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/video/{videoid}/subtitle/{lang}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("lang" = String, Path, description = "Language code, like `en`"),
+    ),
+    responses(
+        (status = 200, description = "Subtitles, as WebVTT", content_type = "text/vtt"),
+        (status = 404, description = "No subtitles were downloaded in this language"),
+    ),
+)]
+fn page_video_subtitle(request: &Request, videoid: i64, lang: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    match v.subtitle_files(&db)?.into_iter().find(|s| s.lang == lang) {
+        Some(s) => serve_file(request, s.path.as_ref()),
+        None => Ok(Response::empty_404()),
+    }
+}
+
+/// Downloaded file attached to a feed item, for podcast apps
+struct FeedEnclosure {
+    url: String,
+    length: u64,
+    content_type: &'static str,
+}
+
+struct FeedItem {
+    title: String,
+    link: String,
+    /// Service and video ID, like `youtube:abc123`
+    guid: String,
+    /// RFC 2822 date
+    published: String,
+    description: String,
+    enclosure: Option<FeedEnclosure>,
+}
+
+#[derive(Template)]
+#[template(path = "feed.xml", escape = "html")]
+struct FeedTemplate {
+    title: String,
+    link: String,
+    description: String,
+    items: Vec<FeedItem>,
+}
+
+/// RSS feed of the newest videos of channel `chanid`, or of all channels
+fn page_feed(
+    request: &Request,
+    chanid: Option<i64>,
+    status: Option<HashSet<VideoStatus>>,
+    status_not: Option<HashSet<VideoStatus>>,
+) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    // Links must be absolute, so use the address the feed was requested from
+    let base = format!(
+        "http://{}{}",
+        request.header("Host").unwrap_or("localhost:8448"),
+        base_path()
+    );
+    let filter = FilterParams {
+        name_contains: None,
+        status,
+        status_not,
+        chanid: None,
+        collection: None,
+        tag: None,
+        search: None,
+        published_after: None,
+        published_before: None,
+        watched: None,
+        order_by: VideoOrder::default(),
+    };
+    let (title, link, description, videos) = match chanid {
+        Some(id) => {
+            let c = Channel::get_by_sqlid(&db, id)?;
+            let videos = c.all_videos(&db, 50, 0, Some(filter))?;
+            let description = format!("Videos from {} in vidl", c.title);
+            (
+                c.title,
+                format!("{}/channel/{}", base, id),
+                description,
+                videos,
+            )
+        }
+        None => {
+            let videos = crate::db::all_videos(&db, 50, 0, Some(filter))?;
+            (
+                "vidl".to_string(),
+                format!("{}/", base),
+                "Videos from all channels in vidl".to_string(),
+                videos,
+            )
+        }
+    };
+
+    let mut items = vec![];
+    for v in videos {
+        let enclosure = v.file_path(&db)?.and_then(|p| {
+            let path = std::path::Path::new(&p);
+            let length = path.metadata().ok().filter(|m| m.is_file())?.len();
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            Some(FeedEnclosure {
+                url: format!("{}/video/{}/file", base, v.id),
+                length,
+                content_type: rouille::extension_to_mime(ext),
+            })
+        });
+        let chan = v.channel(&db)?;
+        items.push(FeedItem {
+            link: format!("{}/video/{}", base, v.id),
+            guid: format!("{}:{}", chan.service.as_str(), v.info.id),
+            published: v.info.published_at.to_rfc2822(),
+            title: v.info.title_alt.unwrap_or(v.info.title),
+            description: v.info.description_alt.unwrap_or(v.info.description),
+            enclosure,
+        });
+    }
+
+    let t = FeedTemplate {
+        title,
+        link,
+        description,
+        items,
+    };
+    Ok(Response::from_data("application/rss+xml", t.render()?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/video_title/{videoid}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("title" = String, Query, description = "Alternative title"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_title_alt(videoid: i64, title: String) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    v.set_title_alt(&db, title)?;
+    Ok(Response::text("ok"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/video_description/{videoid}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("text" = String, Query, description = "Alternative description"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_description_alt(videoid: i64, title: String) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    v.set_description_alt(&db, title)?;
+    Ok(Response::text("ok"))
+}
+
+/// Put back the alternative title or description an edit replaced
+#[utoipa::path(
+    post,
+    path = "/video/{videoid}/revert_edit/{editid}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("editid" = i64, Path, description = "Edit ID, from the video's edit history"),
+    ),
+    responses((status = 303, description = "Reverted, redirecting to the video")),
+)]
+fn page_revert_alt_edit(videoid: i64, editid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    v.revert_alt_edit(&db, editid)?;
+
+    Ok(Response::redirect_303(link(&format!("/video/{}", videoid))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/video_notes/{videoid}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("text" = String, Query, description = "Notes, or empty to remove them. Also accepted as a form field"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_notes(videoid: i64, text: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    v.set_notes(&db, text)?;
+    Ok(Response::text("ok"))
+}
+
+/// Queue all of channel's new videos
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/download_new",
+    tag = "channels",
+    params(("chanid" = i64, Path, description = "Channel ID")),
+    responses((status = 303, description = "Queued, redirecting to the channel")),
+)]
+fn page_download_new(chanid: i64, workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let chan = Channel::get_by_sqlid(&db, chanid)?;
+
+    let queued = chan.queue_new(&db)?;
+    info!("Queued {} new videos from {:?}", queued.len(), &chan.title);
+    {
+        let w = workers.lock().unwrap();
+        for v in queued {
+            w.enqueue(crate::worker::WorkItem::Download(v));
+        }
+    }
+
+    Ok(Response::redirect_303(link(&format!(
+        "/channel/{}",
+        chanid
+    ))))
+}
+
+/// Queue all of channel's failed videos again
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/retry_failed",
+    tag = "channels",
+    params(("chanid" = i64, Path, description = "Channel ID")),
+    responses((status = 303, description = "Queued, redirecting to the channel")),
+)]
+fn page_retry_failed(chanid: i64, workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let chan = Channel::get_by_sqlid(&db, chanid)?;
+
+    let queued = chan.retry_failed(&db)?;
+    info!(
+        "Retrying {} failed videos from {:?}",
+        queued.len(),
+        &chan.title
+    );
+    {
+        let w = workers.lock().unwrap();
+        for v in queued {
+            w.enqueue(crate::worker::WorkItem::Download(v));
+        }
+    }
+
+    Ok(Response::redirect_303(link(&format!(
+        "/channel/{}",
+        chanid
+    ))))
+}
+
+/// Ignore channel's new videos published before `before` (like `2020-01-31`)
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/ignore_older",
+    tag = "channels",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("before" = String, Query, description = "Date like `2020-01-31`, also accepted as a form field"),
+    ),
+    responses((status = 303, description = "Ignored, redirecting to the channel")),
+)]
+fn page_ignore_older(chanid: i64, before: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let chan = Channel::get_by_sqlid(&db, chanid)?;
+
+    let before = parse_date(before)?;
+    let ignored = chan.ignore_older_than(&db, before)?;
+    info!(
+        "Ignored {} new videos from {:?} published before {}",
+        ignored, &chan.title, before
+    );
+
+    Ok(Response::redirect_303(link(&format!(
+        "/channel/{}",
+        chanid
+    ))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/download/{videoid}",
+    tag = "queue",
+    params(("videoid" = i64, Path, description = "Video ID")),
+    responses((status = 303, description = "Queued, redirecting to the channel")),
+)]
+fn page_download_video(videoid: i64, workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    let chanid = v.chanid;
+
+    if v.status == VideoStatus::Unavailable {
+        anyhow::bail!(
+            "Video {} is members-only or paid, so can't be downloaded",
+            videoid
+        );
+    }
+    if v.status == VideoStatus::Removed || v.removed_at.is_some() {
+        anyhow::bail!("Video {} has been removed from the source", videoid);
+    }
+
+    // Mark video as queued
+    v.set_status(&db, VideoStatus::Queued)?;
+
+    // Then add it to the work queue
+    {
         let w = workers.lock().unwrap();
         w.enqueue(crate::worker::WorkItem::Download(v));
     }
 
     // Redirect to channel for no-javascript clicking
-    Ok(Response::redirect_303(format!("/channel/{}", chanid)))
+    Ok(Response::redirect_303(link(&format!(
+        "/channel/{}",
+        chanid
+    ))))
+}
+
+/// Queue a failed video again, starting its automatic retries over
+#[utoipa::path(
+    post,
+    path = "/retry/{videoid}",
+    tag = "queue",
+    params(("videoid" = i64, Path, description = "Video ID")),
+    responses((status = 303, description = "Queued, redirecting to the channel")),
+)]
+fn page_retry_video(videoid: i64, workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    let chanid = v.chanid;
+
+    if !v.retry(&db)? {
+        anyhow::bail!("Video {} has not failed to download", videoid);
+    }
+    {
+        let w = workers.lock().unwrap();
+        w.enqueue(crate::worker::WorkItem::Download(v));
+    }
+
+    Ok(Response::redirect_303(link(&format!(
+        "/channel/{}",
+        chanid
+    ))))
+}
+
+/// Download a single video from a channel which isn't added, by its URL. The video is added to
+/// the ad-hoc channel, unless it's already in vidl
+#[utoipa::path(
+    post,
+    path = "/video/add",
+    tag = "queue",
+    params(("url" = String, Query, description = "Youtube video URL or ID, also accepted as a form field")),
+    responses(
+        (status = 303, description = "Queued, redirecting to its channel. Redirects to the video if already downloaded, or the queue if already queued"),
+    ),
+)]
+fn page_add_video(url: &str, workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let id = crate::common::parse_youtube_video_id(url)?;
+
+    let v = match DBVideoInfo::get_by_video_id(&db, Service::Youtube, &id)? {
+        Some(v) => v,
+        None => {
+            let info = crate::source::invidious::get_video_info(&id)?;
+            let chan = Channel::adhoc(&db)?;
+            info!("Adding video {:?} to {:?}", &info.title, &chan.title);
+            let v = chan.add_video(&db, &info)?;
+            if cfg.fetch_video_details {
+                chan.fetch_video_details(&db, std::slice::from_ref(&v))?;
+            }
+            v
+        }
+    };
+
+    match v.status {
+        VideoStatus::Grabbed => Ok(Response::redirect_303(link(&format!("/video/{}", v.id)))),
+        VideoStatus::Queued | VideoStatus::Downloading => {
+            Ok(Response::redirect_303(link("/queue")))
+        }
+        _ => page_download_video(v.id, workers),
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/backend",
+    tag = "channels",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("backend" = Option<String>, Query, description = "`invidious`, `piped`, `ytdlp` or `api`, or empty for the configured default"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_backend(chanid: i64, backend: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Channel::get_by_sqlid(&db, chanid)?;
+    let backend = if backend.is_empty() {
+        None
+    } else {
+        Some(Backend::from_str(backend)?)
+    };
+    c.set_backend(&db, backend)?;
+    Ok(Response::text("ok"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/format",
+    tag = "channels",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("format" = Option<String>, Query, description = "Downloader format, or empty for the configured default"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_format(chanid: i64, format: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Channel::get_by_sqlid(&db, chanid)?;
+    let format = format.trim();
+    c.set_format(
+        &db,
+        if format.is_empty() {
+            None
+        } else {
+            Some(format)
+        },
+    )?;
+    Ok(Response::text("ok"))
+}
+
+/// Settings used by channels without their own, as their values or null if using the default
+#[utoipa::path(
+    get,
+    path = "/api/v1/settings",
+    tag = "settings",
+    responses((status = 200, description = "Settings", body = HashMap<String, Option<String>>)),
+)]
+fn page_settings() -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let settings: HashMap<&str, Option<String>> = db
+        .settings()?
+        .into_iter()
+        .map(|(k, v)| (k.as_str(), v))
+        .collect();
+    Ok(Response::json(&settings))
+}
+
+#[utoipa::path(
+    post,
+    path = "/settings/{key}",
+    tag = "settings",
+    params(
+        ("key" = String, Path, description = "Setting, `update_interval` (minutes) or `format`"),
+        ("value" = Option<String>, Query, description = "New value, or empty for the default"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_setting(key: &str, value: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let setting = crate::db::Setting::from_str(key)?;
+    let value = value.trim();
+    db.set_setting(setting, Some(value).filter(|v| !v.is_empty()))?;
+    Ok(Response::text("ok"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/output",
+    tag = "channels",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("download_dir" = Option<String>, Query, description = "Download directory, or empty for the configured default"),
+        ("filename_format" = Option<String>, Query, description = "Output template, or empty for the configured default"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_output(chanid: i64, download_dir: &str, filename_format: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Channel::get_by_sqlid(&db, chanid)?;
+    let non_empty =
+        |x: &str| -> Option<String> { Some(x.trim().to_string()).filter(|x| !x.is_empty()) };
+    c.set_download_dir(&db, non_empty(download_dir).as_deref())?;
+    c.set_filename_format(&db, non_empty(filename_format).as_deref())?;
+    Ok(Response::text("ok"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/ignore_rules",
+    tag = "channels",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("min_duration" = Option<i32>, Query, description = "Ignore new videos shorter than this many seconds"),
+        ("max_duration" = Option<i32>, Query, description = "Ignore new videos longer than this many seconds"),
+        ("title" = Option<String>, Query, description = "Ignore new videos with titles matching this regex"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_ignore_rules(
+    chanid: i64,
+    min_duration: &str,
+    max_duration: &str,
+    title: &str,
+) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Channel::get_by_sqlid(&db, chanid)?;
+    let duration = |x: &str| -> Result<Option<i32>> {
+        let x = x.trim();
+        if x.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(x.parse()?))
+        }
+    };
+    let rules = crate::db::IgnoreRules {
+        min_duration: duration(min_duration)?,
+        max_duration: duration(max_duration)?,
+        title: Some(title.trim().to_string()).filter(|t| !t.is_empty()),
+    };
+    c.set_ignore_rules(&db, &rules)?;
+    Ok(Response::text("ok"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/channel/{chanid}/subtitles",
+    tag = "channels",
+    params(
+        ("chanid" = i64, Path, description = "Channel ID"),
+        ("subtitles" = Option<String>, Query, description = "`on`, `off`, or empty for the configured default"),
+    ),
+    responses((status = 200, description = "Set", body = String, content_type = "text/plain")),
+)]
+fn page_set_subtitles(chanid: i64, subtitles: &str) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let c = crate::db::Channel::get_by_sqlid(&db, chanid)?;
+    let subtitles = match subtitles {
+        "on" => Some(true),
+        "off" => Some(false),
+        "" => None,
+        other => anyhow::bail!("Unknown subtitles setting {:?}", other),
+    };
+    c.set_subtitles(&db, subtitles)?;
+    Ok(Response::text("ok"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/bump/{videoid}",
+    tag = "queue",
+    params(("videoid" = i64, Path, description = "Video ID")),
+    responses((status = 303, description = "Moved to the front of the queue, redirecting to the queue")),
+)]
+fn page_bump_video(videoid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    v.bump_priority(&db)?;
+
+    // Redirect to queue for no-javascript clicking
+    Ok(Response::redirect_303(link("/queue")))
+}
+
+/// Move queued video `offset` places, negative being sooner
+#[utoipa::path(
+    post,
+    path = "/queue/{videoid}/{direction}",
+    tag = "queue",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("direction" = String, Path, description = "`up` to download sooner, or `down`"),
+    ),
+    responses((status = 303, description = "Moved, redirecting to the queue")),
+)]
+fn page_move_video(videoid: i64, offset: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    crate::db::move_in_queue(&db, &v, offset)?;
+    Ok(Response::redirect_303(link("/queue")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/queue/{videoid}/remove",
+    tag = "queue",
+    params(("videoid" = i64, Path, description = "Video ID")),
+    responses((status = 303, description = "Removed from the queue, redirecting to the queue")),
+)]
+fn page_unqueue_video(videoid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    if !v.unqueue(&db)? {
+        info!("Video was no longer queued - {:?}", &v.info.title);
+    }
+    Ok(Response::redirect_303(link("/queue")))
+}
+
+/// Stop the download, after which the worker changes the video back to `New`
+#[utoipa::path(
+    post,
+    path = "/queue/{videoid}/cancel",
+    tag = "queue",
+    params(("videoid" = i64, Path, description = "Video ID")),
+    responses(
+        (status = 303, description = "Cancelled, redirecting to the queue"),
+        (status = 409, description = "Not being downloaded by this process"),
+    ),
+)]
+fn page_cancel_video(videoid: i64) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    if !crate::download::cancel(&v.info.id) {
+        // e.g downloaded by a separate `vidl worker` process
+        return Ok(Response::text(format!(
+            "Video {:?} is not being downloaded by the web interface",
+            v.info.title
+        ))
+        .with_status_code(409));
+    }
+    Ok(Response::redirect_303(link("/queue")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/ignore/{videoid}",
+    tag = "videos",
+    params(("videoid" = i64, Path, description = "Video ID")),
+    responses((status = 303, description = "Ignored, redirecting to the channel")),
+)]
 fn page_ignore_video(videoid: i64) -> Result<Response> {
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    let db = crate::db::Database::pooled(&cfg)?;
     let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
     let chanid = v.chanid;
 
@@ -336,7 +2156,55 @@ fn page_ignore_video(videoid: i64) -> Result<Response> {
     v.set_status(&db, VideoStatus::Ignore)?;
 
     // Redirect to channel for no-javascript clicking
-    Ok(Response::redirect_303(format!("/channel/{}", chanid)))
+    Ok(Response::redirect_303(link(&format!(
+        "/channel/{}",
+        chanid
+    ))))
+}
+
+/// Mark video as watched or unwatched
+#[utoipa::path(
+    post,
+    path = "/video/{videoid}/{state}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("state" = String, Path, description = "`watched` or `unwatched`"),
+    ),
+    responses((status = 303, description = "Changed, redirecting to the video")),
+)]
+fn page_set_watched(videoid: i64, watched: bool) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    v.set_watched(&db, watched)?;
+
+    Ok(Response::redirect_303(link(&format!("/video/{}", videoid))))
+}
+
+/// Add or remove one of a video's tags
+#[utoipa::path(
+    post,
+    path = "/video/{videoid}/{action}",
+    tag = "videos",
+    params(
+        ("videoid" = i64, Path, description = "Video ID"),
+        ("action" = String, Path, description = "`tag` or `untag`"),
+        ("tag" = String, Query, description = "Tag name. Also accepted as a form field"),
+    ),
+    responses((status = 303, description = "Changed, redirecting to the video")),
+)]
+fn page_set_tag(videoid: i64, tag: &str, add: bool) -> Result<Response> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let v = crate::db::DBVideoInfo::get_by_sqlid(&db, videoid)?;
+    if add {
+        v.add_tag(&db, tag)?;
+    } else {
+        v.remove_tag(&db, tag)?;
+    }
+
+    Ok(Response::redirect_303(link(&format!("/video/{}", videoid))))
 }
 
 enum ThumbnailType {
@@ -344,13 +2212,30 @@ enum ThumbnailType {
     Channel,
 }
 
+/// Thumbnail, resized to at least `width` pixels wide if given. Videos use the smallest size
+/// of their thumbnail the source listed which is at least as wide
+#[utoipa::path(
+    get,
+    path = "/thumbnail/{kind}/{id}",
+    tag = "videos",
+    params(
+        ("kind" = String, Path, description = "`video` or `channel`"),
+        ("id" = i64, Path, description = "Video or channel ID"),
+        ("w" = Option<u32>, Query, description = "Width in pixels the thumbnail is shown at"),
+    ),
+    responses(
+        (status = 200, description = "Cached thumbnail"),
+        (status = 303, description = "Redirect to the original, until it is cached"),
+    ),
+)]
 fn page_thumbnail(
     id: i64,
     what: ThumbnailType,
+    width: Option<u32>,
     workers: Arc<Mutex<WorkerPool>>,
 ) -> Result<Response> {
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    let db = crate::db::Database::pooled(&cfg)?;
 
     let url = match what {
         ThumbnailType::Channel => {
@@ -359,23 +2244,133 @@ fn page_thumbnail(
         }
         ThumbnailType::Video => {
             let vi = crate::db::DBVideoInfo::get_by_sqlid(&db, id)?;
-            vi.info.thumbnail_url
+            vi.thumbnail_url_for(&db, width)?
         }
     };
 
-    let image = {
-        let mut ic = IMG_CACHE.lock().unwrap();
-        ic.get(url, workers)?
+    let width = width.and_then(crate::image_cache::thumbnail_width);
+    let variant = crate::image_cache::variant_key(&url, width, cfg.thumbnail_webp);
+    let (image, original) = {
+        let mut ic = crate::image_cache::IMG_CACHE.lock().unwrap();
+        match variant.and_then(|k| ic.get(&k)) {
+            Some(image) => (Some(image), None),
+            None => (None, ic.get(&url)),
+        }
     };
-    match image {
-        ImageCacheResponse::Redirect(url) => Ok(Response::redirect_303(url)),
-        ImageCacheResponse::Image(image) => Ok(Response::from_data(image.content_type, image.data)),
+    if let Some(image) = image {
+        return Ok(Response::from_data(image.content_type, image.data));
     }
+
+    // Cached (and resized) in the background, with the original shown until then
+    let pool = workers.lock().unwrap();
+    pool.enqueue(crate::worker::WorkItem::ThumbnailCache(url.clone(), width));
+    match original {
+        Some(image) => Ok(Response::from_data(image.content_type, image.data)),
+        None => Ok(Response::redirect_303(url)),
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthCheck {
+    ok: bool,
+    /// If failing makes the whole service unhealthy, rather than only degraded
+    critical: bool,
+    detail: String,
+}
+
+impl HealthCheck {
+    fn new(critical: bool, result: Result<String>) -> HealthCheck {
+        let (ok, detail) = match result {
+            Ok(d) => (true, d),
+            Err(e) => (false, format!("{:#}", e)),
+        };
+        HealthCheck {
+            ok,
+            critical,
+            detail,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthResponse {
+    /// `ok`, `degraded` if only non-critical checks fail, or `error`
+    status: &'static str,
+    /// Checks by name: `database`, `schema`, `downloader` and `invidious`
+    checks: BTreeMap<&'static str, HealthCheck>,
+}
+
+/// Status of everything needed to work. 503 if a critical check fails, so orchestrators can
+/// restart or stop routing to the service, but an unreachable Invidious instance only degrades it
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "status",
+    responses(
+        (status = 200, description = "Working, possibly degraded", body = HealthResponse),
+        (status = 503, description = "A critical check failed", body = HealthResponse),
+    ),
+)]
+fn page_health() -> Response {
+    let cfg = crate::config::Config::load();
+    let mut checks: BTreeMap<&str, HealthCheck> = BTreeMap::new();
+
+    let database = crate::db::Database::schema_version(&cfg);
+    checks.insert(
+        "database",
+        HealthCheck::new(
+            true,
+            database
+                .as_ref()
+                .map(|_| "Connected".into())
+                .map_err(|e| anyhow::anyhow!("{:#}", e)),
+        ),
+    );
+    let schema = match database {
+        Ok((Some(cur), latest)) if cur == latest => Ok(format!("Version {}", cur)),
+        Ok((cur, latest)) => Err(anyhow::anyhow!(
+            "Version {:?} but should be {} - run `vidl migrate`",
+            cur,
+            latest
+        )),
+        Err(_) => Err(anyhow::anyhow!("Database unavailable")),
+    };
+    checks.insert("schema", HealthCheck::new(true, schema));
+
+    let dl = crate::download::Downloader::from_config(&cfg);
+    checks.insert(
+        "downloader",
+        HealthCheck::new(
+            true,
+            dl.version()
+                .map(|v| format!("{} version {}", &dl.binary, v)),
+        ),
+    );
+
+    checks.insert(
+        "invidious",
+        HealthCheck::new(
+            false,
+            crate::source::invidious::check_instance(Duration::from_secs(5))
+                .map(|v| format!("Version {}", v)),
+        ),
+    );
+
+    let (status, code) = if checks.values().any(|c| c.critical && !c.ok) {
+        ("error", 503)
+    } else if checks.values().any(|c| !c.ok) {
+        ("degraded", 200)
+    } else {
+        ("ok", 200)
+    };
+    Response::json(&HealthResponse { status, checks })
+        .with_status_code(code)
+        .with_no_cache()
 }
 
 fn page_refresh(workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    let db = crate::db::Database::pooled(&cfg)?;
 
     // Then add it to the work queue
     {
@@ -395,21 +2390,258 @@ fn page_refresh(workers: Arc<Mutex<WorkerPool>>) -> Result<Response> {
     }
 
     // Redirect to channel for no-javascript clicking
-    Ok(Response::redirect_303("/channel/_all"))
+    Ok(Response::redirect_303(link("/channel/_all")))
 }
 
-/// Given a space separated list of statuses like `GE,NE`, parses each comma-separated status into actual `VideoStatus` object
-fn parse_statuses(statuses: &str) -> Result<HashSet<VideoStatus>> {
-    let mut ret = HashSet::new();
+/// Statuses to include and to exclude, either `None` if there are none
+type StatusFilter = (Option<HashSet<VideoStatus>>, Option<HashSet<VideoStatus>>);
+
+/// Parses a comma-separated list of statuses like `GE,NE`. Statuses prefixed with `!` like
+/// `!IG,!GR` are excluded
+fn parse_statuses(statuses: &str) -> Result<StatusFilter> {
+    let mut include = HashSet::new();
+    let mut exclude = HashSet::new();
     let split = statuses.split(",");
     for s in split {
-        let status = VideoStatus::from_str(s)?;
-        ret.insert(status);
+        match s.strip_prefix('!') {
+            Some(s) => exclude.insert(VideoStatus::from_str(s)?),
+            None => include.insert(VideoStatus::from_str(s)?),
+        };
+    }
+    let non_empty = |set: HashSet<VideoStatus>| Some(set).filter(|s| !s.is_empty());
+    Ok((non_empty(include), non_empty(exclude)))
+}
+
+/// Statuses from the `status` query parameter, ignored if invalid
+fn status_query(request: &Request) -> StatusFilter {
+    request
+        .get_param("status")
+        .and_then(|x| parse_statuses(&x).ok())
+        .unwrap_or_default()
+}
+
+/// `status` query parameter reproducing the status filters, like `NE,!IG`
+fn status_param(filter: &FilterParams) -> Option<String> {
+    let mut codes: Vec<String> = vec![];
+    if let Some(s) = &filter.status {
+        let mut c: Vec<String> = s.iter().map(|x| x.as_str().to_string()).collect();
+        c.sort();
+        codes.extend(c);
+    }
+    if let Some(s) = &filter.status_not {
+        let mut c: Vec<String> = s.iter().map(|x| format!("!{}", x.as_str())).collect();
+        c.sort();
+        codes.extend(c);
+    }
+    Some(codes.join(",")).filter(|c| !c.is_empty())
+}
+
+/// Start of day like `2023-01-31`, in UTC
+fn parse_date(text: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    Ok(chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")?
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc())
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "VIDL",
+        description = "Endpoints of the web interface for scripts and other programs. Video lists return JSON when `?json` is given, otherwise HTML. Errors are returned as plain text with status 500",
+    ),
+    paths(
+        page_chan_list,
+        page_all_videos,
+        page_search,
+        page_channel_videos,
+        page_video_file,
+        page_video_subtitle,
+        page_thumbnail,
+        page_ignore_video,
+        page_set_watched,
+        page_set_tag,
+        page_set_title_alt,
+        page_set_description_alt,
+        page_revert_alt_edit,
+        page_set_notes,
+        page_queue,
+        page_download_video,
+        page_retry_video,
+        page_add_video,
+        page_bump_video,
+        page_move_video,
+        page_unqueue_video,
+        page_cancel_video,
+        page_add_playlist,
+        page_download_new,
+        page_retry_failed,
+        page_ignore_older,
+        page_set_backend,
+        page_set_format,
+        page_set_output,
+        page_set_ignore_rules,
+        page_set_subtitles,
+        page_settings,
+        page_set_setting,
+        page_health,
+        page_workers,
+        page_collection_list,
+        page_collection_videos,
+        page_create_collection,
+        page_rename_collection,
+        page_delete_collection,
+        page_collection_video,
+    ),
+    tags(
+        (name = "videos"),
+        (name = "queue", description = "Downloading videos"),
+        (name = "channels", description = "Adding channels and changing their settings"),
+        (name = "collections", description = "Grouping videos from any channels by hand"),
+        (name = "settings", description = "Defaults for channels without their own settings"),
+        (name = "status"),
+    ),
+)]
+struct ApiDoc;
+
+/// OpenAPI description of the endpoints, with the base path as the server
+fn page_openapi() -> Result<Response> {
+    let mut doc = ApiDoc::openapi();
+    if !base_path().is_empty() {
+        doc.servers = Some(vec![utoipa::openapi::Server::new(base_path())]);
+    }
+    Ok(Response::from_data("application/json", doc.to_json()?))
+}
+
+#[derive(Template)]
+#[template(path = "api_docs.html")]
+struct ApiDocsTemplate;
+
+/// Swagger UI for the OpenAPI description
+fn page_api_docs() -> Result<Response> {
+    Ok(Response::html(ApiDocsTemplate.render()?))
+}
+
+/// Parameter from the query string, or the form
+fn form_param(request: &Request, name: &str) -> Option<String> {
+    request.get_param(name).or_else(|| {
+        rouille::input::post::raw_urlencoded_post_input(request)
+            .ok()?
+            .into_iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
+    })
+}
+
+/// Date query parameter, ignored if empty or invalid
+fn date_param(request: &Request, name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    request
+        .get_param(name)
+        .and_then(|x| parse_date(x.trim()).ok())
+}
+
+/// Request with the base path removed from its URL, so it can be routed. Requests without it are
+/// routed unchanged, for reverse proxies which remove it themselves
+fn strip_base_path(request: &Request) -> std::result::Result<Option<Request>, Response> {
+    let base = base_path();
+    if base.is_empty() {
+        return Ok(None);
+    }
+    let url = request.url();
+    if url == base {
+        return Err(Response::redirect_301(link("/")));
+    }
+    match url.strip_prefix(base) {
+        Some(rest) if rest.starts_with('/') => Ok(request.remove_prefix(base)),
+        _ => Ok(None),
+    }
+}
+
+/// Response refusing a POST request, if its client has made too many recently, so a misbehaving
+/// script can't queue thousands of downloads
+fn check_rate_limit(request: &Request) -> Option<Response> {
+    if request.method() != "POST" {
+        return None;
+    }
+    let mut limiter = POST_LIMITER.lock().unwrap();
+    let addr = request.remote_addr().ip();
+    match limiter.as_mut()?.check(addr) {
+        Ok(()) => None,
+        Err(e) => {
+            // This is when the limit would be reached again, one window after the next request
+            // is allowed
+            let wait = e
+                .wait_time_from(std::time::Instant::now())
+                .saturating_sub(RATE_LIMIT_WINDOW);
+            warn!("Rate limited POST {} from {}", request.url(), addr);
+            Some(
+                Response::text("Too many requests, try again later")
+                    .with_status_code(429)
+                    .with_additional_header("Retry-After", (wait.as_secs() + 1).to_string()),
+            )
+        }
+    }
+}
+
+/// Whether `url` (an `Origin` or `Referer` header) is on `host`, like `localhost:8448`
+fn same_host(url: &str, host: &str) -> bool {
+    let without_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    without_scheme.split('/').next() == Some(host)
+}
+
+/// The `Origin` (or `Referer`) of a request made from another site, unless it has the CSRF token
+/// as the `X-CSRF-Token` header or `csrf_token` query parameter - needed when a reverse proxy
+/// changes the `Host`. Requests with neither header (from scripts rather than browsers) are allowed
+fn cross_site_source(request: &Request) -> Option<&str> {
+    let token = request
+        .header("X-CSRF-Token")
+        .map(|t| t.to_string())
+        .or_else(|| request.get_param("csrf_token"));
+    if token.as_deref() == Some(csrf_token()) {
+        return None;
+    }
+    let source = request
+        .header("Origin")
+        .or_else(|| request.header("Referer"))?;
+    if same_host(source, request.header("Host").unwrap_or_default()) {
+        return None;
+    }
+    Some(source)
+}
+
+/// Response refusing a POST request made from another site (cross-site request forgery), see
+/// `cross_site_source`
+fn check_csrf(request: &Request) -> Option<Response> {
+    if request.method() != "POST" {
+        return None;
+    }
+    let source = cross_site_source(request)?;
+    warn!(
+        "Refused POST {} from {:?}, which is another site",
+        request.url(),
+        source
+    );
+    Some(Response::text("Cross-site request refused").with_status_code(403))
+}
+
+/// Forget clients which haven't made any POST requests recently
+fn cleanup_rate_limit() {
+    if let Some(limiter) = POST_LIMITER.lock().unwrap().as_mut() {
+        limiter.cleanup(RATE_LIMIT_WINDOW);
     }
-    Ok(ret)
 }
 
 fn handle_response(request: &Request, workers: Arc<Mutex<WorkerPool>>) -> Response {
+    let stripped = match strip_base_path(request) {
+        Ok(r) => r,
+        Err(redirect) => return redirect,
+    };
+    let request = stripped.as_ref().unwrap_or(request);
+
+    if let Some(refused) = check_csrf(request).or_else(|| check_rate_limit(request)) {
+        return refused;
+    }
+
     if let Some(request) = request.remove_prefix("/static") {
         // Can do dynamic serving of files with:
         // return rouille::match_assets(&request, "static");
@@ -428,6 +2660,15 @@ fn handle_response(request: &Request, workers: Arc<Mutex<WorkerPool>>) -> Respon
                 include_str!("../static/luxon.min.js"),
                 "application/javascript",
             )),
+            // Vendored with its license, see the folder's README
+            "/swagger-ui-5.17.14/swagger-ui-bundle.js" => Some((
+                include_str!("../static/swagger-ui-5.17.14/swagger-ui-bundle.js"),
+                "application/javascript",
+            )),
+            "/swagger-ui-5.17.14/swagger-ui.css" => Some((
+                include_str!("../static/swagger-ui-5.17.14/swagger-ui.css"),
+                "text/css",
+            )),
             _ => None,
         };
         return match x {
@@ -438,34 +2679,145 @@ fn handle_response(request: &Request, workers: Arc<Mutex<WorkerPool>>) -> Respon
 
     let resp: Result<Response> = router!(request,
         (GET) ["/"] => {
-            page_chan_list()
+            page_chan_list(false)
         },
         (GET) ["/channel/_all"] => {
-            let page: i64 = request.get_param("page").and_then(|x| x.parse::<i64>().ok()).unwrap_or(0);
-            let statuses = request.get_param("status").and_then(|x| parse_statuses(&x).ok());
-            let filter = FilterParams {
-                name_contains: request.get_param("title"),
-                status: statuses,
-                chanid: None,
-            };
-            page_list_videos(None, page, Some(filter), request.get_param("json").is_some())
+            page_all_videos(request)
+        },
+        (GET) ["/search"] => {
+            page_search(request)
         },
         (GET) ["/channel/{chanid}", chanid: i64] => {
-            let page: i64 = request.get_param("page").and_then(|x| x.parse::<i64>().ok()).unwrap_or(0);
-            let statuses = request.get_param("status").and_then(|x| parse_statuses(&x).ok());
-            let filter = FilterParams {
-                name_contains: request.get_param("title"),
-                status: statuses,
-                chanid: None, // TODO: Can set this to chanid and remove branching here
-            };
-            page_list_videos(Some(chanid), page, Some(filter), request.get_param("json").is_some())
+            page_channel_videos(request, chanid)
+        },
+        (GET) ["/feed/all.xml"] => {
+            let (status, status_not) = status_query(request);
+            page_feed(request, None, status, status_not)
+        },
+        (GET) ["/feed/channel/{file}", file: String] => {
+            // Like `3.xml`
+            match file.strip_suffix(".xml").and_then(|id| id.parse::<i64>().ok()) {
+                Some(chanid) => {
+                    let (status, status_not) = status_query(request);
+                    page_feed(request, Some(chanid), status, status_not)
+                }
+                None => Ok(Response::empty_404()),
+            }
+        },
+        (GET) ["/video/{videoid}", videoid: i64] => {
+            page_video(videoid)
+        },
+        (GET) ["/video/{videoid}/file", videoid: i64] => {
+            page_video_file(request, videoid)
+        },
+        (GET) ["/video/{videoid}/subtitle/{lang}", videoid: i64, lang: String] => {
+            page_video_subtitle(request, videoid, &lang)
         },
         (POST) ["/download/{videoid}", videoid: i64] => {
             page_download_video(videoid, workers.clone())
         },
+        (POST) ["/retry/{videoid}", videoid: i64] => {
+            page_retry_video(videoid, workers.clone())
+        },
+        (POST) ["/video/add"] => {
+            let Some(url) = form_param(request, "url") else {
+                return Response::text("Missing ?url=...").with_status_code(500)
+            };
+            page_add_video(&url, workers.clone())
+        },
         (POST) ["/ignore/{videoid}", videoid: i64] => {
             page_ignore_video(videoid)
         },
+        (POST) ["/video/{videoid}/watched", videoid: i64] => {
+            page_set_watched(videoid, true)
+        },
+        (POST) ["/video/{videoid}/unwatched", videoid: i64] => {
+            page_set_watched(videoid, false)
+        },
+        (POST) ["/video/{videoid}/tag", videoid: i64] => {
+            let Some(tag) = form_param(request, "tag") else {
+                return Response::text("Missing ?tag=...").with_status_code(500)
+            };
+            page_set_tag(videoid, &tag, true)
+        },
+        (POST) ["/video/{videoid}/untag", videoid: i64] => {
+            let Some(tag) = form_param(request, "tag") else {
+                return Response::text("Missing ?tag=...").with_status_code(500)
+            };
+            page_set_tag(videoid, &tag, false)
+        },
+
+        (POST) ["/bump/{videoid}", videoid: i64] => {
+            page_bump_video(videoid)
+        },
+        (POST) ["/queue/{videoid}/up", videoid: i64] => {
+            page_move_video(videoid, -1)
+        },
+        (POST) ["/queue/{videoid}/down", videoid: i64] => {
+            page_move_video(videoid, 1)
+        },
+        (POST) ["/queue/{videoid}/remove", videoid: i64] => {
+            page_unqueue_video(videoid)
+        },
+        (POST) ["/queue/{videoid}/cancel", videoid: i64] => {
+            page_cancel_video(videoid)
+        },
+
+        (POST) ["/channel/{chanid}/download_new", chanid: i64] => {
+            page_download_new(chanid, workers.clone())
+        },
+        (POST) ["/channel/{chanid}/retry_failed", chanid: i64] => {
+            page_retry_failed(chanid, workers.clone())
+        },
+        (POST) ["/channel/{chanid}/ignore_older", chanid: i64] => {
+            let Some(before) = form_param(request, "before") else {
+                return Response::text("Missing ?before=...").with_status_code(500)
+            };
+            page_ignore_older(chanid, &before)
+        },
+
+        (GET) ["/channel/{chanid}/playlists", chanid: i64] => {
+            page_chan_playlists(chanid)
+        },
+        (POST) ["/playlist/add"] => {
+            let Some(id) = request.get_param("id") else {
+                return Response::text("Missing ?id=...").with_status_code(500)
+            };
+            page_add_playlist(&id)
+        },
+
+        (POST) ["/channel/{chanid}/backend", chanid: i64] => {
+            let backend = request.get_param("backend").unwrap_or_default();
+            page_set_backend(chanid, &backend)
+        },
+
+        (POST) ["/channel/{chanid}/format", chanid: i64] => {
+            let format = request.get_param("format").unwrap_or_default();
+            page_set_format(chanid, &format)
+        },
+
+        (POST) ["/channel/{chanid}/output", chanid: i64] => {
+            let download_dir = request.get_param("download_dir").unwrap_or_default();
+            let filename_format = request.get_param("filename_format").unwrap_or_default();
+            page_set_output(chanid, &download_dir, &filename_format)
+        },
+
+        (POST) ["/channel/{chanid}/ignore_rules", chanid: i64] => {
+            let min_duration = request.get_param("min_duration").unwrap_or_default();
+            let max_duration = request.get_param("max_duration").unwrap_or_default();
+            let title = request.get_param("title").unwrap_or_default();
+            page_set_ignore_rules(chanid, &min_duration, &max_duration, &title)
+        },
+
+        (POST) ["/channel/{chanid}/subtitles", chanid: i64] => {
+            let subtitles = request.get_param("subtitles").unwrap_or_default();
+            page_set_subtitles(chanid, &subtitles)
+        },
+
+        (POST) ["/settings/{key}", key: String] => {
+            let value = request.get_param("value").unwrap_or_default();
+            page_set_setting(&key, &value)
+        },
 
         (POST) ["/video_title/{videoid}", videoid: i64] => {
             let Some(title) = request.get_param("title") else {
@@ -481,11 +2833,97 @@ fn handle_response(request: &Request, workers: Arc<Mutex<WorkerPool>>) -> Respon
             page_set_description_alt(videoid, text)
         },
 
+        (POST) ["/video/{videoid}/revert_edit/{editid}", videoid: i64, editid: i64] => {
+            page_revert_alt_edit(videoid, editid)
+        },
+
+        (POST) ["/video_notes/{videoid}", videoid: i64] => {
+            let Some(text) = form_param(request, "text") else {
+                return Response::text("Missing ?text=...").with_status_code(500)
+            };
+            page_set_notes(videoid, &text)
+        },
+
         (GET) ["/thumbnail/video/{id}", id: i64] => {
-            page_thumbnail(id, ThumbnailType::Video, workers.clone())
+            let width = request.get_param("w").and_then(|w| w.parse().ok());
+            page_thumbnail(id, ThumbnailType::Video, width, workers.clone())
         },
         (GET) ["/thumbnail/channel/{id}", id: i64] => {
-            page_thumbnail(id, ThumbnailType::Channel, workers.clone())
+            let width = request.get_param("w").and_then(|w| w.parse().ok());
+            page_thumbnail(id, ThumbnailType::Channel, width, workers.clone())
+        },
+        (GET) ["/healthz"] => {
+            Ok(page_health())
+        },
+        (GET) ["/queue"] => {
+            page_queue(false)
+        },
+        (GET) ["/api/queue"] => {
+            page_queue(true)
+        },
+        (GET) ["/api/v1/channels"] => {
+            page_chan_list(true)
+        },
+        (GET) ["/collections"] => {
+            page_collection_list(false)
+        },
+        (GET) ["/api/v1/collections"] => {
+            page_collection_list(true)
+        },
+        (POST) ["/collections/create"] => {
+            let Some(name) = form_param(request, "name") else {
+                return Response::text("Missing ?name=...").with_status_code(500)
+            };
+            page_create_collection(&name)
+        },
+        (GET) ["/collection/{collid}", collid: i64] => {
+            page_collection_videos(request, collid)
+        },
+        (POST) ["/collection/{collid}/rename", collid: i64] => {
+            let Some(name) = form_param(request, "name") else {
+                return Response::text("Missing ?name=...").with_status_code(500)
+            };
+            page_rename_collection(collid, &name)
+        },
+        (POST) ["/collection/{collid}/delete", collid: i64] => {
+            page_delete_collection(collid)
+        },
+        (POST) ["/collection/{collid}/add", collid: i64] => {
+            let Some(videoid) = form_param(request, "video").and_then(|v| v.parse().ok()) else {
+                return Response::text("Missing ?video=...").with_status_code(500)
+            };
+            page_collection_video(collid, videoid, true)
+        },
+        // From the video page, where the collection is chosen in the form
+        (POST) ["/video/{videoid}/collection", videoid: i64] => {
+            let Some(collid) = form_param(request, "collection").and_then(|v| v.parse().ok()) else {
+                return Response::text("Missing ?collection=...").with_status_code(500)
+            };
+            page_collection_video(collid, videoid, true)
+        },
+        (POST) ["/collection/{collid}/remove", collid: i64] => {
+            let Some(videoid) = form_param(request, "video").and_then(|v| v.parse().ok()) else {
+                return Response::text("Missing ?video=...").with_status_code(500)
+            };
+            page_collection_video(collid, videoid, false)
+        },
+        (GET) ["/api/v1/settings"] => {
+            page_settings()
+        },
+        (GET) ["/api/v1/workers"] => {
+            Ok(page_workers())
+        },
+        (GET) ["/api/openapi.json"] => {
+            page_openapi()
+        },
+        (GET) ["/api/docs"] => {
+            page_api_docs()
+        },
+        (GET) ["/events"] => {
+            Ok(page_events())
+        },
+        (GET) ["/ws"] => {
+            page_ws(request)
         },
         (GET) ["/update/_all"] => {
             page_refresh(workers.clone())
@@ -501,33 +2939,87 @@ fn handle_response(request: &Request, workers: Arc<Mutex<WorkerPool>>) -> Respon
     }
 }
 
+/// Queue failed downloads which are due a retry, and downloads interrupted by a process which
+/// stopped or was killed
+fn requeue_retries(workers: &Arc<Mutex<WorkerPool>>) -> Result<()> {
+    let cfg = Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+    let due = crate::db::requeue_due_retries(&db)?;
+    if !due.is_empty() {
+        info!("Retrying {} failed downloads", due.len());
+    }
+    let interrupted = crate::db::requeue_interrupted(&db)?;
+    let w = workers.lock().unwrap();
+    for v in due.into_iter().chain(interrupted) {
+        w.enqueue(crate::worker::WorkItem::Download(v));
+    }
+    Ok(())
+}
+
 fn serve(workers: Arc<Mutex<WorkerPool>>) -> Result<()> {
     let cfg = Config::load();
+    crate::db::set_origin(crate::db::Origin::Web);
 
     let addr = format!("{}:{}", cfg.web_host, cfg.web_port);
     let url = format!("http://{}", &addr);
     info!("Listening on {}", &url);
+    let handler_workers = workers.clone();
     let srv = rouille::Server::new(&addr, move |request| {
-        handle_response(request, workers.clone())
+        // Requests are handled on rouille's own threads
+        crate::db::set_origin(crate::db::Origin::Web);
+        handle_response(request, handler_workers.clone())
     })
     .unwrap();
 
-    let running = Arc::new(AtomicBool::new(true));
-
-    while running.load(Ordering::SeqCst) {
+    let mut last_retry_check = std::time::Instant::now();
+    while !crate::worker::stopping() {
         srv.poll_timeout(Duration::from_millis(100));
+
+        if last_retry_check.elapsed() > Duration::from_secs(60) {
+            last_retry_check = std::time::Instant::now();
+            if let Err(e) = requeue_retries(&workers) {
+                log::error!("Failed to requeue failed downloads - {:?}", e);
+            }
+            cleanup_rate_limit();
+        }
     }
 
+    // Dropping the server stops accepting connections
+    info!("Stopped listening on {}", &url);
     Ok(())
 }
 
 pub fn main() -> Result<()> {
-    let workers = Arc::new(Mutex::new(crate::worker::WorkerPool::start()));
+    crate::download::check_downloader();
+
+    crate::worker::handle_signals()?;
+
+    let cfg = Config::load();
+    let workers = Arc::new(Mutex::new(crate::worker::WorkerPool::start(
+        cfg.num_download_workers,
+        cfg.num_workers,
+    )));
+
+    // Resume the download queue, including downloads interrupted by stopping or killing a
+    // process (but not those still running in another, e.g `vidl worker`)
+    {
+        let cfg = Config::load();
+        let db = crate::db::Database::pooled(&cfg)?;
+        crate::db::requeue_interrupted(&db)?;
+        let w = workers.lock().unwrap();
+        for v in crate::db::queued_videos(&db)? {
+            w.enqueue(crate::worker::WorkItem::Download(v));
+        }
+    }
 
     let w = workers.clone();
     let web_thread = std::thread::spawn(|| serve(w));
 
     web_thread.join().unwrap()?;
 
+    // Open connections (e.g `/events`) may still hold the pool, so it isn't necessarily dropped
+    workers.lock().unwrap().shutdown();
+    info!("Stopped");
+
     Ok(())
 }
@@ -1,11 +1,16 @@
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::Result;
-use log::{debug, error, info, trace};
+use lazy_static::lazy_static;
+use log::{debug, error, info, trace, warn};
+use serde_derive::Serialize;
 
-use crate::common::VideoStatus;
 use crate::db::{Channel, DBVideoInfo};
+use crate::download::{DownloadEvent, DownloadProgress};
+use crate::events::Event;
 
 pub enum WorkItem {
     Download(DBVideoInfo),
@@ -15,7 +20,8 @@ pub enum WorkItem {
         force: bool,
         full_update: bool,
     },
-    ThumbnailCache(String),
+    /// Cache thumbnail from URL, and its version resized to the width if given
+    ThumbnailCache(String, Option<u32>),
 }
 
 struct Worker {
@@ -23,33 +29,337 @@ struct Worker {
     num: usize,
 }
 
-fn worker_download(val: &DBVideoInfo) -> Result<()> {
-    let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+lazy_static! {
+    /// State of the running worker pool, for the web interface
+    static ref POOL_STATE: Mutex<PoolState> = Mutex::new(PoolState::default());
+}
 
-    // Re-retrieve video info from DB in case it has changed since queuing
-    let val = DBVideoInfo::get_by_sqlid(&db, val.id)?;
+/// What a worker is currently doing
+#[derive(Debug, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Activity {
+    Idle,
+    Download { video: i64, title: String },
+    Update { channel: i64, title: String },
+    Thumbnail,
+}
 
-    // Only proceed with download if still `Queued`
-    if val.status != VideoStatus::Queued {
-        info!("Video already been downloaded, skipping - {:?}", &val);
-        return Ok(());
+/// Which work items a worker runs
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolKind {
+    /// Downloads, which can take hours
+    #[default]
+    Download,
+    /// Channel updates and caching thumbnails, which are quick but shouldn't wait for downloads
+    Light,
+}
+
+impl PoolKind {
+    fn of(item: &WorkItem) -> PoolKind {
+        match item {
+            WorkItem::Download(_) => PoolKind::Download,
+            WorkItem::Update { .. } | WorkItem::ThumbnailCache(..) | WorkItem::Shutdown => {
+                PoolKind::Light
+            }
+        }
+    }
+}
+
+/// State of one worker
+#[derive(Debug, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct WorkerState {
+    /// Which work items it runs
+    pub pool: PoolKind,
+    /// What it is doing now, as `type` with details of the current item
+    #[serde(flatten)]
+    pub activity: Activity,
+    /// False while idle
+    pub busy: bool,
+    /// Number of work items finished since starting, whether or not they succeeded
+    pub processed: u64,
+    /// Error from the most recent work item which failed
+    pub last_error: Option<String>,
+}
+
+impl Default for WorkerState {
+    fn default() -> Self {
+        WorkerState {
+            pool: PoolKind::default(),
+            activity: Activity::Idle,
+            busy: false,
+            processed: 0,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct PoolState {
+    /// State of each worker, by worker number, download workers first
+    pub workers: Vec<WorkerState>,
+    /// Number of work items waiting for a worker
+    pub pending: usize,
+}
+
+/// Set when vidl is stopping, after which workers finish their current work but skip the rest
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// Stop once running work is done, e.g on Ctrl+C
+pub fn request_stop() {
+    STOPPING.store(true, Ordering::SeqCst);
+}
+
+pub fn stopping() -> bool {
+    STOPPING.load(Ordering::SeqCst)
+}
+
+/// Stop on Ctrl+C or SIGTERM (e.g `docker stop`) once running work finishes, killing running
+/// downloads after `VIDL_SHUTDOWN_TIMEOUT`, or immediately if signalled again
+pub fn handle_signals() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if stopping() {
+            warn!("Stopping immediately");
+            std::process::exit(1);
+        }
+        info!("Shutting down once running work finishes. Signal again to stop immediately");
+        request_stop();
+
+        if let Some(timeout) = crate::config::Config::load().shutdown_timeout {
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_secs(timeout));
+                let killed = crate::download::cancel_all();
+                if killed > 0 {
+                    warn!(
+                        "Stopped {} download(s) still running after {}s, will resume on restart",
+                        killed, timeout
+                    );
+                }
+            });
+        }
+    })?;
+    Ok(())
+}
+
+/// Current state of the worker pool
+pub fn pool_state() -> PoolState {
+    POOL_STATE.lock().unwrap().clone()
+}
+
+fn set_activity(num: usize, activity: Activity) {
+    if let Some(w) = POOL_STATE.lock().unwrap().workers.get_mut(num) {
+        w.busy = activity != Activity::Idle;
+        w.activity = activity;
     }
+}
 
-    // Mark as downloading
-    val.set_status(&db, VideoStatus::Downloading)?;
+/// Record that worker `num` finished a work item
+fn finish_item(num: usize) {
+    if let Some(w) = POOL_STATE.lock().unwrap().workers.get_mut(num) {
+        w.processed += 1;
+    }
+}
+
+fn set_last_error(num: usize, error: String) {
+    if let Some(w) = POOL_STATE.lock().unwrap().workers.get_mut(num) {
+        w.last_error = Some(error);
+    }
+}
+
+/// How often a running download shows it is still running, see `db::DOWNLOAD_STALE_AFTER`
+pub const DOWNLOAD_HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// Track progress of download of `val`, record the file being written to so it can be
+/// resumed if interrupted, and log messages from the downloader. Returns `db`, the worker's
+/// connection, once the downloader has finished
+fn handle_download_events(
+    val: &DBVideoInfo,
+    db: crate::db::Database,
+    recv: Receiver<DownloadEvent>,
+) -> crate::db::Database {
+    // Only whole-percent changes are published, as progress is reported many times a second
+    let mut published_percent = -1;
+    let mut set_progress = |p: DownloadProgress| {
+        let progress = DownloadProgress {
+            title: val.info.title.clone(),
+            ..p
+        };
+        if progress.percent as i32 != published_percent {
+            published_percent = progress.percent as i32;
+            crate::events::publish(Event::Progress {
+                video: val.id,
+                progress: progress.clone(),
+            });
+        }
+        crate::download::PROGRESS
+            .lock()
+            .unwrap()
+            .insert(val.id, progress);
+    };
+    set_progress(DownloadProgress::default());
+
+    let mut last_heartbeat = std::time::Instant::now();
+    loop {
+        // Downloads can go quiet for a while, e.g when merging formats, so the heartbeat is
+        // also updated when no event arrives
+        let event = match recv.recv_timeout(DOWNLOAD_HEARTBEAT) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if last_heartbeat.elapsed() >= DOWNLOAD_HEARTBEAT {
+            last_heartbeat = std::time::Instant::now();
+            if let Err(e) = val.download_heartbeat(&db) {
+                warn!("Failed to update heartbeat of {:?} - {:?}", &val.info, e);
+            }
+        }
+        let event = match event {
+            Some(event) => event,
+            None => continue,
+        };
+        match event {
+            DownloadEvent::Progress(p) => set_progress(p),
+            DownloadEvent::Destination(path) => {
+                if let Err(e) = val.set_partial_path(&db, Some(&path)) {
+                    warn!("Failed to record partial path of {:?} - {:?}", &val.info, e);
+                }
+            }
+            DownloadEvent::Subtitle(s) => debug!("Wrote subtitles {:?}", &s.path),
+            DownloadEvent::Warning(msg) => warn!("Downloading {:?}: {}", &val.info.title, msg),
+            DownloadEvent::Error(msg) => error!("Downloading {:?}: {}", &val.info.title, msg),
+            DownloadEvent::Output(line) => debug!("{}", line),
+        }
+    }
+
+    crate::download::PROGRESS.lock().unwrap().remove(&val.id);
+    db
+}
+
+/// Mark the next video to download as `Downloading` and return it
+fn next_download(db: &crate::db::Database) -> Result<Option<DBVideoInfo>> {
+    for v in crate::db::queued_videos(db)? {
+        // Streams can't be fully downloaded until they finish, so leave them queued
+        if v.info.live_status.is_ongoing() {
+            debug!(
+                "Video is a {} stream, deferring download - {:?}",
+                v.info.live_status.as_str(),
+                &v
+            );
+            continue;
+        }
+        // Another worker may have taken it since it was listed
+        if v.claim_download(db)? {
+            return Ok(Some(v));
+        }
+    }
+    Ok(None)
+}
+
+fn worker_download(num: usize, val: &DBVideoInfo) -> Result<()> {
+    let cfg = crate::config::Config::load();
+    let db = crate::db::Database::pooled(&cfg)?;
+
+    // Each queued item is a slot to download one video, which is whichever queued video
+    // has the highest priority rather than necessarily `val`
+    let val = match next_download(&db)? {
+        Some(v) => v,
+        None => {
+            info!("No queued videos left to download, skipping - {:?}", &val);
+            return Ok(());
+        }
+    };
+
+    set_activity(
+        num,
+        Activity::Download {
+            video: val.id,
+            title: val.info.title.clone(),
+        },
+    );
 
     // Download
-    let dl = crate::download::download(&val.info);
+    let chan = val.channel(&db)?;
+    let default_format: Option<String> = db.setting(crate::db::Setting::Format)?;
+    let (events, recv) = std::sync::mpsc::channel();
+    // The event handler uses this worker's connection while downloading, rather than taking
+    // another from the pool
+    let (dl, db) = std::thread::scope(|s| {
+        let handler = s.spawn(|| handle_download_events(&val, db, recv));
+        let dl = crate::download::download(&val.info, &chan, default_format.as_deref(), &events);
+        // Stops the event handler once it has handled everything sent
+        drop(events);
+        (dl, handler.join().unwrap())
+    });
+    val.set_partial_path(&db, None)?;
 
     match dl {
-        Ok(_) => {
+        Ok(downloaded) => {
+            let verified = match &downloaded.path {
+                Some(p) => crate::download::verify(p, val.info.duration, &cfg),
+                None => {
+                    warn!("Unknown output filename, not verifying {:?}", &val.info);
+                    Ok(())
+                }
+            };
+            if let Err(e) = verified {
+                error!("Download of {:?} is corrupt - {:?}", &val.info, e);
+                val.set_status(&db, crate::common::VideoStatus::Corrupt)?;
+                return Ok(());
+            }
+
             info!("Grabbed {:?} successfully", &val.info);
+            val.set_subtitle_files(&db, &downloaded.subtitles)?;
+            if let Some(p) = &downloaded.path {
+                // Absolute, so it can be found from the web interface wherever it is run
+                let p = p.canonicalize().unwrap_or_else(|_| p.clone());
+                let size = std::fs::metadata(&p).map(|m| m.len()).ok();
+                val.set_downloaded_file(&db, &p.to_string_lossy(), size)?;
+            }
+            if cfg.write_nfo {
+                match &downloaded.path {
+                    Some(p) => {
+                        let thumb = val.thumbnail_url_for(&db, None)?;
+                        if let Err(e) = crate::nfo::write_nfo(&val, &chan, p, &thumb) {
+                            warn!("Failed to write .nfo for {:?} - {:?}", &val.info, e);
+                        }
+                    }
+                    None => warn!(
+                        "Unknown output filename, not writing .nfo for {:?}",
+                        &val.info
+                    ),
+                }
+            }
             val.set_status(&db, crate::common::VideoStatus::Grabbed)?;
         }
+        Err(e) if stopping() => {
+            // Queued again, so it is resumed when restarted
+            info!(
+                "Download of {:?} stopped by shutdown, will resume on restart - {:?}",
+                &val.info.title, e
+            );
+            val.set_status(&db, crate::common::VideoStatus::Queued)?;
+        }
+        Err(e) if e.is::<crate::download::Cancelled>() => {
+            info!("Cancelled download of {:?}", &val.info.title);
+            val.set_status(&db, crate::common::VideoStatus::New)?;
+        }
         Err(e) => {
             error!("Error downloading {:?} - {:?}", &val.info, e);
-            val.set_status(&db, crate::common::VideoStatus::GrabError)?;
+            set_last_error(num, format!("Downloading {:?}: {:#}", &val.info.title, e));
+            let kind = e
+                .downcast_ref::<crate::download::DownloadError>()
+                .map(|de| de.kind)
+                .unwrap_or(crate::common::DownloadErrorKind::Other);
+            let retry_at = val.record_grab_error(
+                &db,
+                kind,
+                &format!("{:#}", e),
+                cfg.max_retries,
+                chrono::Duration::seconds(cfg.retry_delay),
+            )?;
+            if let Some(at) = retry_at {
+                info!("Will retry download of {:?} after {}", &val.info.title, at);
+            }
         }
     };
     Ok(())
@@ -59,18 +369,22 @@ fn worker_download(val: &DBVideoInfo) -> Result<()> {
 /// Then either updates the channel or does nothing.
 fn worker_update(chan: &Channel, force: bool, full_update: bool) -> Result<()> {
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    let db = crate::db::Database::pooled(&cfg)?;
     let last_update = chan.last_update(&db)?;
     debug!(
         "Checking channel for update {:?} - last update {:?}",
         chan, last_update
     );
 
-    // Check there hasn't been a recent update
+    // Check there hasn't been a recent update, with a minute's leeway for the time spent queued
+    let interval = chan
+        .settings(&db)?
+        .update_interval(db.default_update_interval()?)
+        - chrono::Duration::minutes(1);
     let time_to_update = if let Some(last_update) = last_update {
         let now = chrono::Utc::now();
         let delta = now - last_update;
-        delta > chrono::Duration::minutes(59)
+        delta > interval
     } else {
         // No last update, so time to update now
         true
@@ -78,25 +392,66 @@ fn worker_update(chan: &Channel, force: bool, full_update: bool) -> Result<()> {
 
     if force || time_to_update {
         info!("Time to update {:?}", &chan);
-        chan.update(&db, full_update)?;
+        let added = chan.update(&db, full_update)?;
+
+        if cfg.fetch_video_details && !added.is_empty() {
+            debug!("Fetching details of {} new videos", added.len());
+            chan.fetch_video_details(&db, &added)?;
+        }
+        crate::events::publish(Event::ChannelUpdated {
+            channel: chan.id,
+            added: added.len(),
+        });
     };
 
     Ok(())
 }
 
-fn worker_thumbnail_cache(url: &str) -> Result<()> {
+fn worker_thumbnail_cache(url: &str, width: Option<u32>) -> Result<()> {
+    use crate::image_cache::IMG_CACHE;
+
+    let cfg = crate::config::Config::load();
+    let variant = crate::image_cache::variant_key(url, width, cfg.thumbnail_webp);
+
     // Check if image is already in cache, as it may have been added since queued
+    if IMG_CACHE
+        .lock()
+        .unwrap()
+        .contains(variant.as_deref().unwrap_or(url))
     {
-        let ic = crate::web::IMG_CACHE.lock().unwrap();
-        if ic.contains(url) {
-            debug!("Image already in cache, skipping");
-            return Ok(());
-        }
+        debug!("Image already in cache, skipping");
+        return Ok(());
+    }
+
+    let cached = IMG_CACHE.lock().unwrap().get(url);
+    let original = match cached {
+        Some(img) => img,
+        None => match fetch_thumbnail(url)? {
+            Some(img) => img,
+            None => return Ok(()),
+        },
+    };
+
+    if let Some(key) = variant {
+        let img = match crate::image_cache::transcode(&original, width, cfg.thumbnail_webp) {
+            Ok(img) => img,
+            Err(e) => {
+                // Cached anyway, so isn't attempted again
+                warn!("Failed to resize thumbnail {} - {:?}", url, e);
+                original
+            }
+        };
+        IMG_CACHE.lock().unwrap().add(&key, img);
     }
+    Ok(())
+}
 
+/// Download thumbnail into the cache, returning it
+fn fetch_thumbnail(url: &str) -> Result<Option<crate::image_cache::Image>> {
     let resp = attohttpc::get(&url).send()?;
     if !resp.status().is_success() {
         error!("Failed to grab thumbnail for {}", &url);
+        Ok(None)
     } else {
         let ct: String = resp
             .headers()
@@ -105,21 +460,21 @@ fn worker_thumbnail_cache(url: &str) -> Result<()> {
             .unwrap_or("image/jpeg")
             .into();
         let data = resp.bytes()?;
-        let img = crate::web::Image {
+        let img = crate::image_cache::Image {
             content_type: ct,
             data: data,
         };
         {
-            let mut ic = crate::web::IMG_CACHE.lock().unwrap();
-            ic.add(&url, img);
+            let mut ic = crate::image_cache::IMG_CACHE.lock().unwrap();
+            ic.add(&url, img.clone());
         };
+        Ok(Some(img))
     }
-
-    Ok(())
 }
 
 impl Worker {
     fn run(&self) {
+        crate::db::set_origin(crate::db::Origin::Worker);
         loop {
             let item = {
                 let lock = self.recv.lock().unwrap();
@@ -127,6 +482,15 @@ impl Worker {
 
                 // Drop lock
             };
+            if !matches!(item, WorkItem::Shutdown) {
+                let mut state = POOL_STATE.lock().unwrap();
+                state.pending = state.pending.saturating_sub(1);
+                // Queued videos are still queued in the database, so are resumed on restart
+                if stopping() {
+                    trace!("Worker {}: Skipping work as stopping", self.num);
+                    continue;
+                }
+            }
 
             match item {
                 WorkItem::Shutdown => {
@@ -136,9 +500,12 @@ impl Worker {
 
                 WorkItem::Download(ref val) => {
                     debug!("Worker {}: Download {:#?}", self.num, val);
-                    match worker_download(val) {
+                    match worker_download(self.num, val) {
                         Ok(_) => (),
-                        Err(e) => error!("Error in worker {}: {:#?}", self.num, e),
+                        Err(e) => {
+                            error!("Error in worker {}: {:#?}", self.num, e);
+                            set_last_error(self.num, format!("{:#}", e));
+                        }
                     }
                 }
 
@@ -148,39 +515,56 @@ impl Worker {
                     full_update,
                 } => {
                     debug!("Worker {}: Updating {:#?}", self.num, chan);
+                    set_activity(
+                        self.num,
+                        Activity::Update {
+                            channel: chan.id,
+                            title: chan.title.clone(),
+                        },
+                    );
                     match worker_update(&chan, force, full_update) {
                         Ok(_) => (),
-                        Err(e) => error!("Error in worker {}: {:#?}", self.num, e),
+                        Err(e) => {
+                            error!("Error in worker {}: {:#?}", self.num, e);
+                            set_last_error(self.num, format!("{:#}", e));
+                        }
                     }
                 }
 
-                WorkItem::ThumbnailCache(ref url) => {
+                WorkItem::ThumbnailCache(ref url, width) => {
                     trace!("Worker {}: Cache thumbnail {:#?}", self.num, url);
-                    match worker_thumbnail_cache(url) {
+                    set_activity(self.num, Activity::Thumbnail);
+                    match worker_thumbnail_cache(url, width) {
                         Ok(_) => (),
-                        Err(e) => error!("Error in worker {}: {:#?}", self.num, e),
+                        Err(e) => {
+                            error!("Error in worker {}: {:#?}", self.num, e);
+                            set_last_error(self.num, format!("{:#}", e));
+                        }
                     }
                 }
             }
+            finish_item(self.num);
+            set_activity(self.num, Activity::Idle);
         }
     }
 }
 
-pub struct WorkerPool {
+/// Threads running one kind of work item
+struct Workers {
     pool: threadpool::ThreadPool,
     num_workers: usize,
     sender: mpsc::Sender<WorkItem>,
 }
 
-impl WorkerPool {
-    pub fn start() -> Self {
-        let num_workers = 4;
+impl Workers {
+    /// Start `num_workers` threads, numbered from `first_num`
+    fn start(num_workers: usize, first_num: usize) -> Workers {
         let pool = threadpool::ThreadPool::new(num_workers);
         let (sender, recv) = mpsc::channel();
         let recv = Arc::new(Mutex::new(recv));
 
         // Launch worker threads
-        for curnum in 0..num_workers {
+        for curnum in first_num..first_num + num_workers {
             let w = Worker {
                 recv: recv.clone(),
                 num: curnum,
@@ -188,38 +572,164 @@ impl WorkerPool {
             pool.execute(move || w.run());
         }
 
-        Self {
+        Workers {
             pool,
             num_workers,
             sender,
         }
     }
 
+    /// Wait for queued work, then stop the threads
+    fn shutdown(&self) {
+        for _ in 0..self.num_workers {
+            self.sender.send(WorkItem::Shutdown).unwrap();
+        }
+        self.pool.join();
+    }
+}
+
+/// Runs downloads and lighter work (channel updates and caching thumbnails) in separate sets
+/// of threads, so the lighter work doesn't wait behind long downloads
+pub struct WorkerPool {
+    downloads: Workers,
+    light: Workers,
+    /// Set once `shutdown` has finished
+    stopped: bool,
+}
+
+impl WorkerPool {
+    /// Start `num_download_workers` threads for downloads, which is how many videos are
+    /// downloaded at once, and `num_light_workers` for other work items
+    pub fn start(num_download_workers: usize, num_light_workers: usize) -> Self {
+        let mut workers = vec![
+            WorkerState {
+                pool: PoolKind::Download,
+                ..Default::default()
+            };
+            num_download_workers
+        ];
+        workers.extend(vec![
+            WorkerState {
+                pool: PoolKind::Light,
+                ..Default::default()
+            };
+            num_light_workers
+        ]);
+        *POOL_STATE.lock().unwrap() = PoolState {
+            workers,
+            pending: 0,
+        };
+
+        Self {
+            downloads: Workers::start(num_download_workers, 0),
+            light: Workers::start(num_light_workers, num_download_workers),
+            stopped: false,
+        }
+    }
+
     pub fn enqueue(&self, item: WorkItem) {
-        self.sender.send(item).unwrap();
+        if stopping() {
+            trace!("Not queueing work as stopping");
+            return;
+        }
+        POOL_STATE.lock().unwrap().pending += 1;
+        let workers = match PoolKind::of(&item) {
+            PoolKind::Download => &self.downloads,
+            PoolKind::Light => &self.light,
+        };
+        workers.sender.send(item).unwrap();
     }
 
     /// Completes all queued work then stops workers
     pub fn stop(self) {
         std::mem::drop(self); // Redundant as this method consumes self anyway
     }
+
+    /// Wait for workers to complete all queued work, or only their current work if
+    /// `request_stop` was called, then stop them
+    pub fn shutdown(&mut self) {
+        if self.stopped {
+            return;
+        }
+        info!("Commencing worker pool shutdown");
+        debug!("Joining worker pool");
+        self.downloads.shutdown();
+        self.light.shutdown();
+        self.stopped = true;
+    }
 }
 
 impl Drop for WorkerPool {
     fn drop(&mut self) {
         debug!("Dropping WorkerPool, starting shutdown");
-        info!("Commencing worker pool shutdown");
-        for _ in 0..self.num_workers {
-            self.sender.send(WorkItem::Shutdown).unwrap();
+        self.shutdown();
+    }
+}
+
+/// Print what would be downloaded, in the order it would be, and where to, without
+/// downloading anything or changing any video's status
+fn dry_run(db: &crate::db::Database) -> Result<()> {
+    let default_format: Option<String> = db.setting(crate::db::Setting::Format)?;
+    let queued = crate::db::queued_videos(db)?;
+    if queued.is_empty() {
+        println!("No videos queued for download");
+    }
+    for v in queued {
+        if v.info.live_status.is_ongoing() {
+            println!(
+                "Would defer {} stream {:?} ({})",
+                v.info.live_status.as_str(),
+                &v.info.title,
+                &v.info.url
+            );
+            continue;
+        }
+        let chan = v.channel(db)?;
+        match crate::download::simulate(&v.info, &chan, default_format.as_deref()) {
+            Ok(Some(path)) => println!(
+                "Would download {:?} ({}) to {}",
+                &v.info.title,
+                &v.info.url,
+                path.display()
+            ),
+            Ok(None) => println!(
+                "Would download {:?} ({}) to unknown location",
+                &v.info.title, &v.info.url
+            ),
+            Err(e) => println!(
+                "Would fail to download {:?} ({}) - {}",
+                &v.info.title, &v.info.url, e
+            ),
         }
-        debug!("Joining worker pool");
-        self.pool.join();
     }
+    Ok(())
 }
 
-pub fn main() -> Result<()> {
+/// Download queued videos, or if `simulate` is set only show what would be downloaded. At most
+/// `num_workers` are downloaded at once, defaulting to `VIDL_NUM_DOWNLOAD_WORKERS`
+pub fn main(simulate: bool, num_workers: Option<usize>) -> Result<()> {
+    crate::download::check_downloader();
     let cfg = crate::config::Config::load();
-    let db = crate::db::Database::open(&cfg)?;
+    // Each worker holds a database connection while downloading, and the pool is sized for
+    // the configured number
+    let num_workers = num_workers.unwrap_or(cfg.num_download_workers);
+    if num_workers == 0 || num_workers > cfg.num_download_workers {
+        anyhow::bail!(
+            "Number of workers must be between 1 and VIDL_NUM_DOWNLOAD_WORKERS ({})",
+            cfg.num_download_workers
+        );
+    }
+    let db = crate::db::Database::pooled(&cfg)?;
+
+    if simulate {
+        return dry_run(&db);
+    }
+    handle_signals()?;
+
+    // Downloads interrupted by stopping or killing a worker are resumed (but not those still
+    // running in another process), and failed downloads which are due a retry are queued again
+    crate::db::requeue_interrupted(&db)?;
+    crate::db::requeue_due_retries(&db)?;
 
     let mut statuses = std::collections::HashSet::new();
     statuses.insert(crate::common::VideoStatus::Queued);
@@ -230,14 +740,51 @@ pub fn main() -> Result<()> {
         Some(crate::db::FilterParams {
             name_contains: None,
             status: Some(statuses),
+            status_not: None,
             chanid: None,
+            collection: None,
+            tag: None,
+            search: None,
+            published_after: None,
+            published_before: None,
+            watched: None,
+            order_by: crate::db::VideoOrder::default(),
         }),
     )?;
 
-    let p = WorkerPool::start();
+    // Only downloads are queued, so one light worker is enough
+    let p = WorkerPool::start(num_workers, 1);
     for q in queued {
         p.enqueue(WorkItem::Download(q));
     }
+    p.stop();
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pool_size() {
+        let mut p = WorkerPool::start(2, 3);
+        let kinds: Vec<PoolKind> = pool_state().workers.iter().map(|w| w.pool).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                PoolKind::Download,
+                PoolKind::Download,
+                PoolKind::Light,
+                PoolKind::Light,
+                PoolKind::Light
+            ]
+        );
+        assert!(pool_state().workers.iter().all(|w| !w.busy));
+        assert_eq!(
+            PoolKind::of(&WorkItem::ThumbnailCache("".into(), None)),
+            PoolKind::Light
+        );
+        p.shutdown();
+    }
+}